@@ -0,0 +1,238 @@
+//! Command line handling for maintenance commands that can be run
+//! instead of starting the server
+//!
+//! Supported commands:
+//!
+//! ```text
+//! pocket-relay migrate down [--steps N]
+//! pocket-relay decode-packet <file>
+//! pocket-relay replay <capture-file> [--target HOST:PORT]
+//! ```
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Commands that can be provided on the command line
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Roll back the last `steps` database migrations
+    MigrateDown {
+        /// The number of migrations to roll back
+        steps: u32,
+    },
+    /// Decode and print the packets stored in a file
+    DecodePacket {
+        /// Path to the file containing the raw packet bytes
+        path: PathBuf,
+    },
+    /// Replay a captured session against a server
+    Replay {
+        /// Path to the capture file
+        path: PathBuf,
+        /// Address of the server to replay against, defaults
+        /// to the local server when not specified
+        target: Option<String>,
+    },
+}
+
+/// Errors that can occur while parsing the command line arguments
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CommandError {
+    /// The command was not known
+    #[error("Unknown command: {0}")]
+    UnknownCommand(String),
+    /// The migrate command was missing its direction
+    #[error("Expected migration direction, usage: migrate down [--steps N]")]
+    MissingDirection,
+    /// Unknown or malformed argument for the command
+    #[error("Unexpected argument: {0}")]
+    UnexpectedArgument(String),
+    /// The command was missing its file path
+    #[error("Expected file path, usage: decode-packet <file> or replay <capture-file>")]
+    MissingFile,
+    /// The replay target option was missing its value
+    #[error("Expected target address after --target")]
+    MissingTarget,
+    /// The provided steps value wasn't a valid number
+    #[error("Invalid number of steps: {0}")]
+    InvalidSteps(String),
+}
+
+/// Parses the provided command line `args` (Excluding the program name)
+/// into a [Command]. Returns [None] when no command is provided and the
+/// server should be started normally
+pub fn parse_command<I>(args: I) -> Result<Option<Command>, CommandError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+
+    let command = match args.next() {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    match command.as_str() {
+        "migrate" => parse_migrate(args),
+        "decode-packet" => parse_decode_packet(args),
+        "replay" => parse_replay(args),
+        _ => Err(CommandError::UnknownCommand(command)),
+    }
+}
+
+/// Parses the arguments for the migrate command
+fn parse_migrate<I>(mut args: I) -> Result<Option<Command>, CommandError>
+where
+    I: Iterator<Item = String>,
+{
+    match args.next().as_deref() {
+        Some("down") => {}
+        Some(value) => return Err(CommandError::UnexpectedArgument(value.to_string())),
+        None => return Err(CommandError::MissingDirection),
+    }
+
+    // Default to rolling back a single migration
+    let mut steps: u32 = 1;
+
+    while let Some(arg) = args.next() {
+        if arg != "--steps" {
+            return Err(CommandError::UnexpectedArgument(arg));
+        }
+
+        let value = args.next().unwrap_or_default();
+        steps = match value.parse() {
+            Ok(value) if value > 0 => value,
+            _ => return Err(CommandError::InvalidSteps(value)),
+        };
+    }
+
+    Ok(Some(Command::MigrateDown { steps }))
+}
+
+/// Parses the arguments for the decode packet command
+fn parse_decode_packet<I>(mut args: I) -> Result<Option<Command>, CommandError>
+where
+    I: Iterator<Item = String>,
+{
+    let path = args.next().ok_or(CommandError::MissingFile)?;
+
+    if let Some(arg) = args.next() {
+        return Err(CommandError::UnexpectedArgument(arg));
+    }
+
+    Ok(Some(Command::DecodePacket { path: path.into() }))
+}
+
+/// Parses the arguments for the replay command
+fn parse_replay<I>(mut args: I) -> Result<Option<Command>, CommandError>
+where
+    I: Iterator<Item = String>,
+{
+    let path = args.next().ok_or(CommandError::MissingFile)?;
+    let mut target = None;
+
+    while let Some(arg) = args.next() {
+        if arg != "--target" {
+            return Err(CommandError::UnexpectedArgument(arg));
+        }
+
+        target = Some(args.next().ok_or(CommandError::MissingTarget)?);
+    }
+
+    Ok(Some(Command::Replay {
+        path: path.into(),
+        target,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_command, Command, CommandError};
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    /// Tests that no arguments starts the server normally
+    #[test]
+    fn test_no_command() {
+        assert_eq!(parse_command(args(&[])), Ok(None));
+    }
+
+    /// Tests parsing the migrate down command with and without steps
+    #[test]
+    fn test_migrate_down() {
+        assert_eq!(
+            parse_command(args(&["migrate", "down"])),
+            Ok(Some(Command::MigrateDown { steps: 1 }))
+        );
+        assert_eq!(
+            parse_command(args(&["migrate", "down", "--steps", "3"])),
+            Ok(Some(Command::MigrateDown { steps: 3 }))
+        );
+    }
+
+    /// Tests parsing the decode packet command
+    #[test]
+    fn test_decode_packet() {
+        assert_eq!(
+            parse_command(args(&["decode-packet", "capture.bin"])),
+            Ok(Some(Command::DecodePacket {
+                path: "capture.bin".into()
+            }))
+        );
+        assert_eq!(
+            parse_command(args(&["decode-packet"])),
+            Err(CommandError::MissingFile)
+        );
+    }
+
+    /// Tests parsing the replay command with and without a target
+    #[test]
+    fn test_replay() {
+        assert_eq!(
+            parse_command(args(&["replay", "capture.bin"])),
+            Ok(Some(Command::Replay {
+                path: "capture.bin".into(),
+                target: None
+            }))
+        );
+        assert_eq!(
+            parse_command(args(&[
+                "replay",
+                "capture.bin",
+                "--target",
+                "127.0.0.1:8080"
+            ])),
+            Ok(Some(Command::Replay {
+                path: "capture.bin".into(),
+                target: Some("127.0.0.1:8080".to_string())
+            }))
+        );
+        assert_eq!(
+            parse_command(args(&["replay", "capture.bin", "--target"])),
+            Err(CommandError::MissingTarget)
+        );
+    }
+
+    /// Tests that malformed commands are rejected
+    #[test]
+    fn test_invalid_commands() {
+        assert_eq!(
+            parse_command(args(&["serve"])),
+            Err(CommandError::UnknownCommand("serve".to_string()))
+        );
+        assert_eq!(
+            parse_command(args(&["migrate"])),
+            Err(CommandError::MissingDirection)
+        );
+        assert_eq!(
+            parse_command(args(&["migrate", "down", "--steps", "0"])),
+            Err(CommandError::InvalidSteps("0".to_string()))
+        );
+        assert_eq!(
+            parse_command(args(&["migrate", "down", "--steps"])),
+            Err(CommandError::InvalidSteps("".to_string()))
+        );
+    }
+}
@@ -1,22 +1,31 @@
 use log::LevelFilter;
-use serde::Deserialize;
+use serde::{de::Error as _, Deserialize, Deserializer};
 use std::{
+    collections::BTreeMap,
     env,
     fs::read_to_string,
     net::{IpAddr, Ipv4Addr},
-    path::Path,
+    path::{Path, PathBuf},
 };
+use thiserror::Error;
 
-use crate::session::models::Port;
+use crate::{session::models::Port, utils::cidr::Cidr};
 
 /// The server version extracted from the Cargo.toml
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// The oldest Pocket Relay client version that is compatible with this
+/// server (First version with association tokens)
+pub const MIN_CLIENT_VERSION: &str = "0.6.0";
+
 /// Config variables that are required to always exist during
 /// runtime for various tasks
 #[derive(Default)]
 pub struct RuntimeConfig {
-    pub qos: QosServerConfig,
+    pub port: Port,
+    pub external_port: Option<Port>,
+    pub server: ServerConfig,
+    pub qos: QosConfig,
     pub reverse_proxy: bool,
     pub galaxy_at_war: GalaxyAtWarConfig,
     pub menu_message: String,
@@ -24,6 +33,19 @@ pub struct RuntimeConfig {
     pub tunnel: TunnelConfig,
     pub udp_tunnel: UdpTunnelConfig,
     pub api: APIConfig,
+    pub game: GameConfig,
+    pub client_config: ClientConfigSettings,
+    pub sessions: SessionsConfig,
+    pub net: NetConfig,
+    pub player: PlayerConfig,
+}
+
+impl RuntimeConfig {
+    /// Get the port of the server exposed to the clients. This is
+    /// [Config::external_port] if set otherwise its [Config::port]
+    pub fn get_exposed_port(&self) -> Port {
+        self.external_port.unwrap_or(self.port)
+    }
 }
 
 /// Environment variable key to load the config from
@@ -72,7 +94,28 @@ pub fn load_config() -> Option<Config> {
 pub struct Config {
     pub host: IpAddr,
     pub port: Port,
-    pub qos: QosServerConfig,
+    /// External facing port, only needed when the port visible to users
+    /// is different to [Config::port]
+    ///
+    /// For cases such as different exposed port in docker or usage behind
+    /// a reverse proxy such as NGINX
+    pub external_port: Option<Port>,
+    /// Accept both IPv4 and IPv6 clients when the host is an unspecified
+    /// address ("::" or "0.0.0.0") (Default: false)
+    pub dual_stack: bool,
+    /// Hostname or address the server is publicly reachable at such as a
+    /// dynamic DNS hostname, when not set the public address is looked up
+    /// using an online service (Default: None)
+    pub external_host: Option<String>,
+    /// Number of seconds between each resolution of the public address
+    /// so address changes are picked up (Default: 300)
+    pub external_host_refresh_seconds: u64,
+    /// Whether the server starts in maintenance mode where new sessions and
+    /// logins are refused, can be toggled while running through the API
+    /// (Default: false)
+    pub maintenance_mode: bool,
+    pub server: ServerConfig,
+    pub qos: QosConfig,
     pub reverse_proxy: bool,
     pub dashboard: DashboardConfig,
     pub menu_message: String,
@@ -82,6 +125,77 @@ pub struct Config {
     pub tunnel: TunnelConfig,
     pub udp_tunnel: UdpTunnelConfig,
     pub api: APIConfig,
+    pub database: DatabaseConfig,
+    pub http: HttpConfig,
+    pub maintenance: MaintenanceConfig,
+    pub game: GameConfig,
+    pub rotation: RotationConfig,
+    pub client_config: ClientConfigSettings,
+    pub sessions: SessionsConfig,
+    pub access: AccessConfig,
+    pub net: NetConfig,
+    pub player: PlayerConfig,
+    pub telemetry: TelemetryConfig,
+}
+
+/// Errors for configured values the server cannot start with
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The server port was zero which would bind a random port
+    #[error("Server port cannot be 0, clients would be unable to find the server")]
+    ZeroPort,
+    /// The enabled UDP tunnel was configured with a zero port
+    #[error("UDP tunnel port cannot be 0, set udp_tunnel.enabled to false to disable the tunnel")]
+    ZeroTunnelPort,
+    /// The official server session limit was zero which would prevent
+    /// any session from connecting
+    #[error("retriever.max_concurrent_sessions cannot be 0, set it to null to disable the limit")]
+    ZeroRetrieverSessions,
+    /// The session subscription limit is too low for every player in a
+    /// full game to subscribe to each other
+    #[error(
+        "sessions.max_subscriptions ({max_subscriptions}) must be at least {required} for games of {max_players} players"
+    )]
+    TooFewSubscriptions {
+        max_subscriptions: usize,
+        required: usize,
+        max_players: usize,
+    },
+}
+
+impl Config {
+    /// Validates the configured ports and limits, ports above the valid
+    /// range are already rejected when deserializing the config
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.port == 0 || self.external_port == Some(0) {
+            return Err(ConfigError::ZeroPort);
+        }
+
+        if self.udp_tunnel.enabled
+            && (self.udp_tunnel.port == 0 || self.udp_tunnel.external_port == Some(0))
+        {
+            return Err(ConfigError::ZeroTunnelPort);
+        }
+
+        if self.retriever.max_concurrent_sessions == Some(0) {
+            return Err(ConfigError::ZeroRetrieverSessions);
+        }
+
+        // Each player in a game subscribes to every other player
+        let max_players = self.game.player_limit();
+        let required = max_players - 1;
+        if let Some(max_subscriptions) = self.sessions.max_subscriptions {
+            if max_subscriptions < required {
+                return Err(ConfigError::TooFewSubscriptions {
+                    max_subscriptions,
+                    required,
+                    max_players,
+                });
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for Config {
@@ -89,7 +203,13 @@ impl Default for Config {
         Self {
             host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
             port: 80,
-            qos: QosServerConfig::default(),
+            external_port: None,
+            dual_stack: false,
+            external_host: None,
+            external_host_refresh_seconds: 300,
+            maintenance_mode: false,
+            server: Default::default(),
+            qos: QosConfig::default(),
             reverse_proxy: false,
             dashboard: Default::default(),
             menu_message: "<font color='#B2B2B2'>Pocket Relay</font> - <font color='#FFFF66'>Logged as: {n}</font>".to_string(),
@@ -98,7 +218,18 @@ impl Default for Config {
             retriever: Default::default(),
             tunnel: Default::default(),
             udp_tunnel: Default::default(),
-            api: Default::default()
+            api: Default::default(),
+            database: Default::default(),
+            http: Default::default(),
+            maintenance: Default::default(),
+            game: Default::default(),
+            rotation: Default::default(),
+            client_config: Default::default(),
+            sessions: Default::default(),
+            access: Default::default(),
+            net: Default::default(),
+            player: Default::default(),
+            telemetry: Default::default(),
         }
     }
 }
@@ -133,11 +264,29 @@ pub struct UdpTunnelConfig {
     ///
     /// For cases such as different exposed port in docker or usage behind
     /// a reverse proxy such as NGINX
+    #[serde(alias = "public_port")]
     pub external_port: Option<Port>,
 
+    /// External facing host the clients should send tunnel traffic to, only
+    /// needed when the tunnel is reached through a different address than
+    /// the server itself (i.e port forwarding to another machine)
+    ///
+    /// When not set the detected public address of the server is used, if
+    /// the public address isn't known clients use the address they connected
+    /// to the server with
+    pub public_host: Option<String>,
+
     /// Optionally choose to disable the tunnel if you don't intend to use it
     /// default value is true
     pub enabled: bool,
+
+    /// Number of attempts to make binding the tunnel socket before the
+    /// tunnel is disabled (Default: 3)
+    pub bind_attempts: u32,
+
+    /// Milliseconds to wait between attempts to bind the tunnel socket
+    /// (Default: 1000)
+    pub bind_retry_delay_ms: u64,
 }
 
 impl Default for UdpTunnelConfig {
@@ -145,7 +294,10 @@ impl Default for UdpTunnelConfig {
         Self {
             port: 9032,
             external_port: None,
+            public_host: None,
             enabled: true,
+            bind_attempts: 3,
+            bind_retry_delay_ms: 1000,
         }
     }
 }
@@ -162,6 +314,60 @@ impl UdpTunnelConfig {
 
         Some(self.external_port.unwrap_or(self.port))
     }
+
+    /// Get the host exposed to the clients for the UDP tunnel. This is
+    /// [None] if the tunnel is disabled. Otherwise its the
+    /// [UdpTunnelConfig::public_host] override if set otherwise its the
+    /// `detected` public address of the server if known.
+    ///
+    /// When this is [None] clients should use the server host
+    pub fn get_exposed_host(&self, detected: Option<Ipv4Addr>) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        match self.public_host.as_deref() {
+            Some(host) if !host.is_empty() => Some(host.to_string()),
+            _ => detected.map(|address| address.to_string()),
+        }
+    }
+}
+
+/// Configuration for the QoS details advertised to clients
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct QosConfig {
+    /// The QoS server clients should use, the local server is used when
+    /// the type is not specified
+    #[serde(flatten, deserialize_with = "deserialize_qos_server")]
+    pub server: QosServerConfig,
+    /// Number of probes clients should send to the QoS server, more probes
+    /// give more accurate results at the cost of connection time (Default: 1)
+    pub probe_count: u8,
+}
+
+impl Default for QosConfig {
+    fn default() -> Self {
+        Self {
+            server: QosServerConfig::default(),
+            probe_count: 1,
+        }
+    }
+}
+
+/// Deserializes the flattened QoS server config, falling back to the default
+/// server when the "type" field is missing as `#[serde(default)]` doesn't
+/// apply to flattened fields
+fn deserialize_qos_server<'de, D>(deserializer: D) -> Result<QosServerConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Map::deserialize(deserializer)?;
+    if !value.contains_key("type") {
+        return Ok(QosServerConfig::default());
+    }
+
+    QosServerConfig::deserialize(serde_json::Value::Object(value)).map_err(D::Error::custom)
 }
 
 /// Configuration for the server QoS setup
@@ -173,8 +379,14 @@ pub enum QosServerConfig {
     /// Use the local QoS server (might cause issues with WAN)
     #[default]
     Local,
-    /// Use a custom QoS server
-    Custom { host: String, port: u16 },
+    /// Use a custom QoS server, for deployments where the QoS server runs
+    /// on a different host or port to the rest of the server
+    Custom {
+        /// Host clients should probe the QoS server at
+        host: String,
+        /// Port clients should probe the QoS server at
+        port: u16,
+    },
     /// Disable the QoS server (Public IP *wont* be resolved)
     Disabled,
     /// Configuration to use when using hamachi
@@ -191,6 +403,9 @@ pub struct APIConfig {
     pub public_games: bool,
     /// Hide players from API response when no auth is provided
     pub public_games_hide_players: bool,
+    /// Allow admins to manually associate tunnels with tunnel pools through
+    /// the API, intended for testing tunnels without a game (Default: false)
+    pub tunnel_associate: bool,
 }
 
 impl Default for APIConfig {
@@ -198,6 +413,368 @@ impl Default for APIConfig {
         Self {
             public_games: false,
             public_games_hide_players: true,
+            tunnel_associate: false,
+        }
+    }
+}
+
+/// Configuration for game behavior
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    /// Whether games should automatically be started once the game is
+    /// full rather than waiting for the host to start (Default: false)
+    pub auto_start_when_full: bool,
+    /// Number of games a matchmaking entry can be skipped for before its
+    /// rules are widened to only the required DLC rules, None to never
+    /// widen the rules (Default: 10)
+    pub matchmaking_widen_after: Option<u32>,
+    /// Number of milliseconds to wait before checking the matchmaking queue
+    /// after a game changes, repeated changes to the same game within this
+    /// window are combined into a single check. None to check immediately
+    /// (Default: 250)
+    pub matchmaking_check_debounce_ms: Option<u64>,
+    /// Number of seconds a player can wait in the matchmaking queue before
+    /// a new game is created for them from their matchmaking rules with
+    /// them as the host. None to keep players waiting (Default: None)
+    pub matchmaking_fallback_seconds: Option<u64>,
+    /// Attributes added to created games when the client doesn't provide
+    /// them, values provided by the client take priority (Default: Empty)
+    pub default_attributes: BTreeMap<String, String>,
+    /// Number of seconds a game that hasn't started can go without any
+    /// activity before it is stopped, None to never stop idle games
+    /// (Default: None)
+    pub pregame_timeout_seconds: Option<u64>,
+    /// Whether a new host is chosen when the host leaves, when disabled
+    /// the game is stopped instead (Default: true)
+    pub host_migration: bool,
+    /// Whether to write a structured debug log line for each game lifecycle
+    /// event such as players joining and state changes (Default: false)
+    pub log_events: bool,
+    /// Number of times the host can report a player as disconnected from
+    /// the mesh before that player is removed from the game, None to never
+    /// remove players for connection failures (Default: 3)
+    pub max_mesh_failures: Option<u32>,
+    /// Maximum number of players allowed in a game, values above 4 are only
+    /// usable with modded clients. Limited to between 1 and 255 (Default: 4)
+    pub max_players: usize,
+    /// Maximum number of attributes a game can have, changes that would take
+    /// a game past this are rejected. None to disable the limit (Default: 100)
+    pub max_attributes: Option<usize>,
+    /// Maximum size in bytes of a single game attribute key and value
+    /// combined, larger attributes are rejected. None to disable the limit
+    /// (Default: 1024)
+    pub max_attribute_bytes: Option<usize>,
+}
+
+impl GameConfig {
+    /// Upper limit for the maximum number of players, player slots are used
+    /// as single byte tunnel pool indices where 255 is reserved
+    const MAX_PLAYERS_LIMIT: usize = 255;
+
+    /// Obtains the maximum number of players clamped to the supported range
+    pub fn player_limit(&self) -> usize {
+        self.max_players.clamp(1, Self::MAX_PLAYERS_LIMIT)
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            auto_start_when_full: false,
+            matchmaking_widen_after: Some(10),
+            matchmaking_check_debounce_ms: Some(250),
+            matchmaking_fallback_seconds: None,
+            default_attributes: BTreeMap::new(),
+            pregame_timeout_seconds: None,
+            host_migration: true,
+            log_events: false,
+            max_mesh_failures: Some(3),
+            max_players: 4,
+            max_attributes: Some(100),
+            max_attribute_bytes: Some(1024),
+        }
+    }
+}
+
+/// Configuration for restricting which addresses can connect
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AccessConfig {
+    /// Address ranges allowed to connect, empty to allow all
+    /// addresses (Default: Empty)
+    pub allow_cidrs: Vec<Cidr>,
+    /// Address ranges denied from connecting, takes precedence over
+    /// the allowed ranges (Default: Empty)
+    pub deny_cidrs: Vec<Cidr>,
+}
+
+/// Configuration for client telemetry messages
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// URL to forward telemetry messages to, messages are sent in batches
+    /// as a JSON array using POST (Default: None)
+    pub forward_url: Option<String>,
+}
+
+/// Configuration for player accounts
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PlayerConfig {
+    /// Maximum number of player data keys each player can store, new keys
+    /// past this are rejected. None to disable the limit (Default: 1000)
+    pub max_data_keys: Option<u32>,
+    /// Maximum size in bytes of a single player data key and value combined,
+    /// larger values are rejected. None to disable the limit (Default: 65536)
+    pub max_value_bytes: Option<usize>,
+    /// Maximum total size in bytes of all the player data keys and values
+    /// for each player, values that would exceed this are rejected. None to
+    /// disable the limit (Default: 4194304)
+    pub max_total_bytes: Option<usize>,
+    /// Maximum number of accounts that can be created from each address
+    /// every hour, further account creations are rejected until the hour
+    /// has passed. None to disable the limit (Default: None)
+    pub max_accounts_per_hour: Option<u32>,
+    /// Whether private and loopback addresses are exempt from the account
+    /// creation limit (Default: true)
+    pub account_limit_exempt_private: bool,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            max_data_keys: Some(1000),
+            max_value_bytes: Some(64 * 1024),
+            max_total_bytes: Some(4 * 1024 * 1024),
+            max_accounts_per_hour: None,
+            account_limit_exempt_private: true,
+        }
+    }
+}
+
+/// Cosmetic details identifying the server to clients and launchers
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Display name for the server, can be included in the menu message
+    /// using the {s} placeholder (Default: None)
+    pub name: Option<String>,
+    /// Short description of the server shown by launchers (Default: None)
+    pub description: Option<String>,
+}
+
+/// Configuration for client networking details
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct NetConfig {
+    /// Whether to trust the external address claimed by clients, when
+    /// disabled claimed public addresses that differ from the connection
+    /// address are replaced with the connection address (Default: false)
+    pub trust_client_address: bool,
+}
+
+/// Configuration for client sessions
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SessionsConfig {
+    /// Maximum number of packets a session can send each second, sessions
+    /// exceeding this are disconnected. None to disable the limit (Default: None)
+    pub max_packets_per_second: Option<u32>,
+    /// Number of packets a session can send at once before the rate limit
+    /// applies, allows for bursts when joining games (Default: 200)
+    pub packet_burst: u32,
+    /// Number of seconds after a session token expires that it can still
+    /// be used to resume a session (Default: 0)
+    pub resume_grace_seconds: u64,
+    /// Maximum size in bytes of packets sent by clients, clients sending larger
+    /// packets are disconnected. None to disable the limit (Default: 1 MiB)
+    pub max_packet_bytes: Option<usize>,
+    /// Whether to send a heartbeat to sessions that have stopped sending
+    /// packets before disconnecting them, any packet from the client keeps
+    /// the session alive when enabled (Default: false)
+    pub server_keepalive: bool,
+    /// Maximum number of connected sessions (Including unauthenticated sessions),
+    /// new connections are refused once reached. None to disable the limit
+    /// (Default: None)
+    pub max_total: Option<usize>,
+    /// Maximum number of requests from a session that can result in a routing
+    /// or system error, sessions exceeding this are disconnected. Expected
+    /// errors such as a wrong password are not counted. None to disable the
+    /// limit (Default: None)
+    pub max_errors: Option<u32>,
+    /// Maximum number of subscribers a session can have at once, further
+    /// subscriptions are rejected. Must allow one subscription for each other
+    /// player in a full game (See [GameConfig::max_players]). None to disable
+    /// the limit (Default: 16)
+    pub max_subscriptions: Option<usize>,
+    /// Number of seconds a connection has to complete the HTTP upgrade
+    /// handshake before it is dropped (Default: 10)
+    pub upgrade_timeout_seconds: u64,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self {
+            max_packets_per_second: None,
+            packet_burst: 200,
+            resume_grace_seconds: 0,
+            max_packet_bytes: Some(1024 * 1024),
+            server_keepalive: false,
+            max_total: None,
+            max_errors: None,
+            max_subscriptions: Some(16),
+            upgrade_timeout_seconds: 10,
+        }
+    }
+}
+
+/// Configuration for the client configs provided to clients
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ClientConfigSettings {
+    /// Minimum size in bytes of the coalesced before it is compressed, smaller
+    /// coalesced files are sent without compression (Default: 1024)
+    pub compress_min_bytes: usize,
+}
+
+impl Default for ClientConfigSettings {
+    fn default() -> Self {
+        Self {
+            compress_min_bytes: 1024,
+        }
+    }
+}
+
+/// Configuration for the rotating banner and new item assigned to players,
+/// the rotation advances through the pools once every period
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct RotationConfig {
+    /// Pool of banner reward IDs stored as the player "csreward" value,
+    /// empty to disable rotating banners (Default: Empty)
+    pub banners: Vec<u32>,
+    /// Pool of values stored as the player "NewItem" value, empty to
+    /// disable rotating new items (Default: Empty)
+    pub new_items: Vec<String>,
+    /// Number of hours between each rotation (Default: 168, weekly)
+    pub period_hours: u64,
+}
+
+impl Default for RotationConfig {
+    fn default() -> Self {
+        Self {
+            banners: Vec::new(),
+            new_items: Vec::new(),
+            period_hours: 24 * 7,
+        }
+    }
+}
+
+/// Configuration for the HTTP server
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Maximum size in bytes of API request bodies, larger requests
+    /// are rejected with 413 Payload Too Large (Default: 1 MiB)
+    pub max_body_bytes: usize,
+    /// Maximum size in bytes of player data request bodies which can
+    /// be larger than other requests (Default: 8 MiB)
+    pub max_player_data_body_bytes: usize,
+    /// Rate limit applied to each address for the unauthenticated game
+    /// and public content routes, [None] to disable (Default: None)
+    pub public_rate_limit: Option<RateLimitConfig>,
+    /// Content types to serve public content with keyed by file extension,
+    /// overrides the built-in content types (Default: Empty)
+    pub content_types: BTreeMap<String, String>,
+    /// Whether the games and leaderboard API routes require authentication,
+    /// the game client routes are never authenticated (Default: false)
+    pub require_auth_for_reads: bool,
+}
+
+/// Configuration for a token bucket rate limit
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RateLimitConfig {
+    /// Maximum number of requests allowed at once
+    pub burst: u32,
+    /// Number of requests allowed each second once the burst is used
+    pub per_second: u32,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 1024 * 1024,
+            max_player_data_body_bytes: 8 * 1024 * 1024,
+            public_rate_limit: None,
+            content_types: BTreeMap::new(),
+            require_auth_for_reads: false,
+        }
+    }
+}
+
+/// Configuration for background maintenance jobs
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// Number of days without logging in before an account is marked as
+    /// deactivated, checked once a day starting a day after startup. No
+    /// account data is deleted and accounts are reactivated when they next
+    /// log in. Accounts without any recorded login and super admin accounts
+    /// are never deactivated (Default: None, disabled)
+    pub prune_inactive_days: Option<u32>,
+    /// Number of hours between compacting the database file, runs are
+    /// delayed until no players are connected (Default: None, disabled)
+    pub vacuum_interval_hours: Option<u32>,
+}
+
+/// Configuration for the database connection pool
+///
+/// SQLite only allows a single writer at a time, with the write-ahead log
+/// enabled the other pooled connections can continue reading while a write
+/// is in progress
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// Maximum number of connections the pool can open (Default: 10)
+    pub max_connections: u32,
+    /// Minimum number of connections the pool keeps open (Default: 1)
+    pub min_connections: u32,
+    /// Number of seconds to wait for a free connection before
+    /// failing (Default: 30)
+    pub acquire_timeout_seconds: u64,
+    /// Number of milliseconds SQLite waits on a locked database before
+    /// failing with "database is locked" (Default: 5000)
+    pub busy_timeout_ms: u64,
+    /// Whether SQLite should use the write-ahead log journal mode which
+    /// allows reads to happen alongside a write (Default: true)
+    pub wal: bool,
+    /// Number of times to retry connecting to the database on startup
+    /// before giving up (Default: 5)
+    pub startup_retries: u32,
+    /// Number of milliseconds to wait before the first connection retry,
+    /// doubled after each failed attempt (Default: 500)
+    pub startup_backoff_ms: u64,
+    /// Number of seconds between each database latency health
+    /// check (Default: 30)
+    pub health_check_interval_seconds: u64,
+    /// Number of milliseconds a health check query can take before the
+    /// database is reported as slow (Default: 500)
+    pub slow_threshold_ms: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 1,
+            acquire_timeout_seconds: 30,
+            busy_timeout_ms: 5000,
+            wal: true,
+            startup_retries: 5,
+            startup_backoff_ms: 500,
+            health_check_interval_seconds: 30,
+            slow_threshold_ms: 500,
         }
     }
 }
@@ -207,6 +784,10 @@ impl Default for APIConfig {
 pub struct GalaxyAtWarConfig {
     pub decay: f32,
     pub promotions: bool,
+    /// Maximum total amount a player can increase their ratings by each
+    /// day, increases past the cap are clamped. None to disable the cap
+    /// (Default: None)
+    pub daily_increase_cap: Option<u32>,
 }
 
 impl Default for GalaxyAtWarConfig {
@@ -214,6 +795,7 @@ impl Default for GalaxyAtWarConfig {
         Self {
             decay: 0.0,
             promotions: true,
+            daily_increase_cap: None,
         }
     }
 }
@@ -224,6 +806,20 @@ pub struct DashboardConfig {
     pub super_email: Option<String>,
     pub super_password: Option<String>,
     pub disable_registration: bool,
+    /// Where the dashboard assets are served from (Default: Embedded)
+    pub source: DashboardSource,
+}
+
+/// Source the dashboard assets are served from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DashboardSource {
+    /// Serve the assets compiled into the server
+    #[default]
+    Embedded,
+    /// Serve the assets from the data/dashboard folder, used for testing
+    /// custom dashboard builds
+    Folder,
 }
 
 impl DashboardConfig {
@@ -240,6 +836,20 @@ pub struct RetrieverConfig {
     pub enabled: bool,
     pub origin_fetch: bool,
     pub origin_fetch_data: bool,
+    /// Path to a JSON file containing an object of player data keys and values
+    /// that new Origin accounts start with when their data is not fetched from
+    /// the official servers. The server fails to start if the file can't be
+    /// loaded (Default: None)
+    pub origin_default_data_file: Option<PathBuf>,
+    /// Number of seconds to wait for a connection to the official
+    /// server to complete before giving up (Default: 10)
+    pub connect_timeout_seconds: u64,
+    /// Maximum number of sessions that can be open to the official server
+    /// at once, further sessions wait for an open session to close until
+    /// the connect timeout. When running as a MITM server every connected
+    /// client holds a session so this also limits the number of connected
+    /// players. Cannot be 0, None to disable the limit (Default: None)
+    pub max_concurrent_sessions: Option<usize>,
 }
 
 impl Default for RetrieverConfig {
@@ -248,6 +858,155 @@ impl Default for RetrieverConfig {
             enabled: true,
             origin_fetch: true,
             origin_fetch_data: true,
+            origin_default_data_file: None,
+            connect_timeout_seconds: 10,
+            max_concurrent_sessions: None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Config, ConfigError, QosConfig, QosServerConfig, UdpTunnelConfig};
+    use std::net::Ipv4Addr;
+
+    /// Tests that the advertised tunnel endpoint uses the public
+    /// overrides when they are set and falls back to the detected
+    /// public address for the host
+    #[test]
+    fn test_udp_tunnel_public_address() {
+        let detected = Some(Ipv4Addr::new(203, 0, 113, 7));
+
+        // Neither configured nor detected
+        let config = UdpTunnelConfig::default();
+        assert_eq!(config.get_exposed_host(None), None);
+        assert_eq!(config.get_exposed_port(), Some(9032));
+
+        // Detected address used without a configured host
+        assert_eq!(
+            config.get_exposed_host(detected).as_deref(),
+            Some("203.0.113.7")
+        );
+
+        // Configured host takes priority over the detected address
+        let config: UdpTunnelConfig =
+            serde_json::from_str(r#"{"public_host": "relay.example.com", "public_port": 19032}"#)
+                .unwrap();
+        assert_eq!(
+            config.get_exposed_host(detected).as_deref(),
+            Some("relay.example.com")
+        );
+        assert_eq!(
+            config.get_exposed_host(None).as_deref(),
+            Some("relay.example.com")
+        );
+        assert_eq!(config.get_exposed_port(), Some(19032));
+
+        let config = UdpTunnelConfig {
+            enabled: false,
+            ..config
+        };
+        assert_eq!(config.get_exposed_host(detected), None);
+        assert_eq!(config.get_exposed_port(), None);
+    }
+
+    /// Tests that the QoS server type and probe count are read from the
+    /// same QoS config object
+    #[test]
+    fn test_qos_config() {
+        let config: QosConfig = serde_json::from_str(r#"{"type": "disabled"}"#).unwrap();
+        assert!(matches!(config.server, QosServerConfig::Disabled));
+        assert_eq!(config.probe_count, 1);
+
+        let config: QosConfig = serde_json::from_str(
+            r#"{"type": "custom", "host": "qos.example.com", "port": 17502, "probe_count": 5}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            config.server,
+            QosServerConfig::Custom { ref host, port: 17502 } if host == "qos.example.com"
+        ));
+        assert_eq!(config.probe_count, 5);
+
+        let config: QosConfig = serde_json::from_str(r#"{}"#).unwrap();
+        assert!(matches!(config.server, QosServerConfig::Local));
+        assert_eq!(config.probe_count, 1);
+    }
+
+    /// Tests that a partial QoS config without the server type keeps the
+    /// default server and the rest of the config
+    #[test]
+    fn test_qos_config_partial() {
+        let config: Config =
+            serde_json::from_str(r#"{"port": 4000, "qos": {"probe_count": 3}}"#).unwrap();
+        assert_eq!(config.port, 4000);
+        assert!(matches!(config.qos.server, QosServerConfig::Local));
+        assert_eq!(config.qos.probe_count, 3);
+
+        // Invalid server types are still rejected
+        let result = serde_json::from_str::<Config>(r#"{"qos": {"type": "unknown"}}"#);
+        assert!(result.is_err());
+    }
+
+    /// Tests that zero ports are rejected when validating the config
+    #[test]
+    fn test_validate_zero_port() {
+        assert_eq!(Config::default().validate(), Ok(()));
+
+        let config: Config = serde_json::from_str(r#"{"port": 0}"#).unwrap();
+        assert_eq!(config.validate(), Err(ConfigError::ZeroPort));
+
+        let config: Config = serde_json::from_str(r#"{"external_port": 0}"#).unwrap();
+        assert_eq!(config.validate(), Err(ConfigError::ZeroPort));
+
+        let config: Config = serde_json::from_str(r#"{"udp_tunnel": {"port": 0}}"#).unwrap();
+        assert_eq!(config.validate(), Err(ConfigError::ZeroTunnelPort));
+
+        // Tunnel port is unused when the tunnel is disabled
+        let config: Config =
+            serde_json::from_str(r#"{"udp_tunnel": {"port": 0, "enabled": false}}"#).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+
+        // Ports outside the valid range fail to deserialize
+        assert!(serde_json::from_str::<Config>(r#"{"port": 65536}"#).is_err());
+    }
+
+    /// Tests that a zero official server session limit is rejected
+    #[test]
+    fn test_validate_zero_retriever_sessions() {
+        let config: Config =
+            serde_json::from_str(r#"{"retriever": {"max_concurrent_sessions": 0}}"#).unwrap();
+        assert_eq!(config.validate(), Err(ConfigError::ZeroRetrieverSessions));
+
+        let config: Config =
+            serde_json::from_str(r#"{"retriever": {"max_concurrent_sessions": 16}}"#).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    /// Tests that the subscription limit must cover every other player
+    /// in a full game
+    #[test]
+    fn test_validate_max_subscriptions() {
+        let config: Config =
+            serde_json::from_str(r#"{"game": {"max_players": 17}, "sessions": {}}"#).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+
+        let config: Config =
+            serde_json::from_str(r#"{"game": {"max_players": 32}, "sessions": {}}"#).unwrap();
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::TooFewSubscriptions {
+                max_subscriptions: 16,
+                required: 31,
+                max_players: 32,
+            })
+        );
+
+        // Disabling the limit allows any number of players
+        let config: Config = serde_json::from_str(
+            r#"{"game": {"max_players": 255}, "sessions": {"max_subscriptions": null}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.validate(), Ok(()));
+    }
+}
@@ -36,6 +36,10 @@ pub struct Model {
     pub group_d: u16,
     /// The fifth group value
     pub group_e: u16,
+    /// Total amount the group values were increased by on the day of
+    /// `last_modified`, used to enforce the daily increase cap
+    #[serde(skip)]
+    pub daily_increase: u32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -69,6 +73,7 @@ impl Model {
             group_c: Set(Self::MIN_VALUE),
             group_d: Set(Self::MIN_VALUE),
             group_e: Set(Self::MIN_VALUE),
+            daily_increase: Set(0),
         }
         .insert(db)
         .await
@@ -76,12 +81,43 @@ impl Model {
 
     /// Increases the stored group values increasing them by the `values`
     /// provided for each respective group
+    ///
+    /// When `daily_cap` is provided the total increase across all groups
+    /// for the current day is clamped to the cap
     pub fn add(
         self,
         db: &DatabaseConnection,
         values: [u16; 5],
+        daily_cap: Option<u32>,
     ) -> impl Future<Output = DbResult<Self>> + '_ {
-        self.transform(db, |a, b| a.saturating_add(b).min(Model::MAX_VALUE), values)
+        self.add_at(db, values, daily_cap, Local::now().naive_local())
+    }
+
+    /// Increases the stored group values as of the time `now`, see [Model::add]
+    async fn add_at(
+        self,
+        db: &DatabaseConnection,
+        mut values: [u16; 5],
+        daily_cap: Option<u32>,
+        now: NaiveDateTime,
+    ) -> DbResult<Self> {
+        let current = self.groups();
+        let mut daily_increase = self.daily_increase_at(now);
+
+        for (value, current) in values.iter_mut().zip(current) {
+            // Only count the increase that can actually be applied
+            *value = (*value).min(Self::MAX_VALUE.saturating_sub(current));
+
+            if let Some(cap) = daily_cap {
+                let remaining = cap.saturating_sub(daily_increase);
+                *value = (*value).min(remaining.try_into().unwrap_or(u16::MAX));
+            }
+
+            daily_increase += *value as u32;
+        }
+
+        let groups: [u16; 5] = std::array::from_fn(|index| current[index] + values[index]);
+        self.update(db, groups, daily_increase, now).await
     }
 
     /// Decrease the stored group values decreasing them by the `values`
@@ -108,15 +144,51 @@ impl Model {
         F: Fn(u16, u16) -> u16,
     {
         let current_time = Local::now().naive_local();
+        let current = self.groups();
+        let groups: [u16; 5] = std::array::from_fn(|index| action(current[index], values[index]));
+        let daily_increase = self.daily_increase_at(current_time);
+        self.update(db, groups, daily_increase, current_time).await
+    }
+
+    /// The current group values
+    fn groups(&self) -> [u16; 5] {
+        [
+            self.group_a,
+            self.group_b,
+            self.group_c,
+            self.group_d,
+            self.group_e,
+        ]
+    }
+
+    /// The amount the groups have been increased by on the day of `now`,
+    /// the stored increase only applies to the day of the last modification
+    fn daily_increase_at(&self, now: NaiveDateTime) -> u32 {
+        if self.last_modified.date() == now.date() {
+            self.daily_increase
+        } else {
+            0
+        }
+    }
+
+    /// Stores the provided group values and daily increase
+    async fn update(
+        self,
+        db: &DatabaseConnection,
+        groups: [u16; 5],
+        daily_increase: u32,
+        now: NaiveDateTime,
+    ) -> DbResult<Self> {
         ActiveModel {
             id: Set(self.id),
             player_id: Set(self.player_id),
-            last_modified: Set(current_time),
-            group_a: Set(action(self.group_a, values[0])),
-            group_b: Set(action(self.group_b, values[1])),
-            group_c: Set(action(self.group_c, values[2])),
-            group_d: Set(action(self.group_d, values[3])),
-            group_e: Set(action(self.group_e, values[4])),
+            last_modified: Set(now),
+            group_a: Set(groups[0]),
+            group_b: Set(groups[1]),
+            group_c: Set(groups[2]),
+            group_d: Set(groups[3]),
+            group_e: Set(groups[4]),
+            daily_increase: Set(daily_increase),
         }
         .update(db)
         .await
@@ -137,3 +209,70 @@ impl Model {
         self.sub(db, [decay_value; 5]).await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Model;
+    use crate::database::{
+        entities::{Player, PlayerRole},
+        test_database,
+    };
+    use chrono::{Duration, Local};
+
+    /// Tests that increases within a day are clamped at the daily cap
+    /// and that the cap resets the following day
+    #[tokio::test]
+    async fn test_daily_increase_cap() {
+        let db = test_database().await;
+        let player = Player::create(
+            &db,
+            "test@test.com".to_string(),
+            "Test".to_string(),
+            None,
+            PlayerRole::Default,
+        )
+        .await
+        .unwrap();
+
+        let today = Local::now().naive_local();
+        let gaw = Model::get(&db, player.id).await.unwrap();
+
+        let gaw = gaw
+            .add_at(&db, [40, 40, 0, 0, 0], Some(100), today)
+            .await
+            .unwrap();
+        assert_eq!(gaw.daily_increase, 80);
+
+        // Only the remaining 20 of the cap can be applied
+        let gaw = gaw
+            .add_at(&db, [0, 0, 15, 15, 15], Some(100), today)
+            .await
+            .unwrap();
+        assert_eq!(gaw.daily_increase, 100);
+        assert_eq!(
+            gaw.groups(),
+            [
+                Model::MIN_VALUE + 40,
+                Model::MIN_VALUE + 40,
+                Model::MIN_VALUE + 15,
+                Model::MIN_VALUE + 5,
+                Model::MIN_VALUE
+            ]
+        );
+
+        let gaw = gaw
+            .add_at(&db, [10, 0, 0, 0, 0], Some(100), today)
+            .await
+            .unwrap();
+        assert_eq!(gaw.group_a, Model::MIN_VALUE + 40);
+
+        // The cap resets on the following day
+        let tomorrow = today + Duration::days(1);
+        let gaw = gaw
+            .add_at(&db, [10, 0, 0, 0, 0], Some(100), tomorrow)
+            .await
+            .unwrap();
+        assert_eq!(gaw.group_a, Model::MIN_VALUE + 50);
+        assert_eq!(gaw.daily_increase, 10);
+    }
+}
@@ -174,7 +174,7 @@ impl Model {
         // The number of ranks to start at before the centered rank
         let before = (count / 2)
             // Add 1 when the count is even
-            .saturating_add((count % 2 == 0) as u32);
+            .saturating_add(count.is_multiple_of(2) as u32);
 
         // Determine the starting rank saturating zero bounds
         let start = value.rank.saturating_sub(before);
@@ -1,13 +1,128 @@
-use crate::{database::DbResult, utils::types::PlayerID};
+use crate::{config::PlayerConfig, database::DbResult, utils::types::PlayerID};
 use sea_orm::{
     entity::prelude::*,
-    sea_query::OnConflict,
+    sea_query::{Expr, OnConflict},
     ActiveValue::{NotSet, Set},
-    DeleteResult, InsertResult,
+    DeleteResult, InsertResult, PaginatorTrait, QuerySelect, TransactionTrait,
 };
 use serde::Serialize;
 use std::future::Future;
 
+/// Names of the player classes
+pub static CLASS_NAMES: &[&str] = &[
+    "Adept",
+    "Soldier",
+    "Engineer",
+    "Sentinel",
+    "Infiltrator",
+    "Vanguard",
+];
+
+/// Default data for each of the player characters
+pub static CHARACTER_DATA: &[&str] = &[
+    "20;4;AdeptHumanMale;MAdept;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptHumanFemale;FAdept;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptAsari;Asari;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptDrell;Drell;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptAsariCommando;Asari;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptHumanMaleCerberus;Human Male;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptN7;N7 Fury;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptVolus;Volus Adept;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptKrogan;Krogan Shaman;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptBatarian;Krogan Shaman;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;AdeptCollector;Awakened Collector;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierHumanMale;MSoldier;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierHumanFemale;FSoldier;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierKrogan;Krogan;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierTurian;Turian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierHumanMaleBF3;MSoldier;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierBatarian;Batarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierVorcha;Vorcha;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierN7;N7 Destroyer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;N7SoldierTurian;Turian Havoc;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierGeth;Geth Trooper;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierMQuarian;Geth Trooper;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SoldierGethDestroyer;Geth Juggernaut;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerHumanMale;MEngineer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerHumanFemale;FEngineer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerQuarian;FEngineer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerSalarian;Salarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerGeth;Geth;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerQuarianMale;Quarian Male;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerN7;N7 Demolisher;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerVolus;Volus Engineer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerTurian;Turian Saboteur;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerVorcha;Turian Saboteur;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;EngineerMerc;Talon Mercenary;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelHumanMale;MSentinel;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelHumanFemale;FSentinel;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelTurian;Turian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelKrogan;Krogan;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelBatarian;Batarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelVorcha;Vorcha;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelN7;N7 Paladin;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelVolus;Volus Mercenary;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelAsari;Volus Mercenary;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;SentinelKroganWarlord;Krogan Warlord;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorHumanMale;MInfiltrate;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorHumanFemale;FInfiltrate;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorSalarian;Salarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorQuarian;Quarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorGeth;Geth;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorQuarianMale;Quarian Male;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorN7;N7 Shadow;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;N7InfiltratorTurian;Turian Ghost;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorDrell;Drell Assassin;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorAsari;Drell Assassin;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorFembot;Krogan Warlord;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;InfiltratorHumanFemaleBF3;MSoldier;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardHumanMale;MVanguard;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardHumanFemale;FVanguard;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardDrell;Drell;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardAsari;Asari;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardKrogan;Krogan;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardHumanMaleCerberus;Human Male;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardN7;N7 Slayer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardVolus;Volus Protector;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardBatarian;Volus Protector;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+    "20;4;VanguardTurianFemale;Cabal Vanguard;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
+];
+
+/// Number of items stored in the player inventory
+pub const INVENTORY_LENGTH: usize = 671;
+
+/// Limits applied when setting player data
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DataLimits {
+    /// Maximum number of keys the player can have
+    pub max_keys: Option<u32>,
+    /// Maximum size in bytes of a single key and value
+    pub max_value_bytes: Option<usize>,
+    /// Maximum total size in bytes of all the player keys and values
+    pub max_total_bytes: Option<usize>,
+}
+
+impl DataLimits {
+    pub fn from_config(config: &PlayerConfig) -> Self {
+        Self {
+            max_keys: config.max_data_keys,
+            max_value_bytes: config.max_value_bytes,
+            max_total_bytes: config.max_total_bytes,
+        }
+    }
+}
+
+/// Limit that prevented player data from being set
+#[derive(Debug, PartialEq, Eq)]
+pub enum DataLimitExceeded {
+    /// The player already has the maximum number of keys
+    Keys,
+    /// The key and value are larger than the maximum size
+    Size,
+    /// The key and value would take the player past the maximum total size
+    TotalSize,
+}
+
 /// Structure for player data
 #[derive(Serialize, Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "player_data")]
@@ -68,6 +183,113 @@ impl Model {
         .exec(db)
     }
 
+    /// Sets the key value data for the specified player, rejecting data that
+    /// is too large and new keys once the player already has the maximum number
+    /// of keys. Existing keys can be updated as long as the new value is within
+    /// the size limits
+    ///
+    /// The limits are checked and the data is set within a single transaction
+    /// so that concurrent updates can't take the player past the limits
+    ///
+    /// `db`        The database connection
+    /// `player_id` The ID of the player to set the data for
+    /// `key`       The data key
+    /// `value`     The data value
+    /// `limits`    The limits to apply to the data
+    pub async fn set_limited(
+        db: &DatabaseConnection,
+        player_id: PlayerID,
+        key: String,
+        value: String,
+        limits: DataLimits,
+    ) -> DbResult<Result<(), DataLimitExceeded>> {
+        let size = key.len() + value.len();
+        if limits
+            .max_value_bytes
+            .is_some_and(|max_value_bytes| size > max_value_bytes)
+        {
+            return Ok(Err(DataLimitExceeded::Size));
+        }
+
+        let transaction = db.begin().await?;
+
+        // Update any existing value first, the write acquires the database write
+        // lock so concurrent updates wait here until this transaction completes
+        // rather than checking the limits against stale counts
+        let updated = Entity::update_many()
+            .col_expr(Column::Value, Expr::value(value.clone()))
+            .filter(Column::PlayerId.eq(player_id).and(Column::Key.eq(&key)))
+            .exec(&transaction)
+            .await?;
+        let exists = updated.rows_affected > 0;
+
+        if let Some(max_keys) = limits.max_keys {
+            if !exists {
+                let count = Entity::find()
+                    .filter(Column::PlayerId.eq(player_id))
+                    .count(&transaction)
+                    .await?;
+
+                if count >= max_keys as u64 {
+                    transaction.rollback().await?;
+                    return Ok(Err(DataLimitExceeded::Keys));
+                }
+            }
+        }
+
+        if let Some(max_total_bytes) = limits.max_total_bytes {
+            // Size of the other keys, the existing value for this key is replaced
+            let other_size = Self::total_size(&transaction, player_id, &key).await?;
+            if other_size + size > max_total_bytes {
+                transaction.rollback().await?;
+                return Ok(Err(DataLimitExceeded::TotalSize));
+            }
+        }
+
+        if !exists {
+            Entity::insert(ActiveModel {
+                id: NotSet,
+                player_id: Set(player_id),
+                key: Set(key),
+                value: Set(value),
+            })
+            .exec(&transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(Ok(()))
+    }
+
+    /// Gets the total size in bytes of the player data keys and values for
+    /// the specified player excluding the `exclude_key` data
+    ///
+    /// `db`          The database connection
+    /// `player_id`   The ID of the player to get the size for
+    /// `exclude_key` The data key to exclude from the total
+    async fn total_size<C>(db: &C, player_id: PlayerID, exclude_key: &str) -> DbResult<usize>
+    where
+        C: ConnectionTrait,
+    {
+        /// Expression for the size in bytes of the key and value
+        const SIZE_EXPR: &str =
+            "SUM(LENGTH(CAST(\"key\" AS BLOB)) + LENGTH(CAST(\"value\" AS BLOB)))";
+
+        let size: Option<Option<i64>> = Entity::find()
+            .select_only()
+            .expr(Expr::cust(SIZE_EXPR))
+            .filter(
+                Column::PlayerId
+                    .eq(player_id)
+                    .and(Column::Key.ne(exclude_key)),
+            )
+            .into_tuple()
+            .one(db)
+            .await?;
+
+        Ok(size.flatten().unwrap_or_default() as usize)
+    }
+
     /// Bulk inserts a collection of player data for the provided player. Will not handle
     /// conflicts so this should only be done on a freshly create player where data doesn't
     /// already exist
@@ -99,6 +321,109 @@ impl Model {
         .exec(db)
     }
 
+    /// Creates the default player data that a fresh player account is
+    /// reset to, consisting of the base data and the class and character
+    /// data with no progression
+    pub fn default_data() -> Vec<(String, String)> {
+        let mut data = Vec::with_capacity(1 + CLASS_NAMES.len() + CHARACTER_DATA.len());
+
+        let inventory = "00".repeat(INVENTORY_LENGTH);
+        data.push((
+            "Base".to_string(),
+            format!("20;4;0;-1;0;0;0;0;0;0;{inventory}"),
+        ));
+
+        for (index, class_name) in CLASS_NAMES.iter().enumerate() {
+            let key = format!("class{}", index + 1);
+            let value = format!("20;4;{class_name};1;0.0000;0");
+            data.push((key, value));
+        }
+
+        for (index, value) in CHARACTER_DATA.iter().enumerate() {
+            let key = format!("char{index}");
+            data.push((key, value.to_string()));
+        }
+
+        data
+    }
+
+    /// Resets the player data for the provided player replacing all
+    /// their existing data with the [Model::default_data]
+    ///
+    /// `db`        The database connection
+    /// `player_id` The ID of the player to reset
+    pub async fn reset(db: &DatabaseConnection, player_id: PlayerID) -> DbResult<()> {
+        let transaction = db.begin().await?;
+
+        Entity::delete_many()
+            .filter(Column::PlayerId.eq(player_id))
+            .exec(&transaction)
+            .await?;
+
+        Entity::insert_many(
+            Self::default_data()
+                .into_iter()
+                .map(|(key, value)| ActiveModel {
+                    id: NotSet,
+                    player_id: Set(player_id),
+                    key: Set(key),
+                    value: Set(value),
+                }),
+        )
+        .exec(&transaction)
+        .await?;
+
+        transaction.commit().await
+    }
+
+    /// Sets the provided key value `data` for every player, existing
+    /// values for the keys are replaced
+    ///
+    /// `db`   The database connection
+    /// `data` The data keys and values to set
+    pub async fn set_for_all_players(
+        db: &DatabaseConnection,
+        data: &[(&str, String)],
+    ) -> DbResult<()> {
+        /// Number of players to insert data for in each query, keeps the
+        /// query within the SQLite variable limit
+        const PLAYERS_PER_QUERY: usize = 256;
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let player_ids: Vec<PlayerID> = super::players::Entity::find()
+            .select_only()
+            .column(super::players::Column::Id)
+            .into_tuple()
+            .all(db)
+            .await?;
+
+        let transaction = db.begin().await?;
+
+        for player_ids in player_ids.chunks(PLAYERS_PER_QUERY) {
+            Entity::insert_many(player_ids.iter().flat_map(|player_id| {
+                data.iter().map(|(key, value)| ActiveModel {
+                    id: NotSet,
+                    player_id: Set(*player_id),
+                    key: Set(key.to_string()),
+                    value: Set(value.clone()),
+                })
+            }))
+            .on_conflict(
+                // Update the value column if a key already exists
+                OnConflict::columns([Column::PlayerId, Column::Key])
+                    .update_column(Column::Value)
+                    .to_owned(),
+            )
+            .exec(&transaction)
+            .await?;
+        }
+
+        transaction.commit().await
+    }
+
     /// Deletes the player data with the provided key for the
     /// current player
     ///
@@ -153,3 +478,206 @@ impl Model {
 pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
+
+#[cfg(test)]
+mod test {
+    use super::{DataLimitExceeded, DataLimits, Model};
+    use crate::{
+        config::DatabaseConfig,
+        database::{
+            connect_pool,
+            entities::{Player, PlayerRole},
+            migration::{Migrator, MigratorTrait},
+            test_database,
+        },
+    };
+    use futures_util::future::join_all;
+
+    /// Tests that resetting a player replaces their data with the defaults
+    #[tokio::test]
+    async fn test_reset() {
+        let db = test_database().await;
+        let player = Player::create(
+            &db,
+            "test@test.com".to_string(),
+            "Test".to_string(),
+            None,
+            PlayerRole::Default,
+        )
+        .await
+        .unwrap();
+
+        Model::set(&db, player.id, "Base".to_string(), "20;4;100".to_string())
+            .await
+            .unwrap();
+        Model::set(&db, player.id, "Custom".to_string(), "Value".to_string())
+            .await
+            .unwrap();
+
+        Model::reset(&db, player.id).await.unwrap();
+
+        let mut data: Vec<(String, String)> = Model::all(&db, player.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|model| (model.key, model.value))
+            .collect();
+        data.sort();
+
+        let mut expected = Model::default_data();
+        expected.sort();
+
+        assert_eq!(data, expected);
+
+        // Account details should be untouched
+        let reset_player = Player::by_id(&db, player.id).await.unwrap();
+        assert_eq!(reset_player, Some(player));
+    }
+
+    /// Tests that new keys past the limit are rejected while existing
+    /// keys can still be updated
+    #[tokio::test]
+    async fn test_set_limited() {
+        let db = test_database().await;
+        let player = Player::create(
+            &db,
+            "test@test.com".to_string(),
+            "Test".to_string(),
+            None,
+            PlayerRole::Default,
+        )
+        .await
+        .unwrap();
+
+        let limits = DataLimits {
+            max_keys: Some(2),
+            ..Default::default()
+        };
+
+        for key in ["First", "Second"] {
+            let set = Model::set_limited(&db, player.id, key.to_string(), "1".to_string(), limits)
+                .await
+                .unwrap();
+            assert_eq!(set, Ok(()));
+        }
+
+        let set = Model::set_limited(&db, player.id, "Third".to_string(), "1".to_string(), limits)
+            .await
+            .unwrap();
+        assert_eq!(set, Err(DataLimitExceeded::Keys));
+        assert!(Model::get(&db, player.id, "Third").await.unwrap().is_none());
+
+        let set = Model::set_limited(&db, player.id, "First".to_string(), "2".to_string(), limits)
+            .await
+            .unwrap();
+        assert_eq!(set, Ok(()));
+
+        let value = Model::get(&db, player.id, "First").await.unwrap().unwrap();
+        assert_eq!(value.value, "2");
+    }
+
+    /// Tests that data within the size limits is stored while oversized
+    /// data and data exceeding the total size is rejected
+    #[tokio::test]
+    async fn test_set_limited_size() {
+        let db = test_database().await;
+        let player = Player::create(
+            &db,
+            "test@test.com".to_string(),
+            "Test".to_string(),
+            None,
+            PlayerRole::Default,
+        )
+        .await
+        .unwrap();
+
+        let limits = DataLimits {
+            max_value_bytes: Some(16),
+            max_total_bytes: Some(24),
+            ..Default::default()
+        };
+
+        // Within the limits (12 bytes)
+        let set = Model::set_limited(&db, player.id, "Key".to_string(), "A".repeat(9), limits)
+            .await
+            .unwrap();
+        assert_eq!(set, Ok(()));
+
+        // Larger than a single value can be (17 bytes)
+        let set = Model::set_limited(&db, player.id, "Big".to_string(), "A".repeat(14), limits)
+            .await
+            .unwrap();
+        assert_eq!(set, Err(DataLimitExceeded::Size));
+        assert!(Model::get(&db, player.id, "Big").await.unwrap().is_none());
+
+        // Exceeds the total size (12 + 16 bytes)
+        let set = Model::set_limited(&db, player.id, "Other".to_string(), "A".repeat(11), limits)
+            .await
+            .unwrap();
+        assert_eq!(set, Err(DataLimitExceeded::TotalSize));
+        assert!(Model::get(&db, player.id, "Other").await.unwrap().is_none());
+
+        // Replacing an existing value only counts the new value (16 bytes)
+        let set = Model::set_limited(&db, player.id, "Key".to_string(), "B".repeat(13), limits)
+            .await
+            .unwrap();
+        assert_eq!(set, Ok(()));
+    }
+
+    /// Tests that concurrent updates setting new keys can't take the player
+    /// past the maximum number of keys
+    #[tokio::test]
+    async fn test_set_limited_concurrent() {
+        let path = std::env::temp_dir().join(format!("pocket-relay-{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+
+        // Pooled connections so the updates run concurrently
+        let db = connect_pool(&url, &DatabaseConfig::default())
+            .await
+            .unwrap();
+        Migrator::up(&db, None).await.unwrap();
+
+        let player = Player::create(
+            &db,
+            "test@test.com".to_string(),
+            "Test".to_string(),
+            None,
+            PlayerRole::Default,
+        )
+        .await
+        .unwrap();
+
+        let limits = DataLimits {
+            max_keys: Some(5),
+            ..Default::default()
+        };
+
+        let results = join_all((0..20).map(|index| {
+            let db = db.clone();
+            tokio::spawn(async move {
+                Model::set_limited(
+                    &db,
+                    player.id,
+                    format!("Key{index}"),
+                    "1".to_string(),
+                    limits,
+                )
+                .await
+            })
+        }))
+        .await;
+
+        let allowed = results
+            .into_iter()
+            .map(|result| result.unwrap().unwrap())
+            .filter(|result| result.is_ok())
+            .count();
+        let stored = Model::all(&db, player.id).await.unwrap().len();
+
+        db.close().await.unwrap();
+        _ = std::fs::remove_file(&path);
+
+        assert_eq!(allowed, 5);
+        assert_eq!(stored, 5);
+    }
+}
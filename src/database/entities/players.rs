@@ -3,7 +3,9 @@
 use crate::database::DbResult;
 use crate::utils::types::PlayerID;
 use futures_util::future::BoxFuture;
+use log::error;
 use sea_orm::prelude::*;
+use sea_orm::sea_query::Func;
 use sea_orm::{
     ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DeleteResult, EntityTrait,
     IntoActiveModel, QueryFilter,
@@ -28,6 +30,12 @@ pub struct Model {
     pub role: PlayerRole,
     /// Last time the player has logged in
     pub last_login_at: Option<DateTimeUtc>,
+    /// Persistent ID of the Origin account linked to the player
+    #[serde(skip)]
+    pub origin_id: Option<i64>,
+    /// Time the player was marked inactive by the inactive account
+    /// pruning, cleared when the player next logs in
+    pub deactivated_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -101,7 +109,7 @@ impl Model {
     /// Deletes the provided player
     ///
     /// `db` The database connection
-    pub fn delete(self, db: &DatabaseConnection) -> BoxFuture<DbResult<DeleteResult>> {
+    pub fn delete(self, db: &DatabaseConnection) -> BoxFuture<'_, DbResult<DeleteResult>> {
         // Delete player itself
         self.into_active_model().delete(db)
     }
@@ -129,6 +137,34 @@ impl Model {
         Entity::find().filter(Column::Email.eq(email)).one(db)
     }
 
+    /// Attempts to find a player with the provided email ignoring
+    /// the case of the stored and provided emails
+    ///
+    /// `db`    The database connection
+    /// `email` The email address to search for
+    pub fn by_email_ignore_case<'a>(
+        db: &'a DatabaseConnection,
+        email: &str,
+    ) -> impl Future<Output = DbResult<Option<Self>>> + Send + 'a {
+        Entity::find()
+            .filter(Expr::expr(Func::lower(Expr::col(Column::Email))).eq(email.to_lowercase()))
+            .one(db)
+    }
+
+    /// Attempts to find the player linked to the Origin account with
+    /// the provided persistent ID
+    ///
+    /// `db`        The database connection
+    /// `origin_id` The persistent ID of the Origin account
+    pub fn by_origin_id(
+        db: &DatabaseConnection,
+        origin_id: i64,
+    ) -> impl Future<Output = DbResult<Option<Self>>> + Send + '_ {
+        Entity::find()
+            .filter(Column::OriginId.eq(origin_id))
+            .one(db)
+    }
+
     /// Attempts to find a player with the provided username
     ///
     /// `db`    The database connection
@@ -167,7 +203,6 @@ impl Model {
     ///
     /// `db`       The database connection
     /// `password` The new hashed password
-
     pub fn set_password(
         self,
         db: &DatabaseConnection,
@@ -192,6 +227,21 @@ impl Model {
         model.update(db)
     }
 
+    /// Links the provided player to the Origin account with the
+    /// provided persistent ID
+    ///
+    /// `db`        The database connection
+    /// `origin_id` The persistent ID of the Origin account
+    pub fn set_origin_id(
+        self,
+        db: &DatabaseConnection,
+        origin_id: i64,
+    ) -> BoxFuture<'_, DbResult<Self>> {
+        let mut model = self.into_active_model();
+        model.origin_id = Set(Some(origin_id));
+        model.update(db)
+    }
+
     /// Updates the basic details of the provided player if
     /// they are provided
     ///
@@ -217,7 +267,49 @@ impl Model {
         model.update(db)
     }
 
-    /// Sets the last login timestamp of
+    /// Marks all the players that last logged in before `before` as
+    /// deactivated, players without a last login time, super admins and
+    /// players that are already deactivated are left unchanged. No player
+    /// data is removed and the player is reactivated on their next login
+    ///
+    /// Returns the number of deactivated players
+    ///
+    /// `db`     The database connection
+    /// `before` Time players must have logged in after to stay active
+    pub async fn deactivate_inactive(
+        db: &DatabaseConnection,
+        before: DateTimeUtc,
+    ) -> DbResult<u64> {
+        let result = Entity::update_many()
+            .col_expr(Column::DeactivatedAt, Expr::value(Some(chrono::Utc::now())))
+            .filter(
+                Column::LastLoginAt
+                    .lt(before)
+                    .and(Column::Role.ne(PlayerRole::SuperAdmin))
+                    .and(Column::DeactivatedAt.is_null()),
+            )
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+
+    /// Records that the player has just logged in, the timestamp update is
+    /// performed in the background so it doesn't delay the auth response
+    ///
+    /// `db` The database connection
+    /// `id` The ID of the player that logged in
+    pub fn record_login(db: &DatabaseConnection, id: PlayerID) {
+        let db = db.clone();
+        tokio::spawn(async move {
+            if let Err(err) = Self::set_last_login(&db, id, chrono::Utc::now()).await {
+                error!("failed to store last login time: {err}");
+            }
+        });
+    }
+
+    /// Sets the last login timestamp of a player, reactivating the
+    /// player if they were deactivated for inactivity
     pub async fn set_last_login(
         db: &DatabaseConnection,
         id: PlayerID,
@@ -225,6 +317,7 @@ impl Model {
     ) -> DbResult<()> {
         Entity::update_many()
             .col_expr(Column::LastLoginAt, Expr::value(Some(last_login_at)))
+            .col_expr(Column::DeactivatedAt, Expr::value(None::<DateTimeUtc>))
             .filter(Column::Id.eq(id))
             .exec(db)
             .await?;
@@ -232,3 +325,76 @@ impl Model {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Model, PlayerRole};
+    use crate::database::test_database;
+    use chrono::{Duration, Utc};
+
+    /// Tests that only players inactive past the threshold are deactivated
+    /// and that logging in reactivates them
+    #[tokio::test]
+    async fn test_deactivate_inactive() {
+        let db = test_database().await;
+        let now = Utc::now();
+
+        let mut players = Vec::new();
+        for (name, role, last_login) in [
+            (
+                "inactive",
+                PlayerRole::Default,
+                Some(now - Duration::days(100)),
+            ),
+            ("active", PlayerRole::Default, Some(now - Duration::days(1))),
+            ("untracked", PlayerRole::Default, None),
+            (
+                "admin",
+                PlayerRole::SuperAdmin,
+                Some(now - Duration::days(100)),
+            ),
+        ] {
+            let player = Model::create(
+                &db,
+                format!("{name}@test.com"),
+                name.to_string(),
+                None,
+                role,
+            )
+            .await
+            .unwrap();
+
+            if let Some(last_login) = last_login {
+                Model::set_last_login(&db, player.id, last_login)
+                    .await
+                    .unwrap();
+            }
+
+            players.push(player.id);
+        }
+
+        let deactivated = Model::deactivate_inactive(&db, now - Duration::days(30))
+            .await
+            .unwrap();
+        assert_eq!(deactivated, 1);
+
+        // Already deactivated players aren't counted again
+        let deactivated = Model::deactivate_inactive(&db, now - Duration::days(30))
+            .await
+            .unwrap();
+        assert_eq!(deactivated, 0);
+
+        // Deactivated players are kept
+        let player = Model::by_id(&db, players[0]).await.unwrap().unwrap();
+        assert!(player.deactivated_at.is_some());
+        for player_id in &players[1..] {
+            let player = Model::by_id(&db, *player_id).await.unwrap().unwrap();
+            assert!(player.deactivated_at.is_none());
+        }
+
+        // Logging in reactivates the player
+        Model::set_last_login(&db, players[0], now).await.unwrap();
+        let player = Model::by_id(&db, players[0]).await.unwrap().unwrap();
+        assert!(player.deactivated_at.is_none());
+    }
+}
@@ -57,12 +57,7 @@ impl MigrationTrait for Migration {
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        // Drop the table
-        manager
-            .drop_table(Table::drop().table(Players::Table).to_owned())
-            .await?;
-
-        // Drop the index
+        // Drop the index (Must happen before the table is dropped)
         manager
             .drop_index(
                 Index::drop()
@@ -70,6 +65,11 @@ impl MigrationTrait for Migration {
                     .table(Players::Table)
                     .to_owned(),
             )
+            .await?;
+
+        // Drop the table
+        manager
+            .drop_table(Table::drop().table(Players::Table).to_owned())
             .await
     }
 }
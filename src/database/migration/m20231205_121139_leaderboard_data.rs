@@ -51,10 +51,7 @@ impl MigrationTrait for Migration {
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        manager
-            .drop_table(Table::drop().table(LeaderboardData::Table).to_owned())
-            .await?;
-
+        // Drop the index (Must happen before the table is dropped)
         manager
             .drop_index(
                 Index::drop()
@@ -62,6 +59,10 @@ impl MigrationTrait for Migration {
                     .name("idx-pid-ty-key")
                     .to_owned(),
             )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(LeaderboardData::Table).to_owned())
             .await
     }
 }
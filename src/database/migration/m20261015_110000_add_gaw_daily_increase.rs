@@ -0,0 +1,43 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add the daily increase accumulator column
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GalaxyAtWar::Table)
+                    .add_column(
+                        ColumnDef::new(GalaxyAtWar::DailyIncrease)
+                            .unsigned()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Drop the daily increase accumulator column
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GalaxyAtWar::Table)
+                    .drop_column(GalaxyAtWar::DailyIncrease)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum GalaxyAtWar {
+    Table,
+
+    DailyIncrease,
+}
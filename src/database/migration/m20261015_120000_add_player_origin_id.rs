@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add the persistent Origin ID column
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Players::Table)
+                    .add_column(ColumnDef::new(Players::OriginId).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Create index for the Origin ID, each Origin account can only
+        // be linked to one player
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-pr-origin-id")
+                    .table(Players::Table)
+                    .col(Players::OriginId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Drop the index (Must happen before the column is dropped)
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-pr-origin-id")
+                    .table(Players::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Drop the persistent Origin ID column
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Players::Table)
+                    .drop_column(Players::OriginId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Players {
+    Table,
+
+    OriginId,
+}
@@ -6,6 +6,9 @@ mod m20221222_174733_player_data;
 mod m20230913_185124_player_data_unique;
 mod m20231205_121139_leaderboard_data;
 mod m20240714_023535_add_player_timestamps;
+mod m20261015_110000_add_gaw_daily_increase;
+mod m20261015_120000_add_player_origin_id;
+mod m20261015_130000_add_player_deactivated_at;
 
 pub struct Migrator;
 
@@ -19,6 +22,161 @@ impl MigratorTrait for Migrator {
             Box::new(m20230913_185124_player_data_unique::Migration),
             Box::new(m20231205_121139_leaderboard_data::Migration),
             Box::new(m20240714_023535_add_player_timestamps::Migration),
+            Box::new(m20261015_110000_add_gaw_daily_increase::Migration),
+            Box::new(m20261015_120000_add_player_origin_id::Migration),
+            Box::new(m20261015_130000_add_player_deactivated_at::Migration),
         ]
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Migrator, MigratorTrait};
+    use sea_orm::{ConnectionTrait, Database, DatabaseConnection, Statement};
+
+    /// Collects the names of the tables currently present in the
+    /// database, excluding SQLite internal and migration tables
+    async fn table_names(db: &DatabaseConnection) -> Vec<String> {
+        let rows = db
+            .query_all(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT name FROM sqlite_master WHERE type = 'table' \
+                AND name NOT LIKE 'sqlite_%' AND name != 'seaql_migrations' ORDER BY name",
+            ))
+            .await
+            .unwrap();
+
+        rows.iter()
+            .map(|row| row.try_get::<String>("", "name").unwrap())
+            .collect()
+    }
+
+    /// Collects the names of the columns in the provided `table`
+    async fn column_names(db: &DatabaseConnection, table: &str) -> Vec<String> {
+        let rows = db
+            .query_all(Statement::from_string(
+                db.get_database_backend(),
+                format!("SELECT name FROM pragma_table_info('{table}')"),
+            ))
+            .await
+            .unwrap();
+
+        rows.iter()
+            .map(|row| row.try_get::<String>("", "name").unwrap())
+            .collect()
+    }
+
+    /// Tests that migrating up then rolling back returns the schema
+    /// to the expected prior state at each step
+    #[tokio::test]
+    async fn test_up_down() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+
+        Migrator::up(&db, None).await.unwrap();
+        assert_eq!(
+            table_names(&db).await,
+            [
+                "galaxy_at_war",
+                "leaderboard_data",
+                "player_data",
+                "players"
+            ]
+        );
+        assert!(column_names(&db, "players")
+            .await
+            .contains(&"last_login_at".to_string()));
+        assert!(column_names(&db, "galaxy_at_war")
+            .await
+            .contains(&"daily_increase".to_string()));
+        assert!(column_names(&db, "players")
+            .await
+            .contains(&"origin_id".to_string()));
+        assert!(column_names(&db, "players")
+            .await
+            .contains(&"deactivated_at".to_string()));
+
+        // Rolling back the latest migration should only remove the deactivated column
+        Migrator::down(&db, Some(1)).await.unwrap();
+        assert_eq!(
+            column_names(&db, "players").await,
+            [
+                "id",
+                "email",
+                "display_name",
+                "password",
+                "role",
+                "last_login_at",
+                "origin_id"
+            ]
+        );
+
+        // Rolling back the origin ID migration should only remove the origin ID column
+        Migrator::down(&db, Some(1)).await.unwrap();
+        assert_eq!(
+            column_names(&db, "players").await,
+            [
+                "id",
+                "email",
+                "display_name",
+                "password",
+                "role",
+                "last_login_at"
+            ]
+        );
+
+        // Rolling back the daily increase migration should only remove the column
+        Migrator::down(&db, Some(1)).await.unwrap();
+        assert_eq!(
+            column_names(&db, "galaxy_at_war").await,
+            [
+                "id",
+                "player_id",
+                "last_modified",
+                "group_a",
+                "group_b",
+                "group_c",
+                "group_d",
+                "group_e"
+            ]
+        );
+
+        // Rolling back the timestamps migration should only remove the timestamp column
+        Migrator::down(&db, Some(1)).await.unwrap();
+        assert_eq!(
+            column_names(&db, "players").await,
+            ["id", "email", "display_name", "password", "role"]
+        );
+        assert_eq!(
+            table_names(&db).await,
+            [
+                "galaxy_at_war",
+                "leaderboard_data",
+                "player_data",
+                "players"
+            ]
+        );
+
+        // Rolling back the leaderboard migration should remove the table
+        Migrator::down(&db, Some(1)).await.unwrap();
+        assert_eq!(
+            table_names(&db).await,
+            ["galaxy_at_war", "player_data", "players"]
+        );
+
+        // Rolling back everything should leave no tables
+        Migrator::down(&db, None).await.unwrap();
+        assert!(table_names(&db).await.is_empty());
+
+        // Migrations should be re-appliable after a full rollback
+        Migrator::up(&db, None).await.unwrap();
+        assert_eq!(
+            table_names(&db).await,
+            [
+                "galaxy_at_war",
+                "leaderboard_data",
+                "player_data",
+                "players"
+            ]
+        );
+    }
+}
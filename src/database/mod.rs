@@ -1,10 +1,17 @@
 use log::{error, info, warn};
 use migration::{Migrator, MigratorTrait};
-use sea_orm::Database as SeaDatabase;
+use sea_orm::{
+    sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode},
+    ConnectOptions, ConnectionTrait, RuntimeErr, SqlxSqliteConnector,
+};
 use std::{
     fs::{create_dir_all, File},
+    future::Future,
     path::Path,
+    str::FromStr,
+    time::Duration,
 };
+use tokio::time::sleep;
 
 pub mod entities;
 mod migration;
@@ -19,7 +26,7 @@ pub use sea_orm::DbErr;
 
 use self::entities::{Player, PlayerRole};
 use crate::{
-    config::RuntimeConfig,
+    config::{DatabaseConfig, RuntimeConfig},
     utils::hashing::{hash_password, verify_password},
 };
 
@@ -31,10 +38,10 @@ const DATABASE_PATH_URL: &str = "sqlite:data/app.db";
 
 /// Connects to the database and applies the admin changes if
 /// required, returning the database connection
-pub async fn init(config: &RuntimeConfig) -> DatabaseConnection {
+pub async fn init(config: &RuntimeConfig, database_config: &DatabaseConfig) -> DatabaseConnection {
     info!("Connected to database..");
 
-    let connection = connect_database().await;
+    let connection = connect_database(database_config).await;
 
     // Setup the super admin account
     init_database_admin(&connection, config).await;
@@ -42,26 +49,26 @@ pub async fn init(config: &RuntimeConfig) -> DatabaseConnection {
     connection
 }
 
-/// Connects to the database
-async fn connect_database() -> DatabaseConnection {
-    let path = Path::new(&DATABASE_PATH);
-
-    // Create path to database file if missing
-    if let Some(parent) = path.parent() {
-        if !parent.exists() {
-            create_dir_all(parent).expect("Unable to create parent directory for sqlite database");
-        }
-    }
+/// Connects to the database and rolls back the last `steps` migrations,
+/// used by the `migrate down` command to undo a botched upgrade
+///
+/// `config` The database connection configuration
+/// `steps`  The number of migrations to roll back
+pub async fn rollback(config: &DatabaseConfig, steps: u32) -> DbResult<()> {
+    let connection = open_database(config).await?;
 
-    // Create the database if file is missing
-    if !path.exists() {
-        File::create(path).expect("Unable to create sqlite database file");
-    }
+    Migrator::down(&connection, Some(steps)).await
+}
 
-    // Connect to database
-    let connection = SeaDatabase::connect(DATABASE_PATH_URL)
-        .await
-        .expect("Unable to create database connection");
+/// Connects to the database
+async fn connect_database(config: &DatabaseConfig) -> DatabaseConnection {
+    let connection = match open_database(config).await {
+        Ok(value) => value,
+        Err(err) => {
+            error!("Unable to connect to the database, giving up: {}", err);
+            std::process::exit(1);
+        }
+    };
 
     // Run migrations
     if let Err(err) = Migrator::up(&connection, None).await {
@@ -86,6 +93,133 @@ async fn connect_database() -> DatabaseConnection {
     connection
 }
 
+/// Opens a connection to the database file without applying
+/// any migrations, creating the file if its missing
+///
+/// Connecting is retried with backoff as configured by `config`
+async fn open_database(config: &DatabaseConfig) -> DbResult<DatabaseConnection> {
+    let path = Path::new(&DATABASE_PATH);
+
+    // Create path to database file if missing
+    if let Some(parent) = path.parent() {
+        if !parent.exists() {
+            create_dir_all(parent).expect("Unable to create parent directory for sqlite database");
+        }
+    }
+
+    // Create the database if file is missing
+    if !path.exists() {
+        File::create(path).expect("Unable to create sqlite database file");
+    }
+
+    // Connect to database
+    retry_connect(
+        config.startup_retries,
+        Duration::from_millis(config.startup_backoff_ms),
+        || connect_pool(DATABASE_PATH_URL, config),
+    )
+    .await
+}
+
+/// Attempts to connect using `connect` retrying up to `retries` times
+/// on failure, waiting `backoff` before the first retry and doubling
+/// the wait after each failed attempt
+///
+/// `retries` The number of times to retry after the first attempt
+/// `backoff` The initial delay between attempts
+/// `connect` Function creating the connection
+async fn retry_connect<F, Fut, T>(retries: u32, backoff: Duration, mut connect: F) -> DbResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = DbResult<T>>,
+{
+    let mut delay = backoff;
+    let mut attempt = 0;
+
+    loop {
+        let err = match connect().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        if attempt >= retries {
+            return Err(err);
+        }
+
+        attempt += 1;
+
+        warn!(
+            "Failed to connect to database, retrying in {}ms (Attempt {}/{}): {}",
+            delay.as_millis(),
+            attempt,
+            retries,
+            err
+        );
+
+        sleep(delay).await;
+        delay = delay.saturating_mul(2);
+    }
+}
+
+/// Creates a connection pool to the SQLite database at `url` applying the
+/// pool and SQLite settings from `config` to every pooled connection
+///
+/// `url`    The SQLite database URL
+/// `config` The database connection configuration
+async fn connect_pool(url: &str, config: &DatabaseConfig) -> DbResult<DatabaseConnection> {
+    let pool = connect_options(url, config)
+        .sqlx_pool_options()
+        .connect_with(sqlite_options(url, config)?)
+        .await
+        .map_err(|err| DbErr::Conn(RuntimeErr::SqlxError(err)))?;
+
+    Ok(SqlxSqliteConnector::from_sqlx_sqlite_pool(pool))
+}
+
+/// Creates the connection pool options from the provided `config`
+fn connect_options(url: &str, config: &DatabaseConfig) -> ConnectOptions {
+    let mut options = ConnectOptions::new(url);
+    options
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout_seconds))
+        .sqlx_logging(false);
+    options
+}
+
+/// Creates the SQLite specific options applied to each new connection
+/// within the pool from the provided `config`.
+///
+/// The busy timeout and journal mode pragmas are issued whenever a
+/// connection is opened so they apply to the entire pool
+fn sqlite_options(url: &str, config: &DatabaseConfig) -> DbResult<SqliteConnectOptions> {
+    let mut options = SqliteConnectOptions::from_str(url)
+        .map_err(|err| DbErr::Conn(RuntimeErr::SqlxError(err)))?
+        .busy_timeout(Duration::from_millis(config.busy_timeout_ms));
+
+    if config.wal {
+        options = options.journal_mode(SqliteJournalMode::Wal);
+    }
+
+    Ok(options)
+}
+
+/// Creates an in-memory database with all the migrations applied
+/// for use within tests
+#[cfg(test)]
+pub async fn test_database() -> DatabaseConnection {
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+    db
+}
+
+/// Runs a trivial query against the database, used to check
+/// that the database is responding
+pub async fn ping(db: &DatabaseConnection) -> DbResult<()> {
+    db.execute_unprepared("SELECT 1").await?;
+    Ok(())
+}
+
 /// Initializes the database super admin account using the
 /// admin email stored within the environment variables if
 /// one is present
@@ -142,3 +276,140 @@ async fn init_database_admin(db: &DatabaseConnection, config: &RuntimeConfig) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{connect_options, connect_pool, retry_connect, sqlite_options, DATABASE_PATH_URL};
+    use crate::config::DatabaseConfig;
+    use sea_orm::{ConnectionTrait, DbErr, Statement, TransactionTrait};
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    /// Tests that the connection options reflect the configured values
+    #[test]
+    fn test_connect_options() {
+        let config = DatabaseConfig {
+            max_connections: 8,
+            min_connections: 2,
+            acquire_timeout_seconds: 12,
+            busy_timeout_ms: 2500,
+            wal: true,
+            ..Default::default()
+        };
+
+        let options = connect_options(DATABASE_PATH_URL, &config);
+        assert_eq!(options.get_max_connections(), Some(8));
+        assert_eq!(options.get_min_connections(), Some(2));
+        assert_eq!(options.get_acquire_timeout(), Some(Duration::from_secs(12)));
+
+        let sqlite = sqlite_options(DATABASE_PATH_URL, &config).unwrap();
+        assert_eq!(sqlite.get_filename().to_str(), Some("data/app.db"));
+    }
+
+    /// Tests that connections use the WAL journal mode by default
+    #[tokio::test]
+    async fn test_wal_enabled() {
+        let path = std::env::temp_dir().join(format!("pocket-relay-{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+
+        let db = connect_pool(&url, &DatabaseConfig::default())
+            .await
+            .unwrap();
+
+        let row = db
+            .query_one(Statement::from_string(
+                db.get_database_backend(),
+                "PRAGMA journal_mode",
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        let journal_mode: String = row.try_get_by_index(0).unwrap();
+
+        db.close().await.unwrap();
+        _ = std::fs::remove_file(&path);
+
+        assert_eq!(journal_mode, "wal");
+    }
+
+    /// Tests that with the default pool and WAL enabled a reader can
+    /// proceed while a write transaction is open on another connection
+    #[tokio::test]
+    async fn test_read_during_write() {
+        let path = std::env::temp_dir().join(format!("pocket-relay-{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+
+        let config = DatabaseConfig::default();
+        let db = connect_pool(&url, &config).await.unwrap();
+
+        db.execute_unprepared("CREATE TABLE test (value INTEGER NOT NULL)")
+            .await
+            .unwrap();
+        db.execute_unprepared("INSERT INTO test (value) VALUES (1)")
+            .await
+            .unwrap();
+
+        // Hold a write transaction open with an uncommitted change
+        let txn = db.begin().await.unwrap();
+        txn.execute_unprepared("INSERT INTO test (value) VALUES (2)")
+            .await
+            .unwrap();
+
+        // Read from another pooled connection, this must complete well
+        // before the busy timeout if the read isn't blocked by the write
+        let read = tokio::time::timeout(
+            Duration::from_millis(config.busy_timeout_ms / 2),
+            db.query_one(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT COUNT(*) FROM test",
+            )),
+        )
+        .await
+        .expect("Read was blocked by the write transaction")
+        .unwrap()
+        .unwrap();
+        let count: i64 = read.try_get_by_index(0).unwrap();
+
+        txn.commit().await.unwrap();
+        db.close().await.unwrap();
+        _ = std::fs::remove_file(&path);
+
+        // Only the committed row is visible to the reader
+        assert_eq!(count, 1);
+    }
+
+    /// Tests that connecting is retried until the connection succeeds
+    #[tokio::test]
+    async fn test_retry_connect() {
+        let attempts = AtomicU32::new(0);
+
+        let result = retry_connect(3, Duration::from_millis(1), || async {
+            // Fail the first two attempts
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err(DbErr::Custom("Not ready".to_string()));
+            }
+            Ok(())
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    /// Tests that the error is returned once the retries are exhausted
+    #[tokio::test]
+    async fn test_retry_connect_exhausted() {
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), DbErr> = retry_connect(2, Duration::from_millis(1), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(DbErr::Custom("Not ready".to_string()))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
@@ -8,97 +8,22 @@ use tokio::{task::JoinSet, try_join};
 
 use crate::{
     database::entities::{
-        galaxy_at_war::ActiveModel as GawActiveModel, leaderboard_data::LeaderboardType,
-        players::ActiveModel as PlayerActiveModel, LeaderboardData, PlayerData, PlayerRole,
+        galaxy_at_war::ActiveModel as GawActiveModel,
+        leaderboard_data::LeaderboardType,
+        player_data::{CHARACTER_DATA, CLASS_NAMES, INVENTORY_LENGTH},
+        players::ActiveModel as PlayerActiveModel,
+        LeaderboardData, PlayerData, PlayerRole,
     },
     utils::hashing::hash_password,
 };
 use std::fmt::Write;
 
 use super::connect_database;
+use crate::config::DatabaseConfig;
 
 /// The number of users to seed
 const SEED_PLAYERS_COUNT: u32 = 10_000;
 
-/// Class names to seed
-static CLASS_NAMES: &[&str] = &[
-    "Adept",
-    "Soldier",
-    "Engineer",
-    "Sentinel",
-    "Infiltrator",
-    "Vanguard",
-];
-
-static CHARACTER_DATA: &[&str] = &[
-    "20;4;AdeptHumanMale;MAdept;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptHumanFemale;FAdept;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptAsari;Asari;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptDrell;Drell;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptAsariCommando;Asari;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptHumanMaleCerberus;Human Male;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptN7;N7 Fury;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptVolus;Volus Adept;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptKrogan;Krogan Shaman;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptBatarian;Krogan Shaman;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;AdeptCollector;Awakened Collector;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierHumanMale;MSoldier;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierHumanFemale;FSoldier;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierKrogan;Krogan;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierTurian;Turian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierHumanMaleBF3;MSoldier;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierBatarian;Batarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierVorcha;Vorcha;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierN7;N7 Destroyer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;N7SoldierTurian;Turian Havoc;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierGeth;Geth Trooper;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierMQuarian;Geth Trooper;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SoldierGethDestroyer;Geth Juggernaut;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerHumanMale;MEngineer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerHumanFemale;FEngineer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerQuarian;FEngineer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerSalarian;Salarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerGeth;Geth;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerQuarianMale;Quarian Male;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerN7;N7 Demolisher;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerVolus;Volus Engineer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerTurian;Turian Saboteur;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerVorcha;Turian Saboteur;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;EngineerMerc;Talon Mercenary;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelHumanMale;MSentinel;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelHumanFemale;FSentinel;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelTurian;Turian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelKrogan;Krogan;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelBatarian;Batarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelVorcha;Vorcha;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelN7;N7 Paladin;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelVolus;Volus Mercenary;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelAsari;Volus Mercenary;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;SentinelKroganWarlord;Krogan Warlord;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorHumanMale;MInfiltrate;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorHumanFemale;FInfiltrate;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorSalarian;Salarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorQuarian;Quarian;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorGeth;Geth;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorQuarianMale;Quarian Male;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorN7;N7 Shadow;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;N7InfiltratorTurian;Turian Ghost;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorDrell;Drell Assassin;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorAsari;Drell Assassin;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorFembot;Krogan Warlord;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;InfiltratorHumanFemaleBF3;MSoldier;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardHumanMale;MVanguard;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardHumanFemale;FVanguard;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardDrell;Drell;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardAsari;Asari;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardKrogan;Krogan;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardHumanMaleCerberus;Human Male;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardN7;N7 Slayer;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardVolus;Volus Protector;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardBatarian;Volus Protector;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-    "20;4;VanguardTurianFemale;Cabal Vanguard;0;45;0;47;45;9;9;0;0;0;0;0;;;;;False;True",
-];
-
 /// Seeds the database with a collection of players and their associated
 /// player data. Ensure the database is empty before seeding as to not
 /// cause conflicts.
@@ -108,7 +33,7 @@ static CHARACTER_DATA: &[&str] = &[
 #[tokio::test]
 #[ignore]
 pub async fn seed() {
-    let db = connect_database().await;
+    let db = connect_database(&DatabaseConfig::default()).await;
 
     // All accounts use the same default password
     let default_password = hash_password("test").unwrap();
@@ -124,8 +49,6 @@ pub async fn seed() {
     // Random sample used for gaw groups
     let gaw_sample = Uniform::new_inclusive(5000, 10099);
 
-    const INVENTORY_LENGTH: usize = 671;
-
     let mut join_set: JoinSet<()> = JoinSet::new();
 
     for i in 0..SEED_PLAYERS_COUNT {
@@ -149,6 +72,8 @@ pub async fn seed() {
             password: Set(Some(password)),
             role: Set(role),
             last_login_at: Set(None),
+            origin_id: Set(None),
+            deactivated_at: Set(None),
         }
         .insert(&db)
         .await
@@ -171,6 +96,7 @@ pub async fn seed() {
             group_c: Set(rng.sample(gaw_sample)),
             group_d: Set(rng.sample(gaw_sample)),
             group_e: Set(rng.sample(gaw_sample)),
+            daily_increase: Set(0),
         }
         .insert(&db)
         .await
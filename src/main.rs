@@ -2,29 +2,43 @@
 
 use crate::{
     config::{RuntimeConfig, VERSION},
+    middleware::access::{access_layer, AccessList},
     services::{
         game::manager::GameManager, retriever::Retriever, sessions::Sessions, tunnel::TunnelService,
     },
     utils::signing::SigningKey,
 };
 use axum::{self, Extension};
-use config::{load_config, TunnelConfig};
+use cli::{parse_command, Command};
+use config::{load_config, Config, TunnelConfig};
+use futures_util::future::join_all;
 use log::{debug, error, info, LevelFilter};
+use replay::{ReplayError, ReplayReport};
 use services::udp_tunnel::{start_udp_tunnel, UdpTunnelService};
-use std::{net::SocketAddr, sync::Arc};
-use tokio::{join, net::TcpListener, signal};
-use utils::logging;
+use services::{
+    account_limit::AccountCreationLimiter, health::HealthService, maintenance,
+    maintenance_mode::MaintenanceMode, public_address::PublicAddressService, rotation,
+    stats_history::StatsHistory, telemetry::TelemetryForwarder,
+};
+use session::packet::{decode_packet, decode_packets};
+use std::{
+    future::IntoFuture, net::SocketAddr, path::Path, process::ExitCode, sync::Arc, time::Duration,
+};
+use tokio::{join, signal, sync::watch};
+use utils::{listener, logging};
 
+mod cli;
 mod config;
 mod database;
 mod middleware;
+mod replay;
 mod routes;
 mod services;
 mod session;
 mod utils;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     // Load configuration
     let config = load_config().unwrap_or_default();
 
@@ -35,9 +49,26 @@ async fn main() {
     // Initialize logging
     logging::setup(config.logging);
 
+    // Handle maintenance commands instead of starting the server
+    match parse_command(std::env::args().skip(1)) {
+        Ok(None) => {}
+        Ok(Some(command)) => return run_command(command, &config).await,
+        Err(err) => {
+            error!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Err(err) = config.validate() {
+        error!("Invalid configuration: {}", err);
+        return ExitCode::FAILURE;
+    }
+
     // Create the server socket address while the port is still available
     let addr: SocketAddr = SocketAddr::new(config.host, config.port);
 
+    let dual_stack = config.dual_stack;
+
     // Create tunnel server socket address
     let tunnel_addr: SocketAddr = SocketAddr::new(config.host, config.udp_tunnel.port);
 
@@ -46,6 +77,9 @@ async fn main() {
 
     // Config data persisted to runtime
     let runtime_config = RuntimeConfig {
+        port: config.port,
+        external_port: config.external_port,
+        server: config.server,
         reverse_proxy: config.reverse_proxy,
         galaxy_at_war: config.galaxy_at_war,
         menu_message: config.menu_message,
@@ -54,19 +88,75 @@ async fn main() {
         tunnel: config.tunnel,
         api: config.api,
         udp_tunnel: config.udp_tunnel,
+        game: config.game,
+        client_config: config.client_config,
+        sessions: config.sessions,
+        net: config.net,
+        player: config.player,
     };
 
-    debug!("QoS server: {:?}", &runtime_config.qos);
+    debug!("QoS server: {:?}", &runtime_config.qos.server);
 
     // This step may take longer than expected so its spawned instead of joined
     tokio::spawn(logging::log_connection_urls(config.port));
 
+    // Report suppressed repeated log messages
+    tokio::spawn(logging::flush_limited_logs_task());
+
     let (db, retriever, signing_key) = join!(
-        database::init(&runtime_config),
+        database::init(&runtime_config, &config.database),
         Retriever::start(config.retriever),
         SigningKey::global(),
     );
+    let retriever = match retriever {
+        Ok(value) => value,
+        Err(err) => {
+            error!("Failed to start retriever: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
     let sessions = Arc::new(Sessions::new(signing_key));
+    let health = Arc::new(HealthService::new(Duration::from_millis(
+        config.database.slow_threshold_ms,
+    )));
+
+    // Periodically resolve the public address
+    let public_address = Arc::new(PublicAddressService::new(config.external_host));
+    tokio::spawn(public_address.clone().refresh_task(Duration::from_secs(
+        config.external_host_refresh_seconds.max(1),
+    )));
+
+    // Deactivate inactive accounts (If enabled)
+    if let Some(inactive_days) = config.maintenance.prune_inactive_days {
+        tokio::spawn(maintenance::prune_inactive_task(db.clone(), inactive_days));
+    }
+
+    // Compact the database (If enabled)
+    if let Some(interval_hours) = config.maintenance.vacuum_interval_hours {
+        tokio::spawn(maintenance::vacuum_task(
+            db.clone(),
+            sessions.clone(),
+            interval_hours,
+        ));
+    }
+
+    // Rotate the player banner and new item (If any are configured)
+    let rotation = config.rotation;
+    if !rotation.banners.is_empty() || !rotation.new_items.is_empty() {
+        tokio::spawn(rotation::rotation_task(db.clone(), rotation));
+    }
+
+    // Periodically sample the database latency
+    tokio::spawn(health.clone().sample_database_task(
+        db.clone(),
+        Duration::from_secs(config.database.health_check_interval_seconds.max(1)),
+    ));
+
+    let http_config = config.http;
+    let telemetry = Arc::new(TelemetryForwarder::start(config.telemetry.forward_url));
+    let access = Arc::new(AccessList::new(&config.access));
+    let account_limiter = Arc::new(AccountCreationLimiter::new(&runtime_config.player));
+    let maintenance_mode = Arc::new(MaintenanceMode::new(config.maintenance_mode));
     let config = Arc::new(runtime_config);
     let tunnel_service = Arc::new(TunnelService::default());
     let udp_tunnel_service = Arc::new(UdpTunnelService::new(sessions.clone()));
@@ -78,10 +168,61 @@ async fn main() {
     ));
     let retriever = Arc::new(retriever);
 
+    // Sample the server activity history
+    let stats_history = Arc::new(StatsHistory::default());
+    tokio::spawn(
+        stats_history
+            .clone()
+            .sample_task(sessions.clone(), game_manager.clone()),
+    );
+
+    // Stop games left idle before starting (If enabled)
+    if let Some(timeout) = config.game.pregame_timeout_seconds {
+        tokio::spawn(
+            game_manager
+                .clone()
+                .pregame_reaper_task(Duration::from_secs(timeout)),
+        );
+    }
+
+    // Create games for players waiting too long in matchmaking (If enabled)
+    if let Some(timeout) = config.game.matchmaking_fallback_seconds {
+        tokio::spawn(
+            game_manager
+                .clone()
+                .matchmaking_fallback_task(Duration::from_secs(timeout)),
+        );
+    }
+
+    // Signal for stopping the servers, set once ctrl-c is received
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        _ = signal::ctrl_c().await;
+        info!("Shutting down");
+        _ = shutdown_tx.send(true);
+    });
+
+    // Close the HTTP tunnels on shutdown so clients disconnect promptly
+    tokio::spawn({
+        let tunnel_service = tunnel_service.clone();
+        let mut shutdown_rx = shutdown_rx.clone();
+        async move {
+            _ = shutdown_rx.wait_for(|value| *value).await;
+            tunnel_service.close_all();
+        }
+    });
+
     // Start the tunnel server (If enabled)
     if tunnel_enabled && config.udp_tunnel.enabled {
         // Start the tunnel service server
-        if let Err(err) = start_udp_tunnel(tunnel_addr, udp_tunnel_service.clone()).await {
+        if let Err(err) = start_udp_tunnel(
+            tunnel_addr,
+            &config.udp_tunnel,
+            udp_tunnel_service.clone(),
+            shutdown_rx.clone(),
+        )
+        .await
+        {
             error!("failed to start udp tunnel server: {}", err);
         }
     }
@@ -91,43 +232,149 @@ async fn main() {
 
     router.add_extension(db.clone());
     router.add_extension(config.clone());
-    router.add_extension(retriever);
+    router.add_extension(retriever.clone());
     router.add_extension(game_manager.clone());
     router.add_extension(sessions.clone());
+    router.add_extension(account_limiter.clone());
+    router.add_extension(maintenance_mode.clone());
     router.add_extension(udp_tunnel_service.clone());
+    router.add_extension(health.clone());
 
     let router = router.build();
 
     // Create the HTTP router
-    let router = routes::router()
+    let mut http_router = routes::router(&http_config, &config.dashboard);
+
+    // Restrict the addresses that can connect (If configured)
+    if access.is_restricted() {
+        http_router = http_router.layer(axum::middleware::from_fn_with_state(access, access_layer));
+    }
+
+    let router = http_router
         // Apply data extensions
         .layer(Extension(db))
         .layer(Extension(config))
         .layer(Extension(router))
         .layer(Extension(game_manager))
         .layer(Extension(sessions))
+        .layer(Extension(account_limiter))
+        .layer(Extension(maintenance_mode))
         .layer(Extension(tunnel_service))
         .layer(Extension(udp_tunnel_service))
+        .layer(Extension(health))
+        .layer(Extension(stats_history))
+        .layer(Extension(public_address))
+        .layer(Extension(telemetry))
+        .layer(Extension(retriever))
         .into_make_service_with_connect_info::<SocketAddr>();
 
     info!("Starting server on {} (v{})", addr, VERSION);
 
-    // Start the TCP listener
-    let listener = match TcpListener::bind(addr).await {
+    // Start the TCP listeners
+    let listeners = match listener::bind_listeners(addr, dual_stack) {
         Ok(value) => value,
         Err(err) => {
             error!("Failed to bind HTTP server pm {}: {:?}", addr, err);
-            return;
+            return ExitCode::FAILURE;
         }
     };
 
-    // Run the HTTP server
-    if let Err(err) = axum::serve(listener, router)
-        .with_graceful_shutdown(async move {
-            _ = signal::ctrl_c().await;
-        })
-        .await
-    {
-        error!("Error within HTTP server {:?}", err);
+    // Run the HTTP server on each of the listeners
+    let servers = listeners.into_iter().map(|listener| {
+        let mut shutdown_rx = shutdown_rx.clone();
+        axum::serve(listener, router.clone())
+            .with_graceful_shutdown(async move {
+                _ = shutdown_rx.wait_for(|value| *value).await;
+            })
+            .into_future()
+    });
+
+    for result in join_all(servers).await {
+        if let Err(err) = result {
+            error!("Error within HTTP server {:?}", err);
+        }
     }
+
+    ExitCode::SUCCESS
+}
+
+/// Runs the provided maintenance `command`
+async fn run_command(command: Command, config: &Config) -> ExitCode {
+    match command {
+        Command::MigrateDown { steps } => {
+            info!("Rolling back {} database migration(s)", steps);
+
+            match database::rollback(&config.database, steps).await {
+                Ok(()) => info!("Rolled back database migrations"),
+                Err(err) => {
+                    error!("Failed to roll back database migrations: {}", err);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        Command::DecodePacket { path } => {
+            let bytes = match tokio::fs::read(&path).await {
+                Ok(value) => value,
+                Err(err) => {
+                    error!("Failed to read packet file {}: {}", path.display(), err);
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            let packets = match decode_packets(&bytes) {
+                Ok(value) => value,
+                Err(err) => {
+                    error!("Failed to decode packets: {}", err);
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            // Component names are required for the decoded output
+            utils::components::initialize();
+
+            for packet in &packets {
+                println!("{}", decode_packet(packet));
+            }
+        }
+        Command::Replay { path, target } => {
+            let target = target.unwrap_or_else(|| format!("127.0.0.1:{}", config.port));
+
+            // Component names are required for the decoded mismatches
+            utils::components::initialize();
+
+            match replay_capture(&path, &target).await {
+                Ok(report) => {
+                    for mismatch in &report.mismatches {
+                        println!(
+                            "Mismatch at record {}:\n{}",
+                            mismatch.index,
+                            mismatch.diff()
+                        );
+                    }
+
+                    info!(
+                        "Replay complete: sent {}, matched {}, mismatched {}",
+                        report.sent,
+                        report.matched,
+                        report.mismatches.len()
+                    );
+                }
+                Err(err) => {
+                    error!("Failed to replay capture: {}", err);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Loads the capture at `path` and replays it against the server at `target`
+async fn replay_capture(path: &Path, target: &str) -> Result<ReplayReport, ReplayError> {
+    let bytes = tokio::fs::read(path).await?;
+    let records = replay::parse_capture(&bytes)?;
+    let stream = replay::connect(target).await?;
+
+    replay::replay(stream, &records).await
 }
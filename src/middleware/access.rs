@@ -0,0 +1,134 @@
+use super::ip_address::{IpAddress, IpAddressError};
+use crate::{config::AccessConfig, utils::cidr::Cidr};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::debug;
+use std::{net::Ipv4Addr, sync::Arc};
+
+/// Lists of address ranges that are allowed or denied from connecting
+pub struct AccessList {
+    /// Ranges allowed to connect, empty to allow all
+    allow: Vec<Cidr>,
+    /// Ranges denied from connecting, takes precedence over `allow`
+    deny: Vec<Cidr>,
+}
+
+impl AccessList {
+    /// Creates a new access list from the provided `config`
+    pub fn new(config: &AccessConfig) -> Self {
+        Self {
+            allow: config.allow_cidrs.clone(),
+            deny: config.deny_cidrs.clone(),
+        }
+    }
+
+    /// Whether the access list restricts any addresses
+    pub fn is_restricted(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty()
+    }
+
+    /// Checks whether the provided `addr` is allowed to connect
+    pub fn is_allowed(&self, addr: Ipv4Addr) -> bool {
+        if self.deny.iter().any(|range| range.contains(addr)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|range| range.contains(addr))
+    }
+
+    /// Checks whether a connection from an address that could not be
+    /// determined is allowed, these are rejected whenever any list is
+    /// configured as the address may be one of the listed addresses
+    pub fn is_unknown_allowed(&self) -> bool {
+        !self.is_restricted()
+    }
+}
+
+/// Middleware layer function that rejects requests with 403 Forbidden
+/// when the source address is not allowed by the access list.
+///
+/// Requests where the address cannot be determined are rejected when
+/// either list is configured
+///
+/// `access` The access list to use
+/// `addr`   The source address of the request
+/// `req`    The request to handle
+/// `next`   The next layer to use
+pub async fn access_layer(
+    State(access): State<Arc<AccessList>>,
+    addr: Result<IpAddress, IpAddressError>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let allowed = match addr {
+        Ok(IpAddress(addr)) => access.is_allowed(addr),
+        Err(_) => access.is_unknown_allowed(),
+    };
+
+    if !allowed {
+        debug!("Rejected connection from address not allowed by access list");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::AccessList;
+    use crate::config::AccessConfig;
+    use std::net::Ipv4Addr;
+
+    /// Creates an access list from the provided ranges
+    fn access_list(allow: &[&str], deny: &[&str]) -> AccessList {
+        AccessList::new(&AccessConfig {
+            allow_cidrs: allow.iter().map(|value| value.parse().unwrap()).collect(),
+            deny_cidrs: deny.iter().map(|value| value.parse().unwrap()).collect(),
+        })
+    }
+
+    /// Tests that only addresses matching the allow list are allowed
+    #[test]
+    fn test_allow_match() {
+        let access = access_list(&["10.0.0.0/8"], &[]);
+        assert!(access.is_allowed(Ipv4Addr::new(10, 1, 2, 3)));
+        assert!(!access.is_allowed(Ipv4Addr::new(192, 168, 1, 1)));
+    }
+
+    /// Tests that the deny list takes precedence over the allow list
+    #[test]
+    fn test_deny_match() {
+        let access = access_list(&["10.0.0.0/8"], &["10.0.0.5"]);
+        assert!(!access.is_allowed(Ipv4Addr::new(10, 0, 0, 5)));
+        assert!(access.is_allowed(Ipv4Addr::new(10, 0, 0, 6)));
+
+        let access = access_list(&[], &["192.168.0.0/16"]);
+        assert!(!access.is_allowed(Ipv4Addr::new(192, 168, 1, 1)));
+        assert!(access.is_allowed(Ipv4Addr::new(10, 0, 0, 1)));
+    }
+
+    /// Tests that all addresses are allowed without any lists
+    #[test]
+    fn test_default_allow() {
+        let access = access_list(&[], &[]);
+        assert!(!access.is_restricted());
+        assert!(access.is_allowed(Ipv4Addr::new(1, 2, 3, 4)));
+        assert!(access.is_unknown_allowed());
+    }
+
+    /// Tests that addresses that couldn't be determined are rejected
+    /// when any list is configured
+    #[test]
+    fn test_unknown_address() {
+        let access = access_list(&[], &["192.168.0.0/16"]);
+        assert!(!access.is_unknown_allowed());
+
+        let access = access_list(&["10.0.0.0/8"], &[]);
+        assert!(!access.is_unknown_allowed());
+    }
+}
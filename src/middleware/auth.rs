@@ -7,8 +7,9 @@ use crate::{
 };
 use axum::{
     body::Body,
-    extract::FromRequestParts,
+    extract::{FromRequestParts, Request},
     http::StatusCode,
+    middleware::Next,
     response::{IntoResponse, Response},
 };
 use futures_util::future::BoxFuture;
@@ -69,6 +70,29 @@ impl<S> FromRequestParts<S> for AdminAuth {
     }
 }
 
+/// Middleware layer that rejects requests without a valid authentication
+/// token, used for routes that are otherwise available without auth
+///
+/// `auth` The authentication from the request
+/// `req`  The request to handle
+/// `next` The next layer to use
+pub async fn require_auth_layer(
+    auth: Result<Auth, TokenError>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match auth {
+        Ok(_) => next.run(req).await,
+        // Missing tokens are unauthorized rather than a bad request here
+        Err(TokenError::MissingToken) => (
+            StatusCode::UNAUTHORIZED,
+            TokenError::MissingToken.to_string(),
+        )
+            .into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
 /// The HTTP header that contains the authentication token
 const TOKEN_HEADER: &str = "X-Token";
 
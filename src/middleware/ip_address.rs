@@ -17,6 +17,10 @@ use thiserror::Error;
 /// Middleware that extracts the IP address of the connection
 pub struct IpAddress(pub Ipv4Addr);
 
+/// Middleware that extracts the IP address of the connection, unlike
+/// [IpAddress] this also accepts IPv6 clients
+pub struct ClientAddress(pub IpAddr);
+
 /// Header used to extract the real client IP address, provided by the reverse proxy
 const REAL_IP_HEADER: &str = "X-Real-IP";
 
@@ -27,6 +31,19 @@ where
 {
     type Rejection = IpAddressError;
 
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ClientAddress(addr) = ClientAddress::from_request_parts(parts, state).await?;
+        try_ip_address(addr).map(Self)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientAddress
+where
+    S: Send + Sync,
+{
+    type Rejection = IpAddressError;
+
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let config = parts
             .extensions
@@ -42,20 +59,19 @@ where
                     correctly according the guide. (Closing connection with error) cause: {}", err);
                     err
                 })
-                .map(Self);
+                .map(|addr| Self(addr.to_canonical()));
         }
 
         Extension::<ConnectInfo<SocketAddr>>::from_request_parts(parts, state)
             .await
             .map_err(IpAddressError::ConnectInfo)
-            .and_then(|value| try_socket_address(value.0 .0))
-            .map(Self)
+            .map(|value| Self(value.0 .0.ip().to_canonical()))
     }
 }
 
 /// Attempts to extract the value of the X-Real-IP header provided
 /// by reverse proxies
-fn extract_ip_header(headers: &HeaderMap) -> Result<Ipv4Addr, IpAddressError> {
+fn extract_ip_header(headers: &HeaderMap) -> Result<IpAddr, IpAddressError> {
     let header = headers
         .get(REAL_IP_HEADER)
         .ok_or(IpAddressError::MissingHeader)
@@ -63,23 +79,21 @@ fn extract_ip_header(headers: &HeaderMap) -> Result<Ipv4Addr, IpAddressError> {
 
     // Attempt to parse as IP address first (address)
     if let Ok(addr) = header.parse::<IpAddr>() {
-        return match addr {
-            IpAddr::V4(addr) => Ok(addr),
-            IpAddr::V6(_) => Err(IpAddressError::Unsupported),
-        };
+        return Ok(addr);
     }
 
     // Fallback attempt to parse as a socket address (address:port)
     let addr = header.parse::<SocketAddr>()?;
-    try_socket_address(addr)
+    Ok(addr.ip())
 }
 
-/// Attempts to extract an [Ipv4Addr] from the provided socket address
-/// returning an error if the [SocketAddr] isn't an IPv4 addr
-fn try_socket_address(addr: SocketAddr) -> Result<Ipv4Addr, IpAddressError> {
+/// Attempts to extract an [Ipv4Addr] from the provided address, IPv4-mapped
+/// IPv6 addresses (i.e "::ffff:a.b.c.d" from IPv4 clients on dual stack
+/// listeners) are converted back into their IPv4 address
+fn try_ip_address(addr: IpAddr) -> Result<Ipv4Addr, IpAddressError> {
     match addr {
-        SocketAddr::V4(addr) => Ok(*addr.ip()),
-        SocketAddr::V6(_) => Err(IpAddressError::Unsupported),
+        IpAddr::V4(addr) => Ok(addr),
+        IpAddr::V6(addr) => addr.to_ipv4_mapped().ok_or(IpAddressError::Unsupported),
     }
 }
 
@@ -121,7 +135,7 @@ impl IntoResponse for IpAddressError {
 
 #[cfg(test)]
 mod test {
-    use super::{extract_ip_header, IpAddress, IpAddressError, REAL_IP_HEADER};
+    use super::{extract_ip_header, ClientAddress, IpAddress, IpAddressError, REAL_IP_HEADER};
     use crate::config::RuntimeConfig;
     use axum::{
         extract::{ConnectInfo, FromRequestParts},
@@ -129,7 +143,7 @@ mod test {
     };
     use hyper::{HeaderMap, Request};
     use std::{
-        net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+        net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
         sync::Arc,
     };
 
@@ -185,17 +199,58 @@ mod test {
         assert!(matches!(value, IpAddressError::ParsingFailed(_)))
     }
 
-    /// Tests that IPv6 headers result in an error
-    #[test]
-    fn test_ipv6_addr() {
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            REAL_IP_HEADER,
-            HeaderValue::from_static("b44e:2ae1:f85e:2381:7a67:fb1e:2ffd:c053"),
-        );
+    /// Extracts the [IpAddress] and [ClientAddress] from a request using the
+    /// provided X-Real-IP `header` value
+    async fn extract_from_header(
+        header: &'static str,
+    ) -> (
+        Result<Ipv4Addr, IpAddressError>,
+        Result<IpAddr, IpAddressError>,
+    ) {
+        let config = Arc::new(RuntimeConfig {
+            reverse_proxy: true,
+            ..Default::default()
+        });
+        let req = Request::builder()
+            .extension(config)
+            .header(REAL_IP_HEADER, HeaderValue::from_static(header))
+            .body("")
+            .unwrap();
+        let (mut parts, _) = req.into_parts();
 
-        let value = extract_ip_header(&headers).unwrap_err();
-        assert!(matches!(value, IpAddressError::Unsupported))
+        let ip = IpAddress::from_request_parts(&mut parts, &())
+            .await
+            .map(|IpAddress(addr)| addr);
+        let client = ClientAddress::from_request_parts(&mut parts, &())
+            .await
+            .map(|ClientAddress(addr)| addr);
+        (ip, client)
+    }
+
+    /// Tests that IPv6 headers result in an error for [IpAddress] but are
+    /// accepted by [ClientAddress]
+    #[tokio::test]
+    async fn test_ipv6_addr() {
+        let header = "b44e:2ae1:f85e:2381:7a67:fb1e:2ffd:c053";
+        let (ip, client) = extract_from_header(header).await;
+
+        assert!(matches!(ip, Err(IpAddressError::Unsupported)));
+        assert_eq!(client.unwrap(), header.parse::<IpAddr>().unwrap());
+    }
+
+    /// Tests that IPv4-mapped IPv6 addresses are extracted as their IPv4 address
+    #[tokio::test]
+    async fn test_ipv4_mapped_addr() {
+        let values = [
+            ("::ffff:127.0.0.1", Ipv4Addr::new(127, 0, 0, 1)),
+            ("[::ffff:192.168.0.1]:5900", Ipv4Addr::new(192, 168, 0, 1)),
+        ];
+
+        for (header, expected) in values {
+            let (ip, client) = extract_from_header(header).await;
+            assert_eq!(ip.unwrap(), expected);
+            assert_eq!(client.unwrap(), expected);
+        }
     }
 
     /// Tests that missing the required header provides an error
@@ -1,3 +1,5 @@
+/// Address allow and deny list middleware
+pub mod access;
 /// Extractor for association tokens
 pub mod association;
 /// Middleware functions an enums related to token authentication
@@ -6,6 +8,8 @@ pub mod auth;
 pub mod cors;
 /// IP address extraction middleware
 pub mod ip_address;
+/// Per address rate limiting middleware
+pub mod rate_limit;
 /// XML response types
 pub mod xml;
 
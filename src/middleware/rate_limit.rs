@@ -0,0 +1,139 @@
+use super::ip_address::{ClientAddress, IpAddressError};
+use crate::config::RateLimitConfig;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use parking_lot::Mutex;
+use std::{collections::HashMap, net::IpAddr, sync::Arc, time::Instant};
+
+/// Number of tracked addresses before buckets that have fully
+/// refilled are removed
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Rate limiter using a token bucket for each source address
+pub struct RateLimiter {
+    /// Maximum number of tokens a bucket can hold
+    burst: f64,
+    /// Number of tokens added to a bucket each second
+    per_second: f64,
+    /// Buckets for each source address
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+/// Token bucket for a single source address
+struct Bucket {
+    /// The number of tokens available
+    tokens: f64,
+    /// The last time the tokens were refilled
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a new rate limiter from the provided `config`
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            burst: config.burst as f64,
+            per_second: config.per_second as f64,
+            buckets: Default::default(),
+        }
+    }
+
+    /// Attempts to take a token for the provided `addr` returning
+    /// whether the request is allowed
+    pub fn try_acquire(&self, addr: IpAddr) -> bool {
+        self.try_acquire_at(addr, Instant::now())
+    }
+
+    fn try_acquire_at(&self, addr: IpAddr, now: Instant) -> bool {
+        let buckets = &mut *self.buckets.lock();
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| self.refilled(bucket, now) < self.burst);
+        }
+
+        let bucket = buckets.entry(addr).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.last_refill = now;
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// Computes the tokens the `bucket` would have at `now`
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        (bucket.tokens + elapsed.as_secs_f64() * self.per_second).min(self.burst)
+    }
+}
+
+/// Middleware layer function that rejects requests with 429 Too Many
+/// Requests when the source address has exceeded the rate limit.
+///
+/// Requests where the address cannot be determined are not limited
+///
+/// `limiter` The rate limiter to use
+/// `addr`    The source address of the request
+/// `req`     The request to handle
+/// `next`    The next layer to use
+pub async fn rate_limit_layer(
+    State(limiter): State<Arc<RateLimiter>>,
+    addr: Result<ClientAddress, IpAddressError>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if let Ok(ClientAddress(addr)) = addr {
+        if !limiter.try_acquire(addr) {
+            return StatusCode::TOO_MANY_REQUESTS.into_response();
+        }
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::RateLimiter;
+    use crate::config::RateLimitConfig;
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::{Duration, Instant},
+    };
+
+    /// Tests that buckets are exhausted by a burst, refill over
+    /// time and are separate for each address
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            burst: 3,
+            per_second: 1,
+        });
+        let a = IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1));
+        let b = "2001:db8::1".parse::<IpAddr>().unwrap();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.try_acquire_at(a, now));
+        }
+        assert!(!limiter.try_acquire_at(a, now));
+
+        // Other addresses have their own bucket
+        assert!(limiter.try_acquire_at(b, now));
+
+        // Single token refilled after a second
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.try_acquire_at(a, later));
+        assert!(!limiter.try_acquire_at(a, later));
+    }
+}
@@ -0,0 +1,400 @@
+//! Replaying of captured sessions against a server, used as a regression
+//! tool to verify the server responds the same way the captured server did
+//!
+//! # Capture format
+//!
+//! A capture file is a sequence of records, each record is structured as:
+//!
+//! ```text
+//! direction: u8  (0 = client to server, 1 = server to client)
+//! delay_ms:  u32 (Milliseconds elapsed since the previous record)
+//! length:    u32 (Length of the packet bytes)
+//! packet:    [u8; length] (The raw encoded packet including its frame)
+//! ```
+//!
+//! All numbers are big-endian
+
+use crate::session::packet::{decode_packet, decode_packets, Packet, PacketCodec};
+use bytes::Buf;
+use futures_util::{SinkExt, StreamExt};
+use std::{io, time::Duration};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    time::{sleep, timeout},
+};
+use tokio_util::codec::Framed;
+
+/// Time to wait for an expected response before its considered missing
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum length of the upgrade response headers before the server
+/// is considered misbehaving
+const MAX_UPGRADE_HEADER_LENGTH: usize = 8 * 1024;
+
+/// Direction a captured packet was travelling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Packet sent from the client to the server
+    ClientToServer = 0,
+    /// Packet sent from the server to the client
+    ServerToClient = 1,
+}
+
+/// Single captured packet
+#[derive(Debug)]
+pub struct CaptureRecord {
+    /// Direction the packet was travelling
+    pub direction: Direction,
+    /// Time elapsed since the previous record
+    pub delay: Duration,
+    /// The captured packet
+    pub packet: Packet,
+}
+
+/// Errors that can occur while replaying a capture
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    /// Failed to read or write to the connection
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    /// The capture file was malformed
+    #[error("Malformed capture: {0}")]
+    MalformedCapture(&'static str),
+    /// The server did not accept the connection upgrade
+    #[error("Server rejected connection upgrade: {0}")]
+    UpgradeRejected(String),
+    /// The upgrade response headers exceeded the maximum length
+    #[error("Upgrade response headers exceeded {0} bytes")]
+    UpgradeHeadersTooLong(usize),
+}
+
+/// Mismatch between an expected server packet and the packet
+/// the server actually sent
+#[derive(Debug)]
+pub struct ReplayMismatch {
+    /// Index of the expected record within the capture
+    pub index: usize,
+    /// The decoded expected packet
+    pub expected: String,
+    /// The decoded actual packet, [None] if the server didn't respond
+    pub actual: Option<String>,
+}
+
+impl ReplayMismatch {
+    /// Creates a line diff between the expected and actual packets
+    pub fn diff(&self) -> String {
+        let mut output = String::new();
+        let actual = self.actual.as_deref().unwrap_or("<No packet received>");
+
+        let mut expected_lines = self.expected.lines();
+        let mut actual_lines = actual.lines();
+
+        loop {
+            match (expected_lines.next(), actual_lines.next()) {
+                (Some(expected), Some(actual)) if expected == actual => {
+                    output.push_str("  ");
+                    output.push_str(expected);
+                    output.push('\n');
+                }
+                (None, None) => break,
+                (expected, actual) => {
+                    if let Some(expected) = expected {
+                        output.push_str("- ");
+                        output.push_str(expected);
+                        output.push('\n');
+                    }
+                    if let Some(actual) = actual {
+                        output.push_str("+ ");
+                        output.push_str(actual);
+                        output.push('\n');
+                    }
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// Outcome of replaying a capture
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    /// Number of packets sent to the server
+    pub sent: usize,
+    /// Number of expected server packets that matched
+    pub matched: usize,
+    /// Expected server packets that didn't match
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+/// Parses the records from the raw capture file `bytes`
+pub fn parse_capture(mut bytes: &[u8]) -> Result<Vec<CaptureRecord>, ReplayError> {
+    const HEADER_SIZE: usize = 9;
+
+    let mut records = Vec::new();
+
+    while !bytes.is_empty() {
+        if bytes.remaining() < HEADER_SIZE {
+            return Err(ReplayError::MalformedCapture("Incomplete record header"));
+        }
+
+        let direction = match bytes.get_u8() {
+            0 => Direction::ClientToServer,
+            1 => Direction::ServerToClient,
+            _ => return Err(ReplayError::MalformedCapture("Unknown direction")),
+        };
+        let delay = Duration::from_millis(bytes.get_u32() as u64);
+        let length = bytes.get_u32() as usize;
+
+        if bytes.remaining() < length {
+            return Err(ReplayError::MalformedCapture("Incomplete packet"));
+        }
+
+        let (packet, remaining) = bytes.split_at(length);
+        bytes = remaining;
+
+        let mut packets = decode_packets(packet)?;
+        if packets.len() != 1 {
+            return Err(ReplayError::MalformedCapture(
+                "Expected one packet per record",
+            ));
+        }
+
+        records.push(CaptureRecord {
+            direction,
+            delay,
+            packet: packets.remove(0),
+        });
+    }
+
+    Ok(records)
+}
+
+/// Connects to the server at `target` and upgrades the connection to
+/// a blaze session the same way the client tool does
+pub async fn connect(target: &str) -> Result<TcpStream, ReplayError> {
+    let mut stream = TcpStream::connect(target).await?;
+
+    let request = format!(
+        "GET /api/server/upgrade HTTP/1.1\r\n\
+        Host: {target}\r\n\
+        Connection: Upgrade\r\n\
+        Upgrade: blaze\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read the response headers one byte at a time to avoid
+    // consuming any of the blaze traffic following them
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_UPGRADE_HEADER_LENGTH {
+            return Err(ReplayError::UpgradeHeadersTooLong(
+                MAX_UPGRADE_HEADER_LENGTH,
+            ));
+        }
+
+        let byte = stream.read_u8().await?;
+        response.push(byte);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+
+    if !status.contains(" 101 ") {
+        return Err(ReplayError::UpgradeRejected(status.to_string()));
+    }
+
+    Ok(stream)
+}
+
+/// Replays the captured `records` over the provided `io` sending the client
+/// packets with their original timing and comparing the server packets
+pub async fn replay<I>(io: I, records: &[CaptureRecord]) -> Result<ReplayReport, ReplayError>
+where
+    I: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut framed = Framed::new(io, PacketCodec::default());
+    let mut report = ReplayReport::default();
+
+    for (index, record) in records.iter().enumerate() {
+        match record.direction {
+            Direction::ClientToServer => {
+                if !record.delay.is_zero() {
+                    sleep(record.delay).await;
+                }
+
+                framed.send(record.packet.clone()).await?;
+                report.sent += 1;
+            }
+            Direction::ServerToClient => {
+                let actual = match timeout(RESPONSE_TIMEOUT, framed.next()).await {
+                    Ok(Some(result)) => Some(result?),
+                    // Connection closed or the server didn't respond in time
+                    Ok(None) | Err(_) => None,
+                };
+
+                if actual
+                    .as_ref()
+                    .is_some_and(|actual| packets_match(&record.packet, actual))
+                {
+                    report.matched += 1;
+                    continue;
+                }
+
+                report.mismatches.push(ReplayMismatch {
+                    index,
+                    expected: decode_packet(&record.packet),
+                    actual: actual.as_ref().map(decode_packet),
+                });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Compares the routing, type, error and contents of two packets
+fn packets_match(expected: &Packet, actual: &Packet) -> bool {
+    let (a, b) = (&expected.frame, &actual.frame);
+
+    a.component == b.component
+        && a.command == b.command
+        && a.ty == b.ty
+        && a.error == b.error
+        && a.seq == b.seq
+        && expected.contents == actual.contents
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        connect, parse_capture, replay, CaptureRecord, Direction, ReplayError,
+        MAX_UPGRADE_HEADER_LENGTH,
+    };
+    use crate::{
+        session::{
+            models::util::PingResponse,
+            packet::{Packet, PacketCodec},
+        },
+        utils::components::{self, util},
+    };
+    use bytes::BufMut;
+    use futures_util::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_util::codec::Framed;
+
+    /// Writes the provided `record` to `dst` in the capture format
+    fn write_capture_record(dst: &mut Vec<u8>, record: &CaptureRecord) {
+        let mut packet = bytes::BytesMut::new();
+        record
+            .packet
+            .frame
+            .write(&mut packet, record.packet.contents.len());
+        packet.extend_from_slice(&record.packet.contents);
+
+        dst.put_u8(record.direction as u8);
+        dst.put_u32(record.delay.as_millis() as u32);
+        dst.put_u32(packet.len() as u32);
+        dst.extend_from_slice(&packet);
+    }
+
+    /// Tests replaying a capture against a stub server that always
+    /// responds to pings with the same server time
+    #[tokio::test]
+    async fn test_replay() {
+        components::initialize();
+
+        let first = Packet::request_empty(1, util::COMPONENT, util::PING);
+        let second = Packet::request_empty(2, util::COMPONENT, util::PING);
+
+        let records = [
+            (Direction::ClientToServer, first.clone()),
+            (
+                Direction::ServerToClient,
+                Packet::response(&first, PingResponse { server_time: 5 }),
+            ),
+            (Direction::ClientToServer, second.clone()),
+            (
+                Direction::ServerToClient,
+                Packet::response(&second, PingResponse { server_time: 6 }),
+            ),
+        ];
+
+        // Round trip the records through the capture format
+        let mut capture = Vec::new();
+        for (direction, packet) in records {
+            write_capture_record(
+                &mut capture,
+                &CaptureRecord {
+                    direction,
+                    delay: Duration::ZERO,
+                    packet,
+                },
+            );
+        }
+        let records = parse_capture(&capture).unwrap();
+        assert_eq!(records.len(), 4);
+
+        let (client, server) = tokio::io::duplex(1024);
+
+        // Stub server that responds to every ping
+        tokio::spawn(async move {
+            let mut framed = Framed::new(server, PacketCodec::default());
+            while let Some(Ok(packet)) = framed.next().await {
+                let response = Packet::response(&packet, PingResponse { server_time: 5 });
+                if framed.send(response).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let report = replay(client, &records).await.unwrap();
+
+        assert_eq!(report.sent, 2);
+        assert_eq!(report.matched, 1);
+        assert_eq!(report.mismatches.len(), 1);
+
+        let mismatch = &report.mismatches[0];
+        assert_eq!(mismatch.index, 3);
+
+        let diff = mismatch.diff();
+        assert!(diff.contains("-   \"STIM\": 6,\n+   \"STIM\": 5,"));
+    }
+
+    /// Tests that truncated captures are rejected
+    #[test]
+    fn test_malformed_capture() {
+        assert!(parse_capture(&[0, 0, 0]).is_err());
+        assert!(parse_capture(&[2, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+
+    /// Tests that upgrade responses with endless headers are rejected
+    /// rather than buffered forever
+    #[tokio::test]
+    async fn test_upgrade_headers_too_long() {
+        use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Stub server that never finishes its headers
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            _ = stream
+                .write_all(b"HTTP/1.1 101 Switching Protocols\r\n")
+                .await;
+            while stream.write_all(b"X-Padding: 0000000000\r\n").await.is_ok() {}
+        });
+
+        let result = connect(&addr.to_string()).await;
+
+        assert!(matches!(
+            result,
+            Err(ReplayError::UpgradeHeadersTooLong(
+                MAX_UPGRADE_HEADER_LENGTH
+            ))
+        ));
+    }
+}
@@ -3,7 +3,8 @@ use std::sync::Arc;
 use crate::{
     config::RuntimeConfig,
     database::entities::{Player, PlayerRole},
-    services::sessions::Sessions,
+    middleware::ip_address::ClientAddress,
+    services::{account_limit::AccountCreationLimiter, sessions::Sessions},
     session::{models::messaging::MessageNotify, packet::Packet},
     utils::{
         components::messaging,
@@ -11,12 +12,10 @@ use crate::{
     },
 };
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Extension, Json,
 };
-use chrono::Utc;
-use log::error;
 use sea_orm::{DatabaseConnection, DbErr};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -56,6 +55,11 @@ pub enum AuthError {
     #[error("This server has disabled dashboard account registration")]
     RegistrationDisabled,
 
+    /// Address has created too many accounts, contains the number of
+    /// seconds until another account can be created
+    #[error("Too many accounts have been created, try again later")]
+    TooManyAccounts(u64),
+
     /// Session is not active
     #[error("This player is not currently connected, please connect to the server and visit the main menu in-game before attempting this action.")]
     SessionNotActive,
@@ -113,9 +117,7 @@ pub async fn login(
     }
 
     // Update last login timestamp
-    if let Err(err) = Player::set_last_login(&db, player.id, Utc::now()).await {
-        error!("failed to store last login time: {err}");
-    }
+    Player::record_login(&db, player.id);
 
     let token = sessions.create_token(player.id);
     Ok(Json(TokenResponse { token }))
@@ -139,9 +141,11 @@ pub struct CreateRequest {
 /// Upon success will provide a [`TokenResponse`] containing
 /// the authentication token for the created user
 pub async fn create(
+    ClientAddress(addr): ClientAddress,
     Extension(db): Extension<DatabaseConnection>,
     Extension(config): Extension<Arc<RuntimeConfig>>,
     Extension(sessions): Extension<Arc<Sessions>>,
+    Extension(account_limiter): Extension<Arc<AccountCreationLimiter>>,
     Json(CreateRequest {
         username,
         email,
@@ -169,13 +173,19 @@ pub async fn create(
         PlayerRole::Default
     };
 
+    // Limit the number of accounts created by the address
+    let permit = account_limiter
+        .try_acquire(addr)
+        .map_err(|retry_after| AuthError::TooManyAccounts(retry_after.as_secs()))?;
+
     let password: String = hash_password(&password)?;
     let player: Player = Player::create(&db, email, username, Some(password), role).await?;
 
+    // Only successfully created accounts count towards the limit
+    permit.commit();
+
     // Update last login timestamp
-    if let Err(err) = Player::set_last_login(&db, player.id, Utc::now()).await {
-        error!("failed to store last login time: {err}");
-    }
+    Player::record_login(&db, player.id);
 
     let token = sessions.create_token(player.id);
     Ok(Json(TokenResponse { token }))
@@ -277,9 +287,7 @@ pub async fn handle_exchange_login_code(
         .ok_or(AuthError::InvalidCode)?;
 
     // Update last login timestamp
-    if let Err(err) = Player::set_last_login(&db, player_id, Utc::now()).await {
-        error!("failed to store last login time: {err}");
-    }
+    Player::record_login(&db, player_id);
 
     Ok(Json(TokenResponse { token }))
 }
@@ -298,8 +306,77 @@ impl IntoResponse for AuthError {
             | Self::NoMatchingAccount
             | Self::InvalidCode => StatusCode::BAD_REQUEST,
             Self::RegistrationDisabled => StatusCode::FORBIDDEN,
+            Self::TooManyAccounts(retry_after) => {
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                    self.to_string(),
+                )
+                    .into_response();
+            }
         };
 
         (status_code, self.to_string()).into_response()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{login, LoginRequest};
+    use crate::{
+        database::{
+            entities::{Player, PlayerRole},
+            test_database,
+        },
+        session::test_util::test_sessions,
+        utils::hashing::hash_password,
+    };
+    use axum::{Extension, Json};
+    use std::time::Duration;
+    use tokio::time::{sleep, timeout};
+
+    /// Tests that logging in updates the player last login time
+    #[tokio::test]
+    async fn test_login_updates_last_login() {
+        let db = test_database().await;
+        let sessions = test_sessions();
+
+        let password = hash_password("password").unwrap();
+        let player = Player::create(
+            &db,
+            "test@test.com".to_string(),
+            "test".to_string(),
+            Some(password),
+            PlayerRole::Default,
+        )
+        .await
+        .unwrap();
+        assert!(player.last_login_at.is_none());
+
+        let _ = login(
+            Extension(db.clone()),
+            Extension(sessions),
+            Json(LoginRequest {
+                email: "test@test.com".to_string(),
+                password: "password".to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+
+        // The timestamp is stored in the background
+        let last_login_at = timeout(Duration::from_secs(5), async {
+            loop {
+                let player = Player::by_id(&db, player.id).await.unwrap().unwrap();
+                if let Some(last_login_at) = player.last_login_at {
+                    return last_login_at;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(last_login_at <= chrono::Utc::now());
+    }
+}
@@ -1,13 +1,15 @@
 use crate::{
     config::RuntimeConfig,
     database::entities::players::PlayerRole,
-    middleware::auth::MaybeAuth,
-    services::game::{manager::GameManager, GameSnapshot},
-    utils::types::GameID,
+    middleware::auth::{AdminAuth, MaybeAuth},
+    services::game::{
+        manager::GameManager, GameDebugSnapshot, GameSnapshot, GameSummary, ResetSeedError,
+    },
+    utils::types::{GameID, PlayerID},
 };
 use axum::{
     extract::{Path, Query},
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Extension, Json,
 };
@@ -23,6 +25,18 @@ pub enum GamesError {
     NotFound,
     #[error("Missing required access")]
     NoPermission,
+    /// The game has already started so its seed cannot be changed
+    #[error("Game has already started")]
+    AlreadyStarted,
+    /// The game has players that were already sent its seed
+    #[error("Game has players")]
+    HasPlayers,
+    /// The player to kick is not in the game
+    #[error("Player not found")]
+    PlayerNotFound,
+    /// Failed to serialize the game snapshot
+    #[error("Internal server error")]
+    Serialize(#[source] serde_json::Error),
 }
 
 /// The query structure for a players query
@@ -35,14 +49,28 @@ pub struct GamesRequest {
     /// of 255 entries to prevent server strain from querying the
     /// entire list of leaderboard entries
     count: Option<u8>,
+    /// Whether to respond with summaries of the games instead
+    /// of the full game snapshots
+    #[serde(default)]
+    summary: bool,
+}
+
+/// List of games in either their full or summarized form
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum GamesList {
+    /// Full snapshots of the games
+    Snapshots(Vec<GameSnapshot>),
+    /// Summaries of the games
+    Summaries(Vec<GameSummary>),
 }
 
 /// Response from the players endpoint which contains a list of
 /// players and whether there is more players after
 #[derive(Serialize)]
 pub struct GamesResponse {
-    /// The list of players retrieved
-    games: Vec<GameSnapshot>,
+    /// The list of games retrieved
+    games: GamesList,
     /// Whether there is more players left in the database
     more: bool,
     /// Total number of items available
@@ -57,9 +85,17 @@ pub struct GamesResponse {
 ///
 /// Player networking information is included for requesting
 /// players with admin level or greater access.
+///
+/// When summary is requested only the ID, state, player count and
+/// whether the game is joinable are included for each game, the
+/// full details can be obtained from [get_game]
 pub async fn get_games(
     MaybeAuth(auth): MaybeAuth,
-    Query(GamesRequest { offset, count }): Query<GamesRequest>,
+    Query(GamesRequest {
+        offset,
+        count,
+        summary,
+    }): Query<GamesRequest>,
     Extension(game_manager): Extension<Arc<GameManager>>,
     Extension(config): Extension<Arc<RuntimeConfig>>,
 ) -> Result<Json<GamesResponse>, GamesError> {
@@ -75,9 +111,15 @@ pub async fn get_games(
     let include_players = auth.is_some() || !config.api.public_games_hide_players;
 
     // Retrieve the game snapshots
-    let (games, more) = game_manager
-        .create_snapshot(offset, count, include_net, include_players)
-        .await;
+    let (games, more) = if summary {
+        let (games, more) = game_manager.create_summaries(offset, count).await;
+        (GamesList::Summaries(games), more)
+    } else {
+        let (games, more) = game_manager
+            .create_snapshot(offset, count, include_net, include_players)
+            .await;
+        (GamesList::Snapshots(games), more)
+    };
 
     // Get the total number of games
     let total_games = game_manager.get_total_games().await;
@@ -121,12 +163,99 @@ pub async fn get_game(
     Ok(Json(snapshot))
 }
 
+/// Query for the game debug snapshot
+#[derive(Deserialize)]
+pub struct DebugSnapshotQuery {
+    /// Whether to pretty-print the snapshot JSON
+    #[serde(default)]
+    pretty: bool,
+}
+
+/// GET /api/games/:id/debug
+///
+/// Handles requests for a detailed snapshot of the full game state
+/// including the mesh and connection details of each player, used
+/// to reproduce reported bugs
+pub async fn get_game_debug(
+    _: AdminAuth,
+    Path(game_id): Path<GameID>,
+    Query(DebugSnapshotQuery { pretty }): Query<DebugSnapshotQuery>,
+    Extension(game_manager): Extension<Arc<GameManager>>,
+) -> Result<Response, GamesError> {
+    let game = game_manager
+        .get_game(game_id)
+        .await
+        .ok_or(GamesError::NotFound)?;
+    let snapshot: GameDebugSnapshot = game.read().await.debug_snapshot();
+
+    if !pretty {
+        return Ok(Json(snapshot).into_response());
+    }
+
+    let body = serde_json::to_string_pretty(&snapshot).map_err(GamesError::Serialize)?;
+    Ok(([(header::CONTENT_TYPE, "application/json")], body).into_response())
+}
+
+/// Response containing the new seed for a game
+#[derive(Serialize)]
+pub struct ResetSeedResponse {
+    /// The new game seed
+    seed: u32,
+}
+
+/// POST /api/games/:id/seed
+///
+/// Handles resetting the randomness seed of a game, only games that
+/// haven't started and have no players can have their seed reset as
+/// players can't be sent the new seed
+pub async fn reset_game_seed(
+    _: AdminAuth,
+    Path(game_id): Path<GameID>,
+    Extension(game_manager): Extension<Arc<GameManager>>,
+) -> Result<Json<ResetSeedResponse>, GamesError> {
+    let game = game_manager
+        .get_game(game_id)
+        .await
+        .ok_or(GamesError::NotFound)?;
+    let game = &mut *game.write().await;
+    let seed = game.reset_seed().map_err(|err| match err {
+        ResetSeedError::AlreadyStarted => GamesError::AlreadyStarted,
+        ResetSeedError::HasPlayers => GamesError::HasPlayers,
+    })?;
+
+    Ok(Json(ResetSeedResponse { seed }))
+}
+
+/// POST /api/games/:id/kick/:player_id
+///
+/// Handles kicking a player from a game, if the kicked player
+/// was the host then host migration will take place
+pub async fn kick_player(
+    _: AdminAuth,
+    Path((game_id, player_id)): Path<(GameID, PlayerID)>,
+    Extension(game_manager): Extension<Arc<GameManager>>,
+) -> Result<StatusCode, GamesError> {
+    let game = game_manager
+        .get_game(game_id)
+        .await
+        .ok_or(GamesError::NotFound)?;
+    let game = &mut *game.write().await;
+
+    if !game.kick_player(player_id) {
+        return Err(GamesError::PlayerNotFound);
+    }
+
+    Ok(StatusCode::OK)
+}
+
 /// Response implementation for games errors
 impl IntoResponse for GamesError {
     fn into_response(self) -> Response {
         let status_code = match &self {
-            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::NotFound | Self::PlayerNotFound => StatusCode::NOT_FOUND,
             Self::NoPermission => StatusCode::FORBIDDEN,
+            Self::AlreadyStarted | Self::HasPlayers => StatusCode::CONFLICT,
+            Self::Serialize(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
         (status_code, self.to_string()).into_response()
@@ -140,7 +140,13 @@ pub async fn increase_ratings(
     Extension(sessions): Extension<Arc<Sessions>>,
 ) -> Result<Xml, GAWError> {
     let (gaw_data, promotions) = get_player_gaw_data(&db, sessions, &id, &config).await?;
-    let gaw_data = gaw_data.add(&db, [a, b, c, d, e]).await?;
+    let gaw_data = gaw_data
+        .add(
+            &db,
+            [a, b, c, d, e],
+            config.galaxy_at_war.daily_increase_cap,
+        )
+        .await?;
     Ok(ratings_response(gaw_data, promotions))
 }
 
@@ -3,6 +3,7 @@ use crate::{
         leaderboard_data::{LeaderboardDataAndRank, LeaderboardType},
         LeaderboardData,
     },
+    services::health::HealthService,
     utils::types::PlayerID,
 };
 use axum::{
@@ -13,6 +14,7 @@ use axum::{
 };
 use sea_orm::{DatabaseConnection, DbErr};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Error type used in leaderboard routes to handle errors
@@ -65,6 +67,7 @@ pub struct LeaderboardResponse {
 pub async fn get_leaderboard(
     Path(ty): Path<LeaderboardType>,
     Extension(db): Extension<DatabaseConnection>,
+    Extension(health): Extension<Arc<HealthService>>,
     Query(LeaderboardQuery { offset, count }): Query<LeaderboardQuery>,
 ) -> Result<Json<LeaderboardResponse>, LeaderboardError> {
     /// The default number of entries to return in a leaderboard response
@@ -75,8 +78,13 @@ pub async fn get_leaderboard(
     // Calculate the start and ending indexes
     let start: u32 = offset * count;
 
-    let values = LeaderboardData::get_offset(&db, ty, start, count).await?;
-    let total = LeaderboardData::count(&db, ty).await? as u32;
+    let values = LeaderboardData::get_offset(&db, ty, start, count).await;
+    health.record_leaderboard(&values);
+    let values = values?;
+
+    let total = LeaderboardData::count(&db, ty).await;
+    health.record_leaderboard(&total);
+    let total = total? as u32;
 
     // There are more if the end < the total number of values
     let more = (start + count) < (total + 1);
@@ -98,8 +106,12 @@ pub async fn get_leaderboard(
 pub async fn get_player_ranking(
     Path((ty, player_id)): Path<(LeaderboardType, PlayerID)>,
     Extension(db): Extension<DatabaseConnection>,
+    Extension(health): Extension<Arc<HealthService>>,
 ) -> Result<Json<LeaderboardDataAndRank>, LeaderboardError> {
-    let entry = match LeaderboardData::get_entry(&db, ty, player_id).await? {
+    let entry = LeaderboardData::get_entry(&db, ty, player_id).await;
+    health.record_leaderboard(&entry);
+
+    let entry = match entry? {
         Some(value) => value,
         None => return Err(LeaderboardError::PlayerNotFound),
     };
@@ -1,10 +1,19 @@
 use axum::{
+    extract::DefaultBodyLimit,
     middleware,
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Router,
 };
 
-use crate::middleware::cors::cors_layer;
+use crate::{
+    config::{DashboardConfig, HttpConfig},
+    middleware::{
+        auth::require_auth_layer,
+        cors::cors_layer,
+        rate_limit::{rate_limit_layer, RateLimiter},
+    },
+};
+use std::sync::Arc;
 
 use self::server::clear_log;
 
@@ -19,8 +28,12 @@ mod server;
 
 /// Function for configuring the provided service config with all the
 /// application routes.
-pub fn router() -> Router {
-    Router::new()
+///
+/// `http`      The HTTP configuration for the request body and rate limits
+/// `dashboard` The dashboard configuration for the dashboard asset source
+pub fn router(http: &HttpConfig, dashboard: &DashboardConfig) -> Router {
+    // Unauthenticated game routes and public content
+    let mut public = Router::new()
         // Galaxy at war
         .route(
             "/authentication/sharedTokenLogin",
@@ -40,23 +53,47 @@ pub fn router() -> Router {
                 .route("/firewall", get(qos::firewall))
                 .route("/firetype", get(qos::firetype)),
         )
+        // Public content fallback
+        .fallback_service(public::PublicContentService::new(
+            &http.content_types,
+            dashboard.source,
+        ));
+
+    if let Some(rate_limit) = &http.public_rate_limit {
+        let limiter = Arc::new(RateLimiter::new(rate_limit));
+        public = public.layer(middleware::from_fn_with_state(limiter, rate_limit_layer));
+    }
+
+    let mut games = Router::new()
+        .route("/", get(games::get_games))
+        .route("/:id", get(games::get_game))
+        .route("/:id/debug", get(games::get_game_debug))
+        .route("/:id/seed", post(games::reset_game_seed))
+        .route("/:id/kick/:player_id", post(games::kick_player));
+
+    let mut leaderboard = Router::new()
+        .route("/:name", get(leaderboard::get_leaderboard))
+        .route("/:name/:player_id", get(leaderboard::get_player_ranking));
+
+    if http.require_auth_for_reads {
+        games = games.route_layer(middleware::from_fn(require_auth_layer));
+        leaderboard = leaderboard.route_layer(middleware::from_fn(require_auth_layer));
+    }
+
+    Router::new()
         // Dashboard API
         .nest(
             "/api",
             Router::new()
                 // Games routing
-                .nest(
-                    "/games",
-                    Router::new()
-                        .route("/", get(games::get_games))
-                        .route("/:id", get(games::get_game)),
-                )
+                .nest("/games", games)
                 // Players routing
                 .nest(
                     "/players",
                     Router::new()
                         .route("/", get(players::get_players))
                         .route("/self", get(players::get_self).delete(players::delete_self))
+                        .route("/by-email", get(players::get_player_by_email))
                         .route("/self/password", put(players::update_password))
                         .route("/self/details", put(players::update_details))
                         .route(
@@ -68,9 +105,13 @@ pub fn router() -> Router {
                             "/:id/data/:key",
                             get(players::get_data)
                                 .put(players::set_data)
-                                .delete(players::delete_data),
+                                .delete(players::delete_data)
+                                // Player data can exceed the default body limit
+                                .layer(DefaultBodyLimit::max(http.max_player_data_body_bytes)),
                         )
+                        .route("/:id/reset", post(players::reset_data))
                         .route("/:id/galaxy_at_war", get(players::get_player_gaw))
+                        .route("/:id/game", get(players::get_player_game))
                         .route("/:id/password", put(players::set_password))
                         .route("/:id/details", put(players::set_details))
                         .route("/:id/role", put(players::set_role)),
@@ -85,12 +126,7 @@ pub fn router() -> Router {
                         .route("/exchange-code", post(auth::handle_exchange_login_code)),
                 )
                 // Leaderboard routing
-                .nest(
-                    "/leaderboard",
-                    Router::new()
-                        .route("/:name", get(leaderboard::get_leaderboard))
-                        .route("/:name/:player_id", get(leaderboard::get_player_ranking)),
-                )
+                .nest("/leaderboard", leaderboard)
                 // Server details routes
                 .nest(
                     "/server",
@@ -99,11 +135,370 @@ pub fn router() -> Router {
                         .route("/log", get(server::get_log).delete(clear_log))
                         .route("/upgrade", get(server::upgrade))
                         .route("/tunnel", get(server::tunnel))
+                        .route("/tunnel/reconnect", get(server::tunnel_reconnect_token))
+                        .route("/tunnel/failures", get(server::tunnel_failures))
+                        .route("/tunnel/stats", get(server::tunnel_stats))
+                        .route("/tunnel/associate", post(server::tunnel_associate))
+                        .route("/maintenance", put(server::set_maintenance))
+                        .route("/sessions", get(server::sessions))
+                        .route(
+                            "/sessions/:player_id/errors",
+                            delete(server::reset_session_errors),
+                        )
+                        .route("/caches", get(server::caches))
+                        .route("/caches/clear", post(server::clear_caches))
                         .route("/telemetry", post(server::submit_telemetry))
-                        .route("/dashboard", get(server::dashboard_details)),
+                        .route("/dashboard", get(server::dashboard_details))
+                        .route("/version", get(server::version))
+                        .route("/health", get(server::health))
+                        .route("/stats/history", get(server::stats_history))
+                        .route("/matchmaking", get(server::matchmaking_stats))
+                        .route("/test/ports", post(server::test_ports)),
                 )
+                .layer(DefaultBodyLimit::max(http.max_body_bytes))
                 .layer(middleware::from_fn(cors_layer)),
         )
-        // Public content fallback
-        .fallback_service(public::PublicContent)
+        .merge(public)
+}
+
+#[cfg(test)]
+mod test {
+    use super::router;
+    use crate::{
+        config::{
+            APIConfig, DashboardConfig, HttpConfig, RateLimitConfig, RuntimeConfig, ServerConfig,
+            MIN_CLIENT_VERSION, VERSION,
+        },
+        database::{
+            entities::{Player, PlayerRole},
+            test_database,
+        },
+        services::{
+            maintenance_mode::MaintenanceMode,
+            public_address::PublicAddressService,
+            stats_history::{StatsHistory, StatsSample},
+            telemetry::TelemetryForwarder,
+        },
+        session::{
+            self,
+            test_util::{test_game_manager, test_sessions},
+        },
+        utils::listener::bind_listeners,
+    };
+    use axum::{
+        body::{to_bytes, Body},
+        extract::ConnectInfo,
+        http::{header, Method, Request, StatusCode},
+        Extension,
+    };
+    use chrono::Utc;
+    use std::{
+        net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+        sync::Arc,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+    use tower::ServiceExt;
+
+    /// Tests that API request bodies over the configured limit are rejected
+    #[tokio::test]
+    async fn test_body_limit() {
+        let router = router(
+            &HttpConfig {
+                max_body_bytes: 64,
+                ..Default::default()
+            },
+            &DashboardConfig::default(),
+        )
+        .layer(Extension(Arc::new(TelemetryForwarder::default())));
+
+        let request = |body: String| {
+            Request::builder()
+                .method(Method::POST)
+                .uri("/api/server/telemetry")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = router
+            .clone()
+            .oneshot(request(r#"{"values":[]}"#.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let value = "A".repeat(128);
+        let body = format!(r#"{{"values":[["KEY","{value}"]]}}"#);
+        let response = router.oneshot(request(body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    /// Tests that the recorded activity samples are returned by the
+    /// stats history endpoint
+    #[tokio::test]
+    async fn test_stats_history() {
+        let history = Arc::new(StatsHistory::default());
+        for value in 1..=2 {
+            history.record(StatsSample {
+                timestamp: Utc::now(),
+                sessions: value,
+                connections: value,
+                rejected_connections: 0,
+                games: value * 3,
+            });
+        }
+
+        let router =
+            router(&HttpConfig::default(), &DashboardConfig::default()).layer(Extension(history));
+        let request = Request::builder()
+            .uri("/api/server/stats/history")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["interval_seconds"], 60);
+        let samples = body["samples"].as_array().unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0]["sessions"], 1);
+        assert_eq!(samples[1]["games"], 6);
+    }
+
+    /// Tests that the configured server name and description are included
+    /// in the server details
+    #[tokio::test]
+    async fn test_server_details_name() {
+        let config = Arc::new(RuntimeConfig {
+            server: ServerConfig {
+                name: Some("Test Server".to_string()),
+                description: Some("Testing description".to_string()),
+            },
+            ..Default::default()
+        });
+        let sessions = test_sessions();
+        let router = router(&HttpConfig::default(), &DashboardConfig::default())
+            .layer(Extension(config))
+            .layer(Extension(sessions))
+            .layer(Extension(Arc::new(MaintenanceMode::new(false))))
+            .layer(Extension(Arc::new(PublicAddressService::new(None))));
+        let request = Request::builder()
+            .uri("/api/server")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["ident"], "POCKET_RELAY_SERVER");
+        assert_eq!(body["name"], "Test Server");
+        assert_eq!(body["description"], "Testing description");
+        assert_eq!(body["maintenance"], false);
+    }
+
+    /// Tests that new sessions are refused while in maintenance mode and
+    /// that the server details report the maintenance
+    #[tokio::test]
+    async fn test_upgrade_maintenance() {
+        let sessions = test_sessions();
+        let maintenance = Arc::new(MaintenanceMode::new(true));
+        let router = router(&HttpConfig::default(), &DashboardConfig::default())
+            .layer(Extension(Arc::new(RuntimeConfig::default())))
+            .layer(Extension(sessions))
+            .layer(Extension(session::routes::router().build()))
+            .layer(Extension(maintenance.clone()))
+            .layer(Extension(Arc::new(PublicAddressService::new(None))));
+
+        let mut request = Request::builder()
+            .uri("/api/server/upgrade")
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "blaze")
+            .extension(ConnectInfo(SocketAddr::from(([1, 2, 3, 4], 1000))))
+            .body(Body::empty())
+            .unwrap();
+        let on_upgrade = hyper::upgrade::on(&mut request);
+        request.extensions_mut().insert(on_upgrade);
+
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let request = Request::builder()
+            .uri("/api/server")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["maintenance"], true);
+    }
+
+    /// Tests that IPv4 clients connecting through a dual stack "::" listener
+    /// are able to upgrade their connection
+    #[tokio::test]
+    async fn test_upgrade_dual_stack() {
+        let addr = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0);
+        let mut listeners = match bind_listeners(addr, true) {
+            Ok(value) => value,
+            // IPv6 is not available on this machine
+            Err(_) => return,
+        };
+        let listener = listeners.remove(0);
+        let port = listener.local_addr().unwrap().port();
+
+        let router = router(&HttpConfig::default(), &DashboardConfig::default())
+            .layer(Extension(Arc::new(RuntimeConfig::default())))
+            .layer(Extension(test_sessions()))
+            .layer(Extension(session::routes::router().build()))
+            .layer(Extension(Arc::new(MaintenanceMode::new(false))))
+            .into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        // Connect using IPv4, arriving at the server as "::ffff:127.0.0.1"
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+            .await
+            .unwrap();
+        stream
+            .write_all(
+                b"GET /api/server/upgrade HTTP/1.1\r\n\
+                Host: 127.0.0.1\r\n\
+                Connection: upgrade\r\n\
+                Upgrade: blaze\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut buffer = [0u8; 12];
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"HTTP/1.1 101");
+    }
+
+    /// Tests that the version endpoint reports the compiled version
+    /// along with the compatibility details
+    #[tokio::test]
+    async fn test_version() {
+        let config = Arc::new(RuntimeConfig::default());
+        let router =
+            router(&HttpConfig::default(), &DashboardConfig::default()).layer(Extension(config));
+        let request = Request::builder()
+            .uri("/api/server/version")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["version"], VERSION);
+        assert_eq!(body["min_client_version"], MIN_CLIENT_VERSION);
+
+        let features = body["features"].as_array().unwrap();
+        assert!(features.contains(&"association".into()));
+        assert!(features.contains(&"udp_tunnel".into()));
+    }
+
+    /// Tests that bursting an unauthenticated route past the rate
+    /// limit is rejected
+    #[tokio::test]
+    async fn test_public_rate_limit() {
+        let router = router(
+            &HttpConfig {
+                public_rate_limit: Some(RateLimitConfig {
+                    burst: 2,
+                    per_second: 1,
+                }),
+                ..Default::default()
+            },
+            &DashboardConfig::default(),
+        )
+        .layer(Extension(Arc::new(RuntimeConfig::default())));
+
+        let request = |addr: SocketAddr| {
+            Request::builder()
+                .uri("/qos/firetype")
+                .extension(ConnectInfo(addr))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // IPv6 sources are limited the same as IPv4 sources
+        let addrs = [
+            SocketAddr::from(([1, 2, 3, 4], 1000)),
+            SocketAddr::from(([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1], 1000)),
+        ];
+        for addr in addrs {
+            for _ in 0..2 {
+                let response = router.clone().oneshot(request(addr)).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+            }
+
+            let response = router.clone().oneshot(request(addr)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        }
+    }
+
+    /// Tests that reading games requires authentication when reads are
+    /// configured to require authentication
+    #[tokio::test]
+    async fn test_require_auth_for_reads() {
+        let db = test_database().await;
+        let player = Player::create(
+            &db,
+            "test@test.com".to_string(),
+            "Test".to_string(),
+            None,
+            PlayerRole::Default,
+        )
+        .await
+        .unwrap();
+
+        // Games would otherwise be readable without authentication
+        let config = Arc::new(RuntimeConfig {
+            api: APIConfig {
+                public_games: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let sessions = test_sessions();
+        let game_manager = test_game_manager(config.clone());
+        let token = sessions.create_token(player.id);
+
+        let router = router(
+            &HttpConfig {
+                require_auth_for_reads: true,
+                ..Default::default()
+            },
+            &DashboardConfig::default(),
+        )
+        .layer(Extension(config))
+        .layer(Extension(db))
+        .layer(Extension(game_manager))
+        .layer(Extension(sessions));
+
+        let request = Request::builder()
+            .uri("/api/games")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let request = Request::builder()
+            .uri("/api/games")
+            .header("X-Token", token)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
 }
@@ -1,14 +1,19 @@
 use crate::{
+    config::RuntimeConfig,
     database::{
         entities::players,
         entities::players::PlayerRole,
-        entities::{GalaxyAtWar, Player, PlayerData},
+        entities::{
+            player_data::{DataLimitExceeded, DataLimits},
+            GalaxyAtWar, Player, PlayerData,
+        },
         DatabaseConnection, DbErr,
     },
     middleware::auth::{AdminAuth, Auth},
+    services::{game::GameSnapshot, sessions::Sessions},
     utils::{
         hashing::{hash_password, verify_password},
-        types::PlayerID,
+        types::{GameID, PlayerID},
     },
 };
 use axum::{
@@ -18,9 +23,9 @@ use axum::{
     Extension, Json,
 };
 use email_address::EmailAddress;
-use log::error;
 use sea_orm::{EntityTrait, PaginatorTrait, QueryOrder};
 use serde::{ser::SerializeMap, Deserialize, Serialize};
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Enum for errors that could occur when accessing any of
@@ -51,6 +56,14 @@ pub enum PlayersError {
     #[error("Unable to find data")]
     DataNotFound,
 
+    /// The player already has the maximum number of data keys
+    #[error("Player data key limit reached")]
+    DataLimitReached,
+
+    /// The player data value was too large to store
+    #[error("Player data too large")]
+    DataTooLarge,
+
     /// The account doesn't have permission to complete the action
     #[error("Invalid permission")]
     InvalidPermission,
@@ -161,6 +174,28 @@ pub async fn get_player(
     Ok(Json(player))
 }
 
+/// Query for looking up a player by email
+#[derive(Deserialize)]
+pub struct PlayerByEmailQuery {
+    /// The email address of the player
+    email: String,
+}
+
+/// GET /api/players/by-email?email=<addr>
+///
+/// Route for retrieving a player from the database with an email that
+/// matches the provided email, ignoring the email case
+pub async fn get_player_by_email(
+    _: AdminAuth,
+    Query(query): Query<PlayerByEmailQuery>,
+    Extension(db): Extension<DatabaseConnection>,
+) -> PlayersRes<Player> {
+    let player = Player::by_email_ignore_case(&db, query.email.trim())
+        .await?
+        .ok_or(PlayersError::PlayerNotFound)?;
+    Ok(Json(player))
+}
+
 /// Request to update the basic details of the currently
 /// authenticated account
 ///
@@ -504,6 +539,7 @@ pub async fn set_data(
     AdminAuth(auth): AdminAuth,
     Path((player_id, key)): Path<(PlayerID, String)>,
     Extension(db): Extension<DatabaseConnection>,
+    Extension(config): Extension<Arc<RuntimeConfig>>,
     Json(SetDataRequest { value }): Json<SetDataRequest>,
 ) -> PlayersResult<()> {
     let player: Player = find_player(&db, player_id).await?;
@@ -512,7 +548,14 @@ pub async fn set_data(
         return Err(PlayersError::InvalidPermission);
     }
 
-    PlayerData::set(&db, player.id, key.clone(), value).await?;
+    let limits = DataLimits::from_config(&config.player);
+    match PlayerData::set_limited(&db, player.id, key, value, limits).await? {
+        Ok(()) => {}
+        Err(DataLimitExceeded::Keys) => return Err(PlayersError::DataLimitReached),
+        Err(DataLimitExceeded::Size | DataLimitExceeded::TotalSize) => {
+            return Err(PlayersError::DataTooLarge)
+        }
+    }
 
     Ok(())
 }
@@ -541,6 +584,29 @@ pub async fn delete_data(
     Ok(())
 }
 
+/// POST /api/players/:id/reset
+///
+/// Route for resetting all the player data for a specific player back
+/// to the defaults, the account itself is left unchanged
+///
+/// `player_id` The ID of the player
+/// `auth`      The currently authenticated (Admin) player
+pub async fn reset_data(
+    AdminAuth(auth): AdminAuth,
+    Path(player_id): Path<PlayerID>,
+    Extension(db): Extension<DatabaseConnection>,
+) -> PlayersResult<()> {
+    let player: Player = find_player(&db, player_id).await?;
+
+    if !auth.has_permission_over(&player) {
+        return Err(PlayersError::InvalidPermission);
+    }
+
+    PlayerData::reset(&db, player.id).await?;
+
+    Ok(())
+}
+
 /// GET /api/players/:id/galaxy_at_war
 ///
 /// Route for retrieving the galaxy at war data for a provided player
@@ -557,6 +623,45 @@ pub async fn get_player_gaw(
     Ok(Json(galax_at_war))
 }
 
+/// Response containing the game a player is currently in
+#[derive(Serialize)]
+pub struct PlayerGameResponse {
+    /// The ID of the game
+    game_id: GameID,
+    /// Snapshot of the game
+    game: GameSnapshot,
+}
+
+/// GET /api/players/:id/game
+///
+/// Route for retrieving the game the player with the provided `id` is
+/// currently in. Responds with null when the player is not online or
+/// is not in a game
+///
+/// `player_id` The ID of the player to get the game for
+pub async fn get_player_game(
+    _: AdminAuth,
+    Path(player_id): Path<PlayerID>,
+    Extension(sessions): Extension<Arc<Sessions>>,
+) -> Json<Option<PlayerGameResponse>> {
+    let game = sessions
+        .lookup_session(player_id)
+        .and_then(|session| session.data.get_game());
+
+    let response = match game {
+        Some((game_id, game_ref)) => {
+            let game = &*game_ref.read().await;
+            Some(PlayerGameResponse {
+                game_id,
+                game: game.snapshot(true, true),
+            })
+        }
+        None => None,
+    };
+
+    Json(response)
+}
+
 /// IntoResponse implementation for PlayersError to allow it to be
 /// used within the result type as a error response
 impl IntoResponse for PlayersError {
@@ -564,7 +669,10 @@ impl IntoResponse for PlayersError {
         let status = match &self {
             Self::DataNotFound => StatusCode::NOT_FOUND,
             Self::PlayerNotFound => StatusCode::NOT_FOUND,
-            Self::EmailTaken | Self::InvalidEmail => StatusCode::BAD_REQUEST,
+            Self::EmailTaken | Self::InvalidEmail | Self::DataLimitReached => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::DataTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
             Self::InvalidPassword | Self::InvalidPermission => StatusCode::UNAUTHORIZED,
             Self::Database(_) | Self::PasswordHash(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
@@ -572,3 +680,103 @@ impl IntoResponse for PlayersError {
         (status, self.to_string()).into_response()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{get_player_by_email, get_player_game, PlayerByEmailQuery, PlayersError};
+    use crate::{
+        config::RuntimeConfig,
+        database::{
+            entities::{players::PlayerRole, Player},
+            test_database,
+        },
+        middleware::auth::AdminAuth,
+        session::{
+            models::game_manager::GameSettings,
+            test_util::{create_auth_session, test_game_manager, test_player, test_sessions},
+        },
+    };
+    use axum::{
+        extract::{Path, Query},
+        Extension,
+    };
+    use std::sync::Arc;
+    use tdf::TdfMap;
+
+    /// Creates an admin player with the provided `id`
+    fn admin(id: u32) -> Player {
+        Player {
+            role: PlayerRole::Admin,
+            ..test_player(id)
+        }
+    }
+
+    /// Tests that the current game is provided for players in a game and
+    /// nothing is provided for idle players
+    #[tokio::test]
+    async fn test_get_player_game() {
+        let sessions = test_sessions();
+        let game_manager = test_game_manager(Arc::new(RuntimeConfig::default()));
+
+        let (in_game, _) = create_auth_session(&sessions, test_player(1));
+        let (idle, _) = create_auth_session(&sessions, test_player(2));
+
+        let (game_ref, game_id) = game_manager
+            .create_game(TdfMap::default(), GameSettings::NONE)
+            .await;
+        in_game.data.set_game(game_id, Arc::downgrade(&game_ref));
+
+        let response = get_player_game(
+            AdminAuth(admin(3)),
+            Path(in_game.id),
+            Extension(sessions.clone()),
+        )
+        .await;
+        let response = response.0.expect("Missing player game");
+        assert_eq!(response.game_id, game_id);
+        assert_eq!(response.game.id, game_id);
+
+        let response =
+            get_player_game(AdminAuth(admin(3)), Path(idle.id), Extension(sessions)).await;
+        assert!(response.0.is_none());
+    }
+
+    /// Tests that players can be found by email regardless of case and
+    /// that unknown emails are not found
+    #[tokio::test]
+    async fn test_get_player_by_email() {
+        let db = test_database().await;
+        let created = Player::create(
+            &db,
+            "Test@Example.com".to_string(),
+            "Test".to_string(),
+            None,
+            PlayerRole::Default,
+        )
+        .await
+        .unwrap();
+
+        let response = get_player_by_email(
+            AdminAuth(admin(100)),
+            Query(PlayerByEmailQuery {
+                email: "test@example.com".to_string(),
+            }),
+            Extension(db.clone()),
+        )
+        .await;
+        let Ok(found) = response else {
+            panic!("Expected player to be found");
+        };
+        assert_eq!(found.0.id, created.id);
+
+        let response = get_player_by_email(
+            AdminAuth(admin(100)),
+            Query(PlayerByEmailQuery {
+                email: "missing@example.com".to_string(),
+            }),
+            Extension(db),
+        )
+        .await;
+        assert!(matches!(response, Err(PlayersError::PlayerNotFound)));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::DashboardSource;
 use axum::{
     body::Body,
     http::{HeaderValue, Request},
@@ -6,9 +7,12 @@ use axum::{
 use embeddy::Embedded;
 use futures_util::future::BoxFuture;
 use hyper::{header::CONTENT_TYPE, StatusCode};
+use log::warn;
 use std::{
+    collections::{BTreeMap, HashMap},
     convert::Infallible,
     path::{Path, PathBuf},
+    sync::Arc,
     task::{Context, Poll},
 };
 use tower::Service;
@@ -16,7 +20,7 @@ use tower::Service;
 /// Resources embedded from the public data folder such as the
 /// dashboard static assets and the content for the in-game store.
 ///
-/// Also acts a service for publicly sharing the content
+/// Shared publicly through the [PublicContentService]
 ///
 /// TODO: This may not be particularly performant with a match statement
 /// over all the public assets
@@ -24,8 +28,12 @@ use tower::Service;
 #[folder = "src/resources/public"]
 pub struct PublicContent;
 
-fn find_local_path(path: &str) -> Option<PathBuf> {
-    let data_path = Path::new("data/public").canonicalize().ok()?;
+/// Folder dashboard assets are served from when using [DashboardSource::Folder]
+const DASHBOARD_FOLDER: &str = "data/dashboard";
+
+/// Finds the path to the file at `path` within the `base` folder
+fn find_local_path(base: &Path, path: &str) -> Option<PathBuf> {
+    let data_path = base.canonicalize().ok()?;
     let file_path = data_path.join(path).canonicalize().ok()?;
     // Folders outside of the data path should be ignored
     if !file_path.starts_with(data_path) {
@@ -35,7 +43,98 @@ fn find_local_path(path: &str) -> Option<PathBuf> {
     Some(file_path)
 }
 
-impl Service<Request<Body>> for PublicContent {
+/// Service for publicly sharing the [PublicContent] and any content
+/// within the local public data folder
+#[derive(Clone)]
+pub struct PublicContentService {
+    /// Content types overriding the built-in types by file extension
+    content_types: Arc<HashMap<String, HeaderValue>>,
+    /// Folder to serve the dashboard from instead of the embedded assets
+    dashboard_folder: Option<Arc<Path>>,
+}
+
+impl PublicContentService {
+    /// Creates a new service using the provided `content_types` overrides,
+    /// invalid content types are ignored. The dashboard is served from the
+    /// provided `dashboard_source`
+    pub fn new(
+        content_types: &BTreeMap<String, String>,
+        dashboard_source: DashboardSource,
+    ) -> Self {
+        let content_types = content_types
+            .iter()
+            .filter_map(
+                |(extension, content_type)| match HeaderValue::from_str(content_type) {
+                    Ok(value) => Some((extension.to_ascii_lowercase(), value)),
+                    Err(_) => {
+                        warn!(
+                            "Ignoring invalid content type for .{} files: {}",
+                            extension, content_type
+                        );
+                        None
+                    }
+                },
+            )
+            .collect();
+
+        let dashboard_folder = match dashboard_source {
+            DashboardSource::Embedded => None,
+            DashboardSource::Folder => dashboard_folder(Path::new(DASHBOARD_FOLDER)),
+        };
+
+        Self {
+            content_types: Arc::new(content_types),
+            dashboard_folder,
+        }
+    }
+
+    /// Determines the content type for a file with the provided `extension`
+    fn content_type(&self, extension: &str) -> HeaderValue {
+        let extension = extension.to_ascii_lowercase();
+        self.content_types
+            .get(&extension)
+            .cloned()
+            .unwrap_or_else(|| HeaderValue::from_static(mime_type(&extension)))
+    }
+}
+
+/// Validates that the dashboard `folder` exists and contains an index
+/// file, warns and falls back to the embedded dashboard otherwise
+fn dashboard_folder(folder: &Path) -> Option<Arc<Path>> {
+    if !folder.join("index.html").is_file() {
+        warn!(
+            "Dashboard folder {} is missing or has no index.html, using the embedded dashboard",
+            folder.display()
+        );
+        return None;
+    }
+
+    Some(Arc::from(folder))
+}
+
+/// Guesses the mime type from a lowercase file `extension`
+fn mime_type(extension: &str) -> &'static str {
+    match extension {
+        "html" => "text/html",
+        "js" | "mjs" => "text/javascript",
+        "json" | "map" => "application/json",
+        "wasm" => "application/wasm",
+        "css" => "text/css",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "webp" => "image/webp",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        _ => "text/plain",
+    }
+}
+
+impl Service<Request<Body>> for PublicContentService {
     type Response = Response;
     type Error = Infallible;
     type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
@@ -66,42 +165,45 @@ impl Service<Request<Body>> for PublicContent {
             }
         };
 
+        let content_type = self.content_type(&extension);
+        let dashboard_folder = self.dashboard_folder.clone();
+
         Box::pin(async move {
             let path = path;
 
-            // Guess mime type from file extension
-            let mime_type: &'static str = match extension.as_ref() {
-                "html" => "text/html",
-                "js" | "mjs" => "text/javascript",
-                "json" => "application/json",
-                "woff" => "font/woff",
-                "woff2" => "font/woff2",
-                "webp" => "image/webp",
-                "css" => "text/css",
-                _ => "text/plain",
-            };
+            // Serve from the dashboard folder when its being used
+            if let Some(local_path) = dashboard_folder
+                .as_deref()
+                .and_then(|folder| find_local_path(folder, &path))
+            {
+                if local_path.is_file() {
+                    if let Ok(contents) = tokio::fs::read(local_path).await {
+                        let mut response = Body::from(contents).into_response();
+                        response
+                            .headers_mut()
+                            .insert(CONTENT_TYPE, content_type.clone());
+                        return Ok(response);
+                    }
+                }
+            }
 
             // File exists in public data folder server try serve that and fallback to next on failure
-            if let Some(local_path) = find_local_path(&path) {
+            if let Some(local_path) = find_local_path(Path::new("data/public"), &path) {
                 if local_path.exists() && local_path.is_file() {
                     if let Ok(contents) = tokio::fs::read(local_path).await {
                         // Create byte response from the embedded file
                         let mut response = Body::from(contents).into_response();
-                        response
-                            .headers_mut()
-                            .insert(CONTENT_TYPE, HeaderValue::from_static(mime_type));
+                        response.headers_mut().insert(CONTENT_TYPE, content_type);
                         return Ok(response);
                     }
                 }
             }
 
             // File exists within binary serve that
-            if let Some(contents) = Self::get(&path) {
+            if let Some(contents) = PublicContent::get(&path) {
                 // Create byte response from the embedded file
                 let mut response = Body::from(contents).into_response();
-                response
-                    .headers_mut()
-                    .insert(CONTENT_TYPE, HeaderValue::from_static(mime_type));
+                response.headers_mut().insert(CONTENT_TYPE, content_type);
                 return Ok(response);
             }
 
@@ -110,3 +212,94 @@ impl Service<Request<Body>> for PublicContent {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{dashboard_folder, PublicContentService};
+    use crate::config::DashboardSource;
+    use axum::{
+        body::{to_bytes, Body},
+        http::Request,
+    };
+    use hyper::{header::CONTENT_TYPE, StatusCode};
+    use std::collections::BTreeMap;
+    use tower::ServiceExt;
+
+    /// Requests the file at `uri` from the `service`
+    async fn request(service: PublicContentService, uri: &str) -> (StatusCode, Vec<u8>) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = service.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    /// Tests that content types are determined from the file extension
+    #[test]
+    fn test_content_type() {
+        let service = PublicContentService::new(&BTreeMap::new(), DashboardSource::Embedded);
+        assert_eq!(service.content_type("wasm"), "application/wasm");
+        assert_eq!(service.content_type("MJS"), "text/javascript");
+        assert_eq!(service.content_type("woff2"), "font/woff2");
+        assert_eq!(service.content_type("unknown"), "text/plain");
+    }
+
+    /// Tests that configured content types are used for served assets
+    #[tokio::test]
+    async fn test_content_type_override() {
+        let overrides = [("DDS".to_string(), "image/vnd-ms.dds".to_string())]
+            .into_iter()
+            .collect();
+        let service = PublicContentService::new(&overrides, DashboardSource::Embedded);
+
+        let request = Request::builder()
+            .uri("/content/StoreBF3.dds")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "image/vnd-ms.dds");
+    }
+
+    /// Tests that the folder dashboard serves files from the folder while
+    /// the embedded dashboard serves the built-in assets
+    #[tokio::test]
+    async fn test_dashboard_source() {
+        let dir = std::env::temp_dir().join(format!("pocket-relay-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Folders without an index aren't used
+        assert!(dashboard_folder(&dir).is_none());
+
+        std::fs::write(dir.join("index.html"), "<custom-index/>").unwrap();
+        std::fs::write(dir.join("app.js"), "custom").unwrap();
+
+        let folder_service = PublicContentService {
+            dashboard_folder: dashboard_folder(&dir),
+            ..PublicContentService::new(&BTreeMap::new(), DashboardSource::Embedded)
+        };
+
+        let (status, body) = request(folder_service.clone(), "/app.js").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"custom");
+
+        // Dashboard routes are served the folder index
+        let (status, body) = request(folder_service.clone(), "/players").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"<custom-index/>");
+
+        // Built-in content is still available
+        let (status, _) = request(folder_service, "/content/StoreBF3.dds").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let embedded_service =
+            PublicContentService::new(&BTreeMap::new(), DashboardSource::Embedded);
+        let (status, _) = request(embedded_service.clone(), "/app.js").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = request(embedded_service, "/content/StoreBF3.dds").await;
+        assert_eq!(status, StatusCode::OK);
+
+        _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -2,28 +2,51 @@
 //! about the server such as the version and services running
 
 use crate::{
-    config::{RuntimeConfig, VERSION},
+    config::{RuntimeConfig, TunnelConfig, MIN_CLIENT_VERSION, VERSION},
     database::entities::players::PlayerRole,
     middleware::{
         association::Association, auth::AdminAuth, ip_address::IpAddress, upgrade::Upgrade,
     },
     services::{
+        game::manager::{GameManager, QueueStats},
+        health::{HealthService, HealthSnapshot},
+        maintenance_mode::MaintenanceMode,
+        public_address::{PublicAddressCache, PublicAddressService},
+        reachability::{
+            check_ports, NetworkReachabilityChecker, PortCheck, PortProtocol, PortsReport,
+            ReachabilityError,
+        },
+        retriever::{InstanceCache, Retriever},
         sessions::{AssociationId, Sessions},
+        stats_history::{StatsHistory, StatsSample, SAMPLE_INTERVAL},
+        telemetry::{TelemetryForwarder, TelemetryMessage},
         tunnel::{Tunnel, TunnelService},
+        tunnel_send::{SendFailuresSnapshot, TunnelStats},
+        udp_tunnel::UdpTunnelService,
+    },
+    session::{
+        data::SessionData, packet::PacketCodec, rate_limit::PacketRateLimit, router::BlazeRouter,
+        Session,
+    },
+    utils::{
+        logging::{log_limited, LOG_FILE_NAME},
+        types::{GameID, PlayerID},
     },
-    session::{data::SessionData, router::BlazeRouter, Session},
-    utils::logging::LOG_FILE_NAME,
 };
 use axum::{
+    extract::Path,
     http::{header, StatusCode},
     response::{IntoResponse, Response},
     Extension, Json,
 };
-use hyper::upgrade::OnUpgrade;
-use log::{debug, error};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use log::{debug, error, warn, Level};
 use serde::{Deserialize, Serialize};
-use std::{net::Ipv4Addr, sync::Arc};
-use tokio::fs::{read_to_string, OpenOptions};
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::{
+    fs::{read_to_string, OpenOptions},
+    time::timeout,
+};
 
 /// Response detailing the information about this Pocket Relay server
 /// contains the version information as well as the server information
@@ -40,6 +63,18 @@ pub struct ServerDetails {
     association: String,
     /// Port the tunnel server is running on
     tunnel_port: Option<u16>,
+    /// Host the tunnel server is reachable on, either the configured
+    /// public host or the detected public address of the server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tunnel_host: Option<String>,
+    /// Display name of the server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// Description of the server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Whether the server is under maintenance and refusing new sessions
+    maintenance: bool,
 }
 
 /// GET /api/server
@@ -50,6 +85,8 @@ pub struct ServerDetails {
 pub async fn server_details(
     Extension(sessions): Extension<Arc<Sessions>>,
     Extension(config): Extension<Arc<RuntimeConfig>>,
+    Extension(maintenance): Extension<Arc<MaintenanceMode>>,
+    Extension(public_address): Extension<Arc<PublicAddressService>>,
 ) -> Json<ServerDetails> {
     let association = sessions.create_assoc_token();
     Json(ServerDetails {
@@ -57,9 +94,109 @@ pub async fn server_details(
         version: VERSION,
         association,
         tunnel_port: config.udp_tunnel.get_exposed_port(),
+        tunnel_host: config.udp_tunnel.get_exposed_host(public_address.get()),
+        name: config.server.name.clone(),
+        description: config.server.description.clone(),
+        maintenance: maintenance.is_enabled(),
+    })
+}
+
+/// Request to enable or disable maintenance mode
+#[derive(Deserialize)]
+pub struct MaintenanceRequest {
+    /// Whether maintenance mode should be enabled
+    enabled: bool,
+}
+
+/// PUT /api/server/maintenance
+///
+/// Enables or disables maintenance mode, while enabled new sessions
+/// and logins are refused. Existing sessions are left connected
+pub async fn set_maintenance(
+    _: AdminAuth,
+    Extension(maintenance): Extension<Arc<MaintenanceMode>>,
+    Json(MaintenanceRequest { enabled }): Json<MaintenanceRequest>,
+) -> StatusCode {
+    maintenance.set_enabled(enabled);
+    StatusCode::OK
+}
+
+/// Response describing the server version and compatibility details
+/// for the Pocket Relay client launcher
+#[derive(Serialize)]
+pub struct VersionDetails {
+    /// The server version
+    version: &'static str,
+    /// The oldest client version compatible with the server
+    min_client_version: &'static str,
+    /// Features the server currently supports
+    features: Vec<&'static str>,
+}
+
+/// GET /api/server/version
+///
+/// Provides the server version along with the minimum compatible
+/// client version and supported features so the client launcher
+/// can determine whether its compatible with the server
+pub async fn version(Extension(config): Extension<Arc<RuntimeConfig>>) -> Json<VersionDetails> {
+    let mut features = vec!["association"];
+
+    if !matches!(config.tunnel, TunnelConfig::Disabled) {
+        features.push("http_tunnel");
+
+        if config.udp_tunnel.enabled {
+            features.push("udp_tunnel");
+            features.push("udp_tunnel_reconnect");
+        }
+    }
+
+    Json(VersionDetails {
+        version: VERSION,
+        min_client_version: MIN_CLIENT_VERSION,
+        features,
     })
 }
 
+/// GET /api/server/health
+///
+/// Responds with the current health state of the server such
+/// as the latency of the database
+pub async fn health(Extension(health): Extension<Arc<HealthService>>) -> Json<HealthSnapshot> {
+    Json(health.snapshot())
+}
+
+/// Response containing the history of server activity
+#[derive(Serialize)]
+pub struct StatsHistoryResponse {
+    /// Number of seconds between each sample
+    pub interval_seconds: u64,
+    /// The samples from oldest to newest
+    pub samples: Vec<StatsSample>,
+}
+
+/// GET /api/server/stats/history
+///
+/// Responds with the number of sessions and games sampled each
+/// minute over the last 24 hours
+pub async fn stats_history(
+    Extension(history): Extension<Arc<StatsHistory>>,
+) -> Json<StatsHistoryResponse> {
+    Json(StatsHistoryResponse {
+        interval_seconds: SAMPLE_INTERVAL.as_secs(),
+        samples: history.samples(),
+    })
+}
+
+/// GET /api/server/matchmaking
+///
+/// Responds with the number of players in the matchmaking queue and
+/// statistics for the recent matchmaking wait times
+pub async fn matchmaking_stats(
+    Extension(game_manager): Extension<Arc<GameManager>>,
+) -> Json<QueueStats> {
+    Json(game_manager.queue_stats().await)
+}
+
 /// Response sent to dashboard clients containing configuration
 /// information about the dashboard
 #[derive(Serialize)]
@@ -80,6 +217,226 @@ pub async fn dashboard_details(
     })
 }
 
+/// Response containing a tunnel reconnect token
+#[derive(Serialize)]
+pub struct TunnelReconnectResponse {
+    /// Token the client can use in place of its association token
+    /// to rebind a dropped tunnel to its game slot
+    token: String,
+}
+
+/// GET /api/server/tunnel/reconnect
+///
+/// Provides a reconnect token for the game slot reserved for the
+/// association of the client, the token is only valid while the
+/// client remains in the game
+pub async fn tunnel_reconnect_token(
+    Association(association_id): Association,
+    Extension(udp_tunnel_service): Extension<Arc<UdpTunnelService>>,
+) -> Result<Json<TunnelReconnectResponse>, StatusCode> {
+    let association_id = association_id.ok_or(StatusCode::BAD_REQUEST)?;
+    let token = udp_tunnel_service
+        .create_reconnect_token(association_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(TunnelReconnectResponse { token }))
+}
+
+/// Response containing the number of failed sends for each tunnel type
+#[derive(Serialize)]
+pub struct TunnelFailuresResponse {
+    /// Failed sends for the HTTP tunnel
+    http: SendFailuresSnapshot,
+    /// Failed sends for the UDP tunnel
+    udp: SendFailuresSnapshot,
+}
+
+/// GET /api/server/tunnel/failures
+///
+/// Provides the number of messages that have failed to be sent
+/// through the tunnels
+pub async fn tunnel_failures(
+    _: AdminAuth,
+    Extension(tunnel_service): Extension<Arc<TunnelService>>,
+    Extension(udp_tunnel_service): Extension<Arc<UdpTunnelService>>,
+) -> Json<TunnelFailuresResponse> {
+    Json(TunnelFailuresResponse {
+        http: tunnel_service.send_failures().snapshot(),
+        udp: udp_tunnel_service.send_failures().snapshot(),
+    })
+}
+
+/// Response containing the forwarding statistics for each tunnel type
+#[derive(Serialize)]
+pub struct TunnelStatsResponse {
+    /// Statistics for tunnels in a pool on the HTTP tunnel
+    http: Vec<TunnelStats>,
+    /// Statistics for tunnels in a pool on the UDP tunnel
+    udp: Vec<TunnelStats>,
+}
+
+/// GET /api/server/tunnel/stats
+///
+/// Provides the number of messages and bytes forwarded from each tunnel
+/// within a pool along with how long ago UDP tunnels were last alive,
+/// used to diagnose NAT and tunneling issues
+///
+/// Requires admin authentication
+pub async fn tunnel_stats(
+    _: AdminAuth,
+    Extension(tunnel_service): Extension<Arc<TunnelService>>,
+    Extension(udp_tunnel_service): Extension<Arc<UdpTunnelService>>,
+) -> Json<TunnelStatsResponse> {
+    Json(TunnelStatsResponse {
+        http: tunnel_service.stats(),
+        udp: udp_tunnel_service.stats(),
+    })
+}
+
+/// Request to associate a tunnel with a slot in a tunnel pool
+#[derive(Deserialize)]
+pub struct AssociateTunnelRequest {
+    /// Association ID of the client the tunnel belongs to
+    association: AssociationId,
+    /// ID of the pool to associate the tunnel with
+    pool_id: GameID,
+    /// Index of the slot within the pool
+    index: u8,
+}
+
+/// POST /api/server/tunnel/associate
+///
+/// Associates the tunnel belonging to a client association with a slot
+/// in a tunnel pool, allowing tunnel forwarding to be tested without
+/// a game. Only available when enabled in the API config
+///
+/// Requires admin authentication
+pub async fn tunnel_associate(
+    _: AdminAuth,
+    Extension(config): Extension<Arc<RuntimeConfig>>,
+    Extension(tunnel_service): Extension<Arc<TunnelService>>,
+    Json(AssociateTunnelRequest {
+        association,
+        pool_id,
+        index,
+    }): Json<AssociateTunnelRequest>,
+) -> Result<StatusCode, (StatusCode, &'static str)> {
+    if !config.api.tunnel_associate {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "Tunnel association is not enabled on this server",
+        ));
+    }
+
+    if !tunnel_service.associate_pool(association, pool_id, index) {
+        return Err((StatusCode::NOT_FOUND, "No tunnel for the association"));
+    }
+
+    debug!(
+        "Manually associated tunnel with pool (ASSOC: {}, POOL: {}, INDEX: {})",
+        association, pool_id, index
+    );
+
+    Ok(StatusCode::OK)
+}
+
+/// Details about an authenticated session
+#[derive(Serialize)]
+pub struct SessionDetails {
+    /// Unique ID of the session
+    session_id: u32,
+    /// ID of the player authenticated on the session
+    player_id: PlayerID,
+    /// Display name of the player authenticated on the session
+    display_name: String,
+    /// Number of requests from the session that resulted in an error
+    errors: u32,
+    /// Number of subscribers to the session
+    subscribers: usize,
+}
+
+/// GET /api/server/sessions
+///
+/// Provides details about each of the authenticated sessions including
+/// the number of requests from each session that resulted in errors
+pub async fn sessions(
+    _: AdminAuth,
+    Extension(sessions): Extension<Arc<Sessions>>,
+) -> Json<Vec<SessionDetails>> {
+    let mut details: Vec<SessionDetails> = sessions
+        .all_sessions()
+        .into_iter()
+        .filter_map(|session| {
+            let player = session.data.get_player()?;
+            Some(SessionDetails {
+                session_id: session.id,
+                player_id: player.id,
+                display_name: player.display_name.clone(),
+                errors: session.data.error_count(),
+                subscribers: session.data.subscriber_count(),
+            })
+        })
+        .collect();
+    details.sort_by_key(|details| details.session_id);
+
+    Json(details)
+}
+
+/// DELETE /api/server/sessions/:player_id/errors
+///
+/// Resets the number of errors for the session of the player
+/// with the provided `player_id`
+pub async fn reset_session_errors(
+    _: AdminAuth,
+    Path(player_id): Path<PlayerID>,
+    Extension(sessions): Extension<Arc<Sessions>>,
+) -> StatusCode {
+    match sessions.lookup_session(player_id) {
+        Some(session) => {
+            session.data.reset_errors();
+            StatusCode::NO_CONTENT
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+/// Response containing the values cached by the server
+#[derive(Serialize)]
+pub struct CachesResponse {
+    /// The cached public address of the server
+    public_address: Option<PublicAddressCache>,
+    /// The cached official server instance
+    official_instance: Option<InstanceCache>,
+}
+
+/// GET /api/server/caches
+///
+/// Provides the values cached by the server and their ages
+pub async fn caches(
+    _: AdminAuth,
+    Extension(public_address): Extension<Arc<PublicAddressService>>,
+    Extension(retriever): Extension<Arc<Retriever>>,
+) -> Json<CachesResponse> {
+    Json(CachesResponse {
+        public_address: public_address.cache(),
+        official_instance: retriever.cache().await,
+    })
+}
+
+/// POST /api/server/caches/clear
+///
+/// Clears the values cached by the server, the values are
+/// resolved again on next use
+pub async fn clear_caches(
+    _: AdminAuth,
+    Extension(public_address): Extension<Arc<PublicAddressService>>,
+    Extension(retriever): Extension<Arc<Retriever>>,
+) -> StatusCode {
+    public_address.clear();
+    retriever.clear_cache().await;
+    StatusCode::OK
+}
+
 /// GET /api/server/upgrade
 ///
 /// Handles upgrading connections from the Pocket Relay Client tool
@@ -89,10 +446,45 @@ pub async fn upgrade(
     IpAddress(addr): IpAddress,
     Association(association_id): Association,
     Extension(router): Extension<Arc<BlazeRouter>>,
+    Extension(config): Extension<Arc<RuntimeConfig>>,
+    Extension(sessions): Extension<Arc<Sessions>>,
+    Extension(maintenance): Extension<Arc<MaintenanceMode>>,
     Upgrade(upgrade): Upgrade,
 ) -> Response {
-    // Spawn the upgrading process to its own task
-    tokio::spawn(handle_upgrade(upgrade, addr, association_id, router));
+    // Refuse new sessions while the server is under maintenance
+    if maintenance.is_enabled() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Server is under maintenance",
+        )
+            .into_response();
+    }
+
+    // Refuse the connection when the server is full
+    let Some(permit) = sessions.try_acquire_connection(config.sessions.max_total) else {
+        log_limited(
+            Level::Warn,
+            "Refused session connection, server is at the maximum number of sessions".to_string(),
+        );
+        return (StatusCode::SERVICE_UNAVAILABLE, "Server is full").into_response();
+    };
+
+    let rate_limit = PacketRateLimit::from_config(&config.sessions);
+    let codec = PacketCodec::with_max_length(config.sessions.max_packet_bytes);
+
+    let upgrade_timeout = Duration::from_secs(config.sessions.upgrade_timeout_seconds);
+
+    let data = SessionData::new(addr, association_id);
+    data.set_server_heartbeat(config.sessions.server_keepalive);
+    data.set_max_errors(config.sessions.max_errors);
+    data.set_max_subscriptions(config.sessions.max_subscriptions);
+
+    // Spawn the upgrading process to its own task, holding the
+    // permit until the session has ended
+    tokio::spawn(async move {
+        handle_upgrade(upgrade, upgrade_timeout, data, router, rate_limit, codec).await;
+        drop(permit);
+    });
 
     // Let the client know to upgrade its connection
     (
@@ -108,21 +500,17 @@ pub async fn upgrade(
 /// from the connection
 pub async fn handle_upgrade(
     upgrade: OnUpgrade,
-    addr: Ipv4Addr,
-    association_id: Option<AssociationId>,
+    upgrade_timeout: Duration,
+    data: SessionData,
     router: Arc<BlazeRouter>,
+    rate_limit: Option<PacketRateLimit>,
+    codec: PacketCodec,
 ) {
-    let upgraded = match upgrade.await {
-        Ok(upgraded) => upgraded,
-        Err(err) => {
-            error!("Failed to upgrade client connection: {}", err);
-            return;
-        }
+    let Some(upgraded) = complete_upgrade(upgrade, upgrade_timeout).await else {
+        return;
     };
 
-    let data = SessionData::new(addr, association_id);
-
-    Session::run(upgraded, data, router).await;
+    Session::run(upgraded, data, router, rate_limit, codec).await;
 }
 
 /// GET /api/server/tunnel
@@ -132,6 +520,7 @@ pub async fn handle_upgrade(
 /// as blaze sessions using HTTP Upgrade
 pub async fn tunnel(
     Association(association_id): Association,
+    Extension(config): Extension<Arc<RuntimeConfig>>,
     Extension(tunnel_service): Extension<Arc<TunnelService>>,
     Upgrade(upgrade): Upgrade,
 ) -> Response {
@@ -143,6 +532,7 @@ pub async fn tunnel(
     // Spawn the upgrading process to its own task
     tokio::spawn(handle_upgrade_tunnel(
         upgrade,
+        Duration::from_secs(config.sessions.upgrade_timeout_seconds),
         association_id,
         tunnel_service,
     ));
@@ -161,21 +551,37 @@ pub async fn tunnel(
 /// from the connection
 pub async fn handle_upgrade_tunnel(
     upgrade: OnUpgrade,
+    upgrade_timeout: Duration,
     association: AssociationId,
     tunnel_service: Arc<TunnelService>,
 ) {
-    let upgraded = match upgrade.await {
-        Ok(upgraded) => upgraded,
-        Err(err) => {
-            error!("Failed to upgrade client connection: {}", err);
-            return;
-        }
+    let Some(upgraded) = complete_upgrade(upgrade, upgrade_timeout).await else {
+        return;
     };
 
     let tunnel_id = Tunnel::start(tunnel_service.clone(), association, upgraded);
     tunnel_service.associate_tunnel(association, tunnel_id);
 }
 
+/// Waits for the `upgrade` handshake to complete, connections that fail
+/// or don't complete the handshake within `upgrade_timeout` are dropped
+async fn complete_upgrade<F>(upgrade: F, upgrade_timeout: Duration) -> Option<Upgraded>
+where
+    F: Future<Output = hyper::Result<Upgraded>>,
+{
+    match timeout(upgrade_timeout, upgrade).await {
+        Ok(Ok(upgraded)) => Some(upgraded),
+        Ok(Err(err)) => {
+            error!("Failed to upgrade client connection: {}", err);
+            None
+        }
+        Err(_) => {
+            warn!("Dropped client connection that didn't complete the upgrade in time");
+            None
+        }
+    }
+}
+
 /// GET /api/server/log
 ///
 /// Responds with the server log file contents
@@ -225,19 +631,128 @@ pub async fn clear_log(AdminAuth(auth): AdminAuth) -> Result<(), StatusCode> {
     Ok(())
 }
 
-/// Structure of a telemetry message coming from a client
-#[derive(Debug, Deserialize)]
-#[allow(unused)]
-pub struct TelemetryMessage {
-    /// The telemetry message values
-    pub values: Vec<(String, String)>,
+/// POST /api/server/test/ports
+///
+/// Attempts to connect to the server ports through the public address
+/// of the server reporting which ports are reachable, used to diagnose
+/// port forwarding issues
+///
+/// Requires admin authentication
+pub async fn test_ports(
+    AdminAuth(_): AdminAuth,
+    Extension(config): Extension<Arc<RuntimeConfig>>,
+    Extension(sessions): Extension<Arc<Sessions>>,
+    Extension(udp_tunnel_service): Extension<Arc<UdpTunnelService>>,
+    Extension(public_address): Extension<Arc<PublicAddressService>>,
+) -> Result<Json<PortsReport>, ReachabilityError> {
+    let checks = port_checks(&config);
+
+    let checker = NetworkReachabilityChecker {
+        sessions,
+        udp_tunnel_service,
+    };
+
+    let report = check_ports(&checker, public_address.resolve().await, &checks).await?;
+
+    Ok(Json(report))
+}
+
+/// Creates the checks for the ports exposed to clients, these are the
+/// external ports when the server is reached through a different port
+/// than it is bound to (i.e port forwarding or a reverse proxy)
+fn port_checks(config: &RuntimeConfig) -> Vec<PortCheck> {
+    let mut checks = vec![PortCheck {
+        name: "http",
+        protocol: PortProtocol::Tcp,
+        port: config.get_exposed_port(),
+    }];
+
+    if let Some(port) = config.udp_tunnel.get_exposed_port() {
+        checks.push(PortCheck {
+            name: "udp_tunnel",
+            protocol: PortProtocol::Udp,
+            port,
+        });
+    }
+
+    checks
+}
+
+/// Response implementation for reachability errors
+impl IntoResponse for ReachabilityError {
+    fn into_response(self) -> Response {
+        let status_code = match &self {
+            Self::UnknownPublicAddress => StatusCode::SERVICE_UNAVAILABLE,
+        };
+
+        (status_code, self.to_string()).into_response()
+    }
 }
 
-/// GET /api/server/telemetry
+/// POST /api/server/telemetry
 ///
 /// Handles the incoming telemetry messages received
 /// from Pocket Relay clients
-pub async fn submit_telemetry(Json(data): Json<TelemetryMessage>) -> StatusCode {
+pub async fn submit_telemetry(
+    Extension(forwarder): Extension<Arc<TelemetryForwarder>>,
+    Json(mut data): Json<TelemetryMessage>,
+) -> StatusCode {
+    data.escape_control();
     debug!("[TELEMETRY] {:?}", data);
+    forwarder.forward(data);
     StatusCode::OK
 }
+
+#[cfg(test)]
+mod test {
+    use super::{complete_upgrade, port_checks};
+    use crate::config::{RuntimeConfig, UdpTunnelConfig};
+    use hyper::upgrade::Upgraded;
+    use std::{future::pending, time::Duration};
+    use tokio::time::Instant;
+
+    /// Tests that a connection stalling the upgrade handshake is dropped
+    /// once the upgrade timeout is reached
+    #[tokio::test(start_paused = true)]
+    async fn test_upgrade_timeout() {
+        let start = Instant::now();
+        let upgraded = complete_upgrade(
+            pending::<hyper::Result<Upgraded>>(),
+            Duration::from_secs(10),
+        )
+        .await;
+
+        assert!(upgraded.is_none());
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+    }
+
+    /// Tests that the reachability checks use the external ports when
+    /// they are configured and the bound ports otherwise
+    #[test]
+    fn test_port_checks() {
+        let config = RuntimeConfig {
+            port: 80,
+            ..Default::default()
+        };
+        let ports: Vec<_> = port_checks(&config)
+            .iter()
+            .map(|check| (check.name, check.port))
+            .collect();
+        assert_eq!(ports, [("http", 80), ("udp_tunnel", 9032)]);
+
+        let config = RuntimeConfig {
+            port: 80,
+            external_port: Some(8080),
+            udp_tunnel: UdpTunnelConfig {
+                external_port: Some(19032),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ports: Vec<_> = port_checks(&config)
+            .iter()
+            .map(|check| (check.name, check.port))
+            .collect();
+        assert_eq!(ports, [("http", 8080), ("udp_tunnel", 19032)]);
+    }
+}
@@ -0,0 +1,231 @@
+//! Limiting of the number of accounts created from each address, shared
+//! between the in-game and dashboard account creation
+
+use crate::config::PlayerConfig;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+/// Length of the window the account creation limit applies to
+const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Number of tracked addresses before expired windows are removed
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Limiter for the number of accounts each address can create within
+/// a fixed window
+pub struct AccountCreationLimiter {
+    /// Maximum number of accounts each address can create within the
+    /// window, [None] to disable the limit
+    limit: Option<u32>,
+    /// Whether private and loopback addresses are exempt from the limit
+    exempt_private: bool,
+    /// Number of accounts created and the start of the current window
+    /// for each address
+    windows: Mutex<HashMap<IpAddr, (u32, Instant)>>,
+}
+
+impl AccountCreationLimiter {
+    /// Creates a new account creation limiter from the provided `config`
+    pub fn new(config: &PlayerConfig) -> Self {
+        Self {
+            limit: config.max_accounts_per_hour,
+            exempt_private: config.account_limit_exempt_private,
+            windows: Default::default(),
+        }
+    }
+
+    /// Attempts to reserve an account creation for the provided `addr`
+    ///
+    /// The reservation is released when the returned permit is dropped
+    /// without calling [AccountCreationPermit::commit], so that failed
+    /// account creations don't count towards the limit.
+    ///
+    /// Returns the remaining time until the window resets when the
+    /// address has reached the limit
+    pub fn try_acquire(&self, addr: IpAddr) -> Result<AccountCreationPermit<'_>, Duration> {
+        self.try_acquire_at(addr, Instant::now())
+    }
+
+    fn try_acquire_at(
+        &self,
+        addr: IpAddr,
+        now: Instant,
+    ) -> Result<AccountCreationPermit<'_>, Duration> {
+        let Some(limit) = self.limit else {
+            return Ok(AccountCreationPermit::exempt());
+        };
+
+        if self.exempt_private && is_private(addr) {
+            return Ok(AccountCreationPermit::exempt());
+        }
+
+        let windows = &mut *self.windows.lock();
+
+        if windows.len() >= PRUNE_THRESHOLD {
+            windows.retain(|_, (_, start)| now.saturating_duration_since(*start) < WINDOW);
+        }
+
+        let (count, start) = windows.entry(addr).or_insert((0, now));
+
+        // Start a new window once the previous window has ended
+        let elapsed = now.saturating_duration_since(*start);
+        if elapsed >= WINDOW {
+            *count = 0;
+            *start = now;
+        }
+
+        if *count >= limit {
+            return Err(WINDOW - elapsed);
+        }
+
+        *count += 1;
+        Ok(AccountCreationPermit {
+            reservation: Some((self, addr, *start)),
+        })
+    }
+
+    /// Releases a reserved account creation for the `addr` if the window
+    /// it was reserved in is still the current window
+    fn release(&self, addr: IpAddr, window_start: Instant) {
+        let windows = &mut *self.windows.lock();
+        if let Some((count, start)) = windows.get_mut(&addr) {
+            if *start == window_start {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Checks whether the `addr` is a private or loopback address
+fn is_private(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => addr.is_private() || addr.is_loopback(),
+        IpAddr::V6(addr) => addr.is_unique_local() || addr.is_loopback(),
+    }
+}
+
+/// Reserved account creation from [AccountCreationLimiter::try_acquire]
+pub struct AccountCreationPermit<'a> {
+    /// The limiter, address and window start of the reservation, [None]
+    /// when the address is not limited
+    reservation: Option<(&'a AccountCreationLimiter, IpAddr, Instant)>,
+}
+
+impl AccountCreationPermit<'_> {
+    /// Creates a permit for an address that isn't limited
+    fn exempt() -> Self {
+        Self { reservation: None }
+    }
+
+    /// Keeps the reservation counting towards the limit, called once the
+    /// account has been created
+    pub fn commit(mut self) {
+        self.reservation = None;
+    }
+}
+
+impl Drop for AccountCreationPermit<'_> {
+    fn drop(&mut self) {
+        if let Some((limiter, addr, window_start)) = self.reservation.take() {
+            limiter.release(addr, window_start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AccountCreationLimiter, WINDOW};
+    use crate::config::PlayerConfig;
+    use std::{
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        time::{Duration, Instant},
+    };
+
+    /// Tests that the limit applies to each address separately and
+    /// resets once the window has ended
+    #[test]
+    fn test_account_limit_window() {
+        let limiter = AccountCreationLimiter::new(&PlayerConfig {
+            max_accounts_per_hour: Some(2),
+            ..Default::default()
+        });
+        let a = IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1));
+        let b = "2001:db8::1".parse::<IpAddr>().unwrap();
+        let now = Instant::now();
+
+        limiter.try_acquire_at(a, now).unwrap().commit();
+        limiter.try_acquire_at(a, now).unwrap().commit();
+
+        // Remaining window is provided as the retry time
+        let later = now + Duration::from_secs(60);
+        assert_eq!(
+            limiter.try_acquire_at(a, later).err(),
+            Some(WINDOW - Duration::from_secs(60))
+        );
+
+        // Other addresses have their own window
+        limiter.try_acquire_at(b, later).unwrap().commit();
+
+        // Window resets once it has ended
+        let next_window = now + WINDOW;
+        limiter.try_acquire_at(a, next_window).unwrap().commit();
+        limiter.try_acquire_at(a, next_window).unwrap().commit();
+        assert!(limiter.try_acquire_at(a, next_window).is_err());
+    }
+
+    /// Tests that private and loopback addresses are only exempt
+    /// from the limit when enabled
+    #[test]
+    fn test_account_limit_exempt_private() {
+        let config = PlayerConfig {
+            max_accounts_per_hour: Some(1),
+            account_limit_exempt_private: true,
+            ..Default::default()
+        };
+        let limiter = AccountCreationLimiter::new(&config);
+        let now = Instant::now();
+
+        let addrs: [IpAddr; 4] = [
+            Ipv4Addr::LOCALHOST.into(),
+            Ipv4Addr::new(192, 168, 1, 2).into(),
+            Ipv6Addr::LOCALHOST.into(),
+            "fd00::1".parse().unwrap(),
+        ];
+        for addr in addrs {
+            limiter.try_acquire_at(addr, now).unwrap().commit();
+            limiter.try_acquire_at(addr, now).unwrap().commit();
+        }
+
+        let limiter = AccountCreationLimiter::new(&PlayerConfig {
+            account_limit_exempt_private: false,
+            ..config
+        });
+        let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        limiter.try_acquire_at(addr, now).unwrap().commit();
+        assert!(limiter.try_acquire_at(addr, now).is_err());
+    }
+
+    /// Tests that reservations dropped without being committed, such as
+    /// from failed account creations, don't count towards the limit
+    #[test]
+    fn test_account_limit_release() {
+        let limiter = AccountCreationLimiter::new(&PlayerConfig {
+            max_accounts_per_hour: Some(1),
+            ..Default::default()
+        });
+        let addr = IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1));
+        let now = Instant::now();
+
+        // Limit is reached while the reservation is held
+        let permit = limiter.try_acquire_at(addr, now).unwrap();
+        assert!(limiter.try_acquire_at(addr, now).is_err());
+
+        drop(permit);
+        limiter.try_acquire_at(addr, now).unwrap().commit();
+        assert!(limiter.try_acquire_at(addr, now).is_err());
+    }
+}
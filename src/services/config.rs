@@ -1,7 +1,13 @@
 use embeddy::Embedded;
 use log::error;
 use me3_coalesced_parser::Coalesced;
-use std::path::Path;
+use std::{collections::BTreeMap, path::Path};
+use tdf::TdfMap;
+
+/// Folder containing the client config overrides, each file is named after
+/// the config type it overrides (i.e. ME3_DATA.json) and contains a JSON
+/// object of the config keys and values
+pub const CLIENT_CONFIG_OVERRIDES_PATH: &str = "data/client_config";
 
 /// Embedded copy of the default known talk files
 #[derive(Embedded)]
@@ -43,9 +49,40 @@ pub async fn local_coalesced_file() -> std::io::Result<Coalesced> {
         Err(err) => {
             error!("Failed to parse server coalesced: {}", err);
 
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to parse server coalesced",
+            Err(std::io::Error::other("Failed to parse server coalesced"))
+        }
+    }
+}
+
+/// Attempts to load an override for the client config type `id` from the
+/// `overrides_path` folder. The file is read on each call so changes are
+/// used without restarting the server
+///
+/// `overrides_path` The folder containing the overrides
+/// `id`             The client config type
+pub async fn local_client_config(
+    overrides_path: &Path,
+    id: &str,
+) -> std::io::Result<TdfMap<String, String>> {
+    // Only allow names that can't escape the overrides folder
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|value| value.is_ascii_alphanumeric() || value == '_')
+    {
+        return Err(std::io::ErrorKind::NotFound.into());
+    }
+
+    let local_path = overrides_path.join(format!("{}.json", id));
+    let bytes = tokio::fs::read(local_path).await?;
+
+    match serde_json::from_slice::<BTreeMap<String, String>>(&bytes) {
+        Ok(value) => Ok(TdfMap::from_presorted_elements(value.into_iter().collect())),
+        Err(err) => {
+            error!("Failed to parse client config override {}: {}", id, err);
+
+            Err(std::io::Error::other(
+                "Failed to parse client config override",
             ))
         }
     }
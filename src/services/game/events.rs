@@ -0,0 +1,109 @@
+//! Structured logging of game lifecycle events, each event is logged
+//! as a single line of key value pairs prefixed with [GAME_EVENT]
+
+use crate::{
+    session::models::game_manager::{GameState, RemoveReason},
+    utils::types::{GameID, PlayerID},
+};
+use log::debug;
+use std::fmt::{Display, Formatter};
+
+/// Prefix used for all game event lines
+pub const GAME_EVENT_PREFIX: &str = "[GAME_EVENT]";
+
+/// Lifecycle event that occurred for a game
+pub enum GameEvent {
+    /// The game was created
+    Created,
+    /// A player joined the game in the provided slot
+    PlayerJoined { player: PlayerID, slot: usize },
+    /// A player was removed from the game from the provided slot
+    PlayerLeft {
+        player: PlayerID,
+        slot: usize,
+        reason: RemoveReason,
+    },
+    /// The game state changed
+    StateChanged { from: GameState, to: GameState },
+    /// The host was migrated to the provided player
+    HostMigrated { player: PlayerID },
+    /// The game was stopped
+    Stopped,
+}
+
+impl Display for GameEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Created => f.write_str("event=created"),
+            Self::PlayerJoined { player, slot } => {
+                write!(f, "event=player_joined player={} slot={}", player, slot)
+            }
+            Self::PlayerLeft {
+                player,
+                slot,
+                reason,
+            } => write!(
+                f,
+                "event=player_left player={} slot={} reason={:?}",
+                player, slot, reason
+            ),
+            Self::StateChanged { from, to } => {
+                write!(f, "event=state_changed from={:?} to={:?}", from, to)
+            }
+            Self::HostMigrated { player } => write!(f, "event=host_migrated player={}", player),
+            Self::Stopped => f.write_str("event=stopped"),
+        }
+    }
+}
+
+/// Creates the log line for the `event` that occurred in the game with `game_id`
+pub fn format_event(game_id: GameID, event: &GameEvent) -> String {
+    format!("{} game={} {}", GAME_EVENT_PREFIX, game_id, event)
+}
+
+/// Logs the `event` that occurred in the game with `game_id`
+pub fn log_event(game_id: GameID, event: GameEvent) {
+    debug!("{}", format_event(game_id, &event));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_event, GameEvent};
+    use crate::session::models::game_manager::{GameState, RemoveReason};
+
+    /// Tests the lines created for each of the game events
+    #[test]
+    fn test_format_event() {
+        let cases = [
+            (GameEvent::Created, "[GAME_EVENT] game=1 event=created"),
+            (
+                GameEvent::PlayerJoined { player: 2, slot: 1 },
+                "[GAME_EVENT] game=1 event=player_joined player=2 slot=1",
+            ),
+            (
+                GameEvent::PlayerLeft {
+                    player: 2,
+                    slot: 1,
+                    reason: RemoveReason::PlayerLeft,
+                },
+                "[GAME_EVENT] game=1 event=player_left player=2 slot=1 reason=PlayerLeft",
+            ),
+            (
+                GameEvent::StateChanged {
+                    from: GameState::Initializing,
+                    to: GameState::PreGame,
+                },
+                "[GAME_EVENT] game=1 event=state_changed from=Initializing to=PreGame",
+            ),
+            (
+                GameEvent::HostMigrated { player: 3 },
+                "[GAME_EVENT] game=1 event=host_migrated player=3",
+            ),
+            (GameEvent::Stopped, "[GAME_EVENT] game=1 event=stopped"),
+        ];
+
+        for (event, expected) in cases {
+            assert_eq!(format_event(1, &event), expected);
+        }
+    }
+}
@@ -1,33 +1,38 @@
-use super::{rules::RuleSet, AttrMap, Game, GameJoinableState, GamePlayer, GameRef, GameSnapshot};
+use super::{
+    check_attribute_limits, rules::RuleSet, AttrMap, AttributeLimitExceeded, Game,
+    GameJoinableState, GamePlayer, GameRef, GameSnapshot, GameSummary,
+};
 use crate::{
     config::RuntimeConfig,
     services::{tunnel::TunnelService, udp_tunnel::UdpTunnelService},
     session::{
         models::game_manager::{
-            AsyncMatchmakingStatus, GameSettings, GameSetupContext, MatchmakingResult,
+            AsyncMatchmakingStatus, GameSettings, GameSetupContext, MatchmakingResult, PlayerState,
         },
         packet::Packet,
         SessionLink,
     },
     utils::{
         components::game_manager,
-        hashing::IntHashMap,
+        hashing::{IntHashMap, IntHashSet},
         types::{GameID, PlayerID},
     },
 };
 use chrono::Utc;
-use log::debug;
+use log::{debug, info};
+use serde::Serialize;
 use std::{
     collections::VecDeque,
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc,
     },
-    time::SystemTime,
+    time::Duration,
 };
 use tokio::{
     sync::{Mutex, RwLock},
     task::JoinSet,
+    time::{interval, sleep, Instant, MissedTickBehavior},
 };
 
 /// Manager which controls all the active games on the server
@@ -40,6 +45,10 @@ pub struct GameManager {
     next_id: AtomicU32,
     /// Matchmaking entry queue
     queue: Mutex<VecDeque<MatchmakingEntry>>,
+    /// Games with a matchmaking queue check scheduled
+    pending_checks: parking_lot::Mutex<IntHashSet<GameID>>,
+    /// Time waited by the most recent players placed from the queue
+    wait_times: parking_lot::Mutex<VecDeque<Duration>>,
     /// Tunneling service
     tunnel_service: Arc<TunnelService>,
     /// Tunneling service v2
@@ -55,11 +64,50 @@ struct MatchmakingEntry {
     /// The rules that a game must match for the player to join
     rule_set: Arc<RuleSet>,
     /// Time that the player entered matchmaking
-    started: SystemTime,
+    started: Instant,
+    /// Number of games the entry has been skipped for because
+    /// the game didn't match its rules
+    requeue_count: u32,
+}
+
+/// Statistics for the time players waited in the matchmaking queue
+/// before being placed into a game
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Number of players currently waiting in the queue
+    pub queued: usize,
+    /// Number of recent wait times the stats are calculated from
+    pub samples: usize,
+    /// Average wait time in milliseconds
+    pub average_ms: u64,
+    /// Median wait time in milliseconds
+    pub p50_ms: u64,
+    /// 90th percentile wait time in milliseconds
+    pub p90_ms: u64,
 }
 
 const DEFAULT_FIT: u16 = 21600;
 
+/// Number of recent matchmaking wait times kept for the queue stats
+const WAIT_TIME_SAMPLES: usize = 100;
+
+/// Delay between each check for idle games that haven't started
+const PREGAME_REAPER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Delay between each check for players that have waited too long in
+/// the matchmaking queue
+const MATCHMAKING_FALLBACK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Settings for games created for players that waited too long in the
+/// matchmaking queue, matches the settings used by clients for public games
+const FALLBACK_GAME_SETTINGS: GameSettings = GameSettings::OPEN_TO_BROWSING
+    .union(GameSettings::OPEN_TO_MATCHMAKING)
+    .union(GameSettings::OPEN_TO_INVITES)
+    .union(GameSettings::OPEN_TO_JOIN_BY_PLAYER)
+    .union(GameSettings::HOST_MIGRATABLE)
+    .union(GameSettings::JOIN_IN_PROGRESS_SUPPORTED)
+    .union(GameSettings::IGNORE_ENTRY_CRITERIA_WITH_INVITE);
+
 impl GameManager {
     /// Starts a new game manager service returning its link
     pub fn new(
@@ -71,12 +119,19 @@ impl GameManager {
             games: Default::default(),
             next_id: AtomicU32::new(1),
             queue: Default::default(),
+            pending_checks: Default::default(),
+            wait_times: Default::default(),
             tunnel_service,
             udp_tunnel_service,
             config,
         }
     }
 
+    /// Provides the runtime configuration
+    pub fn config(&self) -> &RuntimeConfig {
+        &self.config
+    }
+
     /// Obtains the total count of games in the list
     pub async fn get_total_games(&self) -> usize {
         let games = &*self.games.read().await;
@@ -90,6 +145,25 @@ impl GameManager {
         include_net: bool,
         include_players: bool,
     ) -> (Vec<GameSnapshot>, bool) {
+        self.snapshot_games(offset, count, move |game| {
+            game.snapshot(include_net, include_players)
+        })
+        .await
+    }
+
+    /// Creates summaries of the games within the provided `offset` and
+    /// `count` range, also returns whether there are more games after
+    pub async fn create_summaries(&self, offset: usize, count: usize) -> (Vec<GameSummary>, bool) {
+        self.snapshot_games(offset, count, Game::summary).await
+    }
+
+    /// Creates a snapshot using `snapshot` for each of the games within
+    /// the provided `offset` and `count` range ordered by game ID
+    async fn snapshot_games<T, F>(&self, offset: usize, count: usize, snapshot: F) -> (Vec<T>, bool)
+    where
+        T: Send + 'static,
+        F: Fn(&Game) -> T + Copy + Send + 'static,
+    {
         // Create the futures using the handle action before passing
         // them to a future to be awaited
         let mut join_set = JoinSet::new();
@@ -113,11 +187,12 @@ impl GameManager {
                 .take(count)
                 // Iterate over the game links
                 .map(|(_, value)| value.clone())
+                .enumerate()
                 // Spawn the snapshot tasks
-                .for_each(|game| {
+                .for_each(|(index, game)| {
                     join_set.spawn(async move {
                         let game = &*game.read().await;
-                        game.snapshot(include_net, include_players)
+                        (index, snapshot(game))
                     });
                 });
 
@@ -134,6 +209,14 @@ impl GameManager {
             }
         }
 
+        // Tasks complete in any order, restore the game ID order
+        snapshots.sort_by_key(|(index, _)| *index);
+
+        let snapshots = snapshots
+            .into_iter()
+            .map(|(_, snapshot)| snapshot)
+            .collect();
+
         (snapshots, more)
     }
 
@@ -143,12 +226,13 @@ impl GameManager {
     }
 
     pub async fn queue(&self, player: GamePlayer, rule_set: Arc<RuleSet>) {
-        let started = SystemTime::now();
+        let started = Instant::now();
         let queue = &mut *self.queue.lock().await;
         queue.push_back(MatchmakingEntry {
             player,
             rule_set,
             started,
+            requeue_count: 0,
         });
     }
 
@@ -160,7 +244,7 @@ impl GameManager {
         context: GameSetupContext,
     ) {
         // Add the player to the game
-        let (game_id, index) = {
+        let (game_id, slot) = {
             let game = &mut *game_ref.write().await;
             let slot = game.add_player(player, context, &self.config);
             (game.id, slot)
@@ -169,22 +253,60 @@ impl GameManager {
         // Allocate tunnel if supported by client
         if let Some(association) = session.data.get_association() {
             self.tunnel_service
-                .associate_pool(association, game_id, index as u8);
+                .associate_pool(association, game_id, slot as u8);
             self.udp_tunnel_service
-                .associate_pool(association, game_id, index as u8);
+                .associate_pool(association, game_id, slot as u8);
         }
 
         // Update the player current game
         session.data.set_game(game_id, Arc::downgrade(&game_ref));
     }
 
-    pub async fn add_from_matchmaking(&self, game_ref: GameRef, player: GamePlayer) {
-        let session = match player.link.upgrade() {
-            Some(value) => value,
-            // Session was dropped
-            None => return,
+    /// Provides the session of a queued `player` if the session is still
+    /// active and logged in as the player
+    fn matchmaking_session(player: &GamePlayer) -> Option<SessionLink> {
+        // Session was dropped
+        let session = player.link.upgrade()?;
+
+        // Session has logged out of the player
+        if session
+            .data
+            .get_player()
+            .is_none_or(|current| current.id != player.player.id)
+        {
+            return None;
+        }
+
+        Some(session)
+    }
+
+    /// Adds a `player` from matchmaking to the game, provides whether the
+    /// player was added. Players whose session has ended or logged out of
+    /// the player are not added
+    pub async fn add_from_matchmaking(&self, game_ref: GameRef, player: GamePlayer) -> bool {
+        let Some(session) = Self::matchmaking_session(&player) else {
+            return false;
         };
 
+        self.add_matchmaking_player(
+            game_ref,
+            player,
+            session,
+            MatchmakingResult::JoinedExistingGame,
+        )
+        .await;
+        true
+    }
+
+    /// Adds a `player` from matchmaking to the game using the matchmaking
+    /// `result` as the setup context
+    async fn add_matchmaking_player(
+        &self,
+        game_ref: GameRef,
+        player: GamePlayer,
+        session: SessionLink,
+        result: MatchmakingResult,
+    ) {
         let msid = player.player.id;
 
         // MUST be sent to players at least once when matchmaking otherwise it may fail
@@ -203,18 +325,42 @@ impl GameManager {
                 fit_score: DEFAULT_FIT,
                 max_fit_score: DEFAULT_FIT,
                 session_id: msid,
-                result: MatchmakingResult::JoinedExistingGame,
+                result,
                 player_id: msid,
             },
         )
         .await;
     }
 
+    /// Checks that the `attributes` for creating a new game stay within the
+    /// attribute limits once merged with the default attributes
+    pub fn check_create_attributes(
+        &self,
+        attributes: &AttrMap,
+    ) -> Result<(), AttributeLimitExceeded> {
+        let defaults: AttrMap = self
+            .config
+            .game
+            .default_attributes
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        check_attribute_limits(&self.config.game, &defaults, attributes)
+    }
+
     pub async fn create_game(
         self: &Arc<Self>,
-        attributes: AttrMap,
+        mut attributes: AttrMap,
         setting: GameSettings,
     ) -> (GameRef, GameID) {
+        // Fill in any default attributes the client didn't provide
+        for (key, value) in &self.config.game.default_attributes {
+            if !attributes.contains_key(key) {
+                attributes.insert(key.clone(), value.clone());
+            }
+        }
+
         let id = self.next_id.fetch_add(1, Ordering::AcqRel);
         let created_at = Utc::now();
         let game = Game::new(
@@ -264,55 +410,702 @@ impl GameManager {
         Err(player)
     }
 
+    /// Stops any games that haven't started and haven't had any activity
+    /// within the `timeout`, provides the number of games stopped
+    pub async fn stop_idle_games(&self, timeout: Duration) -> usize {
+        let games: Vec<GameRef> = {
+            let games = &*self.games.read().await;
+            games.values().cloned().collect()
+        };
+
+        let mut stopped = 0;
+
+        for game in games {
+            let game = &mut *game.write().await;
+            if !game.is_pregame_idle(timeout) {
+                continue;
+            }
+
+            debug!("Stopping idle pre-game game (GID: {})", game.id);
+            game.stop_with_players();
+            stopped += 1;
+        }
+
+        stopped
+    }
+
+    /// Background task that periodically stops games that have sat idle
+    /// before starting for longer than the `timeout`
+    pub async fn pregame_reaper_task(self: Arc<Self>, timeout: Duration) {
+        let mut interval = interval(PREGAME_REAPER_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let stopped = self.stop_idle_games(timeout).await;
+            if stopped > 0 {
+                info!("Stopped {} idle pre-game games", stopped);
+            }
+        }
+    }
+
+    /// Creates a new game for each player that has been waiting in the
+    /// matchmaking queue for longer than the `timeout`, the game is created
+    /// from the rules the player queued with and the player is made the
+    /// host. Provides the number of games created
+    pub async fn create_fallback_games(self: &Arc<Self>, timeout: Duration) -> usize {
+        // Entries are removed from the queue before their game is created so
+        // that later checks can't create another game for the same player
+        let expired: VecDeque<MatchmakingEntry> = {
+            let queue = &mut *self.queue.lock().await;
+            let (expired, waiting) = queue
+                .drain(..)
+                .partition(|entry| entry.started.elapsed() >= timeout);
+            *queue = waiting;
+            expired
+        };
+
+        let mut created = 0;
+
+        for entry in expired {
+            let Some(session) = Self::matchmaking_session(&entry.player) else {
+                continue;
+            };
+
+            let elapsed = entry.started.elapsed();
+            self.record_wait_time(elapsed);
+
+            let (link, game_id) = self
+                .create_game(entry.rule_set.attributes(), FALLBACK_GAME_SETTINGS)
+                .await;
+
+            debug!(
+                "Created fallback game for player waiting {}s in queue (PID: {}, GID: {})",
+                elapsed.as_secs(),
+                entry.player.player.id,
+                game_id
+            );
+
+            let mut player = entry.player;
+
+            // Player is the host player (They are connected by default)
+            player.state = PlayerState::ActiveConnected;
+
+            self.add_matchmaking_player(
+                link.clone(),
+                player,
+                session,
+                MatchmakingResult::CreatedGame,
+            )
+            .await;
+
+            // Other waiting players may match the new game
+            self.schedule_queue_check(link, game_id).await;
+
+            created += 1;
+        }
+
+        created
+    }
+
+    /// Background task that periodically creates games for players that
+    /// have waited in the matchmaking queue for longer than the `timeout`
+    pub async fn matchmaking_fallback_task(self: Arc<Self>, timeout: Duration) {
+        let mut interval = interval(MATCHMAKING_FALLBACK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let created = self.create_fallback_games(timeout).await;
+            if created > 0 {
+                info!("Created {} fallback matchmaking games", created);
+            }
+        }
+    }
+
     pub async fn remove_game(&self, game_id: GameID) {
         let games = &mut *self.games.write().await;
         _ = games.remove(&game_id);
     }
 
+    /// Schedules a check of the matchmaking queue against the game after the
+    /// configured debounce delay. Checks that are scheduled while a check for
+    /// the same game is already pending are combined into the pending check
+    pub async fn schedule_queue_check(self: &Arc<Self>, link: GameRef, game_id: GameID) {
+        let Some(debounce) = self.config.game.matchmaking_check_debounce_ms else {
+            self.process_queue(link, game_id).await;
+            return;
+        };
+
+        // Check is already pending for this game
+        if !self.pending_checks.lock().insert(game_id) {
+            return;
+        }
+
+        let game_manager = self.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(debounce)).await;
+
+            // Clear the pending check before processing so that changes made
+            // while processing will schedule another check
+            game_manager.pending_checks.lock().remove(&game_id);
+            game_manager.process_queue(link, game_id).await;
+        });
+    }
+
     pub async fn process_queue(&self, link: GameRef, game_id: GameID) {
         let queue = &mut *self.queue.lock().await;
         if queue.is_empty() {
             return;
         }
 
-        while let Some(entry) = queue.front() {
+        let widen_after = self.config.game.matchmaking_widen_after;
+
+        // Entries that didn't match the game
+        let mut skipped = Vec::new();
+
+        while let Some(mut entry) = queue.pop_front() {
             let join_state = {
                 let link = &*link.read().await;
                 link.joinable_state(Some(&entry.rule_set))
             };
 
-            // TODO: If player has been in queue long enough create
-            // a game matching their specifics
-
             match join_state {
                 GameJoinableState::Joinable => {
-                    let entry = queue
-                        .pop_front()
-                        .expect("Expecting matchmaking entry but nothing was present");
-
                     debug!(
                         "Found player from queue adding them to the game (GID: {})",
                         game_id
                     );
-                    let time = SystemTime::now();
-                    let elapsed = time.duration_since(entry.started);
-                    if let Ok(elapsed) = elapsed {
-                        debug!("Matchmaking time elapsed: {}s", elapsed.as_secs())
-                    }
+                    let elapsed = entry.started.elapsed();
+                    debug!("Matchmaking time elapsed: {}s", elapsed.as_secs());
 
-                    // Add the player to the game
-                    self.add_from_matchmaking(link.clone(), entry.player).await;
+                    // Add the player to the game, only players that were added
+                    // are included in the wait times
+                    if self.add_from_matchmaking(link.clone(), entry.player).await {
+                        self.record_wait_time(elapsed);
+                    }
                 }
                 GameJoinableState::Full | GameJoinableState::Stopping => {
                     // If the game is not joinable push the entry back to the
                     // front of the queue and early return
+                    queue.push_front(entry);
                     break;
                 }
                 GameJoinableState::NotMatch => {
-                    // TODO: Check started time and timeout
-                    // player if they've been waiting too long
+                    entry.requeue_count += 1;
+
+                    // Widen the rules of entries that have been skipped too many
+                    // times to prevent them from never finding a game
+                    if widen_after.is_some_and(|widen_after| entry.requeue_count == widen_after) {
+                        debug!(
+                            "Widening matchmaking rules for player (PID: {})",
+                            entry.player.player.id
+                        );
+                        entry.rule_set = Arc::new(entry.rule_set.widened());
+                    }
+
+                    skipped.push(entry);
                 }
             }
         }
+
+        // Return the skipped entries to the front of the queue in their original order
+        for entry in skipped.into_iter().rev() {
+            queue.push_front(entry);
+        }
+    }
+
+    /// Records the time a player waited in the queue before being placed
+    /// into a game, only the most recent wait times are kept
+    fn record_wait_time(&self, elapsed: Duration) {
+        let wait_times = &mut *self.wait_times.lock();
+        if wait_times.len() >= WAIT_TIME_SAMPLES {
+            wait_times.pop_front();
+        }
+        wait_times.push_back(elapsed);
+    }
+
+    /// Creates statistics from the recent matchmaking wait times, players
+    /// that left the queue without being placed are not included
+    pub async fn queue_stats(&self) -> QueueStats {
+        let queued = self.queue.lock().await.len();
+
+        let mut wait_times: Vec<Duration> = self.wait_times.lock().iter().copied().collect();
+        if wait_times.is_empty() {
+            return QueueStats {
+                queued,
+                ..Default::default()
+            };
+        }
+
+        wait_times.sort_unstable();
+
+        let samples = wait_times.len();
+        let total: Duration = wait_times.iter().sum();
+
+        // Nearest rank percentile of the sorted wait times
+        let percentile = |percent: usize| {
+            let rank = (samples * percent).div_ceil(100).max(1);
+            wait_times[rank - 1].as_millis() as u64
+        };
+
+        QueueStats {
+            queued,
+            samples,
+            average_ms: (total / samples as u32).as_millis() as u64,
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AttrMap, GameManager, QueueStats};
+    use crate::{
+        config::{GameConfig, RuntimeConfig},
+        services::{game::rules::RuleSet, tunnel::TunnelService, udp_tunnel::UdpTunnelService},
+        session::{
+            models::game_manager::{
+                DatalessContext, GameSettings, GameSetupContext, GameState, PlayerState,
+                RemoveReason,
+            },
+            test_util::{
+                create_auth_session, create_detached_game_player, create_game_player,
+                test_game_manager, test_player, test_sessions,
+            },
+            Session,
+        },
+    };
+    use std::{
+        collections::VecDeque,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    /// Tests that an entry which is repeatedly skipped has its rules
+    /// widened once the configured threshold is reached allowing it
+    /// to be placed
+    #[tokio::test]
+    async fn test_skipped_entry_widened() {
+        let config = RuntimeConfig {
+            game: GameConfig {
+                matchmaking_widen_after: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let game_manager = test_game_manager(Arc::new(config));
+
+        let mut attributes = AttrMap::default();
+        attributes.insert("ME3map".to_string(), "map1".to_string());
+        let (game_ref, game_id) = game_manager
+            .create_game(attributes, GameSettings::NONE)
+            .await;
+
+        let rule_set = RuleSet::new(vec![(
+            "ME3_gameMapMatchRule".to_string(),
+            "map2".to_string(),
+        )]);
+        game_manager
+            .queue(create_detached_game_player(1).0, Arc::new(rule_set))
+            .await;
+        game_manager
+            .queue(
+                create_detached_game_player(2).0,
+                Arc::new(RuleSet::new(Vec::new())),
+            )
+            .await;
+
+        // Matching entry is placed while the non matching entry is kept
+        game_manager.process_queue(game_ref.clone(), game_id).await;
+        {
+            let queue = &*game_manager.queue.lock().await;
+            assert_eq!(queue.len(), 1);
+            assert_eq!(queue[0].requeue_count, 1);
+        }
+
+        // Entry reaches the threshold and is widened
+        game_manager.process_queue(game_ref.clone(), game_id).await;
+        {
+            let queue = &*game_manager.queue.lock().await;
+            assert_eq!(queue.len(), 1);
+            assert_eq!(queue[0].requeue_count, 2);
+        }
+
+        // Widened entry is now placed
+        game_manager.process_queue(game_ref, game_id).await;
+        assert!(game_manager.queue.lock().await.is_empty());
+    }
+
+    /// Tests that rapid changes to a game are combined into a single
+    /// matchmaking queue check
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_check_debounce() {
+        let config = RuntimeConfig {
+            game: GameConfig {
+                matchmaking_widen_after: None,
+                matchmaking_check_debounce_ms: Some(250),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let game_manager = test_game_manager(Arc::new(config));
+
+        let (game_ref, game_id) = game_manager
+            .create_game(AttrMap::default(), GameSettings::NONE)
+            .await;
+
+        // Entry that never matches so each check increases its requeue count
+        let rule_set = RuleSet::new(vec![(
+            "ME3_gameMapMatchRule".to_string(),
+            "map2".to_string(),
+        )]);
+        game_manager
+            .queue(create_detached_game_player(1).0, Arc::new(rule_set))
+            .await;
+
+        for _ in 0..5 {
+            game_manager
+                .schedule_queue_check(game_ref.clone(), game_id)
+                .await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(game_manager.queue.lock().await[0].requeue_count, 1);
+
+        // Changes after the window schedule a new check
+        game_manager
+            .schedule_queue_check(game_ref.clone(), game_id)
+            .await;
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(game_manager.queue.lock().await[0].requeue_count, 2);
+    }
+
+    /// Tests that the queue stats are calculated from the wait times of
+    /// players placed from the queue, ignoring players that left the queue
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_stats() {
+        let sessions = test_sessions();
+        let game_manager = test_game_manager(Arc::new(RuntimeConfig::default()));
+        assert_eq!(game_manager.queue_stats().await, QueueStats::default());
+
+        let (game_ref, game_id) = game_manager
+            .create_game(AttrMap::default(), GameSettings::NONE)
+            .await;
+
+        // Entry that never matches the game
+        let rule_set = RuleSet::new(vec![(
+            "ME3_gameMapMatchRule".to_string(),
+            "map2".to_string(),
+        )]);
+        game_manager
+            .queue(create_detached_game_player(1).0, Arc::new(rule_set))
+            .await;
+        let (session, _rx) = create_auth_session(&sessions, test_player(2));
+        game_manager
+            .queue(
+                create_game_player(&session),
+                Arc::new(RuleSet::new(Vec::new())),
+            )
+            .await;
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        game_manager.process_queue(game_ref.clone(), game_id).await;
+
+        // Player leaving the queue is not included in the stats
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        game_manager.remove_queue(1).await;
+
+        let (session, _rx) = create_auth_session(&sessions, test_player(3));
+        game_manager
+            .queue(
+                create_game_player(&session),
+                Arc::new(RuleSet::new(Vec::new())),
+            )
+            .await;
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        game_manager.process_queue(game_ref, game_id).await;
+
+        assert_eq!(
+            game_manager.queue_stats().await,
+            QueueStats {
+                queued: 0,
+                samples: 2,
+                average_ms: 15_000,
+                p50_ms: 10_000,
+                p90_ms: 20_000,
+            }
+        );
+    }
+
+    /// Tests that entries removed from the queue because their session
+    /// ended are not included in the queue stats
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_stats_dropped_session() {
+        let game_manager = test_game_manager(Arc::new(RuntimeConfig::default()));
+
+        let (game_ref, game_id) = game_manager
+            .create_game(AttrMap::default(), GameSettings::NONE)
+            .await;
+
+        // Player without an active session
+        game_manager
+            .queue(
+                create_detached_game_player(1).0,
+                Arc::new(RuleSet::new(Vec::new())),
+            )
+            .await;
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        game_manager.process_queue(game_ref.clone(), game_id).await;
+
+        assert!(game_manager.queue.lock().await.is_empty());
+        assert_eq!(game_manager.queue_stats().await, QueueStats::default());
+        assert!(game_ref.read().await.players.is_empty());
+    }
+
+    /// Tests that players waiting past the timeout have a game created from
+    /// their rules with them as the host, and that only one game is created
+    /// for each waiting player
+    #[tokio::test(start_paused = true)]
+    async fn test_fallback_game_created() {
+        let sessions = test_sessions();
+        let game_manager = test_game_manager(Arc::new(RuntimeConfig::default()));
+
+        let (session, _rx) = create_auth_session(&sessions, test_player(1));
+        let game_player = create_game_player(&session);
+
+        let rule_set = RuleSet::new(vec![(
+            "ME3_gameMapMatchRule".to_string(),
+            "map2".to_string(),
+        )]);
+        game_manager.queue(game_player, Arc::new(rule_set)).await;
+
+        // Player without an active session
+        game_manager
+            .queue(
+                create_detached_game_player(2).0,
+                Arc::new(RuleSet::new(Vec::new())),
+            )
+            .await;
+
+        let timeout = Duration::from_secs(30);
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(game_manager.create_fallback_games(timeout).await, 0);
+        assert_eq!(game_manager.queue.lock().await.len(), 2);
+
+        // Only the player with an active session has a game created
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        assert_eq!(game_manager.create_fallback_games(timeout).await, 1);
+        assert!(game_manager.queue.lock().await.is_empty());
+        assert_eq!(game_manager.get_total_games().await, 1);
+
+        let game_ref = game_manager.get_game(1).await.unwrap();
+        {
+            let game = &*game_ref.read().await;
+            assert_eq!(
+                game.attributes.get("ME3map").map(String::as_str),
+                Some("map2")
+            );
+            assert_eq!(game.players.len(), 1);
+            assert_eq!(game.players[0].player.id, 1);
+            assert_eq!(game.players[0].state, PlayerState::ActiveConnected);
+        }
+
+        // Later checks don't create another game for the player
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(game_manager.create_fallback_games(timeout).await, 0);
+        assert_eq!(game_manager.get_total_games().await, 1);
+    }
+
+    /// Tests that the remaining players keep their slot and tunnel pool index
+    /// when a player before them leaves, and that the freed slot is reused
+    #[tokio::test]
+    async fn test_slot_stable_after_leave() {
+        // Tunnel service shares the sessions to verify its reconnect tokens
+        let sessions = test_sessions();
+        let udp_tunnel_service = Arc::new(UdpTunnelService::new(sessions.clone()));
+        let game_manager = Arc::new(GameManager::new(
+            Arc::new(TunnelService::default()),
+            udp_tunnel_service.clone(),
+            Arc::new(RuntimeConfig::default()),
+        ));
+
+        let (game_ref, game_id) = game_manager
+            .create_game(AttrMap::default(), GameSettings::NONE)
+            .await;
+
+        let player_sessions: Vec<_> = (1..=4)
+            .map(|id| create_auth_session(&sessions, test_player(id)).0)
+            .collect();
+        let mut players: VecDeque<_> = player_sessions.iter().map(create_game_player).collect();
+
+        for session in &player_sessions[..3] {
+            game_manager
+                .add_to_game(
+                    game_ref.clone(),
+                    players.pop_front().unwrap(),
+                    session.clone(),
+                    GameSetupContext::Dataless {
+                        context: DatalessContext::JoinGameSetup,
+                    },
+                )
+                .await;
+        }
+
+        // Provides the tunnel pool index reserved for the session
+        let pool_index = |session: &Arc<Session>| {
+            let association = session.data.get_association().unwrap();
+            let token = udp_tunnel_service.create_reconnect_token(association)?;
+            let reconnect = sessions.verify_tunnel_reconnect_token(&token).unwrap();
+            assert_eq!(reconnect.pool_id, game_id);
+            Some(reconnect.pool_index)
+        };
+
+        game_ref
+            .write()
+            .await
+            .remove_player(2, RemoveReason::PlayerLeft);
+
+        assert_eq!(pool_index(&player_sessions[0]), Some(0));
+        assert_eq!(pool_index(&player_sessions[1]), None);
+        assert_eq!(pool_index(&player_sessions[2]), Some(2));
+
+        // Joining player takes the slot that was freed
+        game_manager
+            .add_to_game(
+                game_ref.clone(),
+                players.pop_front().unwrap(),
+                player_sessions[3].clone(),
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+            )
+            .await;
+
+        assert_eq!(pool_index(&player_sessions[2]), Some(2));
+        assert_eq!(pool_index(&player_sessions[3]), Some(1));
+
+        let game = &*game_ref.read().await;
+        let slots: Vec<(u32, usize)> = game
+            .players
+            .iter()
+            .map(|player| (player.player.id, player.slot))
+            .collect();
+        assert_eq!(slots, vec![(1, 0), (3, 2), (4, 1)]);
+    }
+
+    /// Tests that game summaries are paginated in game ID order and
+    /// don't include the game attributes or players
+    #[tokio::test]
+    async fn test_create_summaries() {
+        let game_manager = test_game_manager(Arc::new(RuntimeConfig::default()));
+
+        for _ in 0..3 {
+            game_manager
+                .create_game(AttrMap::default(), GameSettings::NONE)
+                .await;
+        }
+
+        let (games, more) = game_manager.create_summaries(0, 2).await;
+        assert!(more);
+        assert_eq!(games.iter().map(|game| game.id).collect::<Vec<_>>(), [1, 2]);
+
+        let (games, more) = game_manager.create_summaries(2, 2).await;
+        assert!(!more);
+        assert_eq!(games.iter().map(|game| game.id).collect::<Vec<_>>(), [3]);
+        assert!(games[0].joinable);
+
+        let (games, more) = game_manager.create_summaries(4, 2).await;
+        assert!(!more);
+        assert!(games.is_empty());
+
+        let (games, _) = game_manager.create_summaries(0, 1).await;
+        let value = serde_json::to_value(&games[0]).unwrap();
+        assert!(value.get("attributes").is_none());
+        assert!(value.get("players").is_none());
+        assert_eq!(value["total_players"], 0);
+    }
+
+    /// Tests that created games are given the configured default attributes
+    /// for any keys missing from the request without replacing provided values
+    #[tokio::test]
+    async fn test_create_game_default_attributes() {
+        let config = RuntimeConfig {
+            game: GameConfig {
+                default_attributes: [
+                    ("ME3privacy".to_string(), "PUBLIC".to_string()),
+                    ("ME3map".to_string(), "map1".to_string()),
+                ]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let game_manager = test_game_manager(Arc::new(config));
+
+        let mut attributes = AttrMap::default();
+        attributes.insert("ME3map".to_string(), "map2".to_string());
+        let (game_ref, _) = game_manager
+            .create_game(attributes, GameSettings::NONE)
+            .await;
+
+        let game = &*game_ref.read().await;
+        assert_eq!(
+            game.attributes.get("ME3privacy").map(String::as_str),
+            Some("PUBLIC")
+        );
+        assert_eq!(
+            game.attributes.get("ME3map").map(String::as_str),
+            Some("map2")
+        );
+    }
+
+    /// Tests that a game idle before starting past the timeout is stopped
+    /// while a game that has started is left untouched
+    #[tokio::test]
+    async fn test_stop_idle_games() {
+        let game_manager = test_game_manager(Arc::new(RuntimeConfig::default()));
+
+        let timeout = Duration::from_secs(60);
+        let idle_since = Instant::now() - Duration::from_secs(120);
+
+        let mut games = Vec::new();
+        for (player_id, state) in [(1, GameState::PreGame), (2, GameState::InGame)] {
+            let (game_ref, _) = game_manager
+                .create_game(AttrMap::default(), GameSettings::NONE)
+                .await;
+
+            {
+                let game = &mut *game_ref.write().await;
+                game.add_player(
+                    create_detached_game_player(player_id).0,
+                    GameSetupContext::Dataless {
+                        context: DatalessContext::CreateGameSetup,
+                    },
+                    &game_manager.config,
+                );
+                game.state = state;
+                game.last_activity = idle_since;
+            }
+
+            games.push(game_ref);
+        }
+
+        assert_eq!(game_manager.stop_idle_games(timeout).await, 1);
+
+        let pregame = &*games[0].read().await;
+        assert_eq!(pregame.state, GameState::Destructing);
+        assert!(pregame.players.is_empty());
+
+        let in_game = &*games[1].read().await;
+        assert_eq!(in_game.state, GameState::InGame);
+        assert_eq!(in_game.players.len(), 1);
     }
 }
@@ -1,6 +1,10 @@
-use self::{manager::GameManager, rules::RuleSet};
+use self::{
+    events::{log_event, GameEvent},
+    manager::GameManager,
+    rules::RuleSet,
+};
 use crate::{
-    config::RuntimeConfig,
+    config::{GameConfig, RuntimeConfig},
     database::entities::Player,
     session::{
         data::NetData,
@@ -13,6 +17,7 @@ use crate::{
                 RemoveReason, SettingChange, SlotType, StateChange, UNSPECIFIED_TEAM_INDEX,
             },
             util::LOCALE_NZ,
+            NetworkAddress,
         },
         packet::Packet,
         router::RawBlaze,
@@ -26,12 +31,17 @@ use crate::{
 use chrono::{DateTime, Utc};
 use log::{debug, warn};
 use serde::Serialize;
-use std::sync::{Arc, Weak};
+use std::{
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
 use tdf::{ObjectId, TdfMap, TdfSerializer};
+use thiserror::Error;
 use tokio::sync::RwLock;
 
 use super::{tunnel::TunnelService, udp_tunnel::UdpTunnelService};
 
+pub mod events;
 pub mod manager;
 pub mod rules;
 
@@ -50,8 +60,15 @@ pub struct Game {
     pub attributes: AttrMap,
     /// When the game was started
     pub created_at: DateTime<Utc>,
+    /// Randomness seed shared between the game clients
+    pub seed: u32,
+    /// Last time the game state, settings, attributes, or players changed
+    pub last_activity: Instant,
     /// Players currently in the game
     pub players: Vec<GamePlayer>,
+    /// Maximum number of players allowed in the game at one time,
+    /// used to determine the games full state
+    pub max_players: usize,
     /// Services access
     pub game_manager: Arc<GameManager>,
     /// Access to the tunneling service
@@ -77,11 +94,139 @@ pub struct GameSnapshot {
     pub total_players: usize,
     /// When the game was created
     pub created_at: DateTime<Utc>,
+    /// Randomness seed shared between the game clients
+    pub seed: u32,
+}
+
+/// Detailed snapshot of the full game state including the mesh and
+/// connection details of each player, used for debugging
+#[derive(Serialize)]
+pub struct GameDebugSnapshot {
+    /// The ID of the game the snapshot is for
+    pub id: GameID,
+    /// The current game state
+    pub state: GameState,
+    /// The current game setting
+    pub setting: u16,
+    /// Names of the current game setting flags
+    pub setting_flags: String,
+    /// The game attributes
+    pub attributes: AttrMap,
+    /// When the game was created
+    pub created_at: DateTime<Utc>,
+    /// Randomness seed shared between the game clients
+    pub seed: u32,
+    /// Number of seconds since the game last had any activity
+    pub idle_seconds: u64,
+    /// Detailed snapshots of the game players
+    pub players: Vec<GamePlayerDebugSnapshot>,
+}
+
+/// Detailed snapshot of a player within a game including their mesh
+/// and connection details
+#[derive(Serialize)]
+pub struct GamePlayerDebugSnapshot {
+    /// The slot the player is in
+    pub slot: usize,
+    /// The player ID of the snapshot
+    pub player_id: PlayerID,
+    /// The player name of the snapshot
+    pub display_name: Box<str>,
+    /// The mesh state of the player
+    pub state: PlayerState,
+    /// Number of times the player has been reported as disconnected
+    /// from the mesh since they were last connected
+    pub mesh_failures: u32,
+    /// Whether the player session is still connected to the server
+    pub session_connected: bool,
+    /// Whether the player has a network address other players can
+    /// connect to
+    pub has_network_address: bool,
+    /// The player net data
+    pub net: Arc<NetData>,
+}
+
+/// Summary of a game without the attributes or players, used
+/// when listing games where the full details aren't needed
+#[derive(Serialize)]
+pub struct GameSummary {
+    /// The ID of the game the summary is for
+    pub id: GameID,
+    /// The current game state
+    pub state: GameState,
+    /// The total number of players in the game
+    pub total_players: usize,
+    /// Whether the game can currently be joined
+    pub joinable: bool,
+}
+
+/// Error for a game state change that isn't allowed from
+/// the current game state
+#[derive(Debug, Error)]
+#[error("invalid game state transition from {from:?} to {to:?}")]
+pub struct InvalidStateTransition {
+    /// The current game state
+    pub from: GameState,
+    /// The requested game state
+    pub to: GameState,
 }
 
 /// Attributes map type
 pub type AttrMap = TdfMap<String, String>;
 
+/// Limit that prevented game attributes from being set
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AttributeLimitExceeded {
+    /// The attributes would take the game past the maximum number of attributes
+    #[error("too many game attributes")]
+    Count,
+    /// An attribute key and value are larger than the maximum size
+    #[error("game attribute too large")]
+    Size,
+}
+
+/// Reason the seed of a game could not be reset
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ResetSeedError {
+    /// The game has already started using the current seed
+    #[error("game has already started")]
+    AlreadyStarted,
+    /// Players in the game have already been sent the current seed
+    #[error("game has players")]
+    HasPlayers,
+}
+
+/// Checks that merging the new `attributes` into the `current` attributes
+/// stays within the attribute limits from the game `config`
+pub fn check_attribute_limits(
+    config: &GameConfig,
+    current: &AttrMap,
+    attributes: &AttrMap,
+) -> Result<(), AttributeLimitExceeded> {
+    if let Some(max_bytes) = config.max_attribute_bytes {
+        if attributes
+            .iter()
+            .any(|(key, value)| key.len() + value.len() > max_bytes)
+        {
+            return Err(AttributeLimitExceeded::Size);
+        }
+    }
+
+    if let Some(max_attributes) = config.max_attributes {
+        // Existing keys are replaced rather than added
+        let added = attributes
+            .keys()
+            .filter(|key| !current.contains_key(*key))
+            .count();
+
+        if current.len() + added > max_attributes {
+            return Err(AttributeLimitExceeded::Count);
+        }
+    }
+
+    Ok(())
+}
+
 /// Player structure containing details and state for a player
 /// within a game
 pub struct GamePlayer {
@@ -94,6 +239,13 @@ pub struct GamePlayer {
     pub net: Arc<NetData>,
     /// The mesh state of the player
     pub state: PlayerState,
+    /// Number of times the player has been reported as disconnected
+    /// from the mesh since they were last connected
+    pub mesh_failures: u32,
+    /// Slot the player occupies within the game, used as the player slot
+    /// ID and tunnel pool index. Unlike the position within the players
+    /// list this doesn't change when other players leave
+    pub slot: usize,
 }
 
 /// Structure for taking a snapshot of the players current
@@ -106,6 +258,14 @@ pub struct GamePlayerSnapshot {
     pub display_name: Box<str>,
     /// The player net data of the snapshot if collected
     pub net: Option<Arc<NetData>>,
+    /// Whether the player has a network address other players can
+    /// connect to, players without one cannot become the host
+    pub has_network_address: bool,
+    /// The mesh state of the player
+    pub state: PlayerState,
+    /// Number of times the player has been reported as disconnected
+    /// from the mesh since they were last connected
+    pub mesh_failures: u32,
 }
 
 impl GamePlayer {
@@ -127,6 +287,8 @@ impl GamePlayer {
             notify_handle,
             net,
             state: PlayerState::ActiveConnecting,
+            mesh_failures: 0,
+            slot: 0,
         }
     }
 
@@ -136,10 +298,12 @@ impl GamePlayer {
         }
     }
 
-    pub fn try_subscribe(&self, player_id: PlayerID, subscriber: SessionNotifyHandle) {
-        if let Some(link) = self.link.upgrade() {
-            link.data.add_subscriber(player_id, subscriber);
-        }
+    /// Subscribes to the provided player, returns whether the subscription
+    /// was added
+    pub fn try_subscribe(&self, player_id: PlayerID, subscriber: SessionNotifyHandle) -> bool {
+        self.link
+            .upgrade()
+            .is_some_and(|link| link.data.add_subscriber(player_id, subscriber))
     }
 
     pub fn try_unsubscribe(&self, player_id: PlayerID) {
@@ -164,10 +328,19 @@ impl GamePlayer {
             } else {
                 None
             },
+            has_network_address: self.has_network_address(),
+            state: self.state,
+            mesh_failures: self.mesh_failures,
         }
     }
 
-    pub fn encode<S: TdfSerializer>(&self, game_id: GameID, slot: usize, w: &mut S) {
+    /// Checks whether the player has a network address that other
+    /// players are able to connect to
+    pub fn has_network_address(&self) -> bool {
+        matches!(self.net.addr, NetworkAddress::AddressPair(_))
+    }
+
+    pub fn encode<S: TdfSerializer>(&self, game_id: GameID, w: &mut S) {
         w.group_body(|w| {
             // Custom data
             w.tag_blob_empty(b"BLOB");
@@ -184,7 +357,7 @@ impl GamePlayer {
             // Player network data
             w.tag_ref(b"PNET", &self.net.addr);
             // Slot ID
-            w.tag_owned(b"SID", slot);
+            w.tag_owned(b"SID", self.slot);
             // Slot type
             w.tag_alt(b"SLOT", SlotType::PublicParticipant);
             // Player state
@@ -215,10 +388,6 @@ pub enum GameJoinableState {
 }
 
 impl Game {
-    /// Constant for the maximum number of players allowed in
-    /// a game at one time. Used to determine a games full state
-    pub const MAX_PLAYERS: usize = 4;
-
     pub fn new(
         id: GameID,
         attributes: AttrMap,
@@ -228,16 +397,30 @@ impl Game {
         tunnel_service: Arc<TunnelService>,
         udp_tunnel_service: Arc<UdpTunnelService>,
     ) -> Game {
-        Game {
+        let max_players = game_manager.config().game.player_limit();
+
+        let game = Game {
             id,
             attributes,
             settings,
             state: Default::default(),
             players: Default::default(),
+            max_players,
             created_at,
+            seed: rand::random(),
+            last_activity: Instant::now(),
             game_manager,
             tunnel_service,
             udp_tunnel_service,
+        };
+        game.log_event(GameEvent::Created);
+        game
+    }
+
+    /// Logs the lifecycle `event` for this game (If enabled)
+    fn log_event(&self, event: GameEvent) {
+        if self.game_manager.config().game.log_events {
+            log_event(self.id, event);
         }
     }
 
@@ -248,11 +431,20 @@ impl Game {
 
     pub fn add_player(
         &mut self,
-        player: GamePlayer,
+        mut player: GamePlayer,
         context: GameSetupContext,
         config: &RuntimeConfig,
     ) -> usize {
-        let slot = self.players.len();
+        let slot = self.free_slot();
+        player.slot = slot;
+        self.last_activity = Instant::now();
+
+        if !player.has_network_address() {
+            warn!(
+                "Player joined game without a network address, player cannot host (PID: {}, GID: {})",
+                player.player.id, self.id
+            );
+        }
 
         // Update other players with the client details
         self.add_user_sub(&player);
@@ -262,12 +454,16 @@ impl Game {
             game_manager::COMPONENT,
             game_manager::PLAYER_JOINING,
             PlayerJoining {
-                slot,
                 player: &player,
                 game_id: self.id,
             },
         ));
 
+        self.log_event(GameEvent::PlayerJoined {
+            player: player.player.id,
+            slot,
+        });
+
         self.players.push(player);
 
         // Get the player that was just added
@@ -287,14 +483,71 @@ impl Game {
             },
         ));
 
+        // Automatically start the game once its full
+        if config.game.auto_start_when_full
+            && self.players.len() >= self.max_players
+            && self.is_before_start()
+        {
+            debug!("Automatically starting full game (GID: {})", self.id);
+            self.update_state(GameState::InGame);
+        }
+
         slot
     }
 
+    /// Finds the lowest slot that isn't occupied by any of the players
+    fn free_slot(&self) -> usize {
+        (0..self.players.len())
+            .find(|slot| !self.players.iter().any(|player| player.slot == *slot))
+            .unwrap_or(self.players.len())
+    }
+
+    /// Checks whether the game is in a state before the game has started,
+    /// games that have started or are stopping are not in this state
+    fn is_before_start(&self) -> bool {
+        matches!(
+            self.state,
+            GameState::NewState
+                | GameState::Initializing
+                | GameState::InactiveVirtual
+                | GameState::ConnectionVerification
+                | GameState::PreGame
+        )
+    }
+
+    /// Replaces the game seed with a newly generated seed. Clients only
+    /// receive the seed in the game setup when joining and there is no
+    /// message to update it afterwards, so only games that haven't started
+    /// and don't have any players can have their seed reset
+    pub fn reset_seed(&mut self) -> Result<u32, ResetSeedError> {
+        if !self.is_before_start() {
+            return Err(ResetSeedError::AlreadyStarted);
+        }
+
+        if !self.players.is_empty() {
+            return Err(ResetSeedError::HasPlayers);
+        }
+
+        self.seed = rand::random();
+
+        debug!("Reset game seed (GID: {}, Seed: {})", self.id, self.seed);
+
+        Ok(self.seed)
+    }
+
     pub fn add_admin_player(&mut self, target_id: PlayerID) {
         // Add the player to the admin list
         self.modify_admin_list(target_id, AdminListOperation::Add);
     }
 
+    /// Checks whether the player with the provided `player_id` is
+    /// in the game
+    pub fn has_player(&self, player_id: PlayerID) -> bool {
+        self.players
+            .iter()
+            .any(|player| player.player.id == player_id)
+    }
+
     pub fn is_host_player(&self, player_id: PlayerID) -> bool {
         self.players
             .first()
@@ -302,48 +555,113 @@ impl Game {
     }
 
     pub fn update_mesh(&mut self, target_id: PlayerID, status: PlayerNetConnectionStatus) {
-        // We only care about a connected state
-        if !matches!(status, PlayerNetConnectionStatus::Connected) {
-            return;
-        }
-
         // Obtain the target player
-        let target_slot = match self
+        let Some(index) = self
             .players
-            .iter_mut()
-            .find(|slot| slot.player.id == target_id)
-        {
-            Some(value) => value,
-            None => {
-                debug!(
-                    "Unable to find player to update mesh state for (PID: {} GID: {})",
-                    target_id, self.id
+            .iter()
+            .position(|slot| slot.player.id == target_id)
+        else {
+            debug!(
+                "Unable to find player to update mesh state for (PID: {} GID: {})",
+                target_id, self.id
+            );
+            return;
+        };
+
+        match status {
+            PlayerNetConnectionStatus::Connected => {
+                let target = &mut self.players[index];
+                target.mesh_failures = 0;
+
+                // Player is already connected, repeated reports are ignored
+                if target.state == PlayerState::ActiveConnected {
+                    return;
+                }
+
+                let migrating = target.state == PlayerState::ActiveMigrating;
+
+                if !self.set_player_state(index, PlayerState::ActiveConnected) {
+                    return;
+                }
+
+                // Players reconnecting to a migrated host have already joined
+                if migrating {
+                    return;
+                }
+
+                // Notify all players that the player has completely joined
+                self.notify_all(Packet::notify(
+                    game_manager::COMPONENT,
+                    game_manager::PLAYER_JOIN_COMPLETED,
+                    JoinComplete {
+                        game_id: self.id,
+                        player_id: target_id,
+                    },
+                ));
+            }
+            PlayerNetConnectionStatus::EstablishingConnection => {
+                self.set_player_state(index, PlayerState::ActiveConnecting);
+            }
+            PlayerNetConnectionStatus::Disconnected => {
+                let target = &mut self.players[index];
+                target.mesh_failures = target.mesh_failures.saturating_add(1);
+                let failures = target.mesh_failures;
+
+                warn!(
+                    "Player reported as disconnected from mesh (PID: {}, GID: {}, Failures: {})",
+                    target_id, self.id, failures
                 );
-                return;
+
+                // Remove players that repeatedly fail to connect
+                let max_failures = self.game_manager.config().game.max_mesh_failures;
+                if max_failures.is_some_and(|max| failures >= max) {
+                    warn!(
+                        "Removing player after repeated mesh failures (PID: {}, GID: {})",
+                        target_id, self.id
+                    );
+                    self.remove_player(target_id, RemoveReason::PlayerConnectionLost);
+                    return;
+                }
+
+                // Player must reconnect to the mesh
+                self.set_player_state(index, PlayerState::ActiveConnecting);
             }
-        };
+        }
+    }
 
-        // Mark the player as connected and update the state for all users
-        target_slot.state = PlayerState::ActiveConnected;
+    /// Moves the player at `index` into the provided mesh `state` if the
+    /// transition is allowed and notifies all the players of the change
+    ///
+    /// Returns whether the state was changed
+    fn set_player_state(&mut self, index: usize, state: PlayerState) -> bool {
+        let target = &mut self.players[index];
+        if target.state == state {
+            return false;
+        }
+
+        if !target.state.can_transition_to(state) {
+            warn!(
+                "Rejected invalid player state transition (PID: {}, GID: {}, From: {:?}, To: {:?})",
+                target.player.id, self.id, target.state, state
+            );
+            return false;
+        }
+
+        target.state = state;
+        let player_id = target.player.id;
+
+        self.last_activity = Instant::now();
         self.notify_all(Packet::notify(
             game_manager::COMPONENT,
             game_manager::GAME_PLAYER_STATE_CHANGE,
             PlayerStateChange {
                 gid: self.id,
-                pid: target_id,
-                state: PlayerState::ActiveConnected,
+                pid: player_id,
+                state,
             },
         ));
 
-        // Notify all players that the player has completely joined
-        self.notify_all(Packet::notify(
-            game_manager::COMPONENT,
-            game_manager::PLAYER_JOIN_COMPLETED,
-            JoinComplete {
-                game_id: self.id,
-                player_id: target_id,
-            },
-        ));
+        true
     }
 
     pub fn remove_player(&mut self, id: u32, reason: RemoveReason) {
@@ -361,14 +679,15 @@ impl Game {
             None => return,
         };
 
-        // Remove the tunnel
-        self.tunnel_service.dissociate_pool(self.id, index as u8);
-        self.udp_tunnel_service
-            .dissociate_pool(self.id, index as u8);
-
         // Remove the player
         let player = self.players.remove(index);
 
+        // Remove the tunnel
+        self.tunnel_service
+            .dissociate_pool(self.id, player.slot as u8);
+        self.udp_tunnel_service
+            .dissociate_pool(self.id, player.slot as u8);
+
         // Clear current game of this player
         player.try_clear_game();
 
@@ -382,6 +701,12 @@ impl Game {
             player.player.id, self.id
         );
 
+        self.log_event(GameEvent::PlayerLeft {
+            player: player.player.id,
+            slot: player.slot,
+            reason,
+        });
+
         drop(player);
 
         // If the player was in the host slot attempt migration
@@ -395,9 +720,50 @@ impl Game {
         }
     }
 
+    /// Kicks the player with the provided `player_id` from the game on
+    /// behalf of a server admin, host migration is handled the same as
+    /// if the player had left
+    ///
+    /// Returns whether the player was present in the game
+    pub fn kick_player(&mut self, player_id: PlayerID) -> bool {
+        if !self.has_player(player_id) {
+            return false;
+        }
+
+        debug!(
+            "Admin kicking player from game (PID: {}, GID: {})",
+            player_id, self.id
+        );
+
+        self.remove_player(player_id, RemoveReason::PlayerKicked);
+        true
+    }
+
+    /// Checks whether the game hasn't started and hasn't had any activity
+    /// within the provided `timeout`
+    pub fn is_pregame_idle(&self, timeout: Duration) -> bool {
+        self.is_before_start() && self.last_activity.elapsed() >= timeout
+    }
+
+    /// Stops the game removing all of its players, the host is removed
+    /// last to prevent host migration
+    pub fn stop_with_players(&mut self) {
+        while let Some(player_id) = self.players.last().map(|player| player.player.id) {
+            self.remove_player(player_id, RemoveReason::GameDestroyed);
+        }
+
+        self.stop();
+    }
+
     fn stop(&mut self) {
+        // Game has already been stopped
+        if let GameState::Destructing = self.state {
+            return;
+        }
+
         // Mark the game as stopping
         self.state = GameState::Destructing;
+        self.log_event(GameEvent::Stopped);
 
         if !self.players.is_empty() {
             warn!("Game {} was stopped with players still present", self.id);
@@ -417,7 +783,7 @@ impl Game {
         }
 
         // Handle full game
-        if self.players.len() >= Self::MAX_PLAYERS {
+        if self.players.len() >= self.max_players {
             return GameJoinableState::Full;
         }
 
@@ -452,6 +818,47 @@ impl Game {
             players,
             total_players,
             created_at: self.created_at,
+            seed: self.seed,
+        }
+    }
+
+    /// Takes a detailed snapshot of the full game state including the
+    /// mesh and connection details of each player
+    pub fn debug_snapshot(&self) -> GameDebugSnapshot {
+        let players = self
+            .players
+            .iter()
+            .map(|player| GamePlayerDebugSnapshot {
+                slot: player.slot,
+                player_id: player.player.id,
+                display_name: Box::from(player.player.display_name.as_ref()),
+                state: player.state,
+                mesh_failures: player.mesh_failures,
+                session_connected: player.link.strong_count() > 0,
+                has_network_address: player.has_network_address(),
+                net: player.net.clone(),
+            })
+            .collect();
+
+        GameDebugSnapshot {
+            id: self.id,
+            state: self.state,
+            setting: self.settings.bits(),
+            setting_flags: format!("{:?}", self.settings),
+            attributes: self.attributes.clone(),
+            created_at: self.created_at,
+            seed: self.seed,
+            idle_seconds: self.last_activity.elapsed().as_secs(),
+            players,
+        }
+    }
+
+    pub fn summary(&self) -> GameSummary {
+        GameSummary {
+            id: self.id,
+            state: self.state,
+            total_players: self.players.len(),
+            joinable: matches!(self.joinable_state(None), GameJoinableState::Joinable),
         }
     }
 
@@ -466,8 +873,34 @@ impl Game {
             .for_each(|value| value.notify(packet.clone()));
     }
 
-    pub fn set_state(&mut self, state: GameState) {
+    /// Sets the state of the game if the transition from the current
+    /// state is allowed, used for client requested state changes
+    pub fn set_state(&mut self, state: GameState) -> Result<(), InvalidStateTransition> {
+        if !self.state.can_transition_to(state) {
+            warn!(
+                "Rejected invalid game state transition (GID: {}, From: {:?}, To: {:?})",
+                self.id, self.state, state
+            );
+
+            return Err(InvalidStateTransition {
+                from: self.state,
+                to: state,
+            });
+        }
+
+        self.update_state(state);
+        Ok(())
+    }
+
+    /// Updates the game state and notifies the players
+    fn update_state(&mut self, state: GameState) {
+        self.log_event(GameEvent::StateChanged {
+            from: self.state,
+            to: state,
+        });
+
         self.state = state;
+        self.last_activity = Instant::now();
 
         debug!("Updated game state (Value: {:?})", &state);
 
@@ -480,6 +913,7 @@ impl Game {
 
     pub fn set_settings(&mut self, settings: GameSettings) {
         self.settings = settings;
+        self.last_activity = Instant::now();
 
         debug!("Updated game setting (Value: {:?})", &settings);
 
@@ -493,7 +927,15 @@ impl Game {
         ));
     }
 
-    pub fn set_attributes(&mut self, attributes: AttrMap) {
+    /// Merges the `attributes` into the game attributes, rejecting the
+    /// attributes if they exceed the configured attribute limits
+    pub fn set_attributes(&mut self, attributes: AttrMap) -> Result<(), AttributeLimitExceeded> {
+        check_attribute_limits(
+            &self.game_manager.config().game,
+            &self.attributes,
+            &attributes,
+        )?;
+
         let packet = Packet::notify(
             game_manager::COMPONENT,
             game_manager::GAME_ATTRIB_CHANGE,
@@ -504,10 +946,12 @@ impl Game {
         );
 
         self.attributes.insert_presorted(attributes.into_inner());
+        self.last_activity = Instant::now();
 
         debug!("Updated game attributes");
 
         self.notify_all(packet);
+        Ok(())
     }
 
     /// Creates a subscription between all the users and the the target player
@@ -519,8 +963,18 @@ impl Game {
             .iter()
             .filter(|other| other.player.id != target.player.id)
             .for_each(|other| {
-                target.try_subscribe(other.player.id, other.notify_handle.clone());
-                other.try_subscribe(target.player.id, target.notify_handle.clone());
+                if !target.try_subscribe(other.player.id, other.notify_handle.clone()) {
+                    warn!(
+                        "Player {} will not receive updates for player {} in game {}",
+                        target.player.id, other.player.id, self.id
+                    );
+                }
+                if !other.try_subscribe(target.player.id, target.notify_handle.clone()) {
+                    warn!(
+                        "Player {} will not receive updates for player {} in game {}",
+                        other.player.id, target.player.id, self.id
+                    );
+                }
             });
     }
 
@@ -587,18 +1041,52 @@ impl Game {
     /// Attempts to migrate the host of this game if there are still players
     /// left in the game.
     fn try_migrate_host(&mut self) {
-        // TODO: With more than one player this fails
+        if self.players.is_empty() {
+            return;
+        }
 
-        // Obtain the new host player
-        let host_id = match self.players.first().map(|player| player.player.id) {
-            Some(value) => value,
-            None => return,
+        // End the game instead when host migration is disabled
+        if !self.game_manager.config().game.host_migration {
+            debug!(
+                "Host left with host migration disabled, stopping game (GID: {})",
+                self.id
+            );
+            self.stop_with_players();
+            return;
+        }
+
+        // Players without a network address can't be connected to so only players
+        // with an address are considered, preferring the player with the least
+        // restrictive NAT type and then the earliest slot
+        let Some(index) = self
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(_, player)| player.has_network_address())
+            .min_by_key(|(_, player)| player.net.qos.natt)
+            .map(|(index, _)| index)
+        else {
+            // End the game as none of the remaining players can be connected to
+            warn!(
+                "No players with a network address available to host, stopping game (GID: {})",
+                self.id
+            );
+            self.stop_with_players();
+            return;
         };
 
+        // Move the new host into the host slot
+        self.players[..=index].rotate_right(1);
+
+        // Obtain the new host player
+        let host_id = self.players[0].player.id;
+        let host_slot = self.players[0].slot;
+        let previous_state = self.state;
+
         debug!("Starting host migration (GID: {})", self.id);
 
         // Start host migration
-        self.set_state(GameState::Migrating);
+        self.update_state(GameState::Migrating);
         self.notify_all(Packet::notify(
             game_manager::COMPONENT,
             game_manager::HOST_MIGRATION_START,
@@ -606,12 +1094,20 @@ impl Game {
                 game_id: self.id,
                 host_id,
                 pmig: 2,
-                slot: 0,
+                slot: host_slot as u8,
             },
         ));
 
-        // Finished host migration
-        self.set_state(GameState::InGame);
+        // Remaining players must connect to the new host
+        for index in 1..self.players.len() {
+            self.set_player_state(index, PlayerState::ActiveMigrating);
+        }
+
+        // The new host takes over as a game admin
+        self.modify_admin_list(host_id, AdminListOperation::Add);
+
+        // Finished host migration, returning to the state before migrating
+        self.update_state(previous_state);
         self.notify_all(Packet::notify(
             game_manager::COMPONENT,
             game_manager::HOST_MIGRATION_FINISHED,
@@ -619,6 +1115,7 @@ impl Game {
         ));
 
         debug!("Finished host migration (GID: {})", self.id);
+        self.log_event(GameEvent::HostMigrated { player: host_id });
     }
 }
 
@@ -627,3 +1124,686 @@ impl Drop for Game {
         debug!("Game is stopped (GID: {})", self.id);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        check_attribute_limits, AttrMap, AttributeLimitExceeded, Game, GameJoinableState,
+        GamePlayer, ResetSeedError,
+    };
+    use crate::{
+        config::{GameConfig, RuntimeConfig},
+        services::{tunnel::TunnelService, udp_tunnel::UdpTunnelService},
+        session::{
+            data::NetData,
+            models::{
+                game_manager::{
+                    DatalessContext, GameSettings, GameSetupContext, GameState,
+                    PlayerNetConnectionStatus, PlayerState, RemoveReason,
+                },
+                IpPairAddress, NatType, NetworkAddress, PairAddress,
+            },
+            packet::Packet,
+            test_util::{create_detached_game_player, test_game_manager, test_sessions},
+        },
+        utils::components::game_manager,
+    };
+    use chrono::Utc;
+    use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+    use tdf::{GroupSlice, TdfDeserialize, TdfDeserializer, TdfMap, TdfType};
+    use tokio::{sync::mpsc, time::timeout};
+
+    /// Creates a new empty game
+    fn create_game(config: Arc<RuntimeConfig>) -> Game {
+        Game::new(
+            1,
+            TdfMap::default(),
+            GameSettings::NONE,
+            Utc::now(),
+            test_game_manager(config),
+            Arc::new(TunnelService::default()),
+            Arc::new(UdpTunnelService::new(test_sessions())),
+        )
+    }
+
+    /// Adds players to the game until its full, providing the
+    /// receiver for each player
+    fn fill_game(game: &mut Game, config: &RuntimeConfig) -> Vec<mpsc::UnboundedReceiver<Packet>> {
+        (0..game.max_players as u32)
+            .map(|id| {
+                let (player, rx) = create_detached_game_player(id + 1);
+                game.add_player(
+                    player,
+                    GameSetupContext::Dataless {
+                        context: DatalessContext::JoinGameSetup,
+                    },
+                    config,
+                );
+                rx
+            })
+            .collect()
+    }
+
+    /// Waits for the `rx` to receive a game state change, notifications
+    /// are sent in the background so they may not have arrived yet
+    async fn received_state_change(rx: &mut mpsc::UnboundedReceiver<Packet>) -> bool {
+        received_packet(rx, game_manager::GAME_STATE_CHANGE).await
+    }
+
+    /// Waits for the `rx` to receive a game manager notification with
+    /// the provided `command`
+    async fn received_packet(rx: &mut mpsc::UnboundedReceiver<Packet>, command: u16) -> bool {
+        next_packet(rx, command).await.is_some()
+    }
+
+    /// Waits for the next game manager notification with the provided
+    /// `command`, skipping any other notifications before it
+    async fn next_packet(rx: &mut mpsc::UnboundedReceiver<Packet>, command: u16) -> Option<Packet> {
+        timeout(Duration::from_secs(5), async {
+            while let Some(packet) = rx.recv().await {
+                if packet.frame.component == game_manager::COMPONENT
+                    && packet.frame.command == command
+                {
+                    return Some(packet);
+                }
+            }
+            None
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Creates a new game player with a network address
+    fn create_player_with_address(id: u32) -> (GamePlayer, mpsc::UnboundedReceiver<Packet>) {
+        let (mut player, rx) = create_detached_game_player(id);
+        let address = PairAddress {
+            addr: Ipv4Addr::new(10, 0, 0, id as u8),
+            port: 3659,
+        };
+        player.net = Arc::new(NetData {
+            addr: NetworkAddress::AddressPair(IpPairAddress {
+                external: address.clone(),
+                internal: address,
+            }),
+            ..Default::default()
+        });
+        (player, rx)
+    }
+
+    /// Creates a new game player with a network address behind the
+    /// provided `natt` NAT type
+    fn create_player_with_nat(
+        id: u32,
+        natt: NatType,
+    ) -> (GamePlayer, mpsc::UnboundedReceiver<Packet>) {
+        let (mut player, rx) = create_player_with_address(id);
+        let mut net = (*player.net).clone();
+        net.qos.natt = natt;
+        player.net = Arc::new(net);
+        (player, rx)
+    }
+
+    /// Tests that host migration prefers the player with the least
+    /// restrictive NAT type over the next player in slot order
+    #[tokio::test]
+    async fn test_migration_prefers_open_nat() {
+        let config = RuntimeConfig::default();
+        let mut game = create_game(Arc::new(RuntimeConfig::default()));
+
+        let players = [
+            create_player_with_nat(1, NatType::Open),
+            create_player_with_nat(2, NatType::Strict),
+            create_player_with_nat(3, NatType::Open),
+            create_player_with_nat(4, NatType::Moderate),
+        ];
+        for (player, _) in players {
+            game.add_player(
+                player,
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+                &config,
+            );
+        }
+
+        // Host leaves the game
+        game.remove_player(1, RemoveReason::PlayerLeft);
+
+        let player_ids: Vec<u32> = game.players.iter().map(|player| player.player.id).collect();
+        assert_eq!(player_ids, [3, 2, 4]);
+    }
+
+    /// Tests that kicking the host player triggers host migration and
+    /// that kicking a player not in the game is reported
+    #[tokio::test]
+    async fn test_kick_host_migrates() {
+        let config = RuntimeConfig::default();
+        let mut game = create_game(Arc::new(RuntimeConfig::default()));
+
+        let players = [
+            create_player_with_nat(1, NatType::Open),
+            create_player_with_nat(2, NatType::Strict),
+            create_player_with_nat(3, NatType::Open),
+        ];
+        for (player, _) in players {
+            game.add_player(
+                player,
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+                &config,
+            );
+        }
+
+        assert!(!game.kick_player(4));
+
+        // Host is kicked from the game
+        assert!(game.kick_player(1));
+
+        let player_ids: Vec<u32> = game.players.iter().map(|player| player.player.id).collect();
+        assert_eq!(player_ids, [3, 2]);
+        assert_ne!(game.state, GameState::Destructing);
+    }
+
+    /// Tests that the host leaving a four player game migrates the host to
+    /// the next player, notifying the remaining players of the new host
+    #[tokio::test]
+    async fn test_migration_four_players() {
+        let config = RuntimeConfig::default();
+        let mut game = create_game(Arc::new(RuntimeConfig::default()));
+
+        let mut receivers = Vec::new();
+        for id in 1..=4 {
+            let (player, rx) = create_player_with_address(id);
+            game.add_player(
+                player,
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+                &config,
+            );
+            receivers.push(rx);
+        }
+        game.state = GameState::InGame;
+        for index in 0..game.players.len() {
+            game.players[index].state = PlayerState::ActiveConnected;
+        }
+
+        // Host leaves the game
+        game.remove_player(1, RemoveReason::PlayerLeft);
+
+        let player_ids: Vec<u32> = game.players.iter().map(|player| player.player.id).collect();
+        assert_eq!(player_ids, [2, 3, 4]);
+        assert_eq!(game.state, GameState::InGame);
+
+        // New host keeps its connection while the others reconnect to it
+        let states: Vec<PlayerState> = game.players.iter().map(|player| player.state).collect();
+        assert_eq!(
+            states,
+            [
+                PlayerState::ActiveConnected,
+                PlayerState::ActiveMigrating,
+                PlayerState::ActiveMigrating
+            ]
+        );
+
+        for rx in &mut receivers[1..] {
+            let packet = next_packet(rx, game_manager::HOST_MIGRATION_START)
+                .await
+                .expect("Missing host migration start");
+            let mut r = TdfDeserializer::new(&packet.contents);
+            assert_eq!(r.tag::<u32>(b"HOST").unwrap(), 2);
+            // New host keeps the slot it joined into
+            assert_eq!(r.tag::<u8>(b"SLOT").unwrap(), 1);
+
+            let packet = next_packet(rx, game_manager::ADMIN_LIST_CHANGE)
+                .await
+                .expect("Missing admin list change");
+            let mut r = TdfDeserializer::new(&packet.contents);
+            assert_eq!(r.tag::<u32>(b"ALST").unwrap(), 2);
+
+            assert!(received_packet(rx, game_manager::HOST_MIGRATION_FINISHED).await);
+        }
+
+        // Reconnected players don't join the game again
+        game.update_mesh(3, PlayerNetConnectionStatus::Connected);
+        assert_eq!(game.players[1].state, PlayerState::ActiveConnected);
+    }
+
+    /// Reads the slot of the topology host from a game setup `packet`
+    fn setup_host_slot(packet: &Packet) -> (u32, u8) {
+        let mut r = TdfDeserializer::new(&packet.contents);
+        r.until_tag(b"GAME", TdfType::Group).unwrap();
+        let game = GroupSlice::deserialize(&mut r).unwrap();
+        let mut r = TdfDeserializer::new(game.data);
+        r.until_tag(b"THST", TdfType::Group).unwrap();
+        let host = GroupSlice::deserialize(&mut r).unwrap();
+        let mut r = TdfDeserializer::new(host.data);
+        (r.tag(b"HPID").unwrap(), r.tag(b"HSLT").unwrap())
+    }
+
+    /// Tests that the host slot sent to players is the slot of the migrated
+    /// host rather than the slot freed by the previous host
+    #[tokio::test]
+    async fn test_migration_host_slot() {
+        let config = RuntimeConfig::default();
+        let mut game = create_game(Arc::new(RuntimeConfig::default()));
+
+        let mut receivers = Vec::new();
+        for id in 1..=2 {
+            let (player, rx) = create_player_with_address(id);
+            game.add_player(
+                player,
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+                &config,
+            );
+            receivers.push(rx);
+        }
+
+        // Host leaves and the player in slot 1 becomes the host
+        game.remove_player(1, RemoveReason::PlayerLeft);
+
+        let packet = next_packet(&mut receivers[1], game_manager::HOST_MIGRATION_START)
+            .await
+            .expect("Missing host migration start");
+        let mut r = TdfDeserializer::new(&packet.contents);
+        assert_eq!(r.tag::<u32>(b"HOST").unwrap(), 2);
+        assert_eq!(r.tag::<u8>(b"SLOT").unwrap(), 1);
+
+        // Joining player takes the freed slot without becoming the host
+        let (player, mut rx) = create_player_with_address(3);
+        game.add_player(
+            player,
+            GameSetupContext::Dataless {
+                context: DatalessContext::JoinGameSetup,
+            },
+            &config,
+        );
+        assert_eq!(game.players[1].slot, 0);
+
+        let packet = next_packet(&mut rx, game_manager::GAME_SETUP)
+            .await
+            .expect("Missing game setup");
+        assert_eq!(setup_host_slot(&packet), (2, 1));
+    }
+
+    /// Tests that host migration skips players without a network address
+    #[tokio::test]
+    async fn test_migration_skips_unset_network() {
+        let config = RuntimeConfig::default();
+        let mut game = create_game(Arc::new(RuntimeConfig::default()));
+
+        let players = [
+            create_player_with_address(1),
+            create_detached_game_player(2),
+            create_player_with_address(3),
+        ];
+        for (player, _) in players {
+            game.add_player(
+                player,
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+                &config,
+            );
+        }
+
+        assert!(!game.snapshot(false, true).players.unwrap()[1].has_network_address);
+
+        // Host leaves the game
+        game.remove_player(1, RemoveReason::PlayerLeft);
+
+        let player_ids: Vec<u32> = game.players.iter().map(|player| player.player.id).collect();
+        assert_eq!(player_ids, [3, 2]);
+    }
+
+    /// Tests that the game is stopped when the host leaves and none of the
+    /// remaining players have a network address to host with
+    #[tokio::test]
+    async fn test_migration_without_network_stops() {
+        let config = RuntimeConfig::default();
+        let mut game = create_game(Arc::new(RuntimeConfig::default()));
+
+        let players = [
+            create_player_with_address(1),
+            create_detached_game_player(2),
+            create_detached_game_player(3),
+        ];
+        let mut receivers = Vec::new();
+        for (player, rx) in players {
+            game.add_player(
+                player,
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+                &config,
+            );
+            receivers.push(rx);
+        }
+
+        // Host leaves the game
+        game.remove_player(1, RemoveReason::PlayerLeft);
+
+        assert_eq!(game.state, GameState::Destructing);
+        assert!(game.players.is_empty());
+
+        // Remaining players are told they were removed
+        for rx in &mut receivers[1..] {
+            assert!(received_packet(rx, game_manager::PLAYER_REMOVED).await);
+        }
+    }
+
+    /// Tests that the game is stopped when the host leaves with
+    /// host migration disabled
+    #[tokio::test]
+    async fn test_host_leave_without_migration() {
+        let config = Arc::new(RuntimeConfig {
+            game: GameConfig {
+                host_migration: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let mut game = create_game(config.clone());
+
+        let mut receivers = Vec::new();
+        for id in 1..=3 {
+            let (player, rx) = create_player_with_address(id);
+            game.add_player(
+                player,
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+                &config,
+            );
+            receivers.push(rx);
+        }
+        game.state = GameState::InGame;
+
+        // Host leaves the game
+        game.remove_player(1, RemoveReason::PlayerLeft);
+
+        assert_eq!(game.state, GameState::Destructing);
+        assert!(game.players.is_empty());
+
+        // Remaining players are told they were removed
+        for rx in &mut receivers[1..] {
+            assert!(received_packet(rx, game_manager::PLAYER_REMOVED).await);
+        }
+    }
+
+    /// Tests that legal state transitions are applied and that illegal
+    /// transitions are rejected without changing the state
+    #[test]
+    fn test_set_state_transitions() {
+        let mut game = create_game(Arc::new(RuntimeConfig::default()));
+        game.state = GameState::PreGame;
+
+        assert!(game.set_state(GameState::InGame).is_ok());
+        assert_eq!(game.state, GameState::InGame);
+
+        game.state = GameState::Destructing;
+        let Err(err) = game.set_state(GameState::InGame) else {
+            panic!("Expected transition from destructing to be rejected");
+        };
+        assert_eq!(err.from, GameState::Destructing);
+        assert_eq!(err.to, GameState::InGame);
+        assert_eq!(game.state, GameState::Destructing);
+    }
+
+    /// Tests that the seed is included in the snapshot and that resetting
+    /// the seed is only allowed before the game has started and before any
+    /// players have been sent the seed
+    #[tokio::test]
+    async fn test_reset_seed() {
+        let config = Arc::new(RuntimeConfig::default());
+        let mut game = create_game(config.clone());
+        let initial_seed = 0x4cbc8585;
+        game.seed = initial_seed;
+
+        assert_eq!(game.snapshot(false, false).seed, initial_seed);
+
+        let seed = game.reset_seed().expect("Seed should reset before start");
+        assert_ne!(seed, initial_seed);
+        assert_eq!(game.snapshot(false, false).seed, seed);
+
+        // Seed cannot be changed once a player has received it
+        let (player, _rx) = create_detached_game_player(1);
+        game.add_player(
+            player,
+            GameSetupContext::Dataless {
+                context: DatalessContext::CreateGameSetup,
+            },
+            &config,
+        );
+        assert_eq!(game.reset_seed(), Err(ResetSeedError::HasPlayers));
+        assert_eq!(game.seed, seed);
+
+        // Seed cannot be changed once the game has started
+        game.state = GameState::InGame;
+        assert_eq!(game.reset_seed(), Err(ResetSeedError::AlreadyStarted));
+        assert_eq!(game.seed, seed);
+    }
+
+    /// Tests that the game is started when the last player joins
+    /// and auto start is enabled
+    #[tokio::test]
+    async fn test_auto_start_when_full() {
+        let config = Arc::new(RuntimeConfig {
+            game: GameConfig {
+                auto_start_when_full: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let mut game = create_game(config.clone());
+        let mut receivers = fill_game(&mut game, &config);
+
+        assert_eq!(game.state, GameState::InGame);
+        for rx in &mut receivers {
+            assert!(received_state_change(rx).await);
+        }
+    }
+
+    /// Tests that games that have already progressed or have auto
+    /// start disabled are not started when full
+    #[tokio::test]
+    async fn test_auto_start_skipped() {
+        let config = Arc::new(RuntimeConfig::default());
+        let mut game = create_game(config.clone());
+        fill_game(&mut game, &config);
+        assert_eq!(game.state, GameState::Initializing);
+
+        let config = Arc::new(RuntimeConfig {
+            game: GameConfig {
+                auto_start_when_full: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let mut game = create_game(config.clone());
+        game.state = GameState::PostGame;
+        fill_game(&mut game, &config);
+        assert_eq!(game.state, GameState::PostGame);
+    }
+
+    /// Tests that games use the configured maximum number of players
+    /// to determine when they are full
+    #[tokio::test]
+    async fn test_configured_max_players() {
+        let config = Arc::new(RuntimeConfig {
+            game: GameConfig {
+                max_players: 6,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let mut game = create_game(config.clone());
+        assert_eq!(game.max_players, 6);
+        assert!(matches!(
+            game.joinable_state(None),
+            GameJoinableState::Joinable
+        ));
+
+        let receivers = fill_game(&mut game, &config);
+        assert_eq!(receivers.len(), 6);
+        assert!(matches!(game.joinable_state(None), GameJoinableState::Full));
+
+        // Configured values outside the supported range are limited
+        let config = Arc::new(RuntimeConfig {
+            game: GameConfig {
+                max_players: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert_eq!(create_game(config).max_players, 1);
+    }
+
+    /// Adds the host and a second player to the game, providing the
+    /// receiver for the second player
+    fn create_mesh_game(config: Arc<RuntimeConfig>) -> (Game, mpsc::UnboundedReceiver<Packet>) {
+        let mut game = create_game(config.clone());
+        let mut receivers = Vec::new();
+        for id in 1..=2 {
+            let (player, rx) = create_player_with_address(id);
+            game.add_player(
+                player,
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+                &config,
+            );
+            receivers.push(rx);
+        }
+        (game, receivers.pop().unwrap())
+    }
+
+    /// Tests that the debug snapshot includes the mesh state of each player
+    #[tokio::test]
+    async fn test_debug_snapshot() {
+        let (mut game, _rx) = create_mesh_game(Arc::new(RuntimeConfig::default()));
+
+        game.update_mesh(2, PlayerNetConnectionStatus::Disconnected);
+
+        let snapshot = game.debug_snapshot();
+        assert_eq!(snapshot.players.len(), 2);
+
+        let host = &snapshot.players[0];
+        assert_eq!(host.slot, 0);
+        assert_eq!(host.state, PlayerState::ActiveConnecting);
+        assert!(host.has_network_address);
+
+        let player = &snapshot.players[1];
+        assert_eq!(player.player_id, 2);
+        assert_eq!(player.state, PlayerState::ActiveConnecting);
+        assert_eq!(player.mesh_failures, 1);
+        assert!(!player.session_connected);
+
+        let value = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(value["players"][1]["state"], "ActiveConnecting");
+        assert_eq!(value["players"][1]["mesh_failures"], 1);
+    }
+
+    /// Tests that a connected mesh status marks the player as connected
+    /// and completes their join
+    #[tokio::test]
+    async fn test_update_mesh_connected() {
+        let (mut game, mut rx) = create_mesh_game(Arc::new(RuntimeConfig::default()));
+
+        game.update_mesh(2, PlayerNetConnectionStatus::Connected);
+
+        assert_eq!(game.players[1].state, PlayerState::ActiveConnected);
+        assert!(received_packet(&mut rx, game_manager::PLAYER_JOIN_COMPLETED).await);
+    }
+
+    /// Tests that a disconnected mesh status returns the player to
+    /// connecting and is cleared once the player connects
+    #[tokio::test]
+    async fn test_update_mesh_disconnected() {
+        let (mut game, _rx) = create_mesh_game(Arc::new(RuntimeConfig::default()));
+
+        game.update_mesh(2, PlayerNetConnectionStatus::Connected);
+        game.update_mesh(2, PlayerNetConnectionStatus::Disconnected);
+
+        let player = &game.players[1];
+        assert_eq!(player.state, PlayerState::ActiveConnecting);
+        assert_eq!(player.mesh_failures, 1);
+
+        game.update_mesh(2, PlayerNetConnectionStatus::Connected);
+
+        let player = &game.players[1];
+        assert_eq!(player.state, PlayerState::ActiveConnected);
+        assert_eq!(player.mesh_failures, 0);
+    }
+
+    /// Tests that a player repeatedly reported as disconnected is
+    /// removed from the game
+    #[tokio::test]
+    async fn test_update_mesh_repeated_failures() {
+        let config = Arc::new(RuntimeConfig {
+            game: GameConfig {
+                max_mesh_failures: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let (mut game, mut rx) = create_mesh_game(config);
+
+        game.update_mesh(2, PlayerNetConnectionStatus::Disconnected);
+        assert_eq!(game.players.len(), 2);
+
+        game.update_mesh(2, PlayerNetConnectionStatus::Disconnected);
+        assert_eq!(game.players.len(), 1);
+        assert!(received_packet(&mut rx, game_manager::PLAYER_REMOVED).await);
+    }
+
+    /// Tests that mesh state transitions are validated
+    #[test]
+    fn test_player_state_transitions() {
+        assert!(PlayerState::ActiveConnecting.can_transition_to(PlayerState::ActiveConnected));
+        assert!(PlayerState::ActiveConnected.can_transition_to(PlayerState::ActiveConnecting));
+        assert!(!PlayerState::Reserved.can_transition_to(PlayerState::ActiveConnected));
+        assert!(!PlayerState::ActiveConnected.can_transition_to(PlayerState::Queued));
+        assert!(!PlayerState::ActiveKickPending.can_transition_to(PlayerState::ActiveConnected));
+    }
+
+    /// Tests that attributes larger than the size limit are rejected and
+    /// that disabled limits allow any attributes
+    #[test]
+    fn test_attribute_size_limit() {
+        let config = GameConfig {
+            max_attributes: Some(2),
+            max_attribute_bytes: Some(16),
+            ..Default::default()
+        };
+
+        let mut attributes = AttrMap::default();
+        attributes.insert("ME3map".to_string(), "map1".to_string());
+        assert_eq!(
+            check_attribute_limits(&config, &AttrMap::default(), &attributes),
+            Ok(())
+        );
+
+        attributes.insert("ME3privacy".to_string(), "A".repeat(7));
+        assert_eq!(
+            check_attribute_limits(&config, &AttrMap::default(), &attributes),
+            Err(AttributeLimitExceeded::Size)
+        );
+
+        let config = GameConfig {
+            max_attributes: None,
+            max_attribute_bytes: None,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_attribute_limits(&config, &AttrMap::default(), &attributes),
+            Ok(())
+        );
+    }
+}
@@ -81,6 +81,36 @@ impl RuleSet {
         Self { rules }
     }
 
+    /// Creates a widened copy of this rule set which only retains the
+    /// DLC rules, used for entries that have been waiting too long to
+    /// find a game matching their preferences
+    pub fn widened(&self) -> Self {
+        let rules = self
+            .rules
+            .iter()
+            .filter(|match_rule| DLC_RULES.iter().any(|rule| rule.key == match_rule.rule.key))
+            .map(|match_rule| MatchRule {
+                rule: match_rule.rule,
+                value: match_rule.value.clone(),
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Creates the attributes for a public game that matches the rules
+    /// in this rule set
+    pub fn attributes(&self) -> AttrMap {
+        let mut attributes = AttrMap::default();
+        attributes.insert(PRIVACY_ATTR.to_string(), "PUBLIC".to_string());
+
+        for match_rule in &self.rules {
+            attributes.insert(match_rule.rule.attr.to_string(), match_rule.value.clone());
+        }
+
+        attributes
+    }
+
     /// Checks if the rules provided in this rule set match the values in
     /// the attributes map.
     pub fn matches(&self, attributes: &AttrMap) -> bool {
@@ -311,4 +341,28 @@ mod test {
 
         assert!(!matches, "Matched player with missing DLC");
     }
+
+    /// Attributes created from a rule set should be matched by the rule set
+    #[test]
+    fn test_rule_set_attributes() {
+        let rules = [
+            ("ME3_gameMapMatchRule", "map2"),
+            ("ME3_gameEnemyTypeRule", "abstain"),
+            ("ME3_gameDifficultyRule", "difficulty0"),
+            ("ME3_rule_dlc2300", "required"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<Vec<(String, String)>>();
+
+        let rule_set = RuleSet::new(rules);
+        let attributes = rule_set.attributes();
+
+        assert_eq!(attributes.len(), 4);
+        assert!(attributes.get("ME3gameEnemyType").is_none());
+
+        let matches = rule_set.matches(&attributes);
+
+        assert!(matches, "Rule set didn't match its own attributes");
+    }
 }
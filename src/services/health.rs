@@ -0,0 +1,192 @@
+//! Service for tracking the health of the server such as the
+//! latency of database queries
+
+use crate::database::{self, DatabaseConnection, DbResult};
+use log::{error, warn};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::time::{interval, MissedTickBehavior};
+
+/// Service tracking the health state of the server
+pub struct HealthService {
+    /// Latency above which the database is considered slow
+    slow_database_threshold: Duration,
+    /// The most recent database latency sample
+    database: Mutex<DatabaseHealth>,
+    /// Whether the most recent leaderboard query failed
+    leaderboard_failed: AtomicBool,
+}
+
+/// Health details for the database
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct DatabaseHealth {
+    /// Latency of the last sampled query in milliseconds, [None] if
+    /// no sample has been taken or the last query failed
+    pub latency_ms: Option<u64>,
+    /// Whether the last sampled query was slower than the threshold
+    /// or failed entirely
+    pub slow: bool,
+}
+
+/// Health details for the leaderboards
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct LeaderboardHealth {
+    /// Whether the most recent leaderboard query failed, leaderboards
+    /// are served empty while they cannot be computed
+    pub failed: bool,
+}
+
+/// Snapshot of the current server health
+#[derive(Debug, Serialize)]
+pub struct HealthSnapshot {
+    /// Health of the database
+    pub database: DatabaseHealth,
+    /// Health of the leaderboards
+    pub leaderboard: LeaderboardHealth,
+}
+
+impl HealthService {
+    /// Creates a new health service
+    ///
+    /// `slow_database_threshold` Latency above which the database is considered slow
+    pub fn new(slow_database_threshold: Duration) -> Self {
+        Self {
+            slow_database_threshold,
+            database: Default::default(),
+            leaderboard_failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Runs the provided `query` measuring how long it takes to complete,
+    /// storing the latency as the current database health
+    pub async fn sample_database<F, Fut>(&self, query: F)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = DbResult<()>>,
+    {
+        let start = Instant::now();
+        let result = query().await;
+        let elapsed = start.elapsed();
+
+        let health = match result {
+            Ok(()) => DatabaseHealth {
+                latency_ms: Some(elapsed.as_millis() as u64),
+                slow: elapsed > self.slow_database_threshold,
+            },
+            Err(err) => {
+                warn!("Database health check query failed: {}", err);
+                DatabaseHealth {
+                    latency_ms: None,
+                    slow: true,
+                }
+            }
+        };
+
+        if health.slow && health.latency_ms.is_some() {
+            warn!(
+                "Database is responding slowly ({}ms), check disk health",
+                elapsed.as_millis()
+            );
+        }
+
+        *self.database.lock() = health;
+    }
+
+    /// Records the outcome of computing a leaderboard, failures are logged
+    /// and reported until the next successful computation
+    pub fn record_leaderboard<T>(&self, result: &DbResult<T>) {
+        if let Err(err) = result {
+            error!("Failed to compute leaderboard: {}", err);
+        }
+
+        self.leaderboard_failed
+            .store(result.is_err(), Ordering::Release);
+    }
+
+    /// Creates a snapshot of the current health state
+    pub fn snapshot(&self) -> HealthSnapshot {
+        HealthSnapshot {
+            database: *self.database.lock(),
+            leaderboard: LeaderboardHealth {
+                failed: self.leaderboard_failed.load(Ordering::Acquire),
+            },
+        }
+    }
+
+    /// Background task that samples the database latency every `period`
+    pub async fn sample_database_task(self: Arc<Self>, db: DatabaseConnection, period: Duration) {
+        let mut interval = interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            self.sample_database(|| database::ping(&db)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HealthService;
+    use crate::database::DbErr;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    /// Tests that fast, slow and failing queries are reported correctly
+    #[tokio::test]
+    async fn test_sample_database() {
+        let health = HealthService::new(Duration::from_millis(50));
+
+        // No samples taken yet
+        let snapshot = health.snapshot();
+        assert_eq!(snapshot.database.latency_ms, None);
+        assert!(!snapshot.database.slow);
+
+        health.sample_database(|| async { Ok(()) }).await;
+        let snapshot = health.snapshot();
+        assert!(snapshot.database.latency_ms.is_some());
+        assert!(!snapshot.database.slow);
+
+        health
+            .sample_database(|| async {
+                sleep(Duration::from_millis(100)).await;
+                Ok(())
+            })
+            .await;
+        let snapshot = health.snapshot();
+        assert!(snapshot
+            .database
+            .latency_ms
+            .is_some_and(|value| value >= 100));
+        assert!(snapshot.database.slow);
+
+        health
+            .sample_database(|| async { Err(DbErr::Custom("Failed".to_string())) })
+            .await;
+        let snapshot = health.snapshot();
+        assert_eq!(snapshot.database.latency_ms, None);
+        assert!(snapshot.database.slow);
+    }
+
+    /// Tests that leaderboard failures are reported until the
+    /// leaderboard is computed successfully
+    #[test]
+    fn test_record_leaderboard() {
+        let health = HealthService::new(Duration::from_millis(50));
+        assert!(!health.snapshot().leaderboard.failed);
+
+        health.record_leaderboard::<()>(&Err(DbErr::Custom("Failed".to_string())));
+        assert!(health.snapshot().leaderboard.failed);
+
+        health.record_leaderboard(&Ok(()));
+        assert!(!health.snapshot().leaderboard.failed);
+    }
+}
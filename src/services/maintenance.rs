@@ -0,0 +1,114 @@
+//! Background maintenance jobs that keep the database tidy
+
+use crate::{
+    database::{entities::Player, DatabaseConnection, DbResult},
+    services::sessions::Sessions,
+};
+use chrono::{Duration as ChronoDuration, Utc};
+use log::{debug, error, info};
+use sea_orm::{ConnectionTrait, DbBackend, Statement};
+use std::{sync::Arc, time::Duration};
+use tokio::time::{interval_at, sleep, Instant, MissedTickBehavior};
+
+/// Delay between each run of the inactive account pruning
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24 /* 24 hours */);
+/// Delay before checking activity again when a vacuum is deferred
+const VACUUM_RETRY_DELAY: Duration = Duration::from_secs(60 * 10 /* 10 minutes */);
+
+/// Background task that deactivates accounts that haven't logged in within
+/// the last `inactive_days` days. Accounts are only marked as deactivated
+/// and are reactivated when they next log in, no account data is deleted.
+///
+/// The first run happens one interval after startup so a misconfigured
+/// threshold can be noticed before any accounts are changed
+pub async fn prune_inactive_task(db: DatabaseConnection, inactive_days: u32) {
+    let mut interval = interval_at(Instant::now() + PRUNE_INTERVAL, PRUNE_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let before = Utc::now() - ChronoDuration::days(inactive_days as i64);
+
+        match Player::deactivate_inactive(&db, before).await {
+            Ok(0) => {}
+            Ok(count) => info!(
+                "Deactivated {} account(s) inactive for over {} days",
+                count, inactive_days
+            ),
+            Err(err) => error!("Failed to deactivate inactive accounts: {}", err),
+        }
+    }
+}
+
+/// Background task that compacts the database every `interval_hours` hours,
+/// runs are deferred until there are no players connected
+pub async fn vacuum_task(db: DatabaseConnection, sessions: Arc<Sessions>, interval_hours: u32) {
+    let period = Duration::from_secs(interval_hours.max(1) as u64 * 60 * 60);
+
+    loop {
+        sleep(period).await;
+
+        // Wait for a period without any connected players
+        while sessions.count() > 0 {
+            debug!("Deferring database vacuum while players are connected");
+            sleep(VACUUM_RETRY_DELAY).await;
+        }
+
+        if let Err(err) = vacuum(&db).await {
+            error!("Failed to vacuum database: {}", err);
+        }
+    }
+}
+
+/// Rebuilds the database file to reclaim space left by deleted rows
+/// and updates the query planner statistics. Does nothing for non
+/// SQLite databases
+pub async fn vacuum(db: &DatabaseConnection) -> DbResult<()> {
+    if db.get_database_backend() != DbBackend::Sqlite {
+        return Ok(());
+    }
+
+    let before = database_size(db).await?;
+
+    db.execute_unprepared("VACUUM").await?;
+    db.execute_unprepared("PRAGMA optimize").await?;
+
+    let after = database_size(db).await?;
+
+    info!(
+        "Vacuumed database (Before: {} bytes, After: {} bytes)",
+        before, after
+    );
+
+    Ok(())
+}
+
+/// Determines the size in bytes of the SQLite database from its pages
+async fn database_size(db: &DatabaseConnection) -> DbResult<i64> {
+    let result = db
+        .query_one(Statement::from_string(
+            DbBackend::Sqlite,
+            "SELECT page_count * page_size AS size FROM pragma_page_count(), pragma_page_size()",
+        ))
+        .await?;
+
+    match result {
+        Some(result) => result.try_get("", "size"),
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{database_size, vacuum};
+    use crate::database::test_database;
+
+    /// Tests that vacuuming an in-memory database succeeds
+    #[tokio::test]
+    async fn test_vacuum() {
+        let db = test_database().await;
+        assert!(database_size(&db).await.unwrap() > 0);
+        vacuum(&db).await.unwrap();
+    }
+}
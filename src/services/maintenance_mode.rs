@@ -0,0 +1,37 @@
+//! Maintenance mode state, toggled while the server is being maintained
+//! to refuse new sessions and logins
+
+use log::info;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Maintenance mode state, while enabled new sessions and logins are
+/// refused so the server can be safely taken down for maintenance
+pub struct MaintenanceMode {
+    /// Whether maintenance mode is enabled
+    enabled: AtomicBool,
+}
+
+impl MaintenanceMode {
+    /// Creates the maintenance mode state starting as `enabled`
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+        }
+    }
+
+    /// Checks whether maintenance mode is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Enables or disables maintenance mode
+    pub fn set_enabled(&self, enabled: bool) {
+        let previous = self.enabled.swap(enabled, Ordering::AcqRel);
+        if previous != enabled {
+            info!(
+                "Maintenance mode {}",
+                if enabled { "enabled" } else { "disabled" }
+            );
+        }
+    }
+}
@@ -1,6 +1,16 @@
+pub mod account_limit;
 pub mod config;
 pub mod game;
+pub mod health;
+pub mod maintenance;
+pub mod maintenance_mode;
+pub mod public_address;
+pub mod reachability;
 pub mod retriever;
+pub mod rotation;
 pub mod sessions;
+pub mod stats_history;
+pub mod telemetry;
 pub mod tunnel;
+pub mod tunnel_send;
 pub mod udp_tunnel;
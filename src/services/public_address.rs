@@ -0,0 +1,190 @@
+//! Service caching the public address of the server, the address is
+//! periodically resolved again so changes to dynamic addresses are
+//! picked up without restarting the server
+
+use crate::utils::logging::public_address;
+use log::{info, warn};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::lookup_host,
+    time::{interval, MissedTickBehavior},
+};
+
+/// Service storing the resolved public address of the server
+pub struct PublicAddressService {
+    /// Configured external hostname or address of the server, when not
+    /// set the public address is looked up using an online service
+    external_host: Option<String>,
+    /// The last successfully resolved address
+    address: RwLock<Option<CachedAddress>>,
+}
+
+/// Resolved address along with when it was resolved
+#[derive(Clone, Copy)]
+struct CachedAddress {
+    /// The resolved address
+    address: Ipv4Addr,
+    /// When the address was resolved
+    resolved_at: Instant,
+}
+
+/// Details about the cached public address
+#[derive(Debug, Serialize)]
+pub struct PublicAddressCache {
+    /// The cached address
+    pub address: Ipv4Addr,
+    /// Number of seconds since the address was resolved
+    pub age_seconds: u64,
+}
+
+impl PublicAddressService {
+    /// Creates a new public address service
+    ///
+    /// `external_host` The configured external hostname or address
+    pub fn new(external_host: Option<String>) -> Self {
+        Self {
+            external_host: external_host.filter(|value| !value.is_empty()),
+            address: RwLock::new(None),
+        }
+    }
+
+    /// Obtains the cached public address
+    pub fn get(&self) -> Option<Ipv4Addr> {
+        self.address.read().map(|cached| cached.address)
+    }
+
+    /// Obtains the cached public address, resolving the address
+    /// when there isn't one cached
+    pub async fn resolve(&self) -> Option<Ipv4Addr> {
+        if let Some(address) = self.get() {
+            return Some(address);
+        }
+
+        self.refresh().await;
+        self.get()
+    }
+
+    /// Provides details about the cached public address
+    pub fn cache(&self) -> Option<PublicAddressCache> {
+        self.address.read().map(|cached| PublicAddressCache {
+            address: cached.address,
+            age_seconds: cached.resolved_at.elapsed().as_secs(),
+        })
+    }
+
+    /// Clears the cached public address, the address will be
+    /// resolved again on next use
+    pub fn clear(&self) {
+        *self.address.write() = None;
+    }
+
+    /// Resolves the public address again updating the cached address
+    pub async fn refresh(&self) {
+        let address = match &self.external_host {
+            Some(host) => resolve_host(host).await,
+            None => public_address().await,
+        };
+
+        self.update(address);
+    }
+
+    /// Updates the cached address with a newly resolved `address`, failed
+    /// resolutions keep the last known address
+    fn update(&self, address: Option<Ipv4Addr>) {
+        let Some(address) = address else {
+            warn!("Failed to resolve server public address, using last known address");
+            return;
+        };
+
+        let previous = self
+            .address
+            .write()
+            .replace(CachedAddress {
+                address,
+                resolved_at: Instant::now(),
+            })
+            .map(|cached| cached.address);
+
+        match previous {
+            Some(previous) if previous == address => {}
+            Some(previous) => info!("Public address changed from {} to {}", previous, address),
+            None => info!("Resolved public address: {}", address),
+        }
+    }
+
+    /// Background task that resolves the public address every `period`
+    pub async fn refresh_task(self: Arc<Self>, period: Duration) {
+        let mut interval = interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            self.refresh().await;
+        }
+    }
+}
+
+/// Resolves the first IPv4 address for the provided `host`
+async fn resolve_host(host: &str) -> Option<Ipv4Addr> {
+    let mut addresses = lookup_host((host, 0)).await.ok()?;
+    addresses.find_map(|addr| match addr.ip() {
+        IpAddr::V4(addr) => Some(addr),
+        IpAddr::V6(_) => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::PublicAddressService;
+    use std::net::Ipv4Addr;
+
+    /// Tests that a changed resolution updates the cached address and
+    /// failed resolutions keep the last known address
+    #[test]
+    fn test_update_address() {
+        let service = PublicAddressService::new(None);
+        assert_eq!(service.get(), None);
+
+        service.update(Some(Ipv4Addr::new(1, 1, 1, 1)));
+        assert_eq!(service.get(), Some(Ipv4Addr::new(1, 1, 1, 1)));
+
+        service.update(Some(Ipv4Addr::new(2, 2, 2, 2)));
+        assert_eq!(service.get(), Some(Ipv4Addr::new(2, 2, 2, 2)));
+
+        service.update(None);
+        assert_eq!(service.get(), Some(Ipv4Addr::new(2, 2, 2, 2)));
+    }
+
+    /// Tests that the configured external host is resolved
+    #[tokio::test]
+    async fn test_refresh_external_host() {
+        let service = PublicAddressService::new(Some("127.0.0.1".to_string()));
+        service.refresh().await;
+        assert_eq!(service.get(), Some(Ipv4Addr::LOCALHOST));
+    }
+
+    /// Tests that clearing the cache causes the next lookup to resolve
+    /// the address again
+    #[tokio::test]
+    async fn test_clear_cache() {
+        let service = PublicAddressService::new(Some("127.0.0.1".to_string()));
+        service.refresh().await;
+        assert!(service.cache().is_some());
+
+        service.clear();
+        assert!(service.cache().is_none());
+        assert_eq!(service.get(), None);
+
+        assert_eq!(service.resolve().await, Some(Ipv4Addr::LOCALHOST));
+        assert_eq!(
+            service.cache().map(|cache| cache.address),
+            Some(Ipv4Addr::LOCALHOST)
+        );
+    }
+}
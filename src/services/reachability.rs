@@ -0,0 +1,231 @@
+//! Service for checking whether the server ports can be reached through the
+//! public address of the server, used to diagnose port forwarding issues
+
+use super::{sessions::Sessions, udp_tunnel::UdpTunnelService};
+use crate::session::models::Port;
+use futures_util::future::{join_all, BoxFuture};
+use pocket_relay_udp_tunnel::{deserialize_message, serialize_message, TunnelMessage};
+use serde::Serialize;
+use std::{
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::Arc,
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    net::{TcpStream, UdpSocket},
+    time::timeout,
+};
+
+/// Time to wait for a port to respond before its considered unreachable
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Protocol a port is served over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortProtocol {
+    Tcp,
+    Udp,
+}
+
+/// Port that should be checked for reachability
+pub struct PortCheck {
+    /// Name of the service using the port
+    pub name: &'static str,
+    /// Protocol the port is served over
+    pub protocol: PortProtocol,
+    /// The port number
+    pub port: Port,
+}
+
+/// Outcome of checking a [PortCheck]
+#[derive(Debug, Serialize)]
+pub struct PortCheckResult {
+    /// Name of the service using the port
+    pub name: &'static str,
+    /// Protocol the port is served over
+    pub protocol: PortProtocol,
+    /// The port number
+    pub port: Port,
+    /// Whether the port could be reached through the public address
+    pub reachable: bool,
+}
+
+/// Report of the reachability of the checked ports
+#[derive(Debug, Serialize)]
+pub struct PortsReport {
+    /// The public address the ports were checked against
+    pub public_address: Ipv4Addr,
+    /// Results for each of the checked ports
+    pub ports: Vec<PortCheckResult>,
+}
+
+/// Errors that can occur while checking reachability
+#[derive(Debug, Error)]
+pub enum ReachabilityError {
+    /// The public address of the server could not be determined
+    #[error("Unable to determine the server public address")]
+    UnknownPublicAddress,
+}
+
+/// Checker that determines whether an address can be reached
+pub trait ReachabilityChecker {
+    /// Checks whether the provided `addr` can be reached using `protocol`
+    fn check(&self, addr: SocketAddr, protocol: PortProtocol) -> BoxFuture<'_, bool>;
+}
+
+/// Checks each of the provided `checks` against the `public_address`
+/// using the provided `checker`
+///
+/// `checker`        The checker to use
+/// `public_address` The public address of the server if known
+/// `checks`         The ports to check
+pub async fn check_ports<C>(
+    checker: &C,
+    public_address: Option<Ipv4Addr>,
+    checks: &[PortCheck],
+) -> Result<PortsReport, ReachabilityError>
+where
+    C: ReachabilityChecker,
+{
+    let public_address = public_address.ok_or(ReachabilityError::UnknownPublicAddress)?;
+
+    let ports = join_all(checks.iter().map(|check| async move {
+        let addr = SocketAddr::V4(SocketAddrV4::new(public_address, check.port));
+        let reachable = checker.check(addr, check.protocol).await;
+
+        PortCheckResult {
+            name: check.name,
+            protocol: check.protocol,
+            port: check.port,
+            reachable,
+        }
+    }))
+    .await;
+
+    Ok(PortsReport {
+        public_address,
+        ports,
+    })
+}
+
+/// [ReachabilityChecker] that connects to the ports over the network
+///
+/// TCP ports are checked by opening a connection, the UDP tunnel is checked
+/// by initiating a tunnel and waiting for the server to respond
+pub struct NetworkReachabilityChecker {
+    /// Sessions for creating the association token used by the tunnel check
+    pub sessions: Arc<Sessions>,
+    /// Tunnel service to remove the tunnel created by the check
+    pub udp_tunnel_service: Arc<UdpTunnelService>,
+}
+
+impl NetworkReachabilityChecker {
+    /// Checks whether a TCP connection can be established to `addr`
+    async fn check_tcp(addr: SocketAddr) -> bool {
+        matches!(
+            timeout(CHECK_TIMEOUT, TcpStream::connect(addr)).await,
+            Ok(Ok(_))
+        )
+    }
+
+    /// Checks whether the UDP tunnel at `addr` responds to a tunnel initiate
+    async fn check_udp_tunnel(&self, addr: SocketAddr) -> bool {
+        let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await {
+            Ok(value) => value,
+            Err(_) => return false,
+        };
+
+        let association_token = self.sessions.create_assoc_token();
+        let buffer = serialize_message(u32::MAX, &TunnelMessage::Initiate { association_token });
+
+        if socket.send_to(&buffer, addr).await.is_err() {
+            return false;
+        }
+
+        let mut buffer = [0u8; 64];
+        let size = match timeout(CHECK_TIMEOUT, socket.recv(&mut buffer)).await {
+            Ok(Ok(value)) => value,
+            _ => return false,
+        };
+
+        match deserialize_message(&buffer[..size]) {
+            Ok(packet) => match packet.message {
+                TunnelMessage::Initiated { tunnel_id } => {
+                    // Tunnel is no longer needed
+                    self.udp_tunnel_service.remove_tunnel(tunnel_id);
+                    true
+                }
+                _ => false,
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+impl ReachabilityChecker for NetworkReachabilityChecker {
+    fn check(&self, addr: SocketAddr, protocol: PortProtocol) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            match protocol {
+                PortProtocol::Tcp => Self::check_tcp(addr).await,
+                PortProtocol::Udp => self.check_udp_tunnel(addr).await,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_ports, PortCheck, PortProtocol, ReachabilityChecker, ReachabilityError};
+    use futures_util::future::BoxFuture;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    /// Checker that only considers TCP ports reachable
+    struct StubChecker;
+
+    impl ReachabilityChecker for StubChecker {
+        fn check(&self, _addr: SocketAddr, protocol: PortProtocol) -> BoxFuture<'_, bool> {
+            Box::pin(async move { protocol == PortProtocol::Tcp })
+        }
+    }
+
+    const CHECKS: [PortCheck; 2] = [
+        PortCheck {
+            name: "http",
+            protocol: PortProtocol::Tcp,
+            port: 80,
+        },
+        PortCheck {
+            name: "udp_tunnel",
+            protocol: PortProtocol::Udp,
+            port: 9032,
+        },
+    ];
+
+    /// Tests that each port is reported with its reachability
+    #[tokio::test]
+    async fn test_check_ports() {
+        let public_address = Ipv4Addr::new(1, 2, 3, 4);
+        let report = check_ports(&StubChecker, Some(public_address), &CHECKS)
+            .await
+            .unwrap();
+        let results = &report.ports;
+
+        assert_eq!(report.public_address, public_address);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].name, "http");
+        assert!(results[0].reachable);
+        assert_eq!(results[1].name, "udp_tunnel");
+        assert!(!results[1].reachable);
+    }
+
+    /// Tests that an unknown public address is reported as an error
+    #[tokio::test]
+    async fn test_unknown_public_address() {
+        let result = check_ports(&StubChecker, None, &CHECKS).await;
+        assert!(matches!(
+            result,
+            Err(ReachabilityError::UnknownPublicAddress)
+        ));
+    }
+}
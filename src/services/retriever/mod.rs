@@ -6,7 +6,7 @@ use crate::{
     config::RetrieverConfig,
     session::{
         models::{InstanceDetails, InstanceNet, Port},
-        packet::{FireFrame, FrameType, Packet, PacketCodec, PacketDebug},
+        packet::{decode_packet, FireFrame, FrameType, Packet, PacketCodec},
     },
     utils::components::redirector,
 };
@@ -14,17 +14,24 @@ use blaze_ssl_async::BlazeStream;
 use futures_util::{SinkExt, StreamExt};
 use log::{debug, error, log_enabled};
 use models::InstanceRequest;
-use origin::OriginFlow;
+use origin::{OriginDataError, OriginFlow};
 use reqwest;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
     fmt::Display,
+    net::SocketAddr,
     ops::Add,
+    sync::Arc,
     time::{Duration, SystemTime},
 };
 use tdf::{DecodeError, TdfDeserialize, TdfSerialize};
 use thiserror::Error;
-use tokio::{io, sync::RwLock};
+use tokio::{
+    io,
+    net::lookup_host,
+    sync::{OwnedSemaphorePermit, RwLock, Semaphore},
+    time::timeout,
+};
 use tokio_util::codec::Framed;
 
 mod models;
@@ -36,8 +43,28 @@ pub struct Retriever {
     // Optional official instance if fetching is possible
     instance: RwLock<Option<OfficialInstance>>,
 
+    /// Time to wait for connections to the official server
+    connect_timeout: Duration,
+
+    /// Limit on the number of concurrent sessions to the official server
+    session_limit: Option<Arc<Semaphore>>,
+
     /// Optional service for creating origin flows if enabled
     origin_flow: Option<OriginFlowService>,
+
+    /// Whether retrieving from the official server is enabled
+    enabled: bool,
+}
+
+/// Details about the cached official server instance
+#[derive(Debug, Serialize)]
+pub struct InstanceCache {
+    /// The host address of the official server
+    pub host: String,
+    /// The port of the official server
+    pub port: u16,
+    /// Number of seconds until the instance expires
+    pub expires_in_seconds: u64,
 }
 
 #[derive(Debug, Error)]
@@ -71,7 +98,7 @@ pub enum InstanceError {
     #[error("Failed to lookup server response empty")]
     MissingValue,
     #[error("Failed to connect to server: {0}")]
-    Io(#[from] std::io::Error),
+    Connect(#[from] ConnectError),
     #[error("Failed to retrieve instance: {0}")]
     InstanceRequest(#[from] RetrieverError),
     #[error("Server response missing address")]
@@ -109,12 +136,17 @@ impl OfficialInstance {
     /// The port for the redirector server.
     const REDIRECT_PORT: Port = 42127;
 
-    async fn obtain() -> Result<OfficialInstance, InstanceError> {
+    async fn obtain(
+        connect_timeout: Duration,
+        session_limit: Option<&Arc<Semaphore>>,
+    ) -> Result<OfficialInstance, InstanceError> {
         let host = Self::lookup_host().await?;
         debug!("Completed host lookup: {}", &host);
 
         // Create a session to the redirector server
-        let mut session = OfficialSession::connect(&host, Self::REDIRECT_PORT).await?;
+        let mut session =
+            OfficialSession::connect(&host, Self::REDIRECT_PORT, connect_timeout, session_limit)
+                .await?;
 
         // Request the server instance
         let instance: InstanceDetails = session
@@ -187,10 +219,13 @@ impl OfficialInstance {
     }
 
     /// Creates a stream to the main server and wraps it with a
-    /// session returning that session. Will return None if the
-    /// stream failed.
-    pub async fn session(&self) -> Option<OfficialSession> {
-        OfficialSession::connect(&self.host, self.port).await.ok()
+    /// session returning that session
+    pub async fn session(
+        &self,
+        connect_timeout: Duration,
+        session_limit: Option<&Arc<Semaphore>>,
+    ) -> Result<OfficialSession, ConnectError> {
+        OfficialSession::connect(&self.host, self.port, connect_timeout, session_limit).await
     }
 }
 
@@ -199,9 +234,26 @@ impl Retriever {
     /// ip address of the gosredirector.ea.com host and then creates a
     /// connection to the redirector server and obtains the IP and Port
     /// of the Official server.
-    pub async fn start(config: RetrieverConfig) -> Retriever {
+    ///
+    /// Fails if the configured default origin data file could not be loaded
+    pub async fn start(config: RetrieverConfig) -> Result<Retriever, OriginDataError> {
+        // Load the default data for new origin accounts
+        let default_data = match &config.origin_default_data_file {
+            Some(path) => {
+                let data = origin::load_default_data(path).await?;
+                debug!("Loaded {} default origin player data entries", data.len());
+                data
+            }
+            None => Vec::new(),
+        };
+
+        let connect_timeout = Duration::from_secs(config.connect_timeout_seconds);
+        let session_limit = config
+            .max_concurrent_sessions
+            .map(|max| Arc::new(Semaphore::new(max)));
+
         let instance = if config.enabled {
-            match OfficialInstance::obtain().await {
+            match OfficialInstance::obtain(connect_timeout, session_limit.as_ref()).await {
                 Ok(value) => Some(value),
                 Err(error) => {
                     error!("Failed to setup retriever: {}", error);
@@ -215,15 +267,43 @@ impl Retriever {
         let origin_flow = if config.origin_fetch {
             Some(OriginFlowService {
                 data: config.origin_fetch_data,
+                default_data: Arc::new(default_data),
+                identities: Default::default(),
             })
         } else {
             None
         };
 
-        Retriever {
+        Ok(Retriever {
             instance: RwLock::new(instance),
+            connect_timeout,
+            session_limit,
             origin_flow,
-        }
+            enabled: config.enabled,
+        })
+    }
+
+    /// Provides details about the cached official server instance
+    pub async fn cache(&self) -> Option<InstanceCache> {
+        self.instance
+            .read()
+            .await
+            .as_ref()
+            .map(|instance| InstanceCache {
+                host: instance.host.clone(),
+                port: instance.port,
+                expires_in_seconds: instance
+                    .expiry
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default()
+                    .as_secs(),
+            })
+    }
+
+    /// Clears the cached official server instance, the instance
+    /// will be obtained again on next use
+    pub async fn clear_cache(&self) {
+        *self.instance.write().await = None;
     }
 
     pub async fn origin_flow(&self) -> Result<OriginFlow, GetFlowError> {
@@ -232,23 +312,34 @@ impl Retriever {
             .as_ref()
             .ok_or(GetFlowError::OriginDisabled)?;
 
+        if !self.enabled {
+            return Err(GetFlowError::Unavailable);
+        }
+
         let read_guard = self.instance.read().await;
-        let instance = read_guard.as_ref().ok_or(GetFlowError::Unavailable)?;
-        let is_expired = instance.expiry < SystemTime::now();
+
+        // Missing instances (Cleared or failed to obtain) are obtained again
+        let is_expired = read_guard
+            .as_ref()
+            .is_none_or(|instance| instance.expiry < SystemTime::now());
 
         let guard = if is_expired {
-            // Drop the read instance and guard
-            let _ = instance;
+            // Drop the read guard
             drop(read_guard);
 
             debug!("Current official instance is outdated.. retrieving a new instance");
             let mut write_guard = self.instance.write().await;
 
-            let official = match OfficialInstance::obtain().await {
+            let official = match OfficialInstance::obtain(
+                self.connect_timeout,
+                self.session_limit.as_ref(),
+            )
+            .await
+            {
                 Ok(value) => Some(value),
                 Err(err) => {
                     error!(
-                        "Official server instance expired but failed to obtain new instance: {}",
+                        "Official server instance missing or expired but failed to obtain new instance: {}",
                         err
                     );
                     None
@@ -263,7 +354,13 @@ impl Retriever {
         };
 
         let instance = guard.as_ref().ok_or(GetFlowError::Instance)?;
-        let session = instance.session().await.ok_or(GetFlowError::Session)?;
+        let session = instance
+            .session(self.connect_timeout, self.session_limit.as_ref())
+            .await
+            .map_err(|err| {
+                error!("Failed to connect to official server: {}", err);
+                GetFlowError::Session
+            })?;
 
         Ok(flow.create(session))
     }
@@ -275,6 +372,9 @@ pub struct OfficialSession {
     id: u16,
     /// The underlying SSL / TCP stream connection
     stream: Framed<BlazeStream, PacketCodec>,
+    /// Permit held while the session is open when the number of
+    /// concurrent sessions is limited
+    _permit: Option<OwnedSemaphorePermit>,
 }
 
 /// Error type for retriever errors
@@ -296,14 +396,60 @@ pub enum RetrieverError {
 
 pub type RetrieverResult<T> = Result<T, RetrieverError>;
 
+/// Errors that can occur while connecting to an official server
+#[derive(Debug, Error)]
+pub enum ConnectError {
+    /// The host address could not be resolved
+    #[error("Failed to resolve host {0}: {1}")]
+    Dns(String, io::Error),
+    /// The TCP connection could not be established
+    #[error("Connection to {0} failed: {1}")]
+    Tcp(SocketAddr, io::Error),
+    /// The TCP connection was established but the SSL handshake failed
+    #[error("SSL handshake with {0} failed: {1}")]
+    Handshake(SocketAddr, io::Error),
+    /// The connection didn't complete within the timeout
+    #[error("Timed out connecting to {0}")]
+    Timeout(SocketAddr),
+    /// The maximum number of concurrent sessions remained open for
+    /// the whole connection timeout
+    #[error("Too many concurrent sessions to the official server")]
+    SessionLimit,
+}
+
+impl ConnectError {
+    /// Determines whether the `err` from connecting to `addr` was caused by the
+    /// TCP connection or the SSL handshake based on the kind of error
+    fn from_stream_error(addr: SocketAddr, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::AddrNotAvailable
+            | io::ErrorKind::NetworkUnreachable
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::PermissionDenied => Self::Tcp(addr, err),
+            _ => Self::Handshake(addr, err),
+        }
+    }
+}
+
 impl OfficialSession {
     /// Creates a session with an official server at the provided
-    /// `host` and `port`
-    async fn connect(host: &str, port: Port) -> std::io::Result<OfficialSession> {
-        let stream = BlazeStream::connect((host, port)).await?;
+    /// `host` and `port` failing if not connected within `connect_timeout`.
+    ///
+    /// When a `session_limit` is provided the connection waits for a permit
+    /// which is held until the session is dropped
+    async fn connect(
+        host: &str,
+        port: Port,
+        connect_timeout: Duration,
+        session_limit: Option<&Arc<Semaphore>>,
+    ) -> Result<OfficialSession, ConnectError> {
+        let permit = acquire_session_permit(session_limit, connect_timeout).await?;
+        let stream = connect_stream(host, port, connect_timeout).await?;
         Ok(Self {
             id: 0,
             stream: Framed::new(stream, PacketCodec::default()),
+            _permit: permit,
         })
     }
 
@@ -382,11 +528,7 @@ impl OfficialSession {
             let header = &response.frame;
 
             match &header.ty {
-                FrameType::Response => {
-                    if header.path_matches(request) {
-                        return Ok(response);
-                    }
-                }
+                FrameType::Response if header.path_matches(request) => return Ok(response),
                 FrameType::Error => return Err(RetrieverError::Packet(ErrorPacket(response))),
                 _ => {}
             }
@@ -394,6 +536,46 @@ impl OfficialSession {
     }
 }
 
+/// Waits up to `wait` for a permit from the `session_limit`, no permit
+/// is required when there is no limit
+async fn acquire_session_permit(
+    session_limit: Option<&Arc<Semaphore>>,
+    wait: Duration,
+) -> Result<Option<OwnedSemaphorePermit>, ConnectError> {
+    let Some(session_limit) = session_limit else {
+        return Ok(None);
+    };
+
+    match timeout(wait, session_limit.clone().acquire_owned()).await {
+        Ok(Ok(permit)) => Ok(Some(permit)),
+        // Semaphore is never closed so this is only reached on timeout
+        Ok(Err(_)) | Err(_) => Err(ConnectError::SessionLimit),
+    }
+}
+
+/// Resolves the `host` and connects a [BlazeStream] to the first address
+/// failing if the connection isn't complete within `connect_timeout`
+async fn connect_stream(
+    host: &str,
+    port: Port,
+    connect_timeout: Duration,
+) -> Result<BlazeStream, ConnectError> {
+    let addr = lookup_host((host, port))
+        .await
+        .and_then(|mut addrs| {
+            addrs
+                .next()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No addresses found"))
+        })
+        .map_err(|err| ConnectError::Dns(host.to_string(), err))?;
+
+    match timeout(connect_timeout, BlazeStream::connect(addr)).await {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(err)) => Err(ConnectError::from_stream_error(addr, err)),
+        Err(_) => Err(ConnectError::Timeout(addr)),
+    }
+}
+
 /// Logs the contents of the provided packet to the debug output along with
 /// the header information.
 ///
@@ -405,8 +587,7 @@ fn debug_log_packet(packet: &Packet, action: &str) {
     if !log_enabled!(log::Level::Debug) {
         return;
     }
-    let debug = PacketDebug { packet };
-    debug!("\nOfficial: {}\n{:?}", action, debug);
+    debug!("\nOfficial: {}\n{}", action, decode_packet(packet));
 }
 
 /// Wrapping structure for packets to allow them to be
@@ -473,3 +654,122 @@ struct LookupResponse {
 struct Answer {
     data: String,
 }
+
+#[cfg(test)]
+mod test {
+    use super::{acquire_session_permit, connect_stream, ConnectError, Retriever};
+    use crate::config::RetrieverConfig;
+    use std::{sync::Arc, time::Duration};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::Semaphore,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Tests that unresolvable hosts are reported as DNS errors
+    #[tokio::test]
+    async fn test_connect_dns_error() {
+        let result = connect_stream("host.invalid", 42127, TIMEOUT).await;
+        assert!(matches!(result, Err(ConnectError::Dns(..))));
+    }
+
+    /// Tests that refused connections are reported as TCP errors
+    #[tokio::test]
+    async fn test_connect_refused() {
+        // Obtain a free port then close the listener so nothing is listening
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let result = connect_stream("127.0.0.1", port, TIMEOUT).await;
+        assert!(matches!(result, Err(ConnectError::Tcp(..))));
+    }
+
+    /// Tests that servers responding with an unsupported SSL version
+    /// are reported as handshake errors
+    #[tokio::test]
+    async fn test_connect_handshake_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // Read the client hello then respond with a TLS 1.2 handshake record
+            let mut buffer = [0u8; 64];
+            _ = stream.read(&mut buffer).await;
+            _ = stream
+                .write_all(&[0x16, 0x03, 0x03, 0x00, 0x01, 0x00])
+                .await;
+            std::future::pending::<()>().await;
+        });
+
+        let result = connect_stream("127.0.0.1", port, TIMEOUT).await;
+        assert!(matches!(result, Err(ConnectError::Handshake(..))));
+    }
+
+    /// Tests that servers that never complete the handshake are
+    /// reported as timeouts
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let result = connect_stream("127.0.0.1", port, Duration::from_millis(100)).await;
+        assert!(matches!(result, Err(ConnectError::Timeout(..))));
+    }
+
+    /// Tests that sessions beyond the limit wait for an open session to
+    /// be dropped and are rejected if none is dropped in time
+    #[tokio::test(start_paused = true)]
+    async fn test_session_limit() {
+        let limit = Arc::new(Semaphore::new(1));
+
+        // No permit required without a limit
+        assert!(acquire_session_permit(None, TIMEOUT)
+            .await
+            .unwrap()
+            .is_none());
+
+        let permit = acquire_session_permit(Some(&limit), TIMEOUT).await.unwrap();
+        assert!(permit.is_some());
+
+        // Rejected while the only session remains open
+        let result = acquire_session_permit(Some(&limit), TIMEOUT).await;
+        assert!(matches!(result, Err(ConnectError::SessionLimit)));
+
+        // Waiting session proceeds once the open session is dropped
+        let waiting = tokio::spawn({
+            let limit = limit.clone();
+            async move { acquire_session_permit(Some(&limit), TIMEOUT).await }
+        });
+        tokio::time::sleep(TIMEOUT / 2).await;
+        drop(permit);
+
+        assert!(waiting.await.unwrap().unwrap().is_some());
+    }
+
+    /// Tests that starting fails when the default origin data file
+    /// is invalid rather than continuing without the data
+    #[tokio::test]
+    async fn test_start_invalid_origin_data() {
+        let path = std::env::temp_dir().join(format!("pocket-relay-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"["not", "an", "object"]"#).unwrap();
+
+        let result = Retriever::start(RetrieverConfig {
+            enabled: false,
+            origin_default_data_file: Some(path.clone()),
+            ..Default::default()
+        })
+        .await;
+        _ = std::fs::remove_file(&path);
+
+        assert!(result.is_err());
+    }
+}
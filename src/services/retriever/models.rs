@@ -1,5 +1,5 @@
 use crate::session::models::util::LOCALE_NZ;
-use tdf::{TdfDeserializeOwned, TdfSerialize, TdfType};
+use tdf::{TdfDeserializeOwned, TdfSerialize};
 
 /// Packet encoding for Redirector GetServerInstance packets
 /// this contains basic information about the client session.
@@ -33,18 +33,28 @@ pub struct OriginLoginResponse {
     pub email: String,
     /// The display name of the origin account
     pub display_name: String,
+    /// Persistent persona ID of the origin account, [None] when not
+    /// provided by the official server
+    pub origin_id: Option<i64>,
 }
 
 impl TdfDeserializeOwned for OriginLoginResponse {
     fn deserialize_owned(r: &mut tdf::TdfDeserializer<'_>) -> tdf::DecodeResult<Self> {
-        r.until_tag(b"SESS", TdfType::Group)?;
-        let email: String = r.tag(b"MAIL")?;
+        r.group(b"SESS", |r| {
+            let email: String = r.tag(b"MAIL")?;
 
-        r.until_tag(b"PDTL", TdfType::Group)?;
-        let display_name: String = r.tag(b"DSNM")?;
-        Ok(Self {
-            email,
-            display_name,
+            // Read within the group so a missing ID doesn't read past the group
+            let (display_name, origin_id) = r.group(b"PDTL", |r| {
+                let display_name: String = r.tag(b"DSNM")?;
+                let origin_id: Option<i64> = r.try_tag(b"PID")?;
+                Ok((display_name, origin_id))
+            })?;
+
+            Ok(Self {
+                email,
+                display_name,
+                origin_id,
+            })
         })
     }
 }
@@ -4,18 +4,31 @@
 use super::{models::OriginLoginResponse, OfficialSession, RetrieverResult};
 use crate::{
     config::RuntimeConfig,
-    database::entities::{Player, PlayerData, PlayerRole},
+    database::{
+        entities::{Player, PlayerData, PlayerRole},
+        DbResult,
+    },
     session::models::{auth::OriginLoginRequest, util::SettingsResponse},
     utils::{
         components::{authentication, util},
         hashing::hash_password,
+        types::PlayerID,
     },
 };
-use log::{debug, error, warn};
+use log::{debug, warn};
+use parking_lot::Mutex;
 use sea_orm::{DatabaseConnection, DbErr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Arc,
+};
 use tdf::TdfMap;
 use thiserror::Error;
 
+/// Player data that new Origin accounts start with
+pub type OriginDefaultData = Arc<Vec<(String, String)>>;
+
 /// Service for providing origin flows from a retriever
 /// instance if available
 ///
@@ -26,6 +39,10 @@ pub struct OriginFlowService {
     /// Whether data fetching is enabled within the
     /// created origin flows
     pub data: bool,
+    /// Data used for new accounts when data is not fetched
+    pub default_data: OriginDefaultData,
+    /// Cache of the players linked to Origin accounts
+    pub identities: Arc<OriginIdentityCache>,
 }
 
 impl OriginFlowService {
@@ -36,10 +53,68 @@ impl OriginFlowService {
         OriginFlow {
             session,
             data: self.data,
+            default_data: self.default_data.clone(),
+            identities: self.identities.clone(),
         }
     }
 }
 
+/// Cache mapping the persistent IDs of Origin accounts to the ID of
+/// the linked local player, allows repeat logins to skip searching
+/// for the player by Origin ID and email
+#[derive(Default)]
+pub struct OriginIdentityCache {
+    /// Mapping from persistent Origin ID to player ID
+    players: Mutex<HashMap<i64, PlayerID>>,
+}
+
+impl OriginIdentityCache {
+    /// Finds the local player linked to the Origin account from `details`
+    /// using the cached link when available. Players found by email are
+    /// linked to the Origin account.
+    ///
+    /// Without an Origin ID the player is only found by email
+    async fn find_player(
+        &self,
+        db: &DatabaseConnection,
+        details: &OriginLoginResponse,
+    ) -> DbResult<Option<Player>> {
+        let Some(origin_id) = details.origin_id else {
+            return Player::by_email(db, &details.email).await;
+        };
+
+        let cached = self.players.lock().get(&origin_id).copied();
+        if let Some(player_id) = cached {
+            if let Some(player) = Player::by_id(db, player_id).await? {
+                return Ok(Some(player));
+            }
+
+            // Linked player no longer exists
+            self.players.lock().remove(&origin_id);
+        }
+
+        let player = match Player::by_origin_id(db, origin_id).await? {
+            Some(player) => player,
+            None => match Player::by_email(db, &details.email).await? {
+                // Link existing accounts that haven't been linked yet
+                Some(player) if player.origin_id.is_none() => {
+                    player.set_origin_id(db, origin_id).await?
+                }
+                Some(player) => player,
+                None => return Ok(None),
+            },
+        };
+
+        self.insert(origin_id, player.id);
+        Ok(Some(player))
+    }
+
+    /// Caches the link between the Origin account and player
+    fn insert(&self, origin_id: i64, player_id: PlayerID) {
+        self.players.lock().insert(origin_id, player_id);
+    }
+}
+
 /// Flow structure for complete authentication through
 /// origin and optionally loading the player data
 pub struct OriginFlow {
@@ -47,6 +122,10 @@ pub struct OriginFlow {
     session: OfficialSession,
     /// Whether to load the origin account data
     data: bool,
+    /// Data used for new accounts when data is not fetched
+    default_data: OriginDefaultData,
+    /// Cache of the players linked to Origin accounts
+    identities: Arc<OriginIdentityCache>,
 }
 
 #[derive(Debug, Error)]
@@ -59,6 +138,33 @@ pub enum OriginError {
     Database(#[from] DbErr),
 }
 
+/// Errors that can occur when loading the default origin data file
+#[derive(Debug, Error)]
+pub enum OriginDataError {
+    /// Failed to read the file
+    #[error("Failed to read origin data file: {0}")]
+    Io(#[from] std::io::Error),
+    /// The file was not an object of string keys and values
+    #[error("Invalid origin data file: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The file contained an empty key
+    #[error("Invalid origin data file: empty player data key")]
+    EmptyKey,
+}
+
+/// Loads and validates the default origin player data from the
+/// JSON object in the file at `path`
+pub async fn load_default_data(path: &Path) -> Result<Vec<(String, String)>, OriginDataError> {
+    let value = tokio::fs::read_to_string(path).await?;
+    let data: BTreeMap<String, String> = serde_json::from_str(&value)?;
+
+    if data.keys().any(|key| key.is_empty()) {
+        return Err(OriginDataError::EmptyKey);
+    }
+
+    Ok(data.into_iter().collect())
+}
+
 impl OriginFlow {
     /// Attempts to login to the Origin account associated to the provided `token`
     /// then searches for the account details locally, creating the account if
@@ -75,45 +181,35 @@ impl OriginFlow {
             .await
             .map_err(|_| OriginError::FailedAuthenticate)?;
 
-        // Check if the account with that email already exists
-        if let Some(player) = Player::by_email(db, &details.email).await? {
+        // Check if the account has already been linked to a player
+        if let Some(player) = self.identities.find_player(db, &details).await? {
             return Ok(player);
         }
 
-        let mut role = PlayerRole::Default;
-        let mut password: Option<String> = None;
-
-        // If there is a super admin defined
-        if config.dashboard.is_super_email(&details.email) {
-            // Use the super admin role
-            role = PlayerRole::SuperAdmin;
-
-            // Update the password with the specified one
-            if let Some(super_password) = config.dashboard.super_password.as_ref() {
-                if !super_password.is_empty() {
-                    let password_hash =
-                        hash_password(super_password).expect("Failed to hash super user password");
-                    password = Some(password_hash);
+        // Load the player data from the official server (If enabled)
+        let settings = if self.data {
+            match self.get_settings().await {
+                Ok(settings) => {
+                    debug!("Loaded player data from official server");
+                    Some(settings)
+                }
+                Err(_) => {
+                    warn!(
+                        "Unable to load origin player settings from official servers (Name: {}, Email: {})",
+                        &details.display_name, &details.email
+                    );
+                    None
                 }
             }
-        }
+        } else {
+            None
+        };
 
-        let player: Player =
-            Player::create(db, details.email, details.display_name, password, role).await?;
-
-        // If data fetching is ena
-        if self.data {
-            if let Ok(settings) = self.get_settings().await {
-                debug!("Loaded player data from official server");
-                PlayerData::set_bulk(db, player.id, settings.into_iter()).await?;
-            } else {
-                warn!(
-                    "Unable to load origin player settings from official servers (Name: {}, Email: {})",
-                    &player.display_name, &player.email
-                );
-            }
+        let origin_id = details.origin_id;
+        let player = create_player(db, details, config, settings, &self.default_data).await?;
+        if let Some(origin_id) = origin_id {
+            self.identities.insert(origin_id, player.id);
         }
-
         Ok(player)
     }
 
@@ -146,3 +242,223 @@ impl OriginFlow {
         Ok(value.settings)
     }
 }
+
+/// Creates a new local account for the provided Origin `details`, using the
+/// fetched `settings` as the player data when present or the `default_data`
+/// otherwise
+async fn create_player(
+    db: &DatabaseConnection,
+    details: OriginLoginResponse,
+    config: &RuntimeConfig,
+    settings: Option<TdfMap<String, String>>,
+    default_data: &[(String, String)],
+) -> Result<Player, DbErr> {
+    let mut role = PlayerRole::Default;
+    let mut password: Option<String> = None;
+
+    // If there is a super admin defined
+    if config.dashboard.is_super_email(&details.email) {
+        // Use the super admin role
+        role = PlayerRole::SuperAdmin;
+
+        // Update the password with the specified one
+        if let Some(super_password) = config.dashboard.super_password.as_ref() {
+            if !super_password.is_empty() {
+                let password_hash =
+                    hash_password(super_password).expect("Failed to hash super user password");
+                password = Some(password_hash);
+            }
+        }
+    }
+
+    let mut player: Player =
+        Player::create(db, details.email, details.display_name, password, role).await?;
+
+    if let Some(origin_id) = details.origin_id {
+        player = player.set_origin_id(db, origin_id).await?;
+    }
+
+    if let Some(settings) = settings {
+        PlayerData::set_bulk(db, player.id, settings.into_iter()).await?;
+    } else if !default_data.is_empty() {
+        PlayerData::set_bulk(db, player.id, default_data.iter().cloned()).await?;
+    }
+
+    Ok(player)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{create_player, load_default_data, OriginIdentityCache, OriginLoginResponse};
+    use crate::{
+        config::RuntimeConfig,
+        database::{
+            entities::{players, Player, PlayerData},
+            test_database,
+        },
+    };
+    use sea_orm::{prelude::Expr, ColumnTrait, EntityTrait, QueryFilter};
+    use tdf::{TdfDeserializeOwned, TdfDeserializer, TdfSerializer};
+
+    /// Tests that origin accounts created without fetched data use the
+    /// data from the default data file
+    #[tokio::test]
+    async fn test_origin_default_data_file() {
+        let path = std::env::temp_dir().join(format!("pocket-relay-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"{"Base": "20;4;500", "class1": "20;4;Adept;20;0.0000;50"}"#,
+        )
+        .unwrap();
+
+        let data = load_default_data(&path).await.unwrap();
+        _ = std::fs::remove_file(&path);
+
+        let db = test_database().await;
+        let config = RuntimeConfig::default();
+        let details = OriginLoginResponse {
+            email: "origin@test.com".to_string(),
+            display_name: "Origin".to_string(),
+            origin_id: Some(1),
+        };
+
+        let player = create_player(&db, details, &config, None, &data)
+            .await
+            .unwrap();
+
+        let base = PlayerData::get(&db, player.id, "Base")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(base.value, "20;4;500");
+        let class = PlayerData::get(&db, player.id, "class1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(class.value, "20;4;Adept;20;0.0000;50");
+    }
+
+    /// Tests that invalid data files are rejected
+    #[tokio::test]
+    async fn test_origin_default_data_invalid() {
+        let path = std::env::temp_dir().join(format!("pocket-relay-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{"": "value"}"#).unwrap();
+        assert!(load_default_data(&path).await.is_err());
+
+        std::fs::write(&path, r#"{"Base": 1}"#).unwrap();
+        assert!(load_default_data(&path).await.is_err());
+
+        _ = std::fs::remove_file(&path);
+    }
+
+    /// Tests that logins with the same Origin ID resolve to the same player
+    /// and that repeat logins are resolved through the cache
+    #[tokio::test]
+    async fn test_origin_identity_cache() {
+        let db = test_database().await;
+        let config = RuntimeConfig::default();
+        let cache = OriginIdentityCache::default();
+        let origin_id = 1000123456789;
+        let details = OriginLoginResponse {
+            email: "origin@test.com".to_string(),
+            display_name: "Origin".to_string(),
+            origin_id: Some(origin_id),
+        };
+
+        // No player exists for the first login
+        assert!(cache.find_player(&db, &details).await.unwrap().is_none());
+
+        let player = create_player(
+            &db,
+            OriginLoginResponse {
+                email: details.email.clone(),
+                display_name: details.display_name.clone(),
+                origin_id: details.origin_id,
+            },
+            &config,
+            None,
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let found = Player::by_origin_id(&db, origin_id).await.unwrap().unwrap();
+        assert_eq!(found.id, player.id);
+
+        // Second login is found in the database and cached
+        let first = cache.find_player(&db, &details).await.unwrap().unwrap();
+        assert_eq!(first.id, player.id);
+
+        // Remove the stored link so only the cache can resolve the player
+        players::Entity::update_many()
+            .col_expr(players::Column::OriginId, Expr::value(Option::<i64>::None))
+            .filter(players::Column::Id.eq(player.id))
+            .exec(&db)
+            .await
+            .unwrap();
+        assert!(Player::by_origin_id(&db, origin_id)
+            .await
+            .unwrap()
+            .is_none());
+
+        let second = cache.find_player(&db, &details).await.unwrap().unwrap();
+        assert_eq!(second.id, player.id);
+    }
+
+    /// Encodes an Origin login response with the optional `origin_id`
+    fn encode_login_response(origin_id: Option<i64>) -> Vec<u8> {
+        let mut w = Vec::new();
+        w.tag_group(b"SESS");
+        w.tag_str(b"MAIL", "origin@test.com");
+        w.tag_group(b"PDTL");
+        w.tag_str(b"DSNM", "Origin");
+        if let Some(origin_id) = origin_id {
+            w.tag_owned(b"PID", origin_id);
+        }
+        w.tag_group_end();
+        w.tag_u32(b"UID", 1);
+        w.tag_group_end();
+        w
+    }
+
+    /// Tests that login responses without the persistent Origin ID are
+    /// accepted and the player is found by email
+    #[tokio::test]
+    async fn test_origin_missing_id() {
+        let bytes = encode_login_response(Some(1000123456789));
+        let mut r = TdfDeserializer::new(&bytes);
+        let details = OriginLoginResponse::deserialize_owned(&mut r).unwrap();
+        assert_eq!(details.origin_id, Some(1000123456789));
+
+        let bytes = encode_login_response(None);
+        let mut r = TdfDeserializer::new(&bytes);
+        let details = OriginLoginResponse::deserialize_owned(&mut r).unwrap();
+        assert_eq!(details.email, "origin@test.com");
+        assert_eq!(details.display_name, "Origin");
+        assert_eq!(details.origin_id, None);
+
+        let db = test_database().await;
+        let config = RuntimeConfig::default();
+        let cache = OriginIdentityCache::default();
+
+        assert!(cache.find_player(&db, &details).await.unwrap().is_none());
+
+        let player = create_player(
+            &db,
+            OriginLoginResponse {
+                email: details.email.clone(),
+                display_name: details.display_name.clone(),
+                origin_id: None,
+            },
+            &config,
+            None,
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(player.origin_id, None);
+
+        let found = cache.find_player(&db, &details).await.unwrap().unwrap();
+        assert_eq!(found.id, player.id);
+    }
+}
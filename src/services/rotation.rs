@@ -0,0 +1,160 @@
+//! Rotation of the banner and new item assigned to players, mirrors the
+//! weekly rotation performed by the official servers
+
+use crate::{
+    config::RotationConfig,
+    database::{entities::PlayerData, DatabaseConnection, DbResult},
+};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use std::time::Duration;
+use tokio::time::{interval, MissedTickBehavior};
+
+/// Delay between each check for whether the rotation has advanced
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Player data key storing the banner reward
+const BANNER_KEY: &str = "csreward";
+/// Player data key storing the new item
+const NEW_ITEM_KEY: &str = "NewItem";
+
+/// Determines the index of the rotation active at `now`
+fn rotation_index(config: &RotationConfig, now: DateTime<Utc>) -> u64 {
+    let period_secs = config.period_hours.max(1) * 60 * 60;
+    now.timestamp().max(0) as u64 / period_secs
+}
+
+/// Creates the player data for the rotation at `index`, pools without
+/// any values are not included
+fn rotation_data(config: &RotationConfig, index: u64) -> Vec<(&'static str, String)> {
+    let mut data = Vec::with_capacity(2);
+
+    if !config.banners.is_empty() {
+        let banner = config.banners[(index % config.banners.len() as u64) as usize];
+        data.push((BANNER_KEY, banner.to_string()));
+    }
+
+    if !config.new_items.is_empty() {
+        let new_item = &config.new_items[(index % config.new_items.len() as u64) as usize];
+        data.push((NEW_ITEM_KEY, new_item.clone()));
+    }
+
+    data
+}
+
+/// Applies the rotation at `index` to every player
+///
+/// `db`     The database connection
+/// `config` The rotation configuration
+/// `index`  The rotation index to apply
+pub async fn apply_rotation(
+    db: &DatabaseConnection,
+    config: &RotationConfig,
+    index: u64,
+) -> DbResult<()> {
+    let data = rotation_data(config, index);
+    PlayerData::set_for_all_players(db, &data).await
+}
+
+/// Background task that applies the rotation on startup and again
+/// whenever the rotation advances
+pub async fn rotation_task(db: DatabaseConnection, config: RotationConfig) {
+    let mut interval = interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut applied: Option<u64> = None;
+
+    loop {
+        interval.tick().await;
+
+        let index = rotation_index(&config, Utc::now());
+        if applied == Some(index) {
+            continue;
+        }
+
+        match apply_rotation(&db, &config, index).await {
+            Ok(()) => {
+                info!("Applied player banner rotation {}", index);
+                applied = Some(index);
+            }
+            Err(err) => error!("Failed to apply player banner rotation: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply_rotation, rotation_index, BANNER_KEY, NEW_ITEM_KEY};
+    use crate::{
+        config::RotationConfig,
+        database::{
+            entities::{Player, PlayerData, PlayerRole},
+            test_database,
+        },
+    };
+    use chrono::{TimeZone, Utc};
+
+    /// Tests that the rotation index advances once per period
+    #[test]
+    fn test_rotation_index() {
+        let config = RotationConfig {
+            period_hours: 24,
+            ..Default::default()
+        };
+
+        let start = Utc.timestamp_opt(0, 0).unwrap();
+        let same_day = Utc.timestamp_opt(60 * 60 * 23, 0).unwrap();
+        let next_day = Utc.timestamp_opt(60 * 60 * 24, 0).unwrap();
+
+        assert_eq!(rotation_index(&config, start), 0);
+        assert_eq!(rotation_index(&config, same_day), 0);
+        assert_eq!(rotation_index(&config, next_day), 1);
+    }
+
+    /// Tests that applying a rotation updates the banner and new
+    /// item of every player to values from the pools
+    #[tokio::test]
+    async fn test_apply_rotation() {
+        let db = test_database().await;
+        let config = RotationConfig {
+            banners: vec![10, 20, 30],
+            new_items: vec!["20;4;1 100".to_string(), "20;4;2 200".to_string()],
+            ..Default::default()
+        };
+
+        let mut players = Vec::new();
+        for name in ["first", "second"] {
+            let player = Player::create(
+                &db,
+                format!("{name}@test.com"),
+                name.to_string(),
+                None,
+                PlayerRole::Default,
+            )
+            .await
+            .unwrap();
+            players.push(player.id);
+        }
+
+        // Existing values are replaced
+        PlayerData::set(&db, players[0], BANNER_KEY.to_string(), "0".to_string())
+            .await
+            .unwrap();
+
+        apply_rotation(&db, &config, 4).await.unwrap();
+
+        for player_id in players {
+            let banner = PlayerData::get(&db, player_id, BANNER_KEY)
+                .await
+                .unwrap()
+                .unwrap();
+            let new_item = PlayerData::get(&db, player_id, NEW_ITEM_KEY)
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(banner.value, "20");
+            assert_eq!(new_item.value, "20;4;1 100");
+        }
+    }
+}
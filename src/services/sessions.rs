@@ -5,13 +5,14 @@ use crate::database::entities::Player;
 use crate::session::{SessionLink, WeakSessionLink};
 use crate::utils::hashing::IntHashMap;
 use crate::utils::signing::SigningKey;
-use crate::utils::types::PlayerID;
+use crate::utils::types::{GameID, PlayerID};
 use base64ct::{Base64UrlUnpadded, Encoding};
 use hashbrown::HashMap;
 use parking_lot::Mutex;
 use rand::distributions::Distribution;
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
@@ -37,6 +38,24 @@ pub struct Sessions {
 
     /// HMAC key used for computing signatures
     key: SigningKey,
+
+    /// Number of connected sessions including unauthenticated sessions
+    connections: Arc<AtomicUsize>,
+
+    /// Number of connections refused because the server was full
+    rejected_connections: AtomicU64,
+}
+
+/// Permit for a connected session, the session is no longer
+/// counted as connected once the permit is dropped
+pub struct ConnectionPermit {
+    connections: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::AcqRel);
+    }
 }
 
 pub struct LoginCodeData {
@@ -74,7 +93,44 @@ impl Sessions {
             sessions: Default::default(),
             login_codes: Default::default(),
             key,
+            connections: Default::default(),
+            rejected_connections: AtomicU64::new(0),
+        }
+    }
+
+    /// Attempts to obtain a permit for a new connected session, when
+    /// `max` connections are already connected the connection is refused
+    pub fn try_acquire_connection(&self, max: Option<usize>) -> Option<ConnectionPermit> {
+        let result = self
+            .connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                if max.is_some_and(|max| count >= max) {
+                    None
+                } else {
+                    Some(count + 1)
+                }
+            });
+
+        if result.is_err() {
+            self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+            return None;
         }
+
+        Some(ConnectionPermit {
+            connections: self.connections.clone(),
+        })
+    }
+
+    /// Obtains the number of connected sessions including
+    /// unauthenticated sessions
+    pub fn connection_count(&self) -> usize {
+        self.connections.load(Ordering::Acquire)
+    }
+
+    /// Obtains the number of connections refused because the
+    /// server was full
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected_connections.load(Ordering::Relaxed)
     }
 
     /// Creates a new login code for the provider player, returns the
@@ -82,11 +138,7 @@ impl Sessions {
     pub fn create_login_code(&self, player_id: PlayerID) -> Result<LoginCode, ()> {
         let rng = StdRng::from_entropy();
 
-        let code: LoginCode = rng
-            .sample_iter(&LoginCodePart)
-            .take(5)
-            .map(char::from)
-            .collect();
+        let code: LoginCode = rng.sample_iter(&LoginCodePart).take(5).collect();
 
         // Compute expiry timestamp
         let exp = SystemTime::now()
@@ -156,11 +208,100 @@ impl Sessions {
         Ok(uuid)
     }
 
+    /// Creates a tunnel reconnect token for the provided `reconnect` details
+    /// that will expire at the provided `expires_at` time
+    pub fn create_tunnel_reconnect_token(
+        &self,
+        reconnect: &TunnelReconnect,
+        expires_at: SystemTime,
+    ) -> String {
+        let exp = expires_at
+            .duration_since(UNIX_EPOCH)
+            .expect("Clock went backwards")
+            .as_secs();
+
+        // Create encoded token value
+        let mut data = [0u8; 29];
+        data[..16].copy_from_slice(reconnect.association.as_bytes());
+        data[16..20].copy_from_slice(&reconnect.pool_id.to_be_bytes());
+        data[20] = reconnect.pool_index;
+        data[21..].copy_from_slice(&exp.to_be_bytes());
+        let data = &data;
+
+        // Encode the message
+        let msg = Base64UrlUnpadded::encode_string(data);
+
+        // Create a signature from the raw message bytes
+        let sig = self.key.sign(data);
+        let sig = Base64UrlUnpadded::encode_string(sig.as_ref());
+
+        // Join the message and signature to create the token
+        [msg, sig].join(".")
+    }
+
+    /// Verifies a tunnel reconnect token
+    pub fn verify_tunnel_reconnect_token(
+        &self,
+        token: &str,
+    ) -> Result<TunnelReconnect, VerifyError> {
+        // Split the token parts
+        let (msg_raw, sig_raw) = match token.split_once('.') {
+            Some(value) => value,
+            None => return Err(VerifyError::Invalid),
+        };
+
+        // Decode the 29 byte token message
+        let mut msg = [0u8; 29];
+        Base64UrlUnpadded::decode(msg_raw, &mut msg).map_err(|_| VerifyError::Invalid)?;
+
+        // Decode 32byte signature (SHA256)
+        let mut sig = [0u8; 32];
+        Base64UrlUnpadded::decode(sig_raw, &mut sig).map_err(|_| VerifyError::Invalid)?;
+
+        // Verify the signature
+        if !self.key.verify(&msg, &sig) {
+            return Err(VerifyError::Invalid);
+        }
+
+        // Extract the reconnect details and expiration from the msg bytes
+        let mut association = [0u8; 16];
+        association.copy_from_slice(&msg[..16]);
+
+        let mut pool_id = [0u8; 4];
+        pool_id.copy_from_slice(&msg[16..20]);
+
+        let mut exp = [0u8; 8];
+        exp.copy_from_slice(&msg[21..]);
+        let exp = u64::from_be_bytes(exp);
+
+        // Ensure the timestamp is not expired
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Clock went backwards")
+            .as_secs();
+
+        if exp < now {
+            return Err(VerifyError::Expired);
+        }
+
+        Ok(TunnelReconnect {
+            association: Uuid::from_bytes(association),
+            pool_id: u32::from_be_bytes(pool_id),
+            pool_index: msg[20],
+        })
+    }
+
     pub fn create_token(&self, player_id: PlayerID) -> String {
         // Compute expiry timestamp
         let exp = SystemTime::now()
             .checked_add(Self::EXPIRY_TIME)
-            .expect("Expiry timestamp too far into the future")
+            .expect("Expiry timestamp too far into the future");
+        self.create_token_expiring(player_id, exp)
+    }
+
+    /// Creates a token for `player_id` that expires at `exp`
+    fn create_token_expiring(&self, player_id: PlayerID, exp: SystemTime) -> String {
+        let exp = exp
             .duration_since(UNIX_EPOCH)
             .expect("Clock went backwards")
             .as_secs();
@@ -183,6 +324,16 @@ impl Sessions {
     }
 
     pub fn verify_token(&self, token: &str) -> Result<u32, VerifyError> {
+        self.verify_token_with_grace(token, Duration::ZERO)
+    }
+
+    /// Verifies a token allowing it to be used for up to `grace` after
+    /// it has expired
+    pub fn verify_token_with_grace(
+        &self,
+        token: &str,
+        grace: Duration,
+    ) -> Result<u32, VerifyError> {
         // Split the token parts
         let (msg_raw, sig_raw) = match token.split_once('.') {
             Some(value) => value,
@@ -217,7 +368,7 @@ impl Sessions {
             .expect("Clock went backwards")
             .as_secs();
 
-        if exp < now {
+        if exp.saturating_add(grace.as_secs()) < now {
             return Err(VerifyError::Expired);
         }
 
@@ -249,6 +400,28 @@ impl Sessions {
         sessions.remove(&player_id);
     }
 
+    /// Obtains the number of authenticated sessions
+    pub fn count(&self) -> usize {
+        self.sessions.lock().len()
+    }
+
+    /// Obtains the links to all the authenticated sessions
+    pub fn all_sessions(&self) -> Vec<SessionLink> {
+        let sessions = &mut *self.sessions.lock();
+        let mut links = Vec::with_capacity(sessions.len());
+
+        // Collect active sessions removing any that have stopped
+        sessions.retain(|_, session| match session.upgrade() {
+            Some(link) => {
+                links.push(link);
+                true
+            }
+            None => false,
+        });
+
+        links
+    }
+
     pub fn lookup_session(&self, player_id: PlayerID) -> Option<SessionLink> {
         let sessions = &mut *self.sessions.lock();
         let session = sessions.get(&player_id)?;
@@ -283,6 +456,19 @@ impl Drop for SessionPlayerAssociation {
     }
 }
 
+/// Details stored within a tunnel reconnect token, the token allows
+/// a client to rebind a dropped tunnel to the pool slot it was
+/// assigned when joining a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TunnelReconnect {
+    /// Association of the client the slot belongs to
+    pub association: AssociationId,
+    /// ID of the pool the slot is within
+    pub pool_id: GameID,
+    /// Index of the slot within the pool
+    pub pool_index: u8,
+}
+
 /// Errors that can occur while verifying a token
 #[derive(Debug, Error)]
 pub enum VerifyError {
@@ -298,7 +484,8 @@ pub enum VerifyError {
 mod test {
     use crate::utils::signing::SigningKey;
 
-    use super::Sessions;
+    use super::{Sessions, VerifyError};
+    use std::time::{Duration, SystemTime};
 
     /// Tests that tokens can be created and verified correctly
     #[test]
@@ -312,4 +499,53 @@ mod test {
 
         assert_eq!(player_id, claim)
     }
+
+    /// Tests that connections past the maximum are refused while the
+    /// existing connections remain connected
+    #[test]
+    fn test_max_connections() {
+        let (key, _) = SigningKey::generate();
+        let sessions = Sessions::new(key);
+
+        let mut permits: Vec<_> = (0..3)
+            .map(|_| sessions.try_acquire_connection(Some(3)).unwrap())
+            .collect();
+
+        assert!(sessions.try_acquire_connection(Some(3)).is_none());
+        assert_eq!(sessions.connection_count(), 3);
+        assert_eq!(sessions.rejected_connections(), 1);
+
+        // Space is available again once a session disconnects
+        permits.pop();
+        assert_eq!(sessions.connection_count(), 2);
+        assert!(sessions.try_acquire_connection(Some(3)).is_some());
+
+        // Connections are never refused without a limit
+        assert!(sessions.try_acquire_connection(None).is_some());
+    }
+
+    /// Tests that expired tokens are only accepted within the grace period
+    #[test]
+    fn test_token_grace() {
+        let (key, _) = SigningKey::generate();
+        let sessions = Sessions::new(key);
+
+        let expired = SystemTime::now() - Duration::from_secs(60);
+        let token = sessions.create_token_expiring(32, expired);
+
+        assert!(matches!(
+            sessions.verify_token(&token),
+            Err(VerifyError::Expired)
+        ));
+        assert_eq!(
+            sessions
+                .verify_token_with_grace(&token, Duration::from_secs(300))
+                .ok(),
+            Some(32)
+        );
+        assert!(matches!(
+            sessions.verify_token_with_grace(&token, Duration::from_secs(30)),
+            Err(VerifyError::Expired)
+        ));
+    }
 }
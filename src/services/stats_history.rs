@@ -0,0 +1,123 @@
+//! Service keeping a rolling history of server activity so basic
+//! trends can be graphed without an external metrics stack
+
+use super::{game::manager::GameManager, sessions::Sessions};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+use tokio::time::{interval, MissedTickBehavior};
+
+/// Time between each sample of the server activity
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of samples kept in the history (24 hours of samples)
+pub const HISTORY_CAPACITY: usize = 24 * 60;
+
+/// Single sample of the server activity
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct StatsSample {
+    /// When the sample was taken
+    pub timestamp: DateTime<Utc>,
+    /// Number of authenticated sessions
+    pub sessions: usize,
+    /// Number of connected sessions including unauthenticated sessions
+    pub connections: usize,
+    /// Total number of connections refused because the server was full
+    pub rejected_connections: u64,
+    /// Number of active games
+    pub games: usize,
+}
+
+/// Service storing the most recent activity samples in a fixed size
+/// ring, the oldest sample is dropped when a new one is recorded
+pub struct StatsHistory {
+    /// Maximum number of samples to keep
+    capacity: usize,
+    /// The stored samples from oldest to newest
+    samples: Mutex<VecDeque<StatsSample>>,
+}
+
+impl Default for StatsHistory {
+    fn default() -> Self {
+        Self::new(HISTORY_CAPACITY)
+    }
+}
+
+impl StatsHistory {
+    /// Creates a new history that keeps at most `capacity` samples
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Records a new sample, removing the oldest sample if the
+    /// history is full
+    pub fn record(&self, sample: StatsSample) {
+        let samples = &mut *self.samples.lock();
+        while samples.len() >= self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Obtains a copy of the stored samples from oldest to newest
+    pub fn samples(&self) -> Vec<StatsSample> {
+        self.samples.lock().iter().copied().collect()
+    }
+
+    /// Background task that records a sample every [SAMPLE_INTERVAL]
+    pub async fn sample_task(self: Arc<Self>, sessions: Arc<Sessions>, games: Arc<GameManager>) {
+        let mut interval = interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            self.record(StatsSample {
+                timestamp: Utc::now(),
+                sessions: sessions.count(),
+                connections: sessions.connection_count(),
+                rejected_connections: sessions.rejected_connections(),
+                games: games.get_total_games().await,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StatsHistory, StatsSample};
+    use chrono::Utc;
+
+    /// Tests that the history is bounded by its capacity and
+    /// keeps the most recent samples
+    #[test]
+    fn test_record_bounded() {
+        let history = StatsHistory::new(3);
+        assert!(history.samples().is_empty());
+
+        for value in 0..5 {
+            history.record(StatsSample {
+                timestamp: Utc::now(),
+                sessions: value,
+                connections: value,
+                rejected_connections: 0,
+                games: value * 2,
+            });
+        }
+
+        let samples = history.samples();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(
+            samples
+                .iter()
+                .map(|sample| sample.sessions)
+                .collect::<Vec<_>>(),
+            [2, 3, 4]
+        );
+        assert_eq!(samples[2].games, 8);
+    }
+}
@@ -0,0 +1,232 @@
+//! Service for forwarding telemetry messages from clients to an
+//! external collector
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::{
+    sync::mpsc,
+    time::{sleep, timeout_at, Instant},
+};
+
+/// Maximum number of messages sent in a single batch
+const BATCH_SIZE: usize = 50;
+/// Maximum time to wait for a batch to fill before sending it
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// Number of messages that can be waiting to be forwarded, messages
+/// past this are dropped
+const QUEUE_SIZE: usize = 1024;
+/// Number of attempts made to send each batch
+const SEND_ATTEMPTS: u32 = 3;
+/// Delay before retrying a failed batch, doubled after each attempt
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Structure of a telemetry message coming from a client
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TelemetryMessage {
+    /// The telemetry message values
+    pub values: Vec<(String, String)>,
+}
+
+impl TelemetryMessage {
+    /// Escapes any control characters within the message keys and values
+    /// so the message can be safely written to logs
+    pub fn escape_control(&mut self) {
+        for (key, value) in &mut self.values {
+            escape_control(key);
+            escape_control(value);
+        }
+    }
+}
+
+/// Replaces any control characters within `value` with their escaped
+/// form, leaving all other characters untouched
+fn escape_control(value: &mut String) {
+    if !value.contains(char::is_control) {
+        return;
+    }
+
+    *value = value.chars().fold(String::new(), |mut out, c| {
+        if c.is_control() {
+            out.extend(c.escape_default());
+        } else {
+            out.push(c);
+        }
+        out
+    });
+}
+
+/// Service that queues telemetry messages to be sent to the
+/// configured forwarding URL in batches
+#[derive(Default)]
+pub struct TelemetryForwarder {
+    /// Sender for queuing messages, None when forwarding is disabled
+    tx: Option<mpsc::Sender<TelemetryMessage>>,
+}
+
+impl TelemetryForwarder {
+    /// Starts a forwarder sending messages to the provided `url`,
+    /// forwarding is disabled when no URL is provided
+    pub fn start(url: Option<String>) -> Self {
+        match url {
+            Some(url) => Self::start_with(url, BATCH_SIZE, FLUSH_INTERVAL),
+            None => Self::default(),
+        }
+    }
+
+    /// Starts a forwarder with the provided batching options
+    fn start_with(url: String, batch_size: usize, flush_interval: Duration) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(forward_task(url, rx, batch_size, flush_interval));
+        Self { tx: Some(tx) }
+    }
+
+    /// Queues the provided `message` to be forwarded, the message is
+    /// dropped if the queue is full to avoid blocking the caller
+    pub fn forward(&self, message: TelemetryMessage) {
+        if let Some(tx) = &self.tx {
+            if tx.try_send(message).is_err() {
+                debug!("Dropped telemetry message, forwarding queue is full");
+            }
+        }
+    }
+}
+
+/// Background task that collects queued messages into batches and
+/// sends them to the `url`
+async fn forward_task(
+    url: String,
+    mut rx: mpsc::Receiver<TelemetryMessage>,
+    batch_size: usize,
+    flush_interval: Duration,
+) {
+    let client = reqwest::Client::new();
+    let mut batch = Vec::with_capacity(batch_size);
+
+    // Wait for the first message of each batch
+    while let Some(message) = rx.recv().await {
+        batch.push(message);
+
+        // Fill the batch until its full or the flush interval passes
+        let deadline = Instant::now() + flush_interval;
+        while batch.len() < batch_size {
+            match timeout_at(deadline, rx.recv()).await {
+                Ok(Some(message)) => batch.push(message),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        send_batch(&client, &url, &batch).await;
+        batch.clear();
+    }
+}
+
+/// Sends the `batch` to the `url`, retrying on failure and dropping
+/// the batch once all attempts have failed
+async fn send_batch(client: &reqwest::Client, url: &str, batch: &[TelemetryMessage]) {
+    let mut delay = RETRY_DELAY;
+
+    for attempt in 1..=SEND_ATTEMPTS {
+        let result = client
+            .post(url)
+            .json(batch)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        match result {
+            Ok(_) => return,
+            Err(err) => debug!(
+                "Failed to forward telemetry (Attempt {}/{}): {}",
+                attempt, SEND_ATTEMPTS, err
+            ),
+        }
+
+        if attempt < SEND_ATTEMPTS {
+            sleep(delay).await;
+            delay *= 2;
+        }
+    }
+
+    warn!(
+        "Dropped {} telemetry message(s) after failing to forward them",
+        batch.len()
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TelemetryForwarder, TelemetryMessage};
+    use axum::{extract::State, routing::post, Json, Router};
+    use std::time::Duration;
+    use tokio::{net::TcpListener, sync::mpsc, time::timeout};
+
+    /// Tests that forwarded messages are received by the collector as a batch
+    #[tokio::test]
+    async fn test_forward_batch() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Vec<TelemetryMessage>>();
+
+        // Mock collector that passes on the received batches
+        let sink = Router::new()
+            .route(
+                "/collect",
+                post(
+                    |State(tx): State<mpsc::UnboundedSender<Vec<TelemetryMessage>>>,
+                     Json(batch): Json<Vec<TelemetryMessage>>| async move {
+                        _ = tx.send(batch);
+                    },
+                ),
+            )
+            .with_state(tx);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, sink).await });
+
+        let forwarder = TelemetryForwarder::start_with(
+            format!("http://{}/collect", addr),
+            2,
+            Duration::from_secs(5),
+        );
+
+        for index in 0..2 {
+            forwarder.forward(TelemetryMessage {
+                values: vec![("KEY".to_string(), index.to_string())],
+            });
+        }
+
+        let batch = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("Timed out waiting for batch")
+            .unwrap();
+
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[0].values[0].1, "0");
+        assert_eq!(batch[1].values[0].1, "1");
+    }
+
+    /// Tests that control characters from binary values are escaped while
+    /// printable and non-ASCII characters are left as is
+    #[test]
+    fn test_escape_control() {
+        let binary: String = (0u8..0x20).map(char::from).collect();
+        let mut message = TelemetryMessage {
+            values: vec![
+                ("KEY\n".to_string(), "\u{1b}[31mRED\u{7f}".to_string()),
+                ("BIN".to_string(), binary),
+                ("HIGH".to_string(), "café\u{85}漢".to_string()),
+            ],
+        };
+        message.escape_control();
+
+        for (key, value) in &message.values {
+            assert!(!key.contains(char::is_control));
+            assert!(!value.contains(char::is_control));
+        }
+
+        assert_eq!(message.values[0].0, "KEY\\n");
+        assert_eq!(message.values[0].1, "\\u{1b}[31mRED\\u{7f}");
+        assert!(message.values[1].1.starts_with("\\u{0}\\u{1}"));
+        assert_eq!(message.values[2].1, "café\\u{85}漢");
+    }
+}
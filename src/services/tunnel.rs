@@ -26,7 +26,10 @@ use tokio::{
 };
 use tokio_util::codec::Framed;
 
-use super::sessions::AssociationId;
+use super::{
+    sessions::AssociationId,
+    tunnel_send::{ForwardCounters, SendFailures, TunnelSendError, TunnelStats},
+};
 
 /// The port bound on clients representing the host player within the socket pool
 pub const TUNNEL_HOST_LOCAL_PORT: u16 = 42132;
@@ -44,6 +47,8 @@ pub struct TunnelService {
     next_tunnel_id: AtomicU32,
     /// Underlying tunnel mappings
     mappings: RwLock<TunnelMappings>,
+    /// Messages that failed to be forwarded between tunnels
+    send_failures: SendFailures,
 }
 
 pub struct TunnelData {
@@ -51,6 +56,8 @@ pub struct TunnelData {
     association: AssociationId,
     /// Handle for the tunnel
     handle: TunnelHandle,
+    /// Counters for messages forwarded from the tunnel
+    counters: ForwardCounters,
 }
 
 /// Stores mappings between various tunnel objects
@@ -108,22 +115,23 @@ impl TunnelMappings {
 
     /// Attempts to associate the tunnel from `address` to the provided
     /// `pool_id` and `pool_index` if there is a tunnel connected to
-    /// `address`
+    /// `address`, returns whether a tunnel was associated
     fn associate_pool(
         &mut self,
         association: AssociationId,
         pool_id: PoolId,
         pool_index: PoolIndex,
-    ) {
+    ) -> bool {
         let tunnel_id = match self.association_to_tunnel.get(&association) {
             Some(value) => *value,
-            None => return,
+            None => return false,
         };
 
         let key = PoolKey::new(pool_id, pool_index);
 
         self.tunnel_to_index.insert(tunnel_id, key);
         self.index_to_tunnel.insert(key, tunnel_id);
+        true
     }
 
     /// Uses the lookup maps to find the [TunnelHandle] of another tunnel within the same
@@ -165,6 +173,38 @@ impl TunnelMappings {
         }
     }
 
+    /// Records a message of `length` bytes forwarded from the tunnel
+    /// with `tunnel_id`
+    fn record_forward(&self, tunnel_id: TunnelId, length: usize) {
+        if let Some(tunnel) = self.id_to_tunnel.get(&tunnel_id) {
+            tunnel.counters.record(length);
+        }
+    }
+
+    /// Creates a snapshot of the forwarding statistics for each tunnel
+    /// that is within a pool
+    fn stats(&self) -> Vec<TunnelStats> {
+        let mut stats: Vec<TunnelStats> = self
+            .index_to_tunnel
+            .iter()
+            .filter_map(|(key, tunnel_id)| {
+                let tunnel = self.id_to_tunnel.get(tunnel_id)?;
+                let (pool_id, pool_index) = key.parts();
+
+                Some(TunnelStats {
+                    pool_id,
+                    pool_index,
+                    tunnel_id: *tunnel_id,
+                    packets: tunnel.counters.packets(),
+                    bytes: tunnel.counters.bytes(),
+                    last_alive_seconds: None,
+                })
+            })
+            .collect();
+        stats.sort_by_key(|stats| (stats.pool_id, stats.pool_index));
+        stats
+    }
+
     /// Removes the association between a [PoolKey] and a [TunnelId] if
     /// one is present
     fn dissociate_pool(&mut self, pool_id: PoolId, pool_index: PoolIndex) {
@@ -195,7 +235,7 @@ impl TunnelService {
         association: AssociationId,
         pool_id: PoolId,
         pool_index: PoolIndex,
-    ) {
+    ) -> bool {
         self.mappings
             .write()
             .associate_pool(association, pool_id, pool_index)
@@ -225,6 +265,49 @@ impl TunnelService {
     pub fn dissociate_pool(&self, pool_id: PoolId, pool_index: PoolIndex) {
         self.mappings.write().dissociate_pool(pool_id, pool_index);
     }
+
+    /// Forwards the `message` from the tunnel with `tunnel_id` to the tunnel
+    /// at the message index within the same pool, records a failure if the
+    /// target tunnel could not be sent to
+    fn forward(&self, tunnel_id: TunnelId, mut message: TunnelMessage) {
+        // Get the path through the tunnel
+        let (target_handle, index) = match self.get_tunnel_route(tunnel_id, message.index) {
+            Some(value) => value,
+            // Don't have a tunnel to send the message through
+            None => return,
+        };
+
+        // Counters are atomic so only the read lock is required
+        self.mappings
+            .read()
+            .record_forward(tunnel_id, message.message.len());
+
+        // Update the message target index to be from the correct index
+        message.index = index;
+
+        // Send the message to the tunnel
+        if let Err(err) = target_handle.send(message) {
+            self.send_failures.record(tunnel_id, &err);
+        }
+    }
+
+    /// Removes all the tunnels and their pool associations, dropping the
+    /// tunnel handles stops each tunnel closing its connection
+    pub fn close_all(&self) {
+        *self.mappings.write() = TunnelMappings::default();
+    }
+
+    /// Wrapper around [`TunnelMappings::stats`] that holds the service
+    /// read lock before operating
+    #[inline]
+    pub fn stats(&self) -> Vec<TunnelStats> {
+        self.mappings.read().stats()
+    }
+
+    /// Provides the counters for messages that failed to be forwarded
+    pub fn send_failures(&self) -> &SendFailures {
+        &self.send_failures
+    }
 }
 
 /// Handle for sending messages to a tunnel
@@ -234,6 +317,15 @@ pub struct TunnelHandle {
     tx: mpsc::UnboundedSender<TunnelMessage>,
 }
 
+impl TunnelHandle {
+    /// Sends a message to the tunnel
+    fn send(&self, message: TunnelMessage) -> Result<(), TunnelSendError> {
+        self.tx
+            .send(message)
+            .map_err(|_| TunnelSendError::ChannelClosed)
+    }
+}
+
 /// Tunnel connection to a client
 pub struct Tunnel {
     /// ID for this tunnel
@@ -310,6 +402,7 @@ impl Tunnel {
             TunnelData {
                 association,
                 handle: TunnelHandle { tx },
+                counters: Default::default(),
             },
         );
 
@@ -393,7 +486,7 @@ impl Tunnel {
     /// Should be repeatedly called until it no-longer returns [`Poll::Ready`]
     fn poll_read_state(&mut self, cx: &mut Context<'_>) -> Poll<TunnelReadState> {
         // Try receive a message from the `io`
-        let Some(Ok(mut message)) = ready!(Pin::new(&mut self.io).poll_next(cx)) else {
+        let Some(Ok(message)) = ready!(Pin::new(&mut self.io).poll_next(cx)) else {
             // Cannot read next message stop the tunnel
             return Poll::Ready(TunnelReadState::Stop);
         };
@@ -403,18 +496,7 @@ impl Tunnel {
             return Poll::Ready(TunnelReadState::Continue);
         }
 
-        // Get the path through the tunnel
-        let (target_handle, index) = match self.service.get_tunnel_route(self.id, message.index) {
-            Some(value) => value,
-            // Don't have a tunnel to send the message through
-            None => return Poll::Ready(TunnelReadState::Continue),
-        };
-
-        // Update the message target index to be from the correct index
-        message.index = index;
-
-        // Send the message to the tunnel
-        _ = target_handle.tx.send(message);
+        self.service.forward(self.id, message);
 
         Poll::Ready(TunnelReadState::Continue)
     }
@@ -580,3 +662,180 @@ mod codec {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{codec::TunnelMessage, TunnelData, TunnelHandle, TunnelService};
+    use bytes::Bytes;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    /// Tests that forwarding to a tunnel whose channel has closed
+    /// is counted as a fatal failure
+    #[test]
+    fn test_closed_channel_send_failure() {
+        let service = TunnelService::default();
+
+        // Tunnel in slot 0 (sender) and slot 1 (stopped tunnel)
+        let mut receivers = Vec::new();
+        for tunnel_id in [0, 1] {
+            let association = Uuid::new_v4();
+            let (tx, rx) = mpsc::unbounded_channel();
+            receivers.push(rx);
+
+            let mappings = &mut *service.mappings.write();
+            mappings.insert_tunnel(
+                tunnel_id,
+                TunnelData {
+                    association,
+                    handle: TunnelHandle { tx },
+                    counters: Default::default(),
+                },
+            );
+            mappings.associate_tunnel(association, tunnel_id);
+            mappings.associate_pool(association, 1, tunnel_id as u8);
+        }
+
+        // Stop the second tunnel
+        drop(receivers.pop());
+
+        service.forward(
+            0,
+            TunnelMessage {
+                index: 1,
+                message: Bytes::from_static(&[1, 2, 3]),
+            },
+        );
+
+        assert_eq!(service.send_failures().fatal(), 1);
+        assert_eq!(service.send_failures().transient(), 0);
+    }
+
+    /// Tests that tunnels associated with a pool through the service
+    /// can forward messages to each other
+    #[test]
+    fn test_associate_pool_forward() {
+        let service = TunnelService::default();
+
+        let mut associations = Vec::new();
+        let mut receivers = Vec::new();
+        for tunnel_id in [0, 1] {
+            let association = Uuid::new_v4();
+            let (tx, rx) = mpsc::unbounded_channel();
+            associations.push(association);
+            receivers.push(rx);
+
+            let mappings = &mut *service.mappings.write();
+            mappings.insert_tunnel(
+                tunnel_id,
+                TunnelData {
+                    association,
+                    handle: TunnelHandle { tx },
+                    counters: Default::default(),
+                },
+            );
+            mappings.associate_tunnel(association, tunnel_id);
+        }
+
+        // Unknown associations cannot be added to a pool
+        assert!(!service.associate_pool(Uuid::new_v4(), 1, 2));
+
+        assert!(service.associate_pool(associations[0], 1, 0));
+        assert!(service.associate_pool(associations[1], 1, 1));
+
+        service.forward(
+            0,
+            TunnelMessage {
+                index: 1,
+                message: Bytes::from_static(&[1, 2, 3]),
+            },
+        );
+
+        // Message arrives at the second tunnel from the first tunnel index
+        let message = receivers[1].try_recv().unwrap();
+        assert_eq!(message.index, 0);
+        assert_eq!(message.message.as_ref(), &[1, 2, 3]);
+        assert!(receivers[0].try_recv().is_err());
+    }
+
+    /// Tests that closing all tunnels closes the tunnel channels
+    #[test]
+    fn test_close_all() {
+        let service = TunnelService::default();
+        let association = Uuid::new_v4();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        {
+            let mappings = &mut *service.mappings.write();
+            mappings.insert_tunnel(
+                0,
+                TunnelData {
+                    association,
+                    handle: TunnelHandle { tx },
+                    counters: Default::default(),
+                },
+            );
+            mappings.associate_tunnel(association, 0);
+        }
+
+        service.close_all();
+
+        assert!(!service.associate_pool(association, 1, 0));
+        assert!(matches!(
+            rx.try_recv(),
+            Err(mpsc::error::TryRecvError::Disconnected)
+        ));
+    }
+
+    /// Tests that forwarded messages are counted against the sending
+    /// tunnel in the stats for its pool slot
+    #[test]
+    fn test_forward_stats() {
+        let service = TunnelService::default();
+
+        let mut receivers = Vec::new();
+        for tunnel_id in [0, 1] {
+            let association = Uuid::new_v4();
+            let (tx, rx) = mpsc::unbounded_channel();
+            receivers.push(rx);
+
+            let mappings = &mut *service.mappings.write();
+            mappings.insert_tunnel(
+                tunnel_id,
+                TunnelData {
+                    association,
+                    handle: TunnelHandle { tx },
+                    counters: Default::default(),
+                },
+            );
+            mappings.associate_tunnel(association, tunnel_id);
+            mappings.associate_pool(association, 1, tunnel_id as u8);
+        }
+
+        for _ in 0..2 {
+            service.forward(
+                0,
+                TunnelMessage {
+                    index: 1,
+                    message: Bytes::from_static(&[1, 2, 3]),
+                },
+            );
+        }
+
+        // Messages without a route aren't counted
+        service.forward(
+            1,
+            TunnelMessage {
+                index: 5,
+                message: Bytes::from_static(&[1]),
+            },
+        );
+
+        let stats = service.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].pool_id, stats[0].pool_index), (1, 0));
+        assert_eq!((stats[0].packets, stats[0].bytes), (2, 6));
+        assert_eq!((stats[1].packets, stats[1].bytes), (0, 0));
+        assert_eq!(stats[1].last_alive_seconds, None);
+    }
+}
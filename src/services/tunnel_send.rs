@@ -0,0 +1,176 @@
+//! Tracking for messages sent and failed to be sent through the tunnels,
+//! allows tunnels that have silently stopped working to be observed
+
+use crate::utils::{logging::log_limited, types::GameID};
+use log::{debug, Level};
+use serde::Serialize;
+use std::{
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use thiserror::Error;
+
+/// Windows error code for "An existing connection was forcibly closed
+/// by the remote host."
+const WSAECONNRESET: i32 = 10054;
+
+/// Errors that can occur when sending a message through a tunnel
+#[derive(Debug, Error)]
+pub enum TunnelSendError {
+    /// The channel to the target tunnel was closed (Tunnel has stopped)
+    #[error("tunnel channel closed")]
+    ChannelClosed,
+    /// Failed to send the message over the socket
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl TunnelSendError {
+    /// Whether the error is only temporary and further sends through the
+    /// tunnel may still succeed, fatal errors mean the tunnel or socket
+    /// is no longer usable
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::ChannelClosed => false,
+            Self::Io(err) => {
+                err.raw_os_error() == Some(WSAECONNRESET)
+                    || matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock
+                            | io::ErrorKind::Interrupted
+                            | io::ErrorKind::TimedOut
+                            | io::ErrorKind::ConnectionRefused
+                            | io::ErrorKind::ConnectionReset
+                            | io::ErrorKind::HostUnreachable
+                            | io::ErrorKind::NetworkUnreachable
+                    )
+            }
+        }
+    }
+}
+
+/// Counters for failed tunnel sends
+#[derive(Default)]
+pub struct SendFailures {
+    /// Number of sends that failed with a transient error
+    transient: AtomicU64,
+    /// Number of sends that failed with a fatal error
+    fatal: AtomicU64,
+}
+
+impl SendFailures {
+    /// Records and logs a failed send through the tunnel with `tunnel_id`
+    pub fn record(&self, tunnel_id: u32, err: &TunnelSendError) {
+        if err.is_transient() {
+            self.transient.fetch_add(1, Ordering::Relaxed);
+            debug!(
+                "Transient tunnel send failure (TID: {}): {}",
+                tunnel_id, err
+            );
+        } else {
+            self.fatal.fetch_add(1, Ordering::Relaxed);
+            log_limited(
+                Level::Warn,
+                format!("Fatal tunnel send failure (TID: {}): {}", tunnel_id, err),
+            );
+        }
+    }
+
+    /// Number of sends that failed with a transient error
+    pub fn transient(&self) -> u64 {
+        self.transient.load(Ordering::Relaxed)
+    }
+
+    /// Number of sends that failed with a fatal error
+    pub fn fatal(&self) -> u64 {
+        self.fatal.load(Ordering::Relaxed)
+    }
+
+    /// Creates a snapshot of the current counts
+    pub fn snapshot(&self) -> SendFailuresSnapshot {
+        SendFailuresSnapshot {
+            transient: self.transient(),
+            fatal: self.fatal(),
+        }
+    }
+}
+
+/// Snapshot of the [SendFailures] counts
+#[derive(Debug, Serialize)]
+pub struct SendFailuresSnapshot {
+    /// Number of sends that failed with a transient error
+    pub transient: u64,
+    /// Number of sends that failed with a fatal error
+    pub fatal: u64,
+}
+
+/// Counters for the messages forwarded from a tunnel, updated without
+/// requiring exclusive access to the tunnel mappings
+#[derive(Default)]
+pub struct ForwardCounters {
+    /// Number of messages forwarded
+    packets: AtomicU64,
+    /// Total size in bytes of the forwarded messages
+    bytes: AtomicU64,
+}
+
+impl ForwardCounters {
+    /// Records a forwarded message of `length` bytes
+    pub fn record(&self, length: usize) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(length as u64, Ordering::Relaxed);
+    }
+
+    /// Number of messages forwarded
+    pub fn packets(&self) -> u64 {
+        self.packets.load(Ordering::Relaxed)
+    }
+
+    /// Total size in bytes of the forwarded messages
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// Forwarding statistics for a tunnel within a pool
+#[derive(Debug, Serialize)]
+pub struct TunnelStats {
+    /// ID of the pool the tunnel is in
+    pub pool_id: GameID,
+    /// Index of the tunnel within the pool
+    pub pool_index: u8,
+    /// ID of the tunnel
+    pub tunnel_id: u32,
+    /// Number of messages forwarded from the tunnel
+    pub packets: u64,
+    /// Total size in bytes of the messages forwarded from the tunnel
+    pub bytes: u64,
+    /// Number of seconds since the tunnel was last known to be alive,
+    /// None for tunnels that don't track keep-alive messages
+    pub last_alive_seconds: Option<u64>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SendFailures, TunnelSendError};
+    use std::io;
+
+    /// Tests that failures are counted by whether they are transient
+    #[test]
+    fn test_record_failures() {
+        let failures = SendFailures::default();
+
+        failures.record(1, &TunnelSendError::ChannelClosed);
+        failures.record(
+            1,
+            &TunnelSendError::Io(io::Error::from(io::ErrorKind::ConnectionRefused)),
+        );
+        failures.record(
+            1,
+            &TunnelSendError::Io(io::Error::from(io::ErrorKind::NotConnected)),
+        );
+
+        assert_eq!(failures.transient(), 1);
+        assert_eq!(failures.fatal(), 2);
+    }
+}
@@ -1,21 +1,31 @@
-use super::sessions::{AssociationId, Sessions};
-use crate::utils::{hashing::IntHashMap, types::GameID};
-use log::{debug, error};
+use super::{
+    sessions::{AssociationId, Sessions, TunnelReconnect, VerifyError},
+    tunnel_send::{ForwardCounters, SendFailures, TunnelStats},
+};
+use crate::{
+    config::UdpTunnelConfig,
+    utils::{hashing::IntHashMap, logging::log_limited, types::GameID},
+};
+use log::{debug, error, warn, Level};
 use parking_lot::RwLock;
 use pocket_relay_udp_tunnel::{deserialize_message, serialize_message, TunnelMessage};
 use std::{
     collections::HashMap,
+    future::Future,
+    io,
     net::SocketAddr,
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, SystemTime},
 };
+use thiserror::Error;
 use tokio::{
     net::UdpSocket,
+    sync::watch,
     task::JoinSet,
-    time::{interval_at, Instant, MissedTickBehavior},
+    time::{interval_at, sleep, Instant, MissedTickBehavior},
 };
 
 /// The port bound on clients representing the host player within the socket pool
@@ -30,30 +40,93 @@ type PoolId = GameID;
 
 pub async fn start_udp_tunnel(
     tunnel_addr: SocketAddr,
+    config: &UdpTunnelConfig,
     service: Arc<UdpTunnelService>,
+    shutdown: watch::Receiver<bool>,
 ) -> std::io::Result<()> {
-    let socket = UdpSocket::bind(tunnel_addr).await?;
+    let socket = bind_with_retry(
+        tunnel_addr,
+        config.bind_attempts,
+        Duration::from_millis(config.bind_retry_delay_ms),
+        UdpSocket::bind,
+    )
+    .await?;
     let socket = Arc::new(socket);
 
     debug!("started tunneling server {tunnel_addr}");
 
     // Spawn the task to handle accepting messages
-    tokio::spawn(accept_messages(service.clone(), socket.clone()));
+    tokio::spawn(accept_messages(
+        service.clone(),
+        socket.clone(),
+        shutdown.clone(),
+    ));
 
     // Spawn task to keep connections alive
-    tokio::spawn(keep_alive(service, socket));
+    tokio::spawn(keep_alive(service, socket, shutdown));
 
     Ok(())
 }
 
-/// Reads inbound messages from the tunnel service
-pub async fn accept_messages(service: Arc<UdpTunnelService>, socket: Arc<UdpSocket>) {
+/// Binds to `addr` using `bind` making up to `attempts` attempts with
+/// `delay` between each attempt, allows recovering from transient bind
+/// failures such as the port still being in use during a restart
+///
+/// Returns the error from the last attempt if all attempts fail
+async fn bind_with_retry<T, F, Fut>(
+    addr: SocketAddr,
+    attempts: u32,
+    delay: Duration,
+    mut bind: F,
+) -> io::Result<T>
+where
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+{
+    let attempts = attempts.max(1);
+    let mut attempt = 1;
+
+    loop {
+        match bind(addr).await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < attempts => {
+                warn!(
+                    "Failed to bind tunnel socket {addr} (Attempt {attempt}/{attempts}): {err}, retrying"
+                );
+            }
+            Err(err) => return Err(err),
+        }
+
+        attempt += 1;
+        sleep(delay).await;
+    }
+}
+
+/// Reads inbound messages from the tunnel service until `shutdown` is
+/// signaled, messages still being handled are completed before returning
+pub async fn accept_messages(
+    service: Arc<UdpTunnelService>,
+    socket: Arc<UdpSocket>,
+    mut shutdown: watch::Receiver<bool>,
+) {
     // Buffer to recv messages
     let mut buffer = [0; u16::MAX as usize];
 
+    // Tasks handling messages
+    let mut handle_task_set = JoinSet::new();
+
     loop {
+        // Clear out completed message handling tasks
+        while handle_task_set.try_join_next().is_some() {}
+
+        let result = tokio::select! {
+            // Closed channels are treated as a shutdown
+            _ = shutdown.wait_for(|value| *value) => break,
+            result = socket.recv_from(&mut buffer) => result,
+        };
+
         // Receive the message bytes
-        let (size, addr) = match socket.recv_from(&mut buffer).await {
+        let (size, addr) = match result {
             Ok(value) => value,
             Err(err) => {
                 if let Some(error_code) = err.raw_os_error() {
@@ -65,7 +138,7 @@ pub async fn accept_messages(service: Arc<UdpTunnelService>, socket: Arc<UdpSock
                     }
                 }
 
-                error!("failed to recv message: {err}");
+                log_limited(Level::Error, format!("failed to recv message: {err}"));
                 continue;
             }
         };
@@ -76,7 +149,10 @@ pub async fn accept_messages(service: Arc<UdpTunnelService>, socket: Arc<UdpSock
         let packet = match deserialize_message(buffer) {
             Ok(value) => value,
             Err(err) => {
-                error!("failed to deserialize packet: {}", err);
+                log_limited(
+                    Level::Error,
+                    format!("failed to deserialize packet: {}", err),
+                );
                 continue;
             }
         };
@@ -87,14 +163,23 @@ pub async fn accept_messages(service: Arc<UdpTunnelService>, socket: Arc<UdpSock
         let socket = socket.clone();
 
         // Handle the message in its own task
-        tokio::spawn(async move {
+        handle_task_set.spawn(async move {
             service
                 .handle_message(socket, tunnel_id, packet.message, addr)
                 .await;
         });
     }
+
+    debug!("stopping tunneling server, waiting for in-flight messages");
+
+    // Finish handling any in-flight messages
+    while handle_task_set.join_next().await.is_some() {}
 }
 
+/// Duration a tunnel reconnect token remains valid for, tokens are also
+/// no longer accepted once the player leaves the game
+const RECONNECT_TOKEN_EXPIRY: Duration = Duration::from_secs(60 * 60 /* 1 hour */);
+
 /// Delay between each keep-alive packet
 const KEEP_ALIVE_DELAY: Duration = Duration::from_secs(10);
 
@@ -103,8 +188,15 @@ const KEEP_ALIVE_DELAY: Duration = Duration::from_secs(10);
 const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(KEEP_ALIVE_DELAY.as_secs() * 4);
 
 /// Background task that sends out keep alive messages to all the sockets connected
-/// to the tunnel system. Removes inactive and dead connections
-pub async fn keep_alive(service: Arc<UdpTunnelService>, socket: Arc<UdpSocket>) {
+/// to the tunnel system. Removes inactive and dead connections.
+///
+/// Stops once `shutdown` is signaled, removing all the tunnels so no
+/// further messages are forwarded
+pub async fn keep_alive(
+    service: Arc<UdpTunnelService>,
+    socket: Arc<UdpSocket>,
+    mut shutdown: watch::Receiver<bool>,
+) {
     // Task set for keep alive tasks
     let mut send_task_set = JoinSet::new();
 
@@ -116,7 +208,11 @@ pub async fn keep_alive(service: Arc<UdpTunnelService>, socket: Arc<UdpSocket>)
 
     loop {
         // Wait for the next keep-alive tick
-        keep_alive_interval.tick().await;
+        tokio::select! {
+            // Closed channels are treated as a shutdown
+            _ = shutdown.wait_for(|value| *value) => break,
+            _ = keep_alive_interval.tick() => {}
+        }
 
         let now = Instant::now();
 
@@ -140,7 +236,7 @@ pub async fn keep_alive(service: Arc<UdpTunnelService>, socket: Arc<UdpSocket>)
 
         // Send out keep-alive messages for any tunnels that aren't expired
         for (tunnel_id, addr, last_alive) in tunnels {
-            let last_alive = last_alive.duration_since(now);
+            let last_alive = now.duration_since(last_alive);
             if last_alive > KEEP_ALIVE_TIMEOUT {
                 expired_tunnels.push(tunnel_id);
                 continue;
@@ -152,12 +248,16 @@ pub async fn keep_alive(service: Arc<UdpTunnelService>, socket: Arc<UdpSocket>)
             send_task_set.spawn({
                 let socket = socket.clone();
 
-                async move { socket.send_to(&buffer, addr).await }
+                async move { (tunnel_id, socket.send_to(&buffer, addr).await) }
             });
         }
 
         // Join all keep alive tasks
-        while send_task_set.join_next().await.is_some() {}
+        while let Some(result) = send_task_set.join_next().await {
+            if let Ok((tunnel_id, Err(err))) = result {
+                service.send_failures.record(tunnel_id, &err.into());
+            }
+        }
 
         // Drop any tunnel connections that have passed acceptable keep-alive bounds
         if !expired_tunnels.is_empty() {
@@ -168,6 +268,10 @@ pub async fn keep_alive(service: Arc<UdpTunnelService>, socket: Arc<UdpSocket>)
             }
         }
     }
+
+    // The tunnel protocol has no close message, clients are left to
+    // time out once they stop receiving keep-alive messages
+    service.close_all();
 }
 
 /// UDP tunneling service
@@ -179,6 +283,8 @@ pub struct UdpTunnelService {
     /// Access to the session service for exchanging
     /// association tokens
     sessions: Arc<Sessions>,
+    /// Messages that failed to be sent through the tunnel socket
+    send_failures: SendFailures,
 }
 
 pub struct TunnelData {
@@ -188,6 +294,8 @@ pub struct TunnelData {
     addr: SocketAddr,
     /// Last time a keep alive was obtained for the tunnel
     last_alive: Instant,
+    /// Counters for messages forwarded from the tunnel
+    counters: ForwardCounters,
 }
 
 #[derive(Default)]
@@ -205,6 +313,27 @@ pub struct TunnelMappings {
     /// Inverse mapping of `tunnel_to_index` for finding the handle
     /// associated to a specific pool and slot
     index_to_tunnel: IntHashMap<PoolKey, TunnelId>,
+
+    /// Mapping from [AssociationId] to the pool slot reserved for it, kept
+    /// while the tunnel is disconnected so the slot can be reconnected to
+    association_to_pool: HashMap<AssociationId, PoolKey>,
+    /// Inverse mapping of `association_to_pool`
+    pool_to_association: IntHashMap<PoolKey, AssociationId>,
+}
+
+/// Errors that can occur when initiating a tunnel
+#[derive(Debug, Error)]
+pub enum InitiateError {
+    /// The association or reconnect token was not valid
+    #[error(transparent)]
+    Token(#[from] VerifyError),
+    /// The pool slot from the reconnect token is no longer reserved
+    /// for the association (Player left or the game ended)
+    #[error("pool slot is no longer reserved for the association")]
+    SlotReleased,
+    /// No more tunnel IDs could be allocated
+    #[error("failed to allocate a tunnel ID: exhausted")]
+    Exhausted,
 }
 
 /// Represents a key that is created from a [PoolId] and [PoolIndex] combined
@@ -270,13 +399,17 @@ impl TunnelMappings {
         pool_id: PoolId,
         pool_index: PoolIndex,
     ) {
+        let key = PoolKey::new(pool_id, pool_index);
+
+        // Reserve the slot for reconnecting
+        self.association_to_pool.insert(association, key);
+        self.pool_to_association.insert(key, association);
+
         let tunnel_id = match self.association_to_tunnel.get(&association) {
             Some(value) => *value,
             None => return,
         };
 
-        let key = PoolKey::new(pool_id, pool_index);
-
         self.tunnel_to_index.insert(tunnel_id, key);
         self.index_to_tunnel.insert(key, tunnel_id);
     }
@@ -300,6 +433,38 @@ impl TunnelMappings {
         Some((tunnel.addr, self_index))
     }
 
+    /// Records a message of `length` bytes forwarded from the tunnel
+    /// with `tunnel_id`
+    fn record_forward(&self, tunnel_id: TunnelId, length: usize) {
+        if let Some(tunnel) = self.id_to_tunnel.get(&tunnel_id) {
+            tunnel.counters.record(length);
+        }
+    }
+
+    /// Creates a snapshot of the forwarding statistics for each tunnel
+    /// that is within a pool
+    fn stats(&self, now: Instant) -> Vec<TunnelStats> {
+        let mut stats: Vec<TunnelStats> = self
+            .index_to_tunnel
+            .iter()
+            .filter_map(|(key, tunnel_id)| {
+                let tunnel = self.id_to_tunnel.get(tunnel_id)?;
+                let (pool_id, pool_index) = key.parts();
+
+                Some(TunnelStats {
+                    pool_id,
+                    pool_index,
+                    tunnel_id: *tunnel_id,
+                    packets: tunnel.counters.packets(),
+                    bytes: tunnel.counters.bytes(),
+                    last_alive_seconds: Some(now.duration_since(tunnel.last_alive).as_secs()),
+                })
+            })
+            .collect();
+        stats.sort_by_key(|stats| (stats.pool_id, stats.pool_index));
+        stats
+    }
+
     /// Removes the association between the `tunnel_id` and any games and
     /// removes the tunnel itself.
     ///
@@ -320,15 +485,51 @@ impl TunnelMappings {
         }
     }
 
+    /// Removes the tunnel and any reserved pool slot for the provided
+    /// `association`
+    fn dissociate_association(&mut self, association: &AssociationId) {
+        if let Some(tunnel_id) = self.association_to_tunnel.get(association).copied() {
+            self.dissociate_tunnel(tunnel_id);
+        }
+
+        // Release the slot reservation
+        if let Some(key) = self.association_to_pool.remove(association) {
+            self.pool_to_association.remove(&key);
+        }
+    }
+
     /// Removes the association between a [PoolKey] and a [TunnelId] if
     /// one is present
     fn dissociate_pool(&mut self, pool_id: PoolId, pool_index: PoolIndex) {
-        if let Some(tunnel_id) = self
-            .index_to_tunnel
-            .remove(&PoolKey::new(pool_id, pool_index))
-        {
+        let key = PoolKey::new(pool_id, pool_index);
+
+        if let Some(tunnel_id) = self.index_to_tunnel.remove(&key) {
             self.tunnel_to_index.remove(&tunnel_id);
         }
+
+        // Release the slot reservation
+        if let Some(association) = self.pool_to_association.remove(&key) {
+            self.association_to_pool.remove(&association);
+        }
+    }
+
+    /// Finds the pool slot reserved for the provided `association`
+    fn reserved_pool(&self, association: &AssociationId) -> Option<(PoolId, PoolIndex)> {
+        self.association_to_pool
+            .get(association)
+            .map(|key| key.parts())
+    }
+
+    /// Checks whether the pool slot is still reserved for the `association`
+    fn is_pool_reserved(
+        &self,
+        association: &AssociationId,
+        pool_id: PoolId,
+        pool_index: PoolIndex,
+    ) -> bool {
+        self.pool_to_association
+            .get(&PoolKey::new(pool_id, pool_index))
+            .is_some_and(|value| value == association)
     }
 }
 
@@ -338,9 +539,27 @@ impl UdpTunnelService {
             next_tunnel_id: AtomicU32::new(0),
             mappings: RwLock::new(TunnelMappings::default()),
             sessions,
+            send_failures: SendFailures::default(),
         }
     }
 
+    /// Wrapper around [`TunnelMappings::stats`] that holds the service
+    /// read lock before operating
+    #[inline]
+    pub fn stats(&self) -> Vec<TunnelStats> {
+        self.mappings.read().stats(Instant::now())
+    }
+
+    /// Removes all the tunnels and their pool associations
+    pub fn close_all(&self) {
+        *self.mappings.write() = TunnelMappings::default();
+    }
+
+    /// Provides the counters for messages that failed to be sent
+    pub fn send_failures(&self) -> &SendFailures {
+        &self.send_failures
+    }
+
     /// Wrapper around [`TunnelMappings::associate_pool`] that holds the service
     /// write lock before operating
     #[inline]
@@ -373,6 +592,87 @@ impl UdpTunnelService {
         self.mappings.write().dissociate_pool(pool_id, pool_index);
     }
 
+    /// Wrapper around [`TunnelMappings::dissociate_tunnel`] that holds the service
+    /// write lock before operating
+    #[inline]
+    pub fn remove_tunnel(&self, tunnel_id: TunnelId) {
+        self.mappings.write().dissociate_tunnel(tunnel_id);
+    }
+
+    /// Wrapper around [`TunnelMappings::dissociate_association`] that holds the
+    /// service write lock before operating
+    #[inline]
+    pub fn remove_association(&self, association: &AssociationId) {
+        self.mappings.write().dissociate_association(association);
+    }
+
+    /// Creates a tunnel reconnect token for the pool slot reserved for
+    /// the provided `association`, [None] if no slot is reserved
+    pub fn create_reconnect_token(&self, association: AssociationId) -> Option<String> {
+        let (pool_id, pool_index) = self.mappings.read().reserved_pool(&association)?;
+
+        Some(self.sessions.create_tunnel_reconnect_token(
+            &TunnelReconnect {
+                association,
+                pool_id,
+                pool_index,
+            },
+            SystemTime::now() + RECONNECT_TOKEN_EXPIRY,
+        ))
+    }
+
+    /// Initiates a new tunnel at `addr` from the provided `token` which
+    /// is either an association token or a tunnel reconnect token.
+    ///
+    /// Reconnect tokens rebind the new tunnel to the pool slot from the
+    /// token replacing any previous tunnel for the association
+    fn initiate_tunnel(&self, token: &str, addr: SocketAddr) -> Result<TunnelId, InitiateError> {
+        let (association, reconnect) = match self.sessions.verify_assoc_token(token) {
+            Ok(association) => (association, None),
+            // Tokens that aren't association tokens may be reconnect tokens
+            Err(_) => {
+                let reconnect = self.sessions.verify_tunnel_reconnect_token(token)?;
+                (reconnect.association, Some(reconnect))
+            }
+        };
+
+        // Attempt to acquire an available tunnel ID
+        let tunnel_id = self.acquire_tunnel_id().ok_or(InitiateError::Exhausted)?;
+
+        let mappings = &mut *self.mappings.write();
+
+        if let Some(reconnect) = reconnect {
+            if !mappings.is_pool_reserved(&association, reconnect.pool_id, reconnect.pool_index) {
+                return Err(InitiateError::SlotReleased);
+            }
+
+            // Remove the previous tunnel if its still present
+            if let Some(previous) = mappings.association_to_tunnel.get(&association).copied() {
+                mappings.dissociate_tunnel(previous);
+            }
+        }
+
+        mappings.associate_tunnel(association, tunnel_id);
+
+        // Store the tunnel mapping
+        mappings.insert_tunnel(
+            tunnel_id,
+            TunnelData {
+                addr,
+                association,
+                last_alive: Instant::now(),
+                counters: Default::default(),
+            },
+        );
+
+        // Rebind the reconnected tunnel to its pool slot
+        if let Some(reconnect) = reconnect {
+            mappings.associate_pool(association, reconnect.pool_id, reconnect.pool_index);
+        }
+
+        Ok(tunnel_id)
+    }
+
     /// Attempts to obtain the next available tunnel ID to allocate to
     /// a new tunnel, will return [None] if all IDs are determined to
     /// have been exhausted
@@ -421,41 +721,19 @@ impl UdpTunnelService {
 
         match msg {
             TunnelMessage::Initiate { association_token } => {
-                let association = match self.sessions.verify_assoc_token(&association_token) {
+                let tunnel_id = match self.initiate_tunnel(&association_token, addr) {
                     Ok(value) => value,
                     Err(err) => {
-                        error!("client send invalid association token: {}", err);
-                        return;
-                    }
-                };
-
-                // Attempt to acquire an available tunnel ID
-                let tunnel_id = match self.acquire_tunnel_id() {
-                    Some(value) => value,
-                    // Cannot allocate the tunnel an ID
-                    None => {
-                        error!("failed to allocate a tunnel ID: exhausted");
+                        error!("failed to initiate tunnel: {}", err);
                         return;
                     }
                 };
 
-                self.mappings
-                    .write()
-                    .associate_tunnel(association, tunnel_id);
-
-                // Store the tunnel mapping
-                self.mappings.write().insert_tunnel(
-                    tunnel_id,
-                    TunnelData {
-                        addr,
-                        association,
-                        last_alive: Instant::now(),
-                    },
-                );
-
                 let buffer = serialize_message(tunnel_id, &TunnelMessage::Initiated { tunnel_id });
 
-                _ = socket.send_to(&buffer, addr).await;
+                if let Err(err) = socket.send_to(&buffer, addr).await {
+                    self.send_failures.record(tunnel_id, &err.into());
+                }
             }
             TunnelMessage::Initiated { .. } => {
                 // Server shouldn't be receiving this message... ignore it
@@ -470,10 +748,17 @@ impl UdpTunnelService {
                     }
                 };
 
+                // Counters are atomic so only the read lock is required
+                self.mappings
+                    .read()
+                    .record_forward(tunnel_id, message.len());
+
                 let buffer =
                     serialize_message(tunnel_id, &TunnelMessage::Forward { index, message });
 
-                _ = socket.send_to(&buffer, target_addr).await;
+                if let Err(err) = socket.send_to(&buffer, target_addr).await {
+                    self.send_failures.record(tunnel_id, &err.into());
+                }
             }
             TunnelMessage::KeepAlive => {
                 // Update tunnel last alive time
@@ -484,3 +769,258 @@ impl UdpTunnelService {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        accept_messages, bind_with_retry, keep_alive, InitiateError, UdpTunnelService,
+        KEEP_ALIVE_TIMEOUT,
+    };
+    use crate::{
+        services::sessions::{Sessions, TunnelReconnect, VerifyError},
+        session::test_util::test_sessions,
+    };
+    use pocket_relay_udp_tunnel::TunnelMessage;
+    use std::{
+        io,
+        net::SocketAddr,
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+    use tokio::{net::UdpSocket, sync::watch};
+
+    /// Creates a new tunnel service
+    fn create_service() -> (UdpTunnelService, Arc<Sessions>) {
+        let sessions = test_sessions();
+        (UdpTunnelService::new(sessions.clone()), sessions)
+    }
+
+    /// Tests that a bind which fails once is retried and the tunnel
+    /// socket is bound on the next attempt
+    #[tokio::test]
+    async fn test_bind_retry() {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let mut attempts = 0;
+
+        let socket = bind_with_retry(addr, 3, Duration::from_millis(10), |addr| {
+            attempts += 1;
+            let fail = attempts == 1;
+            async move {
+                if fail {
+                    return Err(io::ErrorKind::AddrInUse.into());
+                }
+                UdpSocket::bind(addr).await
+            }
+        })
+        .await;
+
+        assert!(socket.is_ok());
+        assert_eq!(attempts, 2);
+    }
+
+    /// Tests that binding gives up with the error once all the
+    /// attempts have failed
+    #[tokio::test]
+    async fn test_bind_retry_exhausted() {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let mut attempts = 0;
+
+        let result: io::Result<()> = bind_with_retry(addr, 3, Duration::from_millis(10), |_| {
+            attempts += 1;
+            async { Err(io::ErrorKind::AddrInUse.into()) }
+        })
+        .await;
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::AddrInUse);
+        assert_eq!(attempts, 3);
+    }
+
+    /// Tests that a reconnect token rebinds a new tunnel to the pool
+    /// slot the client was assigned
+    #[test]
+    fn test_reconnect_rebinds_slot() {
+        let (service, sessions) = create_service();
+
+        let host_addr: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+        let reconnect_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+
+        // Host in slot 0 and client in slot 1
+        let host_token = sessions.create_assoc_token();
+        let host_association = sessions.verify_assoc_token(&host_token).unwrap();
+        let client_token = sessions.create_assoc_token();
+        let client_association = sessions.verify_assoc_token(&client_token).unwrap();
+
+        let host_tunnel = service.initiate_tunnel(&host_token, host_addr).unwrap();
+        let client_tunnel = service.initiate_tunnel(&client_token, client_addr).unwrap();
+
+        service.associate_pool(host_association, 1, 0);
+        service.associate_pool(client_association, 1, 1);
+        assert_eq!(
+            service.get_tunnel_route(host_tunnel, 1),
+            Some((client_addr, 0))
+        );
+
+        // Client tunnel drops
+        service.remove_tunnel(client_tunnel);
+        assert_eq!(service.get_tunnel_route(host_tunnel, 1), None);
+
+        let token = service.create_reconnect_token(client_association).unwrap();
+        let new_tunnel = service.initiate_tunnel(&token, reconnect_addr).unwrap();
+
+        assert_eq!(
+            service.get_tunnel_route(host_tunnel, 1),
+            Some((reconnect_addr, 0))
+        );
+        assert_eq!(
+            service.get_tunnel_route(new_tunnel, 0),
+            Some((host_addr, 1))
+        );
+    }
+
+    /// Tests that removing an association that isn't in a game removes
+    /// its tunnel
+    #[test]
+    fn test_remove_association() {
+        let (service, sessions) = create_service();
+        let addr: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+
+        let token = sessions.create_assoc_token();
+        let association = sessions.verify_assoc_token(&token).unwrap();
+        let tunnel_id = service.initiate_tunnel(&token, addr).unwrap();
+        assert!(service.mappings.read().tunnel_exists(tunnel_id));
+
+        service.remove_association(&association);
+
+        let mappings = service.mappings.read();
+        assert!(!mappings.tunnel_exists(tunnel_id));
+        assert!(!mappings.association_to_tunnel.contains_key(&association));
+    }
+
+    /// Tests that expired reconnect tokens and tokens for slots that
+    /// have been released are rejected
+    #[test]
+    fn test_reconnect_rejected() {
+        let (service, sessions) = create_service();
+        let addr: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+
+        let association = sessions
+            .verify_assoc_token(&sessions.create_assoc_token())
+            .unwrap();
+        service.associate_pool(association, 1, 1);
+
+        let expired = sessions.create_tunnel_reconnect_token(
+            &TunnelReconnect {
+                association,
+                pool_id: 1,
+                pool_index: 1,
+            },
+            SystemTime::now() - Duration::from_secs(60),
+        );
+        assert!(matches!(
+            service.initiate_tunnel(&expired, addr),
+            Err(InitiateError::Token(VerifyError::Expired))
+        ));
+
+        // Player leaves the game
+        let token = service.create_reconnect_token(association).unwrap();
+        service.dissociate_pool(1, 1);
+        assert!(service.create_reconnect_token(association).is_none());
+        assert!(matches!(
+            service.initiate_tunnel(&token, addr),
+            Err(InitiateError::SlotReleased)
+        ));
+    }
+
+    /// Tests that signaling shutdown stops accepting tunnel messages
+    #[tokio::test]
+    async fn test_accept_shutdown() {
+        let (service, _) = create_service();
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let task = tokio::spawn(accept_messages(Arc::new(service), socket, shutdown_rx));
+
+        shutdown_tx.send(true).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("Accept loop should stop after shutdown")
+            .unwrap();
+    }
+
+    /// Tests that the keep-alive task removes tunnels that have stopped
+    /// responding and removes all tunnels once shutdown is signaled
+    #[tokio::test(start_paused = true)]
+    async fn test_keep_alive_shutdown() {
+        let (service, sessions) = create_service();
+        let service = Arc::new(service);
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let addr: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let token = sessions.create_assoc_token();
+        service.initiate_tunnel(&token, addr).unwrap();
+
+        let task = tokio::spawn(keep_alive(service.clone(), socket, shutdown_rx));
+
+        // Tunnel is kept while within the timeout
+        tokio::time::sleep(KEEP_ALIVE_TIMEOUT / 2).await;
+        assert_eq!(service.mappings.read().id_to_tunnel.len(), 1);
+
+        // Tunnel is removed once it has timed out
+        tokio::time::sleep(KEEP_ALIVE_TIMEOUT).await;
+        assert!(service.mappings.read().id_to_tunnel.is_empty());
+
+        let token = sessions.create_assoc_token();
+        service.initiate_tunnel(&token, addr).unwrap();
+
+        shutdown_tx.send(true).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("Keep-alive task should stop after shutdown")
+            .unwrap();
+        assert!(service.mappings.read().id_to_tunnel.is_empty());
+    }
+
+    /// Tests that forwarded messages are counted against the sending
+    /// tunnel in the stats for its pool slot
+    #[tokio::test]
+    async fn test_forward_stats() {
+        let (service, sessions) = create_service();
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+
+        let host_addr: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+
+        let host_token = sessions.create_assoc_token();
+        let host_association = sessions.verify_assoc_token(&host_token).unwrap();
+        let client_token = sessions.create_assoc_token();
+        let client_association = sessions.verify_assoc_token(&client_token).unwrap();
+
+        let host_tunnel = service.initiate_tunnel(&host_token, host_addr).unwrap();
+        service.initiate_tunnel(&client_token, client_addr).unwrap();
+        service.associate_pool(host_association, 1, 0);
+        service.associate_pool(client_association, 1, 1);
+
+        service
+            .handle_message(
+                socket,
+                host_tunnel,
+                TunnelMessage::Forward {
+                    index: 1,
+                    message: vec![1, 2, 3, 4],
+                },
+                host_addr,
+            )
+            .await;
+
+        let stats = service.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].tunnel_id, host_tunnel);
+        assert_eq!((stats[0].packets, stats[0].bytes), (1, 4));
+        assert_eq!((stats[1].packets, stats[1].bytes), (0, 0));
+        assert_eq!(stats[0].last_alive_seconds, Some(0));
+    }
+}
@@ -1,5 +1,6 @@
 use std::{net::Ipv4Addr, sync::Arc, task::Context, time::Duration};
 
+use log::warn;
 use parking_lot::{RwLock, RwLockReadGuard};
 use serde::Serialize;
 use tokio::time::{interval_at, Instant, Interval, MissedTickBehavior};
@@ -48,6 +49,15 @@ struct SessionDataExt {
 
     /// Keep-alive data for the session
     keep_alive: SessionDataKeepAlive,
+
+    /// Number of requests from the session that resulted in an error
+    error_count: u32,
+
+    /// Maximum number of errors before the session is disconnected
+    max_errors: Option<u32>,
+
+    /// Maximum number of subscribers the session can have at once
+    max_subscriptions: Option<usize>,
 }
 
 impl SessionDataExt {
@@ -55,6 +65,9 @@ impl SessionDataExt {
         Self {
             auth: None,
             keep_alive: SessionDataKeepAlive::new(),
+            error_count: 0,
+            max_errors: None,
+            max_subscriptions: None,
         }
     }
 }
@@ -63,12 +76,29 @@ pub struct SessionDataKeepAlive {
     /// Last time a keep-alive message was received through the tunnel
     pub last_keep_alive: Instant,
 
-    /// Time that has been granted as a grace period to allow the
-    /// session to go without a keep-alive message for
-    pub extended_grace: Duration,
+    /// Time the client has suspended sending keep-alive messages until, the
+    /// timeout is measured from this time when its after the last keep-alive
+    pub suspended_until: Option<Instant>,
 
     /// Interval for polling connection alive checks
     pub keep_alive_interval: Interval,
+
+    /// Whether the server sends a heartbeat before timing out the session
+    pub server_heartbeat: bool,
+
+    /// Time the server sent a heartbeat that has not been followed by a packet
+    pub heartbeat_sent: Option<Instant>,
+}
+
+/// Result of a keep-alive check
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeepAliveState {
+    /// The connection is alive
+    Alive,
+    /// The connection has been idle and should be sent a heartbeat
+    Heartbeat,
+    /// The connection is considered to be dead
+    Dead,
 }
 
 /// Delay between each keep-alive check
@@ -78,6 +108,10 @@ pub const KEEP_ALIVE_DELAY: Duration = Duration::from_secs(15);
 /// the connection is considered to be dead (4 missed keep-alive check intervals)
 pub const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(KEEP_ALIVE_DELAY.as_secs() * 4);
 
+/// When this duration elapses after sending a server heartbeat without receiving
+/// any packets the connection is considered to be dead (2 keep-alive check intervals)
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(KEEP_ALIVE_DELAY.as_secs() * 2);
+
 impl SessionDataKeepAlive {
     fn new() -> Self {
         let now = Instant::now();
@@ -90,8 +124,47 @@ impl SessionDataKeepAlive {
 
         Self {
             last_keep_alive: Instant::now(),
-            extended_grace: Duration::from_secs(0),
+            suspended_until: None,
             keep_alive_interval,
+            server_heartbeat: false,
+            heartbeat_sent: None,
+        }
+    }
+
+    /// Checks whether the connection should be considered dead at `now`
+    /// based on the last keep-alive and any suspended period
+    fn is_expired(&self, now: Instant) -> bool {
+        let mut alive_at = self.last_keep_alive;
+
+        // Suspended periods extend the deadline
+        if let Some(suspended_until) = self.suspended_until {
+            alive_at = alive_at.max(suspended_until);
+        }
+
+        now.saturating_duration_since(alive_at) > KEEP_ALIVE_TIMEOUT
+    }
+
+    /// Checks the state of the connection at `now`, when server heartbeats are
+    /// enabled expired connections are sent a heartbeat and only considered dead
+    /// once no packets are received within the [HEARTBEAT_TIMEOUT]
+    fn check(&mut self, now: Instant) -> KeepAliveState {
+        if !self.is_expired(now) {
+            return KeepAliveState::Alive;
+        }
+
+        if !self.server_heartbeat {
+            return KeepAliveState::Dead;
+        }
+
+        match self.heartbeat_sent {
+            None => {
+                self.heartbeat_sent = Some(now);
+                KeepAliveState::Heartbeat
+            }
+            Some(sent) if now.saturating_duration_since(sent) > HEARTBEAT_TIMEOUT => {
+                KeepAliveState::Dead
+            }
+            Some(_) => KeepAliveState::Alive,
         }
     }
 }
@@ -106,53 +179,93 @@ impl SessionData {
         }
     }
 
-    /// Polls the keep alive check to see if its ready and if the connection is dead
-    pub fn poll_keep_alive_dead(&self, cx: &mut Context<'_>) -> bool {
+    /// Polls the keep alive check to see if its ready and the state of the connection
+    pub fn poll_keep_alive(&self, cx: &mut Context<'_>) -> KeepAliveState {
         let keep_alive = &mut self.ext.write().keep_alive;
 
         // Not ready to perform a keep-alive check
         if !keep_alive.keep_alive_interval.poll_tick(cx).is_ready() {
-            return false;
+            return KeepAliveState::Alive;
         }
 
-        // Check the keep alive state
-        let now = Instant::now();
-        let last_alive = keep_alive
-            .last_keep_alive
-            // Get time since last keep alive message
-            .duration_since(now)
-            // Remove current grace period from the elapsed time
-            .saturating_sub(keep_alive.extended_grace);
+        keep_alive.check(Instant::now())
+    }
 
-        // Connection to the client has timed out as no keep alive messages were
-        // given by the client
-        last_alive > KEEP_ALIVE_TIMEOUT
+    /// Sets whether the server sends a heartbeat to idle connections before
+    /// considering them dead, when enabled any received packet keeps the
+    /// connection alive
+    pub fn set_server_heartbeat(&self, enabled: bool) {
+        self.ext.write().keep_alive.server_heartbeat = enabled;
+    }
+
+    /// Marks a packet as received from the connection, only keeps the
+    /// connection alive when server heartbeats are enabled
+    pub fn set_packet_received(&self) {
+        let keep_alive = &mut self.ext.write().keep_alive;
+        if !keep_alive.server_heartbeat {
+            return;
+        }
+
+        keep_alive.heartbeat_sent = None;
+        keep_alive.last_keep_alive = Instant::now();
     }
 
     /// Sets the connection as alive
     pub fn set_alive(&self) {
         let keep_alive = &mut self.ext.write().keep_alive;
 
-        // Clear existing grace period
-        keep_alive.extended_grace = Duration::from_secs(0);
+        // Clear existing suspended period and heartbeat
+        keep_alive.suspended_until = None;
+        keep_alive.heartbeat_sent = None;
 
         // Mark current alive period
         keep_alive.last_keep_alive = Instant::now();
     }
 
     /// Grants a grace period duration where the client is allowed to not send any keep-alive
-    /// messages and won't be timed-out for doing so
+    /// messages and won't be timed-out for doing so, the keep-alive deadline is extended
+    /// until the grace period has elapsed
     pub fn set_keep_alive_grace(&self, grace: Duration) {
         let keep_alive = &mut self.ext.write().keep_alive;
-        let now = Instant::now()
+        let suspended_until = Instant::now()
             .checked_add(grace)
             .expect("reached limit of time");
 
         // Delay next keep alive check
-        keep_alive.keep_alive_interval.reset_at(now);
+        keep_alive.keep_alive_interval.reset_at(suspended_until);
 
-        // Apply grace period to next check
-        keep_alive.extended_grace = grace;
+        // Apply grace period to the following checks
+        keep_alive.suspended_until = Some(suspended_until);
+    }
+
+    /// Sets the maximum number of errors the session can cause before
+    /// it should be disconnected, None to allow any number of errors
+    pub fn set_max_errors(&self, max_errors: Option<u32>) {
+        self.ext.write().max_errors = max_errors;
+    }
+
+    /// Sets the maximum number of subscribers the session can have at
+    /// once, None to allow any number of subscribers
+    pub fn set_max_subscriptions(&self, max_subscriptions: Option<usize>) {
+        self.ext.write().max_subscriptions = max_subscriptions;
+    }
+
+    /// Records a request from the session that resulted in an error,
+    /// returns whether the session has exceeded its maximum errors
+    pub fn record_error(&self) -> bool {
+        let ext = &mut *self.ext.write();
+        ext.error_count = ext.error_count.saturating_add(1);
+        ext.max_errors.is_some_and(|max| ext.error_count > max)
+    }
+
+    /// Obtains the number of requests that resulted in an error
+    pub fn error_count(&self) -> u32 {
+        self.read().error_count
+    }
+
+    /// Resets the number of requests that resulted in an error
+    pub fn reset_errors(&self) {
+        self.ext.write().error_count = 0;
     }
 
     pub fn get_addr(&self) -> Ipv4Addr {
@@ -215,6 +328,17 @@ impl SessionData {
             .map(|value| value.player_assoc.player.clone())
     }
 
+    /// Marks the session as having logged into the persona of the
+    /// authenticated player
+    pub fn set_persona(&self) {
+        self.write_silent(|data| data.persona = true);
+    }
+
+    /// Checks whether the session has logged into its persona
+    pub fn has_persona(&self) -> bool {
+        self.read().auth.as_ref().is_some_and(|value| value.persona)
+    }
+
     /// Obtains the parts required to create a game player
     pub fn get_game_player_data(&self) -> Option<(Arc<Player>, Arc<NetData>)> {
         self.read()
@@ -292,9 +416,34 @@ impl SessionData {
         })
     }
 
-    /// Adds a subscriber to the session
-    pub fn add_subscriber(&self, player_id: PlayerID, subscriber: SessionNotifyHandle) {
-        self.write_silent(|data| data.add_subscriber(player_id, subscriber));
+    /// Adds a subscriber to the session, returns whether the subscriber
+    /// was added. Subscribers beyond the maximum are rejected
+    pub fn add_subscriber(&self, player_id: PlayerID, subscriber: SessionNotifyHandle) -> bool {
+        let ext = &mut *self.ext.write();
+        let max_subscriptions = ext.max_subscriptions;
+        let Some(data) = ext.auth.as_mut() else {
+            return false;
+        };
+
+        if max_subscriptions.is_some_and(|max| data.subscribers.len() >= max) {
+            warn!(
+                "Rejected subscription from player {} to player {}, session is at the maximum subscriptions",
+                player_id, data.player_assoc.player.id
+            );
+            return false;
+        }
+
+        data.add_subscriber(player_id, subscriber);
+        true
+    }
+
+    /// Obtains the number of subscribers to the session
+    pub fn subscriber_count(&self) -> usize {
+        self.read()
+            .auth
+            .as_ref()
+            .map(|data| data.subscribers.len())
+            .unwrap_or_default()
     }
 
     /// Removes a subscriber from the session
@@ -311,6 +460,8 @@ struct SessionDataAuth {
     net: Arc<NetData>,
     /// Currently connected game for the session
     game: Option<SessionGameData>,
+    /// Whether the session has logged into its persona
+    persona: bool,
     /// Subscribers listening for changes to this session
     subscribers: Vec<SessionSubscription>,
 }
@@ -321,6 +472,7 @@ impl SessionDataAuth {
             player_assoc: Arc::new(player),
             net: Default::default(),
             game: Default::default(),
+            persona: false,
             subscribers: Default::default(),
         }
     }
@@ -476,3 +628,113 @@ impl NetData {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{KeepAliveState, SessionData, HEARTBEAT_TIMEOUT, KEEP_ALIVE_TIMEOUT};
+    use crate::session::{
+        test_util::{create_auth_session, test_player, test_sessions},
+        SessionNotifyHandle,
+    };
+    use std::{net::Ipv4Addr, time::Duration};
+    use tokio::time::Instant;
+
+    /// Checks whether the keep-alive for `data` is expired at `now`
+    fn is_expired(data: &SessionData, now: Instant) -> bool {
+        data.ext.read().keep_alive.is_expired(now)
+    }
+
+    /// Tests that suspending pings extends the keep-alive deadline and
+    /// a normal ping resumes the standard timing
+    #[tokio::test]
+    async fn test_suspend_keep_alive() {
+        let data = SessionData::new(Ipv4Addr::LOCALHOST, None);
+        let grace = Duration::from_secs(90);
+        let now = Instant::now();
+
+        // Standard timing without suspending
+        assert!(!is_expired(&data, now));
+        assert!(is_expired(
+            &data,
+            now + KEEP_ALIVE_TIMEOUT + Duration::from_secs(1)
+        ));
+
+        data.set_keep_alive_grace(grace);
+
+        // Deadline is extended by the grace period
+        assert!(!is_expired(
+            &data,
+            now + KEEP_ALIVE_TIMEOUT + Duration::from_secs(1)
+        ));
+        assert!(!is_expired(&data, now + grace + KEEP_ALIVE_TIMEOUT));
+        assert!(is_expired(
+            &data,
+            Instant::now() + grace + KEEP_ALIVE_TIMEOUT + Duration::from_secs(1)
+        ));
+
+        // Normal ping clears the suspended period
+        data.set_alive();
+        let now = Instant::now();
+        assert!(is_expired(
+            &data,
+            now + KEEP_ALIVE_TIMEOUT + Duration::from_secs(1)
+        ));
+    }
+
+    /// Tests that idle sessions are sent a heartbeat before being reaped when
+    /// server heartbeats are enabled
+    #[tokio::test]
+    async fn test_server_heartbeat() {
+        let data = SessionData::new(Ipv4Addr::LOCALHOST, None);
+        let idle = Instant::now() + KEEP_ALIVE_TIMEOUT + Duration::from_secs(1);
+
+        // Without server heartbeats idle sessions are dead
+        assert_eq!(
+            data.ext.write().keep_alive.check(idle),
+            KeepAliveState::Dead
+        );
+
+        data.set_server_heartbeat(true);
+
+        let check = |now: Instant| data.ext.write().keep_alive.check(now);
+
+        // Idle session is sent a heartbeat first
+        assert_eq!(check(idle), KeepAliveState::Heartbeat);
+        assert_eq!(check(idle + HEARTBEAT_TIMEOUT), KeepAliveState::Alive);
+
+        // Reaped when nothing is received after the heartbeat
+        assert_eq!(
+            check(idle + HEARTBEAT_TIMEOUT + Duration::from_secs(1)),
+            KeepAliveState::Dead
+        );
+
+        // Receiving a packet keeps the session alive
+        data.set_packet_received();
+        assert_eq!(check(Instant::now()), KeepAliveState::Alive);
+        assert_eq!(
+            check(Instant::now() + KEEP_ALIVE_TIMEOUT + Duration::from_secs(1)),
+            KeepAliveState::Heartbeat
+        );
+    }
+
+    /// Tests that subscriptions beyond the maximum are rejected and
+    /// accepted again once a subscriber is removed
+    #[tokio::test]
+    async fn test_max_subscriptions() {
+        let sessions = test_sessions();
+        let (session, _rx) = create_auth_session(&sessions, test_player(1));
+        session.data.set_max_subscriptions(Some(2));
+
+        let (subscriber, _sub_rx) = SessionNotifyHandle::new();
+
+        assert!(session.data.add_subscriber(1, subscriber.clone()));
+        assert!(session.data.add_subscriber(2, subscriber.clone()));
+        assert!(!session.data.add_subscriber(3, subscriber.clone()));
+        assert_eq!(session.data.subscriber_count(), 2);
+
+        // Removing a subscriber frees up space for another
+        session.data.remove_subscriber(1);
+        assert!(session.data.add_subscriber(3, subscriber));
+        assert_eq!(session.data.subscriber_count(), 2);
+    }
+}
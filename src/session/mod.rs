@@ -3,18 +3,22 @@
 //! networking data.
 
 use self::{
-    packet::{Packet, PacketCodec, PacketDebug},
+    data::KeepAliveState,
+    models::{errors::GlobalError, util::PingResponse},
+    packet::{decode_packet, FrameType, Packet, PacketCodec},
+    rate_limit::PacketRateLimit,
     router::BlazeRouter,
 };
 use crate::{
     database::entities::Player,
-    utils::components::{component_key, DEBUG_IGNORED_PACKETS},
+    services::udp_tunnel::UdpTunnelService,
+    utils::components::{component_key, util, DEBUG_IGNORED_PACKETS},
 };
 use data::SessionData;
 use futures_util::{future::BoxFuture, Sink, Stream};
 use hyper::upgrade::Upgraded;
 use hyper_util::rt::TokioIo;
-use log::{debug, log_enabled};
+use log::{debug, log_enabled, warn};
 use std::{
     fmt::Debug,
     pin::Pin,
@@ -23,16 +27,23 @@ use std::{
         Arc,
     },
     task::{ready, Context, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use std::{future::Future, sync::Weak};
-use tokio::sync::{mpsc, OwnedMutexGuard};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, OwnedMutexGuard},
+};
 use tokio_util::codec::Framed;
 
 pub mod data;
 pub mod models;
 pub mod packet;
+pub mod rate_limit;
 pub mod router;
 pub mod routes;
+#[cfg(test)]
+pub mod test_util;
 
 pub type SessionLink = Arc<Session>;
 pub type WeakSessionLink = Weak<Session>;
@@ -98,7 +109,13 @@ impl SessionNotifyHandle {
 }
 
 impl Session {
-    pub async fn run(io: Upgraded, data: SessionData, router: Arc<BlazeRouter>) {
+    pub async fn run(
+        io: Upgraded,
+        data: SessionData,
+        router: Arc<BlazeRouter>,
+        rate_limit: Option<PacketRateLimit>,
+        codec: PacketCodec,
+    ) {
         // Obtain a session ID
         let id = SESSION_IDS.fetch_add(1, Ordering::AcqRel);
 
@@ -109,7 +126,16 @@ impl Session {
             data,
         });
 
-        SessionFuture::new(io, &session, &router, rx).await;
+        SessionFuture::new(TokioIo::new(io), &session, &router, rx, rate_limit, codec).await;
+
+        // Remove any UDP tunnel the client established, these aren't bound to
+        // a connection so would otherwise remain until the tunnel times out
+        if let (Some(association), Some(udp_tunnel_service)) = (
+            session.data.get_association(),
+            router.extensions.get::<Arc<UdpTunnelService>>(),
+        ) {
+            udp_tunnel_service.remove_association(&association);
+        }
     }
 }
 
@@ -138,9 +164,9 @@ impl Debug for DebugSessionData {
 }
 
 /// Future for processing a session
-struct SessionFuture<'a> {
+struct SessionFuture<'a, Io> {
     /// The IO for reading and writing
-    io: Framed<TokioIo<Upgraded>, PacketCodec>,
+    io: Framed<Io, PacketCodec>,
     /// Receiver for packets to write
     rx: mpsc::UnboundedReceiver<Packet>,
     /// The session this link is for
@@ -153,6 +179,14 @@ struct SessionFuture<'a> {
     write_state: WriteState,
     /// Whether the future has been stopped
     stop: bool,
+    /// Whether the session has stopped reading and is closing once the
+    /// queued packets have been written
+    closing: bool,
+    /// Limit on the rate packets are accepted
+    rate_limit: Option<PacketRateLimit>,
+    /// Packet that was read while a handler was running, handled
+    /// once the running handler completes
+    next_packet: Option<Packet>,
 }
 
 /// Session future writing state
@@ -185,21 +219,29 @@ enum ReadState<'a> {
     },
 }
 
-impl SessionFuture<'_> {
-    pub fn new<'a>(
-        io: Upgraded,
+impl<'a, Io> SessionFuture<'a, Io>
+where
+    Io: AsyncRead + AsyncWrite + Unpin,
+{
+    pub fn new(
+        io: Io,
         session: &'a Arc<Session>,
         router: &'a BlazeRouter,
         rx: mpsc::UnboundedReceiver<Packet>,
-    ) -> SessionFuture<'a> {
+        rate_limit: Option<PacketRateLimit>,
+        codec: PacketCodec,
+    ) -> SessionFuture<'a, Io> {
         SessionFuture {
-            io: Framed::new(TokioIo::new(io), PacketCodec::default()),
+            io: Framed::new(io, codec),
             router,
             rx,
             session,
             read_state: ReadState::Recv,
             write_state: WriteState::Recv,
             stop: false,
+            closing: false,
+            rate_limit,
+            next_packet: None,
         }
     }
 
@@ -222,7 +264,10 @@ impl SessionFuture<'_> {
             }
             WriteState::Write { packet } => {
                 // Wait until the inner is ready
-                if ready!(Pin::new(&mut self.io).poll_ready(cx)).is_ok() {
+                if let Err(err) = ready!(Pin::new(&mut self.io).poll_ready(cx)) {
+                    // Failed to ready, the packet will never be written
+                    self.stop_writing(&err, 1);
+                } else {
                     let packet = packet
                         .take()
                         .expect("Unexpected write state without packet");
@@ -236,18 +281,15 @@ impl SessionFuture<'_> {
                         .expect("Packet encoder errored");
 
                     self.write_state = WriteState::Flush;
-                } else {
-                    // Failed to ready, session must be closed
-                    self.stop = true;
                 }
             }
             WriteState::Flush => {
                 // Wait until the flush is complete
-                if ready!(Pin::new(&mut self.io).poll_flush(cx)).is_ok() {
-                    self.write_state = WriteState::Recv;
+                if let Err(err) = ready!(Pin::new(&mut self.io).poll_flush(cx)) {
+                    // Failed to flush, the buffered packet may be partially written
+                    self.stop_writing(&err, 1);
                 } else {
-                    // Failed to flush, session must be closed
-                    self.stop = true
+                    self.write_state = WriteState::Recv;
                 }
             }
         }
@@ -255,21 +297,82 @@ impl SessionFuture<'_> {
         Poll::Ready(())
     }
 
+    /// Stops the session after the writer encountered an error, any packets
+    /// that were queued but could not be written are discarded and logged
+    ///
+    /// `in_flight` is the number of packets already taken from the queue
+    /// that were not successfully written
+    fn stop_writing(&mut self, err: &std::io::Error, in_flight: usize) {
+        self.stop = true;
+        self.write_state = WriteState::Recv;
+
+        // Prevent further packets from being queued and count the remaining ones
+        self.rx.close();
+        let mut unsent = in_flight;
+        while self.rx.try_recv().is_ok() {
+            unsent += 1;
+        }
+
+        warn!(
+            "Failed to write to session, closing (SID: {}, Unsent packets: {}): {}",
+            self.session.id, unsent, err
+        );
+    }
+
     /// Polls the read state, the poll ready state returns whether
     /// the future should continue
     fn poll_read_state(&mut self, cx: &mut Context<'_>) -> Poll<()> {
         // Poll checking if the connection has timed-out
-        if self.session.data.poll_keep_alive_dead(cx) {
-            self.stop = true;
-            return Poll::Ready(());
+        match self.session.data.poll_keep_alive(cx) {
+            KeepAliveState::Alive => {}
+            KeepAliveState::Heartbeat => {
+                debug!(
+                    "Sending heartbeat to idle session (SID: {})",
+                    self.session.id
+                );
+                self.session.notify_handle.notify(Packet::notify(
+                    util::COMPONENT,
+                    util::PING,
+                    PingResponse {
+                        server_time: SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or(Duration::ZERO)
+                            .as_secs(),
+                    },
+                ));
+            }
+            KeepAliveState::Dead => {
+                self.stop = true;
+                return Poll::Ready(());
+            }
         }
 
         match &mut self.read_state {
             ReadState::Recv => {
-                // Try receive a packet from the write channel
-                let result = ready!(Pin::new(&mut self.io).poll_next(cx));
+                // Use the packet read while the previous handler was running
+                // otherwise try receive a packet from the write channel
+                let result = match self.next_packet.take() {
+                    Some(packet) => Some(Ok(packet)),
+                    None => ready!(Pin::new(&mut self.io).poll_next(cx)).inspect(|result| {
+                        if result.is_ok() {
+                            self.session.data.set_packet_received();
+                        }
+                    }),
+                };
 
                 if let Some(Ok(packet)) = result {
+                    // Disconnect sessions sending packets faster than allowed
+                    if let Some(rate_limit) = &mut self.rate_limit {
+                        if !rate_limit.try_acquire(Instant::now()) {
+                            warn!(
+                                "Session exceeded packet rate limit, disconnecting (SID: {})",
+                                self.session.id
+                            );
+                            self.stop = true;
+                            return Poll::Ready(());
+                        }
+                    }
+
                     let lock_future = self.session.notify_handle.lock_internal();
 
                     self.read_state = ReadState::Acquire {
@@ -302,27 +405,72 @@ impl SessionFuture<'_> {
                 future,
             } => {
                 // Poll the handler until completion
-                let response = ready!(Pin::new(future).poll(cx));
+                let Poll::Ready(response) = Pin::new(future).poll(cx) else {
+                    // Keep reading while the handler is running so that the client
+                    // closing the connection is noticed, the handler is then dropped
+                    // with the session rather than continuing its work (i.e upstream
+                    // connections for the Origin login flow)
+                    if self.next_packet.is_none() {
+                        match ready!(Pin::new(&mut self.io).poll_next(cx)) {
+                            Some(Ok(packet)) => {
+                                self.session.data.set_packet_received();
+                                self.next_packet = Some(packet);
+                            }
+                            _ => {
+                                self.stop = true;
+                                return Poll::Ready(());
+                            }
+                        }
+                    }
+
+                    return Poll::Pending;
+                };
+
+                // Only routing and system failures count towards the error limit
+                let failed = matches!(response.frame.ty, FrameType::Error)
+                    && GlobalError::is_failure_code(response.frame.error);
+                let exceeded = failed && self.session.data.record_error();
 
                 // Send the response to the writer
                 self.session.notify_handle.send_internal(response);
 
                 // Reset back to the reading state
                 self.read_state = ReadState::Recv;
+
+                // Disconnect sessions causing too many errors, the writer ends
+                // the session once the queued packets have been written
+                if exceeded {
+                    warn!(
+                        "Session exceeded maximum errors, disconnecting (SID: {})",
+                        self.session.id
+                    );
+                    self.closing = true;
+                    self.rx.close();
+                }
             }
         }
         Poll::Ready(())
     }
 }
 
-impl Future for SessionFuture<'_> {
+impl<Io> Future for SessionFuture<'_, Io>
+where
+    Io: AsyncRead + AsyncWrite + Unpin,
+{
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
 
-        while this.poll_write_state(cx).is_ready() {}
-        while this.poll_read_state(cx).is_ready() {}
+        // Once stopped neither half is polled again, a failed writer must not
+        // keep the reader handling packets whose responses can't be sent
+        while !this.stop && this.poll_write_state(cx).is_ready() {}
+        while !this.stop && !this.closing && this.poll_read_state(cx).is_ready() {}
+
+        // Write the packets queued before the reader stopped
+        if this.closing {
+            while !this.stop && this.poll_write_state(cx).is_ready() {}
+        }
 
         if this.stop {
             Poll::Ready(())
@@ -332,7 +480,7 @@ impl Future for SessionFuture<'_> {
     }
 }
 
-impl Drop for SessionFuture<'_> {
+impl<Io> Drop for SessionFuture<'_, Io> {
     fn drop(&mut self) {
         // Clear session data, speeds up process of ending the session
         // prevents session data being accessed while shutting down
@@ -358,7 +506,192 @@ fn debug_log_packet(session: &Session, action: &'static str, packet: &Packet) {
     let auth = session.data.get_player();
 
     let debug_data = DebugSessionData { action, id, auth };
-    let debug_packet = PacketDebug { packet };
 
-    debug!("\n{:?}{:?}", debug_data, debug_packet);
+    debug!("\n{:?}{}", debug_data, decode_packet(packet));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{packet::PacketCodec, router::BlazeRouterBuilder, SessionFuture};
+    use crate::session::{
+        models::{
+            errors::{BlazeError, GlobalError},
+            game_manager::GameManagerError,
+        },
+        packet::{decode_packets, Packet},
+        test_util::create_session,
+    };
+    use bytes::{Bytes, BytesMut};
+    use parking_lot::Mutex;
+    use std::{
+        io,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+        time::Duration,
+    };
+    use tokio::io::{duplex, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+    use tokio_util::codec::Encoder;
+
+    /// IO that never produces data and fails every write
+    struct FailingWriteIo;
+
+    impl AsyncRead for FailingWriteIo {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Poll::Pending
+        }
+    }
+
+    impl AsyncWrite for FailingWriteIo {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Tests that a failing writer ends the session future rather than
+    /// leaving it running with queued packets
+    #[tokio::test]
+    async fn test_write_error_stops_session() {
+        let router = BlazeRouterBuilder::new().build();
+        let (session, rx) = create_session(0);
+
+        // Queue packets that will never be written
+        for _ in 0..3 {
+            session
+                .notify_handle
+                .send_internal(Packet::new_notify(0, 0, Bytes::new()));
+        }
+
+        let future = SessionFuture::new(
+            FailingWriteIo,
+            &session,
+            &router,
+            rx,
+            None,
+            PacketCodec::default(),
+        );
+
+        tokio::time::timeout(Duration::from_secs(5), future)
+            .await
+            .expect("Session future should complete after a write error");
+    }
+
+    /// Tests that a handler still running when the client disconnects is
+    /// dropped along with the session, closing any upstream connection it
+    /// holds rather than leaving it open
+    #[tokio::test]
+    async fn test_disconnect_cancels_handler() {
+        let (upstream, mut upstream_peer) = duplex(64);
+        let upstream = Arc::new(Mutex::new(Some(upstream)));
+
+        let mut router = BlazeRouterBuilder::new();
+        router.route(1, 1, move || {
+            let upstream = upstream.lock().take();
+            async move {
+                // Hold the upstream connection while never completing
+                let _upstream = upstream;
+                std::future::pending::<()>().await
+            }
+        });
+        let router = router.build();
+
+        let (session, rx) = create_session(0);
+
+        let (io, mut client) = duplex(1024);
+        let future = SessionFuture::new(io, &session, &router, rx, None, PacketCodec::default());
+
+        // Send the request then disconnect while it is being handled
+        let mut bytes = BytesMut::new();
+        PacketCodec::default()
+            .encode(Packet::new_request(0, 1, 1, Bytes::new()), &mut bytes)
+            .unwrap();
+        client.write_all(&bytes).await.unwrap();
+        drop(client);
+
+        tokio::time::timeout(Duration::from_secs(5), future)
+            .await
+            .expect("Session future should complete after the client disconnects");
+
+        // The upstream connection should have been closed
+        let mut buffer = [0u8; 1];
+        let read = tokio::time::timeout(Duration::from_secs(5), upstream_peer.read(&mut buffer))
+            .await
+            .expect("Upstream connection should be closed");
+        assert_eq!(read.unwrap(), 0);
+    }
+
+    /// Tests that a session causing more than the maximum number of system
+    /// errors is disconnected after being sent the final error response,
+    /// while expected errors don't count towards the limit
+    #[tokio::test]
+    async fn test_max_errors_disconnects() {
+        let mut router = BlazeRouterBuilder::new();
+        router.route(1, 1, || async {
+            Err::<(), BlazeError>(GlobalError::System.into())
+        });
+        router.route(1, 2, || async {
+            Err::<(), BlazeError>(GameManagerError::GameFull.into())
+        });
+        let router = router.build();
+
+        let (session, rx) = create_session(0);
+        session.data.set_max_errors(Some(2));
+
+        // Client is kept open so only the error limit can end the session
+        let (io, mut client) = duplex(4096);
+        let future = SessionFuture::new(io, &session, &router, rx, None, PacketCodec::default());
+
+        // Expected errors followed by system errors exceeding the limit
+        let mut bytes = BytesMut::new();
+        for id in 0..6 {
+            let command = if id < 3 { 2 } else { 1 };
+            PacketCodec::default()
+                .encode(
+                    Packet::new_request(id, 1, command, Bytes::new()),
+                    &mut bytes,
+                )
+                .unwrap();
+        }
+        client.write_all(&bytes).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), future)
+            .await
+            .expect("Session future should complete after exceeding the error limit");
+
+        assert_eq!(session.data.error_count(), 3);
+
+        // Every response including the one exceeding the limit is sent
+        let mut buffer = Vec::new();
+        client.read_to_end(&mut buffer).await.unwrap();
+        let errors: Vec<u16> = decode_packets(&buffer)
+            .unwrap()
+            .iter()
+            .map(|packet| packet.frame.error)
+            .collect();
+        let game_full = GameManagerError::GameFull as u16;
+        let system = GlobalError::System as u16;
+        assert_eq!(
+            errors,
+            [game_full, game_full, game_full, system, system, system]
+        );
+
+        session.data.reset_errors();
+        assert_eq!(session.data.error_count(), 0);
+    }
 }
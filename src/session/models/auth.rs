@@ -161,6 +161,19 @@ pub struct CreateAccountRequest {
     pub password: String,
 }
 
+/// Request to login to a persona on the authenticated account
+/// ```
+/// {
+///     "PMAM": "Jacobtread"
+/// }
+/// ```
+#[derive(TdfDeserialize)]
+pub struct LoginPersonaRequest {
+    /// The name of the persona to login to
+    #[tdf(tag = "PMAM")]
+    pub name: String,
+}
+
 /// Structure for the persona response which contains details
 /// about the current persona. Which in this case is just the
 /// player details
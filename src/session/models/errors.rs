@@ -35,6 +35,17 @@ pub enum GlobalError {
     System = 0x4001,
 }
 
+impl GlobalError {
+    /// Checks whether the error `code` is a routing or system failure such
+    /// as an unknown command or a request that failed to decode, rather
+    /// than an expected response to the request (i.e. wrong password)
+    pub fn is_failure_code(code: u16) -> bool {
+        [Self::System, Self::ComponentNotFound, Self::CommandNotFound]
+            .into_iter()
+            .any(|error| error as u16 == code)
+    }
+}
+
 #[derive(Debug, Clone)]
 #[repr(u16)]
 #[allow(unused)]
@@ -2,7 +2,7 @@ use super::{util::PING_SITE_ALIAS, NatType, NetworkAddress};
 use crate::{
     config::{RuntimeConfig, TunnelConfig},
     services::{
-        game::{rules::RuleSet, AttrMap, Game, GamePlayer},
+        game::{rules::RuleSet, AttrMap, Game, GameJoinableState, GamePlayer},
         tunnel::TUNNEL_HOST_LOCAL_PORT,
     },
     utils::types::{GameID, PlayerID},
@@ -28,6 +28,23 @@ pub enum GameManagerError {
     JoinPlayerFailed = 0x6c,
     AlreadyInQueue = 0x70,
     TeamFull = 0xff,
+    /// The game attributes exceed the configured attribute limits, this
+    /// is a server specific code that the client shows as a generic failure
+    AttributeLimitExceeded = 0x100,
+}
+
+impl GameManagerError {
+    /// Maps the joinable `state` of a game to the error for attempting
+    /// to join the game, [None] when the game can be joined
+    pub fn from_joinable_state(state: GameJoinableState) -> Option<Self> {
+        match state {
+            GameJoinableState::Joinable => None,
+            // Game is being torn down, the client should look for another game
+            GameJoinableState::Stopping => Some(Self::InvalidGameId),
+            GameJoinableState::Full => Some(Self::GameFull),
+            GameJoinableState::NotMatch => Some(Self::JoinPlayerFailed),
+        }
+    }
 }
 
 /// Structure of the request for creating new games contains the
@@ -558,8 +575,6 @@ pub struct AttributesChange<'a> {
 pub struct PlayerJoining<'a> {
     /// The ID of the game
     pub game_id: GameID,
-    /// The slot the player is joining into
-    pub slot: usize,
     /// The player that is joining
     pub player: &'a GamePlayer,
 }
@@ -569,7 +584,7 @@ impl TdfSerialize for PlayerJoining<'_> {
         w.tag_u32(b"GID", self.game_id);
 
         w.tag_group(b"PDAT");
-        self.player.encode(self.game_id, self.slot, w);
+        self.player.encode(self.game_id, w);
     }
 }
 
@@ -607,6 +622,53 @@ pub enum GameState {
     GameGroupInitialized = 0x10,
 }
 
+impl PlayerState {
+    /// Checks whether a player in this state is allowed to move into
+    /// the `next` state
+    pub fn can_transition_to(self, next: PlayerState) -> bool {
+        use PlayerState::*;
+
+        if self == next {
+            return true;
+        }
+
+        match (self, next) {
+            // Players pending a kick cannot change state
+            (ActiveKickPending, _) => false,
+            // Active players cannot return to being reserved or queued
+            (_, Reserved | Queued) => false,
+            // Players must be connecting before they can be connected
+            (Reserved | Queued, ActiveConnected) => false,
+            _ => true,
+        }
+    }
+}
+
+impl GameState {
+    /// Checks whether a game in this state is allowed to move into
+    /// the `next` state
+    pub fn can_transition_to(self, next: GameState) -> bool {
+        use GameState::*;
+
+        if self == next {
+            return true;
+        }
+
+        match (self, next) {
+            // Games being destroyed cannot be revived
+            (Destructing, _) => false,
+            // Games cannot return to being newly created
+            (_, NewState) => false,
+            // Games cannot return to initialization once they have started
+            (
+                PreGame | InGame | PostGame | Migrating | Resettable | Unresponsive,
+                Initializing | InactiveVirtual | ConnectionVerification,
+            ) => false,
+            _ => true,
+        }
+    }
+}
+
 bitflags! {
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
     pub struct GameSettings: u16 {
@@ -690,7 +752,7 @@ pub enum GameSetupContext {
 #[derive(Debug, Copy, Clone, TdfSerialize, TdfTyped)]
 #[repr(u8)]
 pub enum MatchmakingResult {
-    // CreatedGame = 0x0,
+    CreatedGame = 0x0,
     // JoinedNewGame = 0x1,
     JoinedExistingGame = 0x2,
     // TimedOut = 0x3,
@@ -779,8 +841,8 @@ impl TdfSerialize for GameSetupResponse<'_> {
             w.tag_list_slice::<usize>(
                 b"CAP",
                 &[
-                    Game::MAX_PLAYERS, /* Public slots */
-                    0,                 /* Private slots */
+                    game.max_players, /* Public slots */
+                    0,                /* Private slots */
                 ],
             );
             // Game ID
@@ -838,7 +900,7 @@ impl TdfSerialize for GameSetupResponse<'_> {
             w.tag_zero(b"IGNO");
 
             // Max player capacity
-            w.tag_usize(b"MCAP", Game::MAX_PLAYERS);
+            w.tag_usize(b"MCAP", game.max_players);
 
             // Host network qos data
             w.tag_ref(b"NQOS", &host.net.qos);
@@ -865,7 +927,7 @@ impl TdfSerialize for GameSetupResponse<'_> {
             // Platform host info
             w.group(b"PHST", |w| {
                 w.tag_u32(b"HPID", host.player.id);
-                w.tag_zero(b"HSLT");
+                w.tag_owned(b"HSLT", host.slot);
             });
 
             // Presence mode
@@ -874,9 +936,8 @@ impl TdfSerialize for GameSetupResponse<'_> {
             w.tag_str(b"PSAS", PING_SITE_ALIAS);
             // Queue capacity
             w.tag_zero(b"QCAP");
-            // Shared game randomness seed? (a 32 bit number shared between clients)
-            // TODO: Randomly generate this when creating a game?
-            w.tag_u32(b"SEED", 0x4cbc8585);
+            // Shared game randomness seed (a 32 bit number shared between clients)
+            w.tag_u32(b"SEED", game.seed);
             // Team capacity
             w.tag_zero(b"TCAP");
 
@@ -885,7 +946,7 @@ impl TdfSerialize for GameSetupResponse<'_> {
                 // Player ID
                 w.tag_u32(b"HPID", host.player.id);
                 // Slot ID
-                w.tag_zero(b"HSLT");
+                w.tag_owned(b"HSLT", host.slot);
             });
 
             w.tag_str(b"UUID", "286a2373-3e6e-46b9-8294-3ef05e479503");
@@ -901,8 +962,8 @@ impl TdfSerialize for GameSetupResponse<'_> {
 
         // Player list
         w.tag_list_start(b"PROS", TdfType::Group, game.players.len());
-        for (slot, player) in game.players.iter().enumerate() {
-            player.encode(game.id, slot, w);
+        for player in game.players.iter() {
+            player.encode(game.id, w);
         }
 
         w.tag_ref(b"REAS", &self.context);
@@ -929,8 +990,8 @@ impl TdfSerialize for GetGameDetails<'_> {
             w.tag_list_slice::<usize>(
                 b"CAP",
                 &[
-                    Game::MAX_PLAYERS, /* Public slots */
-                    0,                 /* Private slots */
+                    game.max_players, /* Public slots */
+                    0,                /* Private slots */
                 ],
             );
 
@@ -135,8 +135,21 @@ pub struct QosNetworkData {
     pub ubps: u32,
 }
 
-//
-#[derive(Debug, Default, Copy, Clone, Serialize, TdfDeserialize, TdfSerialize, TdfTyped)]
+/// NAT types are ordered from the best to the worst game hosts
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    TdfDeserialize,
+    TdfSerialize,
+    TdfTyped,
+)]
 #[repr(u8)]
 pub enum NatType {
     /// Players behind an open NAT can usually connect to any other player and are ideal game hosts.
@@ -94,6 +94,17 @@ pub const BLAZE_VERSION: &str = "Blaze 3.15.08.0 (CL# 1905397)\n";
 /// Alias used for ping sites
 pub const PING_SITE_ALIAS: &str = "ea-sjc";
 
+/// Obtains the host and port of the QoS server advertised to clients
+/// for the provided `qos` config, [None] when QoS is disabled
+pub fn qos_address(qos: &QosServerConfig) -> Option<(&str, Port)> {
+    match qos {
+        QosServerConfig::Official => Some(("gossjcprod-qos01.ea.com", 17502)),
+        QosServerConfig::Local => Some(("127.0.0.1", LOCAL_HTTP_PORT)),
+        QosServerConfig::Custom { host, port } => Some((host.as_str(), *port)),
+        QosServerConfig::Disabled | QosServerConfig::Hamachi { .. } => None,
+    }
+}
+
 /// Structure for the response to a pre authentication request
 pub struct PreAuthResponse {
     pub config: Arc<RuntimeConfig>,
@@ -146,19 +157,9 @@ impl TdfSerialize for PreAuthResponse {
 
         // Quality Of Service Server details
         w.group(b"QOSS", |w| {
-            let qos = &self.config.qos;
-
-            let mut disabled = false;
-
-            let (http_host, http_port) = match qos {
-                QosServerConfig::Official => ("gossjcprod-qos01.ea.com", 17502),
-                QosServerConfig::Local => ("127.0.0.1", LOCAL_HTTP_PORT),
-                QosServerConfig::Custom { host, port } => (host.as_str(), *port),
-                QosServerConfig::Disabled | QosServerConfig::Hamachi { .. } => {
-                    disabled = true;
-                    ("0", 0)
-                }
-            };
+            let address = qos_address(&self.config.qos.server);
+            let disabled = address.is_none();
+            let (http_host, http_port) = address.unwrap_or(("0", 0));
 
             // let http_host = "127.0.0.1";
             // let http_port = 17499;
@@ -171,7 +172,7 @@ impl TdfSerialize for PreAuthResponse {
             });
 
             // Number of probes to send to BWPS
-            w.tag_u8(b"LNP", 1);
+            w.tag_u8(b"LNP", self.config.qos.probe_count);
 
             // List of other Quality Of Service servers? Values present in this
             // list are later included in a ping list
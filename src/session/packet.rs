@@ -292,6 +292,19 @@ impl Packet {
 pub struct PacketCodec {
     /// The current partially decoded frame
     partial: Option<PartialFrame>,
+    /// Maximum length of packet contents that will be decoded
+    max_length: Option<usize>,
+}
+
+impl PacketCodec {
+    /// Creates a codec that fails to decode packets with contents
+    /// longer than `max_length` bytes, None for no limit
+    pub fn with_max_length(max_length: Option<usize>) -> Self {
+        Self {
+            partial: None,
+            max_length,
+        }
+    }
 }
 
 impl Decoder for PacketCodec {
@@ -339,6 +352,17 @@ impl Decoder for PacketCodec {
             partial.need_jumbo = false;
         }
 
+        // Reject packets over the maximum length before waiting on the contents
+        if self
+            .max_length
+            .is_some_and(|max_length| partial.length > max_length)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Packet length {} exceeds the maximum", partial.length),
+            ));
+        }
+
         // We don't have enough bytes for the content yet
         if src.len() < partial.length {
             return Ok(None);
@@ -370,11 +394,41 @@ impl Encoder<Packet> for PacketCodec {
     }
 }
 
+/// Decodes all the packets from the provided raw `bytes`, used for
+/// inspecting captured packets outside of a session.
+///
+/// Fails if the bytes end part way through a packet
+pub fn decode_packets(bytes: &[u8]) -> io::Result<Vec<Packet>> {
+    let mut codec = PacketCodec::default();
+    let mut src = BytesMut::from(bytes);
+    let mut packets = Vec::new();
+
+    while let Some(packet) = codec.decode(&mut src)? {
+        packets.push(packet);
+    }
+
+    if !src.is_empty() || codec.partial.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Packet data ended part way through a packet",
+        ));
+    }
+
+    Ok(packets)
+}
+
+/// Decodes the contents of the provided `packet` into a readable form with
+/// the component and command names resolved. This is the decoding shared by
+/// the packet debug loggers, capture replay and the decode-packet command
+pub fn decode_packet(packet: &Packet) -> String {
+    format!("{:?}", PacketDebug { packet })
+}
+
 /// Wrapper over a packet structure to provide debug logging
 /// with names resolved for the component
-pub struct PacketDebug<'a> {
+struct PacketDebug<'a> {
     /// Reference to the packet itself
-    pub packet: &'a Packet,
+    packet: &'a Packet,
 }
 
 impl<'a> Debug for PacketDebug<'a> {
@@ -428,3 +482,68 @@ impl<'a> Debug for PacketDebug<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{decode_packet, decode_packets, Packet, PacketCodec};
+    use crate::{
+        session::models::util::PingResponse,
+        utils::components::{self, util},
+    };
+    use bytes::{Bytes, BytesMut};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    /// Tests decoding an encoded packet renders the expected debug output
+    #[test]
+    fn test_decode_packets() {
+        components::initialize();
+
+        let request = Packet::request_empty(1, util::COMPONENT, util::PING);
+        let response = Packet::response(&request, PingResponse { server_time: 32 });
+
+        let mut bytes = BytesMut::new();
+        let mut codec = PacketCodec::default();
+        codec.encode(response, &mut bytes).unwrap();
+
+        let packets = decode_packets(&bytes).unwrap();
+        assert_eq!(packets.len(), 1);
+
+        let output = decode_packet(&packets[0]);
+        assert_eq!(
+            output,
+            "Response (1): Util->Ping (0x0009->0x0002)\n\
+            Options: PacketOptions(0x0)\n\
+            Content: {\n  \"STIM\": 32,\n}"
+        );
+
+        // Incomplete packets should fail to decode
+        assert!(decode_packets(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    /// Tests that packets with a length over the maximum are rejected from
+    /// the header alone without waiting on the contents
+    #[test]
+    fn test_decode_max_length() {
+        let mut packet = Packet::request_empty(1, util::COMPONENT, util::PING);
+        packet.contents = Bytes::from(vec![0u8; 70000]);
+
+        let mut bytes = BytesMut::new();
+        PacketCodec::default().encode(packet, &mut bytes).unwrap();
+
+        // Only provide the header bytes
+        let mut header = bytes.split_to(bytes.len() - 70000);
+        let capacity = header.capacity();
+
+        let mut codec = PacketCodec::with_max_length(Some(1024));
+        assert!(codec.decode(&mut header).is_err());
+        assert!(header.capacity() <= capacity);
+
+        // Packets within the limit decode normally
+        let request = Packet::request_empty(2, util::COMPONENT, util::PING);
+        let mut bytes = BytesMut::new();
+        codec.encode(request, &mut bytes).unwrap();
+
+        let mut codec = PacketCodec::with_max_length(Some(1024));
+        assert!(codec.decode(&mut bytes).unwrap().is_some());
+    }
+}
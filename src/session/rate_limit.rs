@@ -0,0 +1,95 @@
+//! Inbound packet rate limiting for sessions
+
+use crate::config::SessionsConfig;
+use std::time::Instant;
+
+/// Token bucket limiting the rate packets are accepted from a session
+pub struct PacketRateLimit {
+    /// Maximum number of tokens the bucket can hold
+    burst: f64,
+    /// Number of tokens added to the bucket each second
+    per_second: f64,
+    /// The number of tokens available
+    tokens: f64,
+    /// The last time the tokens were refilled
+    last_refill: Instant,
+}
+
+impl PacketRateLimit {
+    /// Creates a packet rate limit from the provided `config`, provides
+    /// [None] when the limit is disabled
+    pub fn from_config(config: &SessionsConfig) -> Option<Self> {
+        let per_second = config.max_packets_per_second?;
+        Some(Self::new(config.packet_burst, per_second, Instant::now()))
+    }
+
+    fn new(burst: u32, per_second: u32, now: Instant) -> Self {
+        let burst = burst.max(1) as f64;
+        Self {
+            burst,
+            per_second: per_second as f64,
+            tokens: burst,
+            last_refill: now,
+        }
+    }
+
+    /// Attempts to take a token for a packet received at `now` returning
+    /// whether the packet is allowed
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.per_second).min(self.burst);
+        self.last_refill = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PacketRateLimit;
+    use crate::config::SessionsConfig;
+    use std::time::{Duration, Instant};
+
+    /// Tests that a session sending past the limit is rejected once
+    /// the burst is used
+    #[test]
+    fn test_flood_rejected() {
+        let now = Instant::now();
+        let mut limit = PacketRateLimit::new(10, 5, now);
+
+        for _ in 0..10 {
+            assert!(limit.try_acquire(now));
+        }
+
+        assert!(!limit.try_acquire(now));
+    }
+
+    /// Tests that a session sending at a normal rate is never limited
+    #[test]
+    fn test_normal_rate_allowed() {
+        let start = Instant::now();
+        let mut limit = PacketRateLimit::new(10, 5, start);
+
+        for i in 0..100 {
+            let now = start + Duration::from_millis(250 * i);
+            assert!(limit.try_acquire(now));
+        }
+    }
+
+    /// Tests that the limit is only created when a packet rate is configured
+    #[test]
+    fn test_from_config() {
+        assert!(PacketRateLimit::from_config(&SessionsConfig::default()).is_none());
+
+        let config = SessionsConfig {
+            max_packets_per_second: Some(50),
+            ..Default::default()
+        };
+        assert!(PacketRateLimit::from_config(&config).is_some());
+    }
+}
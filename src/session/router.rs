@@ -9,11 +9,12 @@ use crate::{
     utils::{
         components::{component_key, ComponentKey},
         hashing::IntHashMap,
+        logging::log_limited,
     },
 };
 use bytes::Bytes;
 use futures_util::future::BoxFuture;
-use log::{debug, error};
+use log::{debug, error, Level};
 use std::{
     any::{Any, TypeId},
     convert::Infallible,
@@ -285,7 +286,10 @@ where
             req.packet
                 .deserialize::<'a, V>()
                 .map_err(|err| {
-                    error!("Error while decoding packet: {:?}", err);
+                    log_limited(
+                        Level::Error,
+                        format!("Error while decoding packet: {:?}", err),
+                    );
                     GlobalError::System.into()
                 })
                 .map(Blaze),
@@ -5,6 +5,9 @@ use crate::{
         DatabaseConnection,
     },
     services::{
+        account_limit::AccountCreationLimiter,
+        game::manager::GameManager,
+        maintenance_mode::MaintenanceMode,
         retriever::Retriever,
         sessions::{Sessions, VerifyError},
     },
@@ -21,19 +24,36 @@ use crate::{
         random_name::generate_random_name,
     },
 };
-use chrono::Utc;
 use email_address::EmailAddress;
-use log::{debug, error};
+use log::{debug, error, warn};
 use rand::{rngs::StdRng, SeedableRng};
 use std::{borrow::Cow, sync::Arc};
 use tokio::fs::read_to_string;
 
+/// Rejects logins while the server is under maintenance
+///
+/// Blaze has no maintenance error, the system error is used rather than
+/// an authentication error which would imply the credentials were wrong.
+/// As a system error it counts towards the session error limit, this is
+/// acceptable as the session has no use while logins are refused
+fn ensure_not_maintenance(maintenance: &MaintenanceMode) -> ServerResult<()> {
+    if maintenance.is_enabled() {
+        debug!("Rejected login while the server is under maintenance");
+        return Err(GlobalError::System.into());
+    }
+
+    Ok(())
+}
+
 pub async fn handle_login(
     session: SessionLink,
     Extension(db): Extension<DatabaseConnection>,
+    Extension(maintenance): Extension<Arc<MaintenanceMode>>,
     Extension(sessions): Extension<Arc<Sessions>>,
     Blaze(LoginRequest { email, password }): Blaze<LoginRequest>,
 ) -> ServerResult<Blaze<AuthResponse>> {
+    ensure_not_maintenance(&maintenance)?;
+
     // Ensure the email is actually valid
     if !EmailAddress::is_valid(&email) {
         return Err(AuthenticationError::InvalidEmail.into());
@@ -56,9 +76,7 @@ pub async fn handle_login(
     }
 
     // Update last login timestamp
-    if let Err(err) = Player::set_last_login(&db, player.id, Utc::now()).await {
-        error!("failed to store last login time: {err}");
-    }
+    Player::record_login(&db, player.id);
 
     // Create the player session mapping
     let player = sessions.add_session(player, Arc::downgrade(&session));
@@ -78,9 +96,12 @@ pub async fn handle_login(
 pub async fn handle_silent_login(
     session: SessionLink,
     Extension(db): Extension<DatabaseConnection>,
+    Extension(maintenance): Extension<Arc<MaintenanceMode>>,
     Extension(sessions): Extension<Arc<Sessions>>,
     Blaze(SilentLoginRequest { token }): Blaze<SilentLoginRequest>,
 ) -> ServerResult<Blaze<AuthResponse>> {
+    ensure_not_maintenance(&maintenance)?;
+
     // Verify the authentication token
     let player_id = sessions.verify_token(&token).map_err(|err| match err {
         VerifyError::Expired => AuthenticationError::ExpiredToken,
@@ -92,9 +113,7 @@ pub async fn handle_silent_login(
         .ok_or(AuthenticationError::InvalidToken)?;
 
     // Update last login timestamp
-    if let Err(err) = Player::set_last_login(&db, player_id, Utc::now()).await {
-        error!("failed to store last login time: {err}");
-    }
+    Player::record_login(&db, player_id);
 
     // Create the session association
     let player = sessions.add_session(player, Arc::downgrade(&session));
@@ -114,9 +133,12 @@ pub async fn handle_origin_login(
     Extension(db): Extension<DatabaseConnection>,
     Extension(config): Extension<Arc<RuntimeConfig>>,
     Extension(sessions): Extension<Arc<Sessions>>,
+    Extension(maintenance): Extension<Arc<MaintenanceMode>>,
     Extension(retriever): Extension<Arc<Retriever>>,
     Blaze(OriginLoginRequest { token, .. }): Blaze<OriginLoginRequest>,
 ) -> ServerResult<Blaze<AuthResponse>> {
+    ensure_not_maintenance(&maintenance)?;
+
     // Obtain an origin flow
     let mut flow = retriever.origin_flow().await.map_err(|err| {
         error!("Failed to obtain origin flow: {}", err);
@@ -129,9 +151,7 @@ pub async fn handle_origin_login(
     })?;
 
     // Update last login timestamp
-    if let Err(err) = Player::set_last_login(&db, player.id, Utc::now()).await {
-        error!("failed to store last login time: {err}");
-    }
+    Player::record_login(&db, player.id);
 
     // Create the session association
     let player = sessions.add_session(player, Arc::downgrade(&session));
@@ -151,13 +171,45 @@ pub async fn handle_origin_login(
 /// Handles logging out by the client this removes any current player data from the
 /// session and updating anything that depends on the session having a player.
 ///
+/// Clearing the authentication removes the session from [Sessions], removes the
+/// player from their current game and drops their subscriptions. The connection
+/// is left open so the client can login again.
+///
 /// ```
 /// Route: Authentication(Logout)
 /// ID: 8
 /// Content: {}
 /// ```
-pub async fn handle_logout(session: SessionLink) {
+pub async fn handle_logout(
+    session: SessionLink,
+    Extension(game_manager): Extension<Arc<GameManager>>,
+) {
+    let Some(player) = session.data.get_player() else {
+        return;
+    };
+
+    // The session stays connected so any matchmaking entry must be removed
+    // to prevent the session being placed into a game after logging out
+    game_manager.remove_queue(player.id).await;
+
     session.data.clear_auth();
+
+    debug!(
+        "Player logged out (PID: {}, SID: {})",
+        player.id, session.id
+    );
+}
+
+/// Handles login types that the server doesn't support such as the console
+/// and express logins. These are rejected so the client doesn't treat the
+/// default empty response as a successful login.
+///
+/// ```
+/// Route: Authentication(ExpressLogin | XboxLogin | PS3Login)
+/// ```
+pub async fn handle_unsupported_login(session: SessionLink) -> ServerResult<()> {
+    debug!("Rejected unsupported login type (SID: {})", session.id);
+    Err(GlobalError::CommandNotFound.into())
 }
 
 // Skip formatting these entitlement creations
@@ -248,7 +300,8 @@ pub async fn handle_list_entitlements(
 }
 
 /// Handles logging into a persona. This system doesn't implement the persona system so
-/// the account details are just used instead
+/// each account has a single persona using the account display name, requests for any
+/// other persona are rejected
 ///
 /// ```
 /// Route: Authentication(LoginPersona),
@@ -257,8 +310,26 @@ pub async fn handle_list_entitlements(
 ///     "PMAM": "Jacobtread"
 /// }
 /// ```
-pub async fn handle_login_persona(SessionAuth(player): SessionAuth) -> Blaze<PersonaResponse> {
-    Blaze(PersonaResponse { player })
+pub async fn handle_login_persona(
+    session: SessionLink,
+    SessionAuth(player): SessionAuth,
+    Blaze(LoginPersonaRequest { name }): Blaze<LoginPersonaRequest>,
+) -> ServerResult<Blaze<PersonaResponse>> {
+    if !name.eq_ignore_ascii_case(&player.display_name) {
+        debug!(
+            "Attempted to login to unknown persona (PID: {}, Persona: {})",
+            player.id, name
+        );
+        return Err(AuthenticationError::PersonaNotFound.into());
+    }
+
+    if session.data.has_persona() {
+        debug!("Session already logged into persona (PID: {})", player.id);
+    } else {
+        session.data.set_persona();
+    }
+
+    Ok(Blaze(PersonaResponse { player }))
 }
 
 /// Handles forgot password requests. This normally would send a forgot password
@@ -314,8 +385,12 @@ pub async fn handle_create_account(
     Extension(db): Extension<DatabaseConnection>,
     Extension(config): Extension<Arc<RuntimeConfig>>,
     Extension(sessions): Extension<Arc<Sessions>>,
+    Extension(maintenance): Extension<Arc<MaintenanceMode>>,
+    Extension(account_limiter): Extension<Arc<AccountCreationLimiter>>,
     Blaze(CreateAccountRequest { email, password }): Blaze<CreateAccountRequest>,
 ) -> ServerResult<Blaze<AuthResponse>> {
+    ensure_not_maintenance(&maintenance)?;
+
     if !EmailAddress::is_valid(&email) {
         return Err(AuthenticationError::InvalidEmail.into());
     }
@@ -325,6 +400,20 @@ pub async fn handle_create_account(
         return Err(AuthenticationError::Exists.into());
     }
 
+    // Limit the number of accounts created by the session address
+    let addr = session.data.get_addr();
+    let permit = match account_limiter.try_acquire(addr.into()) {
+        Ok(value) => value,
+        Err(retry_after) => {
+            warn!(
+                "Rejected account creation, address created too many accounts (Addr: {}, Retry After: {}s)",
+                addr,
+                retry_after.as_secs()
+            );
+            return Err(GlobalError::System.into());
+        }
+    };
+
     // Hash the provided plain text password using Argon2
     let hashed_password: String = hash_password(&password).map_err(|err| {
         error!("Failed to hash password for creating account: {}", err);
@@ -355,10 +444,11 @@ pub async fn handle_create_account(
     let player: Player =
         Player::create(&db, email, display_name, Some(hashed_password), role).await?;
 
+    // Only successfully created accounts count towards the limit
+    permit.commit();
+
     // Update last login timestamp
-    if let Err(err) = Player::set_last_login(&db, player.id, Utc::now()).await {
-        error!("failed to store last login time: {err}");
-    }
+    Player::record_login(&db, player.id);
 
     // Create the session association
     let player = sessions.add_session(player, Arc::downgrade(&session));
@@ -440,7 +530,8 @@ pub async fn handle_privacy_policy() -> Blaze<LegalContent> {
 }
 
 /// Handles retrieving an authentication token for use with the Galaxy At War HTTP service.
-/// This implementation uses the session token for the player
+/// This implementation uses the session token for the player which is also accepted by
+/// the dashboard API, allowing the client to open the dashboard already authenticated
 ///
 /// ```
 /// Route: Authentication(GetAuthToken),
@@ -455,3 +546,221 @@ pub async fn handle_get_auth_token(
     let token = sessions.create_token(player.id);
     Blaze(GetTokenResponse { token })
 }
+
+#[cfg(test)]
+mod test {
+    use super::{handle_get_auth_token, handle_login_persona, handle_logout};
+    use crate::{
+        config::RuntimeConfig,
+        database::{
+            entities::{Player, PlayerRole},
+            test_database,
+        },
+        services::maintenance_mode::MaintenanceMode,
+        session::{
+            models::{
+                auth::LoginPersonaRequest,
+                game_manager::{DatalessContext, GameSettings, GameSetupContext},
+            },
+            packet::{FrameType, Packet},
+            router::{Blaze, Extension, SessionAuth},
+            routes::router,
+            test_util::{
+                create_auth_session, create_game_player, create_session, test_game_manager,
+                test_player, test_sessions,
+            },
+        },
+        utils::{components::authentication as a, hashing::hash_password},
+    };
+    use bytes::Bytes;
+    use std::sync::Arc;
+    use tdf::TdfMap;
+    use tdf::{serialize_vec, TdfDeserialize, TdfDeserializer, TdfSerialize};
+
+    /// Tests that logging out removes the session from the sessions and
+    /// the game while leaving the connection usable
+    #[tokio::test]
+    async fn test_logout() {
+        let sessions = test_sessions();
+        let game_manager = test_game_manager(Arc::new(RuntimeConfig::default()));
+
+        let (session, mut rx) = create_auth_session(&sessions, test_player(1));
+
+        // Join the session into a game
+        let (game_ref, _) = game_manager
+            .create_game(TdfMap::default(), GameSettings::NONE)
+            .await;
+        game_manager
+            .add_to_game(
+                game_ref.clone(),
+                create_game_player(&session),
+                session.clone(),
+                GameSetupContext::Dataless {
+                    context: DatalessContext::CreateGameSetup,
+                },
+            )
+            .await;
+        assert!(session.data.get_game().is_some());
+
+        handle_logout(session.clone(), Extension(game_manager)).await;
+
+        assert!(session.data.get_player().is_none());
+        assert!(session.data.get_game().is_none());
+        assert!(sessions.lookup_session(1).is_none());
+
+        // Player is removed from the game in the background
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !game_ref.read().await.players.is_empty() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("Player should be removed from the game");
+
+        // Connection can still be used to send messages
+        while rx.try_recv().is_ok() {}
+        session
+            .notify_handle
+            .notify(Packet::new_notify(0, 0, Bytes::new()));
+        assert!(rx.recv().await.is_some());
+    }
+
+    /// Tests that unsupported login types are rejected with an error
+    /// rather than the default empty response
+    #[tokio::test]
+    async fn test_unsupported_login() {
+        let router = router().build();
+        let (session, _rx) = create_session(0);
+
+        for command in [a::EXPRESS_LOGIN, a::XBOX_LOGIN, a::PS3_LOGIN] {
+            let request = Packet::new_request(0, a::COMPONENT, command, Bytes::new());
+            let response = router.handle(session.clone(), request).await;
+            assert!(matches!(response.frame.ty, FrameType::Error));
+        }
+    }
+
+    /// Subset of the persona response fields
+    #[derive(TdfDeserialize)]
+    struct PersonaFields {
+        #[tdf(tag = "BUID")]
+        buid: u32,
+        #[tdf(tag = "MAIL")]
+        email: String,
+        #[tdf(tag = "UID")]
+        uid: u32,
+    }
+
+    /// Tests that logging into the player persona marks the session and
+    /// responds with the persona details
+    #[tokio::test]
+    async fn test_login_persona() {
+        let sessions = test_sessions();
+        let (session, _rx) = create_auth_session(&sessions, test_player(1));
+        let player = session.data.get_player().unwrap();
+
+        let result = handle_login_persona(
+            session.clone(),
+            SessionAuth(player.clone()),
+            Blaze(LoginPersonaRequest {
+                name: "Other".to_string(),
+            }),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(!session.data.has_persona());
+
+        let Ok(Blaze(response)) = handle_login_persona(
+            session.clone(),
+            SessionAuth(player.clone()),
+            Blaze(LoginPersonaRequest {
+                name: "test1".to_string(),
+            }),
+        )
+        .await
+        else {
+            panic!("Failed to login persona");
+        };
+        assert!(session.data.has_persona());
+
+        let bytes = serialize_vec(&response);
+        let fields = PersonaFields::deserialize(&mut TdfDeserializer::new(&bytes)).unwrap();
+        assert_eq!(fields.buid, player.id);
+        assert_eq!(fields.email, "test1@test.com");
+        assert_eq!(fields.uid, player.id);
+    }
+
+    /// Tests that the auth token provided to an authenticated session
+    /// can be verified as belonging to the session player
+    #[tokio::test]
+    async fn test_get_auth_token() {
+        let player = test_player(1);
+        let sessions = test_sessions();
+
+        let Blaze(response) = handle_get_auth_token(
+            SessionAuth(Arc::new(player.clone())),
+            Extension(sessions.clone()),
+        )
+        .await;
+
+        assert_eq!(sessions.verify_token(&response.token).ok(), Some(player.id));
+    }
+
+    #[derive(TdfSerialize)]
+    struct LoginRequest {
+        #[tdf(tag = "MAIL")]
+        email: &'static str,
+        #[tdf(tag = "PASS")]
+        password: &'static str,
+    }
+
+    /// Tests that logins are refused while the server is in maintenance
+    /// mode and accepted once maintenance mode is disabled
+    #[tokio::test]
+    async fn test_login_maintenance() {
+        let db = test_database().await;
+        let password = hash_password("password").unwrap();
+        Player::create(
+            &db,
+            "test@test.com".to_string(),
+            "Test".to_string(),
+            Some(password),
+            PlayerRole::Default,
+        )
+        .await
+        .unwrap();
+
+        let sessions = test_sessions();
+        let maintenance = Arc::new(MaintenanceMode::new(true));
+
+        let mut builder = router();
+        builder.add_extension(db);
+        builder.add_extension(sessions);
+        builder.add_extension(maintenance.clone());
+        let router = builder.build();
+
+        let (session, _rx) = create_session(0);
+
+        let request = || {
+            Packet::new_request(
+                0,
+                a::COMPONENT,
+                a::LOGIN,
+                serialize_vec(&LoginRequest {
+                    email: "test@test.com",
+                    password: "password",
+                })
+                .into(),
+            )
+        };
+
+        let response = router.handle(session.clone(), request()).await;
+        assert!(matches!(response.frame.ty, FrameType::Error));
+        assert!(session.data.get_player().is_none());
+
+        maintenance.set_enabled(false);
+
+        let response = router.handle(session.clone(), request()).await;
+        assert!(matches!(response.frame.ty, FrameType::Response));
+        assert!(session.data.get_player().is_some());
+    }
+}
@@ -12,7 +12,7 @@ use crate::{
         SessionLink,
     },
 };
-use log::{debug, info};
+use log::{debug, info, warn};
 use std::sync::Arc;
 
 pub async fn handle_join_game(
@@ -34,13 +34,16 @@ pub async fn handle_join_game(
         .ok_or(GameManagerError::InvalidGameId)?;
 
     // Check the game is joinable
-    let join_state = {
+    {
         let game = &*game_ref.read().await;
-        game.joinable_state(None)
-    };
 
-    if !matches!(join_state, GameJoinableState::Joinable) {
-        return Err(GameManagerError::GameFull.into());
+        if game.has_player(player.player.id) {
+            return Err(GameManagerError::AlreadyGameMember.into());
+        }
+
+        if let Some(err) = GameManagerError::from_joinable_state(game.joinable_state(None)) {
+            return Err(err.into());
+        }
     }
 
     // Join the game
@@ -139,6 +142,11 @@ pub async fn handle_create_game(
         setting,
     }): Blaze<CreateGameRequest>,
 ) -> ServerResult<Blaze<CreateGameResponse>> {
+    if let Err(err) = game_manager.check_create_attributes(&attributes) {
+        warn!("Rejected game creation: {}", err);
+        return Err(GameManagerError::AttributeLimitExceeded.into());
+    }
+
     let (link, game_id) = game_manager.create_game(attributes, setting).await;
 
     // Notify matchmaking of the new game
@@ -198,7 +206,13 @@ pub async fn handle_set_attributes(
 
     {
         let game = &mut *link.write().await;
-        game.set_attributes(attributes);
+        if let Err(err) = game.set_attributes(attributes) {
+            warn!(
+                "Rejected game attributes change (GID: {}): {}",
+                game_id, err
+            );
+            return Err(GameManagerError::AttributeLimitExceeded.into());
+        }
     }
 
     // Update matchmaking for the changed game
@@ -207,7 +221,7 @@ pub async fn handle_set_attributes(
         game.joinable_state(None)
     };
     if let GameJoinableState::Joinable = join_state {
-        game_manager.process_queue(link, game_id).await;
+        game_manager.schedule_queue_check(link, game_id).await;
     }
 
     Ok(())
@@ -233,7 +247,11 @@ pub async fn handle_set_state(
         .ok_or(GameManagerError::InvalidGameId)?;
 
     let game = &mut *link.write().await;
-    game.set_state(state);
+
+    // Invalid state changes reuse an error code the client already handles,
+    // refusing the change the same way as other actions the player can't take
+    game.set_state(state)
+        .map_err(|_| GameManagerError::PermissionDenied)?;
 
     Ok(())
 }
@@ -277,6 +295,7 @@ pub async fn handle_set_setting(
 /// }
 /// ```
 pub async fn handle_remove_player(
+    SessionAuth(player): SessionAuth,
     Extension(game_manager): Extension<Arc<GameManager>>,
     Blaze(RemovePlayerRequest {
         game_id,
@@ -290,6 +309,16 @@ pub async fn handle_remove_player(
         .ok_or(GameManagerError::InvalidGameId)?;
 
     let game = &mut *link.write().await;
+
+    if !game.has_player(player_id) {
+        return Err(GameManagerError::PlayerNotFound.into());
+    }
+
+    // Only the host can remove other players
+    if player_id != player.id && !game.is_host_player(player.id) {
+        return Err(GameManagerError::PermissionDenied.into());
+    }
+
     game.remove_player(player_id, reason);
 
     Ok(())
@@ -353,10 +382,16 @@ pub async fn handle_add_admin_player(
     let game = &mut *link.write().await;
 
     // Ensure the host is the one making the change
-    if game.is_host_player(player.id) {
-        game.add_admin_player(player_id);
+    if !game.is_host_player(player.id) {
+        return Err(GameManagerError::PermissionDenied.into());
     }
 
+    if !game.has_player(player_id) {
+        return Err(GameManagerError::PlayerNotFound.into());
+    }
+
+    game.add_admin_player(player_id);
+
     Ok(())
 }
 
@@ -518,3 +553,391 @@ pub async fn handle_cancel_matchmaking(
     session.data.clear_game();
     game_manager.remove_queue(player.id).await;
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        config::{GameConfig, RuntimeConfig},
+        services::{
+            game::{manager::GameManager, AttrMap, GameRef},
+            sessions::Sessions,
+        },
+        session::{
+            models::game_manager::{
+                DatalessContext, GameManagerError, GameSettings, GameSetupContext, GameState,
+                RemoveReason,
+            },
+            packet::{FrameType, Packet},
+            router::BlazeRouter,
+            routes::router,
+            test_util::{
+                create_auth_session, create_game_player, test_game_manager, test_player,
+                test_sessions,
+            },
+            Session,
+        },
+        utils::{
+            components::game_manager as g,
+            types::{GameID, PlayerID},
+        },
+    };
+    use std::sync::Arc;
+    use tdf::{serialize_vec, TdfMap, TdfSerialize, TdfTyped};
+
+    #[derive(TdfSerialize)]
+    struct JoinGameRequest {
+        #[tdf(tag = "USER")]
+        user: JoinGameRequestUser,
+    }
+
+    #[derive(TdfSerialize, TdfTyped)]
+    #[tdf(group)]
+    struct JoinGameRequestUser {
+        #[tdf(tag = "ID")]
+        id: PlayerID,
+    }
+
+    #[derive(TdfSerialize)]
+    struct RemovePlayerRequest {
+        #[tdf(tag = "GID")]
+        game_id: GameID,
+        #[tdf(tag = "PID")]
+        player_id: PlayerID,
+        #[tdf(tag = "REAS")]
+        reason: RemoveReason,
+    }
+
+    #[derive(TdfSerialize)]
+    struct CreateGameRequest {
+        #[tdf(tag = "ATTR")]
+        attributes: TdfMap<String, String>,
+        #[tdf(tag = "GSET")]
+        setting: u16,
+    }
+
+    #[derive(TdfSerialize)]
+    struct SetAttributesRequest {
+        #[tdf(tag = "ATTR")]
+        attributes: TdfMap<String, String>,
+        #[tdf(tag = "GID")]
+        game_id: GameID,
+    }
+
+    #[derive(TdfSerialize)]
+    struct AddAdminPlayerRequest {
+        #[tdf(tag = "GID")]
+        game_id: GameID,
+        #[tdf(tag = "PID")]
+        player_id: PlayerID,
+    }
+
+    /// Creates the router and the services it uses with the provided `config`
+    fn create_router(config: RuntimeConfig) -> (Arc<Sessions>, Arc<GameManager>, Arc<BlazeRouter>) {
+        let sessions = test_sessions();
+        let game_manager = test_game_manager(Arc::new(config));
+
+        let mut builder = router();
+        builder.add_extension(sessions.clone());
+        builder.add_extension(game_manager.clone());
+        (sessions, game_manager, builder.build())
+    }
+
+    /// Adds the player of the `session` to the game
+    async fn add_to_game(game_manager: &GameManager, game_ref: &GameRef, session: &Arc<Session>) {
+        game_manager
+            .add_to_game(
+                game_ref.clone(),
+                create_game_player(session),
+                session.clone(),
+                GameSetupContext::Dataless {
+                    context: DatalessContext::JoinGameSetup,
+                },
+            )
+            .await;
+    }
+
+    /// Routes a game manager request from the `session` and provides
+    /// the error code of the response, [None] for successful responses
+    async fn request_error<V: TdfSerialize>(
+        router: &BlazeRouter,
+        session: &Arc<Session>,
+        command: u16,
+        contents: V,
+    ) -> Option<u16> {
+        let request =
+            Packet::new_request(0, g::COMPONENT, command, serialize_vec(&contents).into());
+        let response = router.handle(session.clone(), request).await;
+        matches!(response.frame.ty, FrameType::Error).then_some(response.frame.error)
+    }
+
+    /// Tests that joining a game that is being torn down responds with
+    /// an invalid game error rather than joining
+    #[tokio::test]
+    async fn test_join_destructing_game() {
+        let (sessions, game_manager, router) = create_router(RuntimeConfig::default());
+
+        let (host, _) = create_auth_session(&sessions, test_player(1));
+        let (joiner, _) = create_auth_session(&sessions, test_player(2));
+
+        // Place the host into a game that is being torn down
+        let (game_ref, game_id) = game_manager
+            .create_game(TdfMap::default(), GameSettings::NONE)
+            .await;
+        host.data.set_game(game_id, Arc::downgrade(&game_ref));
+        game_ref.write().await.state = GameState::Destructing;
+
+        let host_id = host.data.get_player().unwrap().id;
+        let error = request_error(
+            &router,
+            &joiner,
+            g::JOIN_GAME,
+            JoinGameRequest {
+                user: JoinGameRequestUser { id: host_id },
+            },
+        )
+        .await;
+
+        assert_eq!(error, Some(GameManagerError::InvalidGameId as u16));
+        assert!(joiner.data.get_game().is_none());
+    }
+
+    /// Tests that each failure to join a game responds with its
+    /// specific error code
+    #[tokio::test]
+    async fn test_join_game_errors() {
+        let (sessions, game_manager, router) = create_router(RuntimeConfig {
+            game: GameConfig {
+                max_players: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let (host, _) = create_auth_session(&sessions, test_player(1));
+        let (member, _) = create_auth_session(&sessions, test_player(2));
+        let (joiner, _) = create_auth_session(&sessions, test_player(3));
+        let host_id = host.data.get_player().unwrap().id;
+        let joiner_id = joiner.data.get_player().unwrap().id;
+
+        let join = |id: PlayerID| JoinGameRequest {
+            user: JoinGameRequestUser { id },
+        };
+
+        // Target player is not connected
+        let error = request_error(&router, &joiner, g::JOIN_GAME, join(100)).await;
+        assert_eq!(error, Some(GameManagerError::JoinPlayerFailed as u16));
+
+        // Target player is not in a game
+        let error = request_error(&router, &joiner, g::JOIN_GAME, join(host_id)).await;
+        assert_eq!(error, Some(GameManagerError::InvalidGameId as u16));
+
+        let (game_ref, _) = game_manager
+            .create_game(TdfMap::default(), GameSettings::NONE)
+            .await;
+        add_to_game(&game_manager, &game_ref, &host).await;
+        add_to_game(&game_manager, &game_ref, &member).await;
+
+        // Player is already in the game
+        let error = request_error(&router, &member, g::JOIN_GAME, join(host_id)).await;
+        assert_eq!(error, Some(GameManagerError::AlreadyGameMember as u16));
+
+        // Game has reached the maximum number of players
+        let error = request_error(&router, &joiner, g::JOIN_GAME, join(host_id)).await;
+        assert_eq!(error, Some(GameManagerError::GameFull as u16));
+        assert!(!game_ref.read().await.has_player(joiner_id));
+    }
+
+    /// Tests that removing players responds with the error for missing
+    /// players and that only the host can remove other players
+    #[tokio::test]
+    async fn test_remove_player_errors() {
+        let (sessions, game_manager, router) = create_router(RuntimeConfig::default());
+
+        let (host, _) = create_auth_session(&sessions, test_player(1));
+        let (member, _) = create_auth_session(&sessions, test_player(2));
+        let (other, _) = create_auth_session(&sessions, test_player(3));
+        let member_id = member.data.get_player().unwrap().id;
+        let other_id = other.data.get_player().unwrap().id;
+
+        let (game_ref, game_id) = game_manager
+            .create_game(TdfMap::default(), GameSettings::NONE)
+            .await;
+        add_to_game(&game_manager, &game_ref, &host).await;
+        add_to_game(&game_manager, &game_ref, &member).await;
+        add_to_game(&game_manager, &game_ref, &other).await;
+
+        let remove = |player_id: PlayerID| RemovePlayerRequest {
+            game_id,
+            player_id,
+            reason: RemoveReason::PlayerKicked,
+        };
+
+        // Game doesn't exist
+        let error = request_error(
+            &router,
+            &host,
+            g::REMOVE_PLAYER,
+            RemovePlayerRequest {
+                game_id: game_id + 1,
+                ..remove(member_id)
+            },
+        )
+        .await;
+        assert_eq!(error, Some(GameManagerError::InvalidGameId as u16));
+
+        // Player is not in the game
+        let error = request_error(&router, &host, g::REMOVE_PLAYER, remove(100)).await;
+        assert_eq!(error, Some(GameManagerError::PlayerNotFound as u16));
+
+        // Only the host can remove other players
+        let error = request_error(&router, &member, g::REMOVE_PLAYER, remove(other_id)).await;
+        assert_eq!(error, Some(GameManagerError::PermissionDenied as u16));
+
+        // Players can remove themselves
+        let error = request_error(&router, &member, g::REMOVE_PLAYER, remove(member_id)).await;
+        assert_eq!(error, None);
+
+        let error = request_error(&router, &host, g::REMOVE_PLAYER, remove(other_id)).await;
+        assert_eq!(error, None);
+        assert!(!game_ref.read().await.has_player(other_id));
+    }
+
+    /// Tests that only the host can add admin players and only for
+    /// players within the game
+    #[tokio::test]
+    async fn test_add_admin_player_errors() {
+        let (sessions, game_manager, router) = create_router(RuntimeConfig::default());
+
+        let (host, _) = create_auth_session(&sessions, test_player(1));
+        let (member, _) = create_auth_session(&sessions, test_player(2));
+        let member_id = member.data.get_player().unwrap().id;
+
+        let (game_ref, game_id) = game_manager
+            .create_game(TdfMap::default(), GameSettings::NONE)
+            .await;
+        add_to_game(&game_manager, &game_ref, &host).await;
+        add_to_game(&game_manager, &game_ref, &member).await;
+
+        let add_admin = |player_id: PlayerID| AddAdminPlayerRequest { game_id, player_id };
+
+        let error =
+            request_error(&router, &member, g::ADD_ADMIN_PLAYER, add_admin(member_id)).await;
+        assert_eq!(error, Some(GameManagerError::PermissionDenied as u16));
+
+        let error = request_error(&router, &host, g::ADD_ADMIN_PLAYER, add_admin(100)).await;
+        assert_eq!(error, Some(GameManagerError::PlayerNotFound as u16));
+
+        let error = request_error(&router, &host, g::ADD_ADMIN_PLAYER, add_admin(member_id)).await;
+        assert_eq!(error, None);
+    }
+
+    /// Tests that attribute changes within the limit are applied while
+    /// changes that would take the game past the limit are rejected
+    #[tokio::test]
+    async fn test_set_attributes_limit() {
+        let (sessions, game_manager, router) = create_router(RuntimeConfig {
+            game: GameConfig {
+                max_attributes: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let (host, _) = create_auth_session(&sessions, test_player(1));
+
+        let attributes = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<AttrMap>()
+        };
+
+        let (game_ref, game_id) = game_manager
+            .create_game(
+                attributes(&[("ME3map", "map1"), ("ME3privacy", "PUBLIC")]),
+                GameSettings::NONE,
+            )
+            .await;
+        add_to_game(&game_manager, &game_ref, &host).await;
+
+        // Replacing an attribute and adding one more is within the limit
+        let error = request_error(
+            &router,
+            &host,
+            g::SET_GAME_ATTRIBUTES,
+            SetAttributesRequest {
+                attributes: attributes(&[("ME3map", "map2"), ("ME3gameDifficulty", "difficulty0")]),
+                game_id,
+            },
+        )
+        .await;
+        assert_eq!(error, None);
+
+        let error = request_error(
+            &router,
+            &host,
+            g::SET_GAME_ATTRIBUTES,
+            SetAttributesRequest {
+                attributes: attributes(&[("ME3gameEnemyType", "enemy1")]),
+                game_id,
+            },
+        )
+        .await;
+        assert_eq!(error, Some(GameManagerError::AttributeLimitExceeded as u16));
+
+        let game = &*game_ref.read().await;
+        assert_eq!(game.attributes.len(), 3);
+        assert_eq!(
+            game.attributes.get("ME3map").map(String::as_str),
+            Some("map2")
+        );
+        assert!(game.attributes.get("ME3gameEnemyType").is_none());
+    }
+
+    /// Tests that the attribute limit for created games includes the
+    /// default attributes that are added to the game
+    #[tokio::test]
+    async fn test_create_game_attributes_limit() {
+        let (sessions, game_manager, router) = create_router(RuntimeConfig {
+            game: GameConfig {
+                max_attributes: Some(2),
+                default_attributes: [("ME3privacy".to_string(), "PUBLIC".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let (host, _) = create_auth_session(&sessions, test_player(1));
+
+        let create_game = |pairs: &[(&str, &str)]| CreateGameRequest {
+            attributes: pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            setting: 0,
+        };
+
+        let error = request_error(
+            &router,
+            &host,
+            g::CREATE_GAME,
+            create_game(&[("ME3map", "map1"), ("ME3gameDifficulty", "difficulty0")]),
+        )
+        .await;
+        assert_eq!(error, Some(GameManagerError::AttributeLimitExceeded as u16));
+        assert_eq!(game_manager.get_total_games().await, 0);
+
+        // Attributes replacing the defaults don't count towards the limit
+        let error = request_error(
+            &router,
+            &host,
+            g::CREATE_GAME,
+            create_game(&[("ME3map", "map1"), ("ME3privacy", "PRIVATE")]),
+        )
+        .await;
+        assert_eq!(error, None);
+        assert_eq!(game_manager.get_total_games().await, 1);
+    }
+}
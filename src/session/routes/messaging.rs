@@ -38,7 +38,8 @@ pub async fn handle_fetch_messages(
     let mut message: String = config
         .menu_message
         .replace("{v}", VERSION)
-        .replace("{n}", &player.display_name);
+        .replace("{n}", &player.display_name)
+        .replace("{s}", config.server.name.as_deref().unwrap_or_default());
     // Line terminator for the end of the message
     message.push(char::from(0x0A));
 
@@ -30,6 +30,9 @@ pub fn router() -> BlazeRouterBuilder {
         builder.route(a::COMPONENT, a::SILENT_LOGIN, handle_silent_login);
         builder.route(a::COMPONENT, a::ORIGIN_LOGIN, handle_origin_login);
         builder.route(a::COMPONENT, a::LOGIN, handle_login);
+        builder.route(a::COMPONENT, a::EXPRESS_LOGIN, handle_unsupported_login);
+        builder.route(a::COMPONENT, a::XBOX_LOGIN, handle_unsupported_login);
+        builder.route(a::COMPONENT, a::PS3_LOGIN, handle_unsupported_login);
         builder.route(a::COMPONENT, a::LOGIN_PERSONA, handle_login_persona);
         builder.route(a::COMPONENT, a::LIST_USER_ENTITLEMENTS_2, handle_list_entitlements);
         builder.route(a::COMPONENT, a::CREATE_ACCOUNT,handle_create_account);
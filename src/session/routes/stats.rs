@@ -1,41 +1,45 @@
 use crate::{
     database::entities::LeaderboardData,
+    services::health::HealthService,
     session::{
         models::stats::*,
         router::{Blaze, Extension},
     },
 };
 use sea_orm::DatabaseConnection;
+use std::sync::Arc;
 
 pub async fn handle_normal_leaderboard(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(health): Extension<Arc<HealthService>>,
     Blaze(query): Blaze<LeaderboardRequest>,
 ) -> Blaze<LeaderboardResponse> {
-    let values = LeaderboardData::get_offset(&db, query.name, query.start, query.count)
-        .await
-        .unwrap_or_default();
+    let values = LeaderboardData::get_offset(&db, query.name, query.start, query.count).await;
+    health.record_leaderboard(&values);
+    let values = values.unwrap_or_default();
     Blaze(LeaderboardResponse { values })
 }
 
 pub async fn handle_centered_leaderboard(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(health): Extension<Arc<HealthService>>,
     Blaze(query): Blaze<CenteredLeaderboardRequest>,
 ) -> Blaze<LeaderboardResponse> {
-    let values = LeaderboardData::get_centered(&db, query.name, query.center, query.count)
-        .await
-        .unwrap_or_default()
-        .unwrap_or_default();
+    let values = LeaderboardData::get_centered(&db, query.name, query.center, query.count).await;
+    health.record_leaderboard(&values);
+    let values = values.unwrap_or_default().unwrap_or_default();
 
     Blaze(LeaderboardResponse { values })
 }
 
 pub async fn handle_filtered_leaderboard(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(health): Extension<Arc<HealthService>>,
     Blaze(query): Blaze<FilteredLeaderboardRequest>,
 ) -> Blaze<LeaderboardResponse> {
-    let values = LeaderboardData::get_filtered(&db, query.name, query.ids)
-        .await
-        .unwrap_or_default();
+    let values = LeaderboardData::get_filtered(&db, query.name, query.ids).await;
+    health.record_leaderboard(&values);
+    let values = values.unwrap_or_default();
 
     Blaze(LeaderboardResponse { values })
 }
@@ -57,11 +61,12 @@ pub async fn handle_filtered_leaderboard(
 /// ```
 pub async fn handle_leaderboard_entity_count(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(health): Extension<Arc<HealthService>>,
     Blaze(req): Blaze<EntityCountRequest>,
 ) -> Blaze<EntityCountResponse> {
-    let total = LeaderboardData::count(&db, req.name)
-        .await
-        .unwrap_or_default();
+    let total = LeaderboardData::count(&db, req.name).await;
+    health.record_leaderboard(&total);
+    let total = total.unwrap_or_default();
 
     Blaze(EntityCountResponse {
         count: total as usize,
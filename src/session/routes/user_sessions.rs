@@ -7,16 +7,17 @@ use crate::{
             auth::{AuthResponse, AuthenticationError},
             errors::ServerResult,
             user_sessions::*,
-            NetworkAddress,
+            IpPairAddress, NetworkAddress,
         },
         router::{Blaze, Extension},
         SessionLink,
     },
+    utils::types::PlayerID,
 };
-use chrono::Utc;
-use log::error;
+use log::debug;
 use sea_orm::DatabaseConnection;
-use std::sync::Arc;
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+use thiserror::Error;
 
 /// Attempts to lookup another authenticated session details
 ///
@@ -65,24 +66,26 @@ pub async fn handle_resume_session(
     session: SessionLink,
     Extension(db): Extension<DatabaseConnection>,
     Extension(sessions): Extension<Arc<Sessions>>,
+    Extension(config): Extension<Arc<RuntimeConfig>>,
     Blaze(ResumeSessionRequest { session_token }): Blaze<ResumeSessionRequest>,
 ) -> ServerResult<Blaze<AuthResponse>> {
+    let grace = Duration::from_secs(config.sessions.resume_grace_seconds);
+
     // Verify the authentication token
     let player_id = sessions
-        .verify_token(&session_token)
+        .verify_token_with_grace(&session_token, grace)
         .map_err(|err| match err {
-            VerifyError::Expired => AuthenticationError::ExpiredToken,
-            VerifyError::Invalid => AuthenticationError::InvalidToken,
-        })?;
+            VerifyError::Expired => ResumeFailure::Expired,
+            VerifyError::Invalid => ResumeFailure::Invalid,
+        })
+        .map_err(ResumeFailure::into_error)?;
 
     let player = Player::by_id(&db, player_id)
         .await?
-        .ok_or(AuthenticationError::InvalidToken)?;
+        .ok_or_else(|| ResumeFailure::UnknownPlayer(player_id).into_error())?;
 
     // Update last login timestamp
-    if let Err(err) = Player::set_last_login(&db, player_id, Utc::now()).await {
-        error!("failed to store last login time: {err}");
-    }
+    Player::record_login(&db, player_id);
 
     let player = sessions.add_session(player, Arc::downgrade(&session));
     let player = session.data.set_auth(player);
@@ -94,6 +97,31 @@ pub async fn handle_resume_session(
     }))
 }
 
+/// Reasons a session could not be resumed
+#[derive(Debug, Error)]
+enum ResumeFailure {
+    /// The session token expired (Including the grace period)
+    #[error("session token expired")]
+    Expired,
+    /// The session token was malformed or signed with a different key,
+    /// usually from a server restart rotating the signing key
+    #[error("session token invalid")]
+    Invalid,
+    /// The session token was for a player that no longer exists
+    #[error("unknown player {0}")]
+    UnknownPlayer(PlayerID),
+}
+
+impl ResumeFailure {
+    /// Logs the failure and provides the error for the client, all failures
+    /// use an invalid session key which prompts the client to perform a full
+    /// login rather than waiting on the resume
+    fn into_error(self) -> AuthenticationError {
+        debug!("Failed to resume session: {}", self);
+        AuthenticationError::InvalidSessionKey
+    }
+}
+
 /// Handles updating the stored networking information for the current session
 /// this is required for clients to be able to connect to each-other
 ///
@@ -134,7 +162,7 @@ pub async fn handle_update_network(
 ) {
     let session_addr = session.data.get_addr();
 
-    match &config.qos {
+    match &config.qos.server {
         QosServerConfig::Disabled => {}
         // Hamachi should override local addresses
         QosServerConfig::Hamachi { host } => {
@@ -153,14 +181,7 @@ pub async fn handle_update_network(
         _ => {
             // TODO: This won't be required after QoS servers are correctly functioning
             if let NetworkAddress::AddressPair(pair) = &mut address {
-                let ext = &mut pair.external;
-
-                // If address is missing
-                if ext.addr.is_unspecified() {
-                    // Replace address with new address and port with same as local port
-                    ext.addr = session_addr;
-                    ext.port = pair.internal.port;
-                }
+                validate_external_address(pair, session_addr, config.net.trust_client_address);
             }
         }
     }
@@ -176,6 +197,44 @@ pub async fn handle_update_network(
         .set_network_info(address, qos, ping_site_latency);
 }
 
+/// Validates the external address claimed by the client against the
+/// `observed` connection address, filling in missing addresses and
+/// replacing mismatched addresses unless `trust_client` is enabled.
+///
+/// Mismatches are only replaced when the observed address is public,
+/// connections from a private network cannot observe the real external
+/// address so the client claim is kept
+fn validate_external_address(pair: &mut IpPairAddress, observed: Ipv4Addr, trust_client: bool) {
+    let ext = &mut pair.external;
+
+    // If address is missing
+    if ext.addr.is_unspecified() {
+        // Replace address with new address and port with same as local port
+        ext.addr = observed;
+        ext.port = pair.internal.port;
+        return;
+    }
+
+    if trust_client || ext.addr == observed || !is_public(observed) {
+        return;
+    }
+
+    debug!(
+        "Client claimed external address {} differs from connection address {}, using connection address",
+        ext.addr, observed
+    );
+    ext.addr = observed;
+}
+
+/// Checks whether the provided `addr` is a publicly routable address
+fn is_public(addr: Ipv4Addr) -> bool {
+    !(addr.is_loopback()
+        || addr.is_private()
+        || addr.is_link_local()
+        || addr.is_unspecified()
+        || addr.is_broadcast())
+}
+
 /// Handles updating the stored hardware flag with the client provided hardware flag
 ///
 /// ```
@@ -191,3 +250,71 @@ pub async fn handle_update_hardware_flag(
 ) {
     session.data.set_hardware_flags(hardware_flags);
 }
+
+#[cfg(test)]
+mod test {
+    use super::{validate_external_address, ResumeFailure};
+    use crate::session::models::{auth::AuthenticationError, IpPairAddress, PairAddress};
+    use std::net::Ipv4Addr;
+
+    /// Creates an address pair with the provided claimed external address
+    fn claimed(external: Ipv4Addr) -> IpPairAddress {
+        IpPairAddress {
+            external: PairAddress {
+                addr: external,
+                port: 3659,
+            },
+            internal: PairAddress {
+                addr: Ipv4Addr::new(192, 168, 1, 2),
+                port: 3659,
+            },
+        }
+    }
+
+    /// Tests that a claimed address matching the connection is kept
+    #[test]
+    fn test_external_address_matching() {
+        let observed = Ipv4Addr::new(203, 0, 113, 5);
+        let mut pair = claimed(observed);
+        validate_external_address(&mut pair, observed, false);
+        assert_eq!(pair.external.addr, observed);
+        assert_eq!(pair.external.port, 3659);
+    }
+
+    /// Tests that mismatched claimed addresses are replaced unless trusted
+    /// or the connection is from a private network
+    #[test]
+    fn test_external_address_mismatched() {
+        let observed = Ipv4Addr::new(203, 0, 113, 5);
+        let claim = Ipv4Addr::new(198, 51, 100, 9);
+
+        let mut pair = claimed(claim);
+        validate_external_address(&mut pair, observed, false);
+        assert_eq!(pair.external.addr, observed);
+
+        let mut pair = claimed(claim);
+        validate_external_address(&mut pair, observed, true);
+        assert_eq!(pair.external.addr, claim);
+
+        let mut pair = claimed(claim);
+        validate_external_address(&mut pair, Ipv4Addr::new(192, 168, 1, 2), false);
+        assert_eq!(pair.external.addr, claim);
+    }
+
+    /// Tests that every resume failure tells the client to perform a full login
+    #[test]
+    fn test_resume_failure_response() {
+        let failures = [
+            ResumeFailure::Expired,
+            ResumeFailure::Invalid,
+            ResumeFailure::UnknownPlayer(1),
+        ];
+
+        for failure in failures {
+            assert!(matches!(
+                failure.into_error(),
+                AuthenticationError::InvalidSessionKey
+            ));
+        }
+    }
+}
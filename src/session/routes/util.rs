@@ -1,8 +1,9 @@
 use crate::{
     config::{RuntimeConfig, VERSION},
-    database::entities::PlayerData,
+    database::entities::{player_data::DataLimits, PlayerData},
     services::config::{
-        fallback_coalesced_file, fallback_talk_file, local_coalesced_file, local_talk_file,
+        fallback_coalesced_file, fallback_talk_file, local_client_config, local_coalesced_file,
+        local_talk_file, CLIENT_CONFIG_OVERRIDES_PATH,
     },
     session::{
         models::{
@@ -15,12 +16,12 @@ use crate::{
     },
     utils::encoding::{create_base64_map, generate_coalesced, ChunkMap},
 };
-use log::{debug, error};
+use log::{debug, error, warn};
 use me3_coalesced_parser::{serialize_coalesced, Coalesced};
 use sea_orm::DatabaseConnection;
 use std::{
     borrow::Cow,
-    cmp::Ordering,
+    path::Path,
     sync::Arc,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
@@ -153,9 +154,44 @@ const ME3_DIME: &str = include_str!("../../resources/data/dime.xml");
 /// }
 /// ```
 pub async fn handle_fetch_client_config(
+    Extension(config): Extension<Arc<RuntimeConfig>>,
     Blaze(FetchConfigRequest { id }): Blaze<FetchConfigRequest>,
 ) -> ServerResult<Blaze<FetchConfigResponse>> {
-    let config = match id.as_str() {
+    let config = client_config(
+        Path::new(CLIENT_CONFIG_OVERRIDES_PATH),
+        &id,
+        config.client_config.compress_min_bytes,
+    )
+    .await?;
+    Ok(Blaze(FetchConfigResponse { config }))
+}
+
+/// Obtains the client config for the provided type `id` using the override
+/// from `overrides_path` if present before falling back to the built-in
+/// configs
+///
+/// `overrides_path`     The folder containing the config overrides
+/// `id`                 The client config type
+/// `compress_min_bytes` Minimum size of the coalesced before its compressed
+async fn client_config(
+    overrides_path: &Path,
+    id: &str,
+    compress_min_bytes: usize,
+) -> ServerResult<TdfMap<String, String>> {
+    match local_client_config(overrides_path, id).await {
+        Ok(config) => return Ok(config),
+        Err(err) => {
+            // Log errors if the file existed
+            if !matches!(err.kind(), std::io::ErrorKind::NotFound) {
+                error!(
+                    "Unable to load client config override {} falling back to default: {}",
+                    id, err
+                );
+            }
+        }
+    }
+
+    let config = match id {
         "ME3_DATA" => data_config(),
         "ME3_MSG" => messages(),
         "ME3_ENT" => load_entitlements(),
@@ -170,7 +206,7 @@ pub async fn handle_fetch_client_config(
             map.insert("VERSION".to_string(), "40128".to_string());
             map
         }
-        "ME3_BINI_PC_COMPRESSED" => match create_coalesced_map().await {
+        "ME3_BINI_PC_COMPRESSED" => match create_coalesced_map(compress_min_bytes).await {
             Ok(map) => map,
             Err(err) => {
                 error!("Failed to load server coalesced: {}", err);
@@ -186,7 +222,7 @@ pub async fn handle_fetch_client_config(
         }
     };
 
-    Ok(Blaze(FetchConfigResponse { config }))
+    Ok(config)
 }
 
 /// Loads the entitlements from the entitlements file and parses
@@ -220,7 +256,9 @@ async fn load_coalesced() -> Coalesced {
 
 /// Loads the local coalesced if one is present falling back
 /// to the default one on error or if its missing
-async fn create_coalesced_map() -> std::io::Result<ChunkMap> {
+///
+/// `compress_min_bytes` Minimum size of the coalesced before its compressed
+async fn create_coalesced_map(compress_min_bytes: usize) -> std::io::Result<ChunkMap> {
     // Load the coalesced from JSON
     let coalesced = load_coalesced().await;
 
@@ -228,7 +266,7 @@ async fn create_coalesced_map() -> std::io::Result<ChunkMap> {
     let serialized = serialize_coalesced(&coalesced);
 
     // Encode and compress the coalesced
-    generate_coalesced(&serialized)
+    generate_coalesced(&serialized, compress_min_bytes)
 }
 
 /// Retrieves a talk file for the specified language code falling back
@@ -404,15 +442,16 @@ fn data_config() -> TdfMap<String, String> {
     config
 }
 
-/// Handles suspend user ping packets. The usage of this is unknown and needs
-/// further research
-///
+/// Longest period the client is allowed to suspend pings for (The time
+/// requested by the ME3 client during loading screens)
+const MAX_SUSPEND_PING: Duration = Duration::from_secs(90);
+
 /// Handles suspending user ping timeout for a specific period of time. The client
-/// provides a time in microseconds and the server responds with whether it will
-/// allow the time
-///
-/// [UtilError::]
+/// sends this before loading screens where it won't send pings, providing a time in
+/// microseconds. The server responds with whether it will allow the time and when
+/// allowed the keep-alive deadline of the session is extended by that time.
 ///
+/// A normal ping from the client ends the suspended period early
 ///
 /// ```
 /// Route: Util(SuspendUserPing)
@@ -425,16 +464,15 @@ pub async fn handle_suspend_user_ping(
     session: SessionLink,
     Blaze(SuspendPingRequest { time_value }): Blaze<SuspendPingRequest>,
 ) -> BlazeError {
-    let res = match time_value.cmp(&90000000) {
-        Ordering::Less => UtilError::SuspendPingTimeTooSmall,
-        Ordering::Greater => UtilError::SuspendPingTimeTooLarge,
-        Ordering::Equal => {
-            session
-                .data
-                .set_keep_alive_grace(Duration::from_micros(time_value as u64));
-
-            UtilError::PingSuspended
-        }
+    let time_value = Duration::from_micros(time_value as u64);
+
+    let res = if time_value.is_zero() {
+        UtilError::SuspendPingTimeTooSmall
+    } else if time_value > MAX_SUSPEND_PING {
+        UtilError::SuspendPingTimeTooLarge
+    } else {
+        session.data.set_keep_alive_grace(time_value);
+        UtilError::PingSuspended
     };
     res.into()
 }
@@ -453,9 +491,17 @@ pub async fn handle_suspend_user_ping(
 pub async fn handle_user_settings_save(
     SessionAuth(player): SessionAuth,
     Extension(db): Extension<DatabaseConnection>,
+    Extension(config): Extension<Arc<RuntimeConfig>>,
     Blaze(SettingsSaveRequest { value, key }): Blaze<SettingsSaveRequest>,
 ) -> ServerResult<()> {
-    PlayerData::set(&db, player.id, key, value).await?;
+    let limits = DataLimits::from_config(&config.player);
+    if let Err(limit) = PlayerData::set_limited(&db, player.id, key, value, limits).await? {
+        warn!(
+            "Player {} ({}) exceeded the player data limits: {:?}",
+            player.display_name, player.id, limit
+        );
+        return Err(GlobalError::System.into());
+    }
     Ok(())
 }
 
@@ -541,3 +587,132 @@ pub async fn handle_set_client_metrics(
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{client_config, handle_ping, handle_pre_auth};
+    use crate::{
+        config::{QosConfig, QosServerConfig, RuntimeConfig},
+        session::{
+            data::{KeepAliveState, KEEP_ALIVE_TIMEOUT},
+            router::{Blaze, Extension},
+            test_util::create_session,
+            Session,
+        },
+    };
+    use std::{
+        future::poll_fn,
+        sync::Arc,
+        task::Poll,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+    use tdf::{serialize_vec, TdfDeserialize, TdfDeserializer, TdfTyped};
+
+    /// Polls the keep-alive state of the `session`
+    async fn keep_alive_state(session: &Session) -> KeepAliveState {
+        poll_fn(|cx| Poll::Ready(session.data.poll_keep_alive(cx))).await
+    }
+
+    /// Tests that the ping response contains the current server time and
+    /// that pinging keeps the session alive
+    #[tokio::test(start_paused = true)]
+    async fn test_ping() {
+        let (session, _rx) = create_session(1);
+        let (idle_session, _idle_rx) = create_session(2);
+
+        tokio::time::advance(KEEP_ALIVE_TIMEOUT + Duration::from_secs(1)).await;
+
+        let Blaze(response) = handle_ping(session.clone()).await;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(now.abs_diff(response.server_time) <= 5);
+
+        assert_eq!(keep_alive_state(&session).await, KeepAliveState::Alive);
+        assert_eq!(keep_alive_state(&idle_session).await, KeepAliveState::Dead);
+    }
+
+    /// Subset of the pre authentication response fields
+    #[derive(TdfDeserialize)]
+    struct PreAuthFields {
+        #[tdf(tag = "QOSS")]
+        qos: QosFields,
+    }
+
+    /// Subset of the QoS server details
+    #[derive(TdfDeserialize, TdfTyped)]
+    #[tdf(group)]
+    struct QosFields {
+        #[tdf(tag = "BWPS")]
+        server: QosServerFields,
+        #[tdf(tag = "LNP")]
+        probe_count: u8,
+    }
+
+    /// QoS server address details
+    #[derive(TdfDeserialize, TdfTyped)]
+    #[tdf(group)]
+    struct QosServerFields {
+        #[tdf(tag = "PSA")]
+        host: String,
+        #[tdf(tag = "PSP")]
+        port: u16,
+    }
+
+    /// Tests that a custom QoS server address and probe count are
+    /// advertised to the client
+    #[tokio::test]
+    async fn test_pre_auth_custom_qos() {
+        let config = Arc::new(RuntimeConfig {
+            qos: QosConfig {
+                server: QosServerConfig::Custom {
+                    host: "qos.example.com".to_string(),
+                    port: 17502,
+                },
+                probe_count: 5,
+            },
+            ..Default::default()
+        });
+
+        let Ok(Blaze(response)) = handle_pre_auth(Extension(config)).await else {
+            panic!("Failed to create pre auth response");
+        };
+
+        let bytes = serialize_vec(&response);
+        let fields = PreAuthFields::deserialize(&mut TdfDeserializer::new(&bytes)).unwrap();
+        assert_eq!(fields.qos.server.host, "qos.example.com");
+        assert_eq!(fields.qos.server.port, 17502);
+        assert_eq!(fields.qos.probe_count, 5);
+    }
+
+    /// Tests that overrides are used in preference to the built-in
+    /// configs and that custom config types can be added
+    #[tokio::test]
+    async fn test_client_config_override() {
+        let dir = std::env::temp_dir().join(format!("pocket-relay-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Built-in config without an override
+        let Ok(config) = client_config(&dir, "ME3_DIME", 0).await else {
+            panic!("Failed to load ME3_DIME config");
+        };
+        assert!(config.get("Config").is_some());
+
+        std::fs::write(dir.join("ME3_DIME.json"), r#"{"Config": "<custom/>"}"#).unwrap();
+        std::fs::write(dir.join("ME3_CUSTOM.json"), r#"{"KEY": "VALUE"}"#).unwrap();
+
+        let Ok(config) = client_config(&dir, "ME3_DIME", 0).await else {
+            panic!("Failed to load ME3_DIME config");
+        };
+        assert_eq!(config.get("Config").map(String::as_str), Some("<custom/>"));
+
+        let Ok(config) = client_config(&dir, "ME3_CUSTOM", 0).await else {
+            panic!("Failed to load ME3_CUSTOM config");
+        };
+        assert_eq!(config.get("KEY").map(String::as_str), Some("VALUE"));
+
+        _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,98 @@
+//! Shared fixtures for creating players and sessions within tests
+
+use super::{data::SessionData, packet::Packet, Session, SessionNotifyHandle};
+use crate::{
+    config::RuntimeConfig,
+    database::entities::{Player, PlayerRole},
+    services::{
+        game::{manager::GameManager, GamePlayer},
+        sessions::Sessions,
+        tunnel::TunnelService,
+        udp_tunnel::UdpTunnelService,
+    },
+    utils::signing::SigningKey,
+};
+use std::{net::Ipv4Addr, sync::Arc};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Creates the sessions store using a newly generated signing key
+pub fn test_sessions() -> Arc<Sessions> {
+    let (key, _) = SigningKey::generate();
+    Arc::new(Sessions::new(key))
+}
+
+/// Creates a game manager using the provided `config` with its own
+/// tunnel services
+pub fn test_game_manager(config: Arc<RuntimeConfig>) -> Arc<GameManager> {
+    Arc::new(GameManager::new(
+        Arc::new(TunnelService::default()),
+        Arc::new(UdpTunnelService::new(test_sessions())),
+        config,
+    ))
+}
+
+/// Creates a player with the provided `id` without storing it in the
+/// database, the email and display name are derived from the `id`
+pub fn test_player(id: u32) -> Player {
+    Player {
+        id,
+        email: format!("test{id}@test.com"),
+        display_name: format!("Test{id}"),
+        password: None,
+        role: PlayerRole::Default,
+        last_login_at: None,
+        origin_id: None,
+        deactivated_at: None,
+    }
+}
+
+/// Creates an unauthenticated session with the provided `id` and a new
+/// association, providing the session and the receiver for the packets
+/// sent to it
+pub fn create_session(id: u32) -> (Arc<Session>, mpsc::UnboundedReceiver<Packet>) {
+    let (notify_handle, rx) = SessionNotifyHandle::new();
+    let session = Arc::new(Session {
+        id,
+        notify_handle,
+        data: SessionData::new(Ipv4Addr::LOCALHOST, Some(Uuid::new_v4())),
+    });
+    (session, rx)
+}
+
+/// Creates a session authenticated as the `player` using the player ID
+/// as the session ID, providing the session and the receiver for the
+/// packets sent to it
+pub fn create_auth_session(
+    sessions: &Arc<Sessions>,
+    player: Player,
+) -> (Arc<Session>, mpsc::UnboundedReceiver<Packet>) {
+    let (session, rx) = create_session(player.id);
+    session
+        .data
+        .set_auth(sessions.add_session(player, Arc::downgrade(&session)));
+    (session, rx)
+}
+
+/// Creates the game player for the authenticated `session`
+pub fn create_game_player(session: &Arc<Session>) -> GamePlayer {
+    let (player, net) = session
+        .data
+        .get_game_player_data()
+        .expect("Session is not authenticated");
+    GamePlayer::new(
+        player,
+        net,
+        Arc::downgrade(session),
+        session.notify_handle.clone(),
+    )
+}
+
+/// Creates the game player for a session authenticated as the test player
+/// with the provided `id`, providing the game player and the receiver for
+/// the packets sent to it. The session isn't kept alive so the game player
+/// behaves as if its session has disconnected
+pub fn create_detached_game_player(id: u32) -> (GamePlayer, mpsc::UnboundedReceiver<Packet>) {
+    let (session, rx) = create_auth_session(&test_sessions(), test_player(id));
+    (create_game_player(&session), rx)
+}
@@ -0,0 +1,102 @@
+//! IPv4 CIDR ranges used for matching connection addresses
+
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{fmt::Display, net::Ipv4Addr, str::FromStr};
+use thiserror::Error;
+
+/// IPv4 address range in CIDR notation (e.g. 192.168.0.0/16), plain
+/// addresses are treated as a range containing only that address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    /// The network address of the range
+    network: Ipv4Addr,
+    /// Number of leading bits of the address that must match
+    prefix: u8,
+}
+
+/// Errors that can occur when parsing a [Cidr]
+#[derive(Debug, Error)]
+pub enum CidrError {
+    /// The address portion was not a valid IPv4 address
+    #[error("invalid address: {0}")]
+    Address(#[from] std::net::AddrParseError),
+    /// The prefix portion was not a number from 0 to 32
+    #[error("invalid prefix length")]
+    Prefix,
+}
+
+impl Cidr {
+    /// Creates the bit mask for the prefix length
+    fn mask(&self) -> u32 {
+        u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0)
+    }
+
+    /// Checks whether the provided `addr` is within the range
+    pub fn contains(&self, addr: Ipv4Addr) -> bool {
+        let mask = self.mask();
+        (u32::from(addr) & mask) == (u32::from(self.network) & mask)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = CidrError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (network, prefix) = match value.split_once('/') {
+            Some((network, prefix)) => {
+                let prefix: u8 = prefix.trim().parse().map_err(|_| CidrError::Prefix)?;
+                (network, prefix)
+            }
+            None => (value, 32),
+        };
+
+        if prefix > 32 {
+            return Err(CidrError::Prefix);
+        }
+
+        Ok(Self {
+            network: network.trim().parse()?,
+            prefix,
+        })
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Cidr;
+    use std::net::Ipv4Addr;
+
+    /// Tests parsing and matching ranges and plain addresses
+    #[test]
+    fn test_contains() {
+        let range: Cidr = "192.168.0.0/16".parse().unwrap();
+        assert!(range.contains(Ipv4Addr::new(192, 168, 20, 1)));
+        assert!(!range.contains(Ipv4Addr::new(192, 169, 0, 1)));
+
+        let single: Cidr = "10.0.0.5".parse().unwrap();
+        assert!(single.contains(Ipv4Addr::new(10, 0, 0, 5)));
+        assert!(!single.contains(Ipv4Addr::new(10, 0, 0, 6)));
+
+        let all: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains(Ipv4Addr::new(1, 2, 3, 4)));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("not-an-ip/8".parse::<Cidr>().is_err());
+    }
+}
@@ -1,4 +1,8 @@
 use super::hashing::{int_hash_map, IntHashMap};
+use std::{
+    ptr::{addr_of, addr_of_mut},
+    sync::Once,
+};
 
 /// Key created from a component and command
 pub type ComponentKey = u32;
@@ -49,12 +53,14 @@ pub static DEBUG_IGNORED_PACKETS: &[ComponentKey] = &[
 pub static DEBUG_IGNORED_PACKETS: &[ComponentKey] = &[];
 
 /// Initializes the stored component state. Should only be
-/// called on initial startup
+/// called on initial startup, repeated calls are ignored
 pub fn initialize() {
-    unsafe {
+    static INIT: Once = Once::new();
+
+    INIT.call_once(|| unsafe {
         init_commands();
         init_notifications();
-    }
+    });
 }
 
 pub fn get_component_name(component: u16) -> Option<&'static str> {
@@ -67,9 +73,9 @@ pub fn get_component_name(component: u16) -> Option<&'static str> {
 pub fn get_command_name(key: ComponentKey, notify: bool) -> Option<&'static str> {
     unsafe {
         if notify {
-            NOTIFICATIONS.get(&key).copied()
+            (*addr_of!(NOTIFICATIONS)).get(&key).copied()
         } else {
-            COMMANDS.get(&key).copied()
+            (*addr_of!(COMMANDS)).get(&key).copied()
         }
     }
 }
@@ -420,7 +426,7 @@ unsafe fn init_commands() {
     use game_reporting as gr;
     use user_sessions as us;
 
-    COMMANDS.extend([
+    (*addr_of_mut!(COMMANDS)).extend([
         // Authentication
         (component_key(a::COMPONENT, a::CREATE_ACCOUNT), "CreateAccount"),
         (component_key(a::COMPONENT, a::UPDATE_ACCOUNT), "UpdateAccount"),
@@ -631,7 +637,7 @@ unsafe fn init_notifications() {
     use user_sessions as us;
 
 
-    NOTIFICATIONS.extend([
+    (*addr_of_mut!(NOTIFICATIONS)).extend([
         // Game Manager
         (component_key(g::COMPONENT, g::MATCHMAKING_FAILED), "MatchmakingFailed"),
         (component_key(g::COMPONENT, g::MATCHMAKING_ASYNC_STATUS), "MatchmakingAsyncStatus"),
@@ -42,10 +42,18 @@ pub fn create_base64_map(bytes: &[u8]) -> ChunkMap {
     output
 }
 
-/// Generates a compressed coalesced from the provided bytes
-pub fn generate_coalesced(bytes: &[u8]) -> std::io::Result<ChunkMap> {
+/// Generates a compressed coalesced from the provided bytes, payloads smaller
+/// than `compress_min_bytes` are stored without compression (The zlib framing
+/// is still required by the client)
+pub fn generate_coalesced(bytes: &[u8], compress_min_bytes: usize) -> std::io::Result<ChunkMap> {
+    let level = if bytes.len() < compress_min_bytes {
+        Compression::none()
+    } else {
+        Compression::new(6)
+    };
+
     let compressed: Vec<u8> = {
-        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(6));
+        let mut encoder = ZlibEncoder::new(Vec::new(), level);
         encoder.write_all(bytes)?;
         encoder.finish()?
     };
@@ -58,3 +66,41 @@ pub fn generate_coalesced(bytes: &[u8]) -> std::io::Result<ChunkMap> {
     encoded.extend_from_slice(&compressed);
     Ok(create_base64_map(&encoded))
 }
+
+#[cfg(test)]
+mod test {
+    use super::{generate_coalesced, ChunkMap};
+    use base64ct::{Base64, Encoding};
+
+    /// Decodes the NIBC payload from a chunk map providing the compressed length
+    /// from the header and the payload bytes
+    fn decode_coalesced(map: &ChunkMap) -> (usize, Vec<u8>) {
+        let chunks: usize = map
+            .iter()
+            .filter(|(key, _)| key.starts_with("CHUNK_") && key != "CHUNK_SIZE")
+            .count();
+        let encoded: String = (0..chunks)
+            .map(|index| map.get(&format!("CHUNK_{}", index)).unwrap().as_str())
+            .collect();
+        let bytes = Base64::decode_vec(&encoded).unwrap();
+
+        let compressed_len = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        (compressed_len, bytes[16..].to_vec())
+    }
+
+    /// Tests that payloads below the threshold are stored uncompressed while
+    /// payloads above the threshold are compressed
+    #[test]
+    fn test_compress_threshold() {
+        let bytes = vec![b'A'; 4096];
+
+        let map = generate_coalesced(&bytes, 8192).unwrap();
+        let (compressed_len, payload) = decode_coalesced(&map);
+        assert!(compressed_len > bytes.len());
+        assert!(payload.windows(bytes.len()).any(|window| window == bytes));
+
+        let map = generate_coalesced(&bytes, 1024).unwrap();
+        let (compressed_len, _) = decode_coalesced(&map);
+        assert!(compressed_len < bytes.len());
+    }
+}
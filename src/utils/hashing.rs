@@ -4,7 +4,7 @@ use argon2::{
     password_hash::{self, rand_core::OsRng, PasswordVerifier, SaltString},
     Argon2, PasswordHash, PasswordHasher,
 };
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use std::hash::{BuildHasher, Hasher};
 
 /// Hashes the provided password using the Argon2 algorithm returning
@@ -36,6 +36,9 @@ pub fn verify_password(password: &str, hash: &str) -> bool {
 /// Alias for a [`HashMap`] that used [`IntHasher`] as its [`Hasher`]
 pub type IntHashMap<K, V> = HashMap<K, V, BuildIntHasher>;
 
+/// Alias for a [`HashSet`] that used [`IntHasher`] as its [`Hasher`]
+pub type IntHashSet<K> = HashSet<K, BuildIntHasher>;
+
 /// Const safe [`BuildHasher`] implementation for [`IntHasher`]
 #[derive(Default)]
 pub struct BuildIntHasher;
@@ -0,0 +1,119 @@
+//! Binding of the TCP listeners for the HTTP server
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    io,
+    net::{Ipv6Addr, SocketAddr},
+};
+use tokio::net::TcpListener;
+
+/// Maximum number of pending connections for each listener
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// Binds the TCP listeners for the provided `addr`
+///
+/// When `dual_stack` is enabled and `addr` is an unspecified address the
+/// listeners accept both IPv4 and IPv6 clients. For "::" a single socket
+/// is bound with IPV6_V6ONLY disabled, for "0.0.0.0" a separate IPv6
+/// listener is bound on the same port
+pub fn bind_listeners(addr: SocketAddr, dual_stack: bool) -> io::Result<Vec<TcpListener>> {
+    if !dual_stack || !addr.ip().is_unspecified() {
+        return Ok(vec![bind(addr, None)?]);
+    }
+
+    match addr {
+        SocketAddr::V6(_) => Ok(vec![bind(addr, Some(false))?]),
+        SocketAddr::V4(_) => {
+            let v4 = bind(addr, None)?;
+
+            // Use the port assigned to the IPv4 listener in case it was random
+            let port = v4.local_addr()?.port();
+            let v6 = bind(
+                SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port),
+                Some(true),
+            )?;
+
+            Ok(vec![v4, v6])
+        }
+    }
+}
+
+/// Binds a single listener to `addr` setting IPV6_V6ONLY to `only_v6`
+/// if provided
+fn bind(addr: SocketAddr, only_v6: Option<bool>) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    if let Some(only_v6) = only_v6 {
+        socket.set_only_v6(only_v6)?;
+    }
+
+    // Matches the behavior of the tokio listener on unix platforms
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::bind_listeners;
+    use socket2::SockRef;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+    use tokio::net::TcpStream;
+
+    /// Tests that dual stack listeners on "::" accept IPv4 clients
+    #[tokio::test]
+    async fn test_dual_stack_v6() {
+        let addr = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0);
+        let listeners = match bind_listeners(addr, true) {
+            Ok(value) => value,
+            // IPv6 is not available on this machine
+            Err(_) => return,
+        };
+        assert_eq!(listeners.len(), 1);
+
+        let listener = &listeners[0];
+        assert!(!SockRef::from(listener).only_v6().unwrap());
+
+        // Connect using IPv4
+        let port = listener.local_addr().unwrap().port();
+        let (stream, accepted) = tokio::join!(
+            TcpStream::connect((Ipv4Addr::LOCALHOST, port)),
+            listener.accept()
+        );
+        assert!(stream.is_ok());
+        assert!(accepted.is_ok());
+    }
+
+    /// Tests that dual stack listeners on "0.0.0.0" bind an additional
+    /// IPv6 listener on the same port
+    #[tokio::test]
+    async fn test_dual_stack_v4() {
+        let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0);
+        let listeners = match bind_listeners(addr, true) {
+            Ok(value) => value,
+            // IPv6 is not available on this machine
+            Err(_) => return,
+        };
+        assert_eq!(listeners.len(), 2);
+
+        let v4 = listeners[0].local_addr().unwrap();
+        let v6 = listeners[1].local_addr().unwrap();
+        assert!(v4.is_ipv4());
+        assert!(v6.is_ipv6());
+        assert_eq!(v4.port(), v6.port());
+        assert!(SockRef::from(&listeners[1]).only_v6().unwrap());
+    }
+
+    /// Tests that only a single listener is bound without dual stack
+    #[tokio::test]
+    async fn test_single_stack() {
+        let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0);
+        let listeners = bind_listeners(addr, false).unwrap();
+        assert_eq!(listeners.len(), 1);
+    }
+}
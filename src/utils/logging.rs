@@ -1,12 +1,18 @@
 use futures_util::TryFutureExt;
-use log::{info, LevelFilter};
+use log::{info, log, Level, LevelFilter};
 use log4rs::{
     append::{console::ConsoleAppender, file::FileAppender},
     config::{Appender, Logger, Root},
     encode::pattern::PatternEncoder,
     init_config, Config,
 };
-use std::net::Ipv4Addr;
+use parking_lot::{const_mutex, Mutex};
+use std::{
+    collections::BTreeMap,
+    net::Ipv4Addr,
+    time::{Duration, Instant},
+};
+use tokio::time::{interval, MissedTickBehavior};
 
 /// The pattern to use when logging
 const LOGGING_PATTERN: &str = "[{d} {h({l})} {M}] {m}{n}";
@@ -14,6 +20,173 @@ const LOGGING_PATTERN: &str = "[{d} {h({l})} {M}] {m}{n}";
 /// Log file name
 pub const LOG_FILE_NAME: &str = "data/server.log";
 
+/// Window that repeated identical messages logged through [log_limited]
+/// are collapsed within
+const LIMITED_LOG_WINDOW: Duration = Duration::from_secs(60);
+
+/// Interval between checks for suppressed messages whose window has
+/// ended, see [flush_limited_logs_task]
+const LIMITED_LOG_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Maximum number of distinct messages tracked by a [LogLimiter]
+const LIMITED_LOG_MAX_ENTRIES: usize = 256;
+
+/// Limiter shared by [log_limited]
+static LOG_LIMITER: LogLimiter = LogLimiter::new(LIMITED_LOG_WINDOW);
+
+/// Logs `message` at `level` collapsing identical messages repeated within
+/// a short window, used for error paths that misbehaving clients can trigger
+/// repeatedly to prevent them flooding the logs
+pub fn log_limited(level: Level, message: String) {
+    if !log::log_enabled!(level) {
+        return;
+    }
+
+    match LOG_LIMITER.check(level, message, Instant::now()) {
+        LimitedLog::Emit {
+            message,
+            suppressed: 0,
+        } => log!(level, "{}", message),
+        LimitedLog::Emit {
+            message,
+            suppressed,
+        } => log!(
+            level,
+            "{} ({} occurrences in the last {}s)",
+            message,
+            suppressed + 1,
+            LOG_LIMITER.window.as_secs()
+        ),
+        LimitedLog::Suppress => {}
+    }
+}
+
+/// Background task that logs the number of suppressed messages for
+/// messages logged through [log_limited] once their window has ended,
+/// so the count isn't lost when the message doesn't happen again
+pub async fn flush_limited_logs_task() {
+    let mut interval = interval(LIMITED_LOG_FLUSH_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        for (level, message, suppressed) in LOG_LIMITER.flush(Instant::now()) {
+            log!(
+                level,
+                "{} ({} more occurrences in the last {}s)",
+                message,
+                suppressed,
+                LOG_LIMITER.window.as_secs()
+            );
+        }
+    }
+}
+
+/// Tracks recently logged messages to collapse repeated messages
+pub struct LogLimiter {
+    /// Duration repeated messages are suppressed for
+    window: Duration,
+    /// Recently logged messages
+    entries: Mutex<BTreeMap<String, LimitedEntry>>,
+}
+
+/// State for a recently logged message
+struct LimitedEntry {
+    /// Level the message was logged at
+    level: Level,
+    /// When the message was last emitted
+    emitted_at: Instant,
+    /// Number of times the message was suppressed since it was emitted
+    suppressed: u32,
+}
+
+/// Outcome of checking a message against a [LogLimiter]
+#[derive(Debug, PartialEq, Eq)]
+pub enum LimitedLog {
+    /// The message should be logged, `suppressed` is the number of
+    /// identical messages that were skipped since it was last logged
+    Emit { message: String, suppressed: u32 },
+    /// The message was recently logged and should be skipped
+    Suppress,
+}
+
+impl LogLimiter {
+    pub const fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: const_mutex(BTreeMap::new()),
+        }
+    }
+
+    /// Checks whether `message` logged at `level` should be logged at `now`
+    pub fn check(&self, level: Level, message: String, now: Instant) -> LimitedLog {
+        let entries = &mut *self.entries.lock();
+
+        if let Some(entry) = entries.get_mut(&message) {
+            if now.duration_since(entry.emitted_at) < self.window {
+                entry.suppressed = entry.suppressed.saturating_add(1);
+                return LimitedLog::Suppress;
+            }
+
+            let suppressed = entry.suppressed;
+            entry.emitted_at = now;
+            entry.suppressed = 0;
+            return LimitedLog::Emit {
+                message,
+                suppressed,
+            };
+        }
+
+        // Drop messages that are no longer being suppressed, messages with
+        // suppressed occurrences are kept until they are flushed
+        if entries.len() >= LIMITED_LOG_MAX_ENTRIES {
+            entries.retain(|_, entry| {
+                entry.suppressed > 0 || now.duration_since(entry.emitted_at) < self.window
+            });
+        }
+
+        // Too many distinct messages to track, always log
+        if entries.len() < LIMITED_LOG_MAX_ENTRIES {
+            entries.insert(
+                message.clone(),
+                LimitedEntry {
+                    level,
+                    emitted_at: now,
+                    suppressed: 0,
+                },
+            );
+        }
+
+        LimitedLog::Emit {
+            message,
+            suppressed: 0,
+        }
+    }
+
+    /// Removes the messages whose window has ended at `now`, providing the
+    /// level, message and suppressed count of each removed message that had
+    /// suppressed occurrences
+    pub fn flush(&self, now: Instant) -> Vec<(Level, String, u32)> {
+        let entries = &mut *self.entries.lock();
+        let mut flushed = Vec::new();
+
+        entries.retain(|message, entry| {
+            if now.duration_since(entry.emitted_at) < self.window {
+                return true;
+            }
+
+            if entry.suppressed > 0 {
+                flushed.push((entry.level, message.clone(), entry.suppressed));
+            }
+
+            false
+        });
+
+        flushed
+    }
+}
+
 /// Setup function for setting up the Log4rs logging configuring it
 /// for all the different modules and and setting up file and stdout logging
 pub fn setup(logging_level: LevelFilter) {
@@ -92,9 +265,10 @@ pub async fn log_connection_urls(http_port: u16) {
     info!("Connection URLS ({output})");
 }
 
-/// Retrieves the public address of the server either using the cached
-/// value if its not expired or fetching the new value from the API using
-/// `fetch_public_addr`
+/// Retrieves the public address of the server by looking it up using
+/// online services, see [PublicAddressService] for the cached address
+///
+/// [PublicAddressService]: crate::services::public_address::PublicAddressService
 pub async fn public_address() -> Option<Ipv4Addr> {
     // Try fetch from cloudflare first
     if let Some(addr) = public_address_cloudflare().await {
@@ -154,3 +328,81 @@ pub async fn public_address_cloudflare() -> Option<Ipv4Addr> {
 
     addr.ok()
 }
+
+#[cfg(test)]
+mod test {
+    use super::{LimitedLog, LogLimiter};
+    use log::Level;
+    use std::time::{Duration, Instant};
+
+    /// Tests that a message is emitted once and repeated messages within
+    /// the window are summarized when the window ends
+    #[test]
+    fn test_log_limiter() {
+        let limiter = LogLimiter::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let message = || "Error while decoding packet".to_string();
+
+        assert_eq!(
+            limiter.check(Level::Error, message(), start),
+            LimitedLog::Emit {
+                message: message(),
+                suppressed: 0
+            }
+        );
+
+        for offset in 1..=5 {
+            assert_eq!(
+                limiter.check(Level::Error, message(), start + Duration::from_secs(offset)),
+                LimitedLog::Suppress
+            );
+        }
+
+        // Different messages are not suppressed
+        assert!(matches!(
+            limiter.check(Level::Error, "Other error".to_string(), start),
+            LimitedLog::Emit { suppressed: 0, .. }
+        ));
+
+        assert_eq!(
+            limiter.check(Level::Error, message(), start + Duration::from_secs(61)),
+            LimitedLog::Emit {
+                message: message(),
+                suppressed: 5
+            }
+        );
+    }
+
+    /// Tests that suppressed counts are flushed once the window ends
+    /// when the message doesn't happen again
+    #[test]
+    fn test_log_limiter_flush() {
+        let limiter = LogLimiter::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let message = || "Error while decoding packet".to_string();
+
+        limiter.check(Level::Warn, message(), start);
+        limiter.check(Level::Warn, message(), start + Duration::from_secs(1));
+        limiter.check(Level::Warn, message(), start + Duration::from_secs(2));
+        limiter.check(Level::Warn, "Other error".to_string(), start);
+
+        // Nothing is flushed before the window ends
+        assert!(limiter.flush(start + Duration::from_secs(30)).is_empty());
+
+        // Only messages with suppressed occurrences are reported
+        assert_eq!(
+            limiter.flush(start + Duration::from_secs(60)),
+            [(Level::Warn, message(), 2)]
+        );
+
+        // Flushed messages are logged again immediately
+        assert!(limiter.flush(start + Duration::from_secs(120)).is_empty());
+        assert_eq!(
+            limiter.check(Level::Warn, message(), start + Duration::from_secs(121)),
+            LimitedLog::Emit {
+                message: message(),
+                suppressed: 0
+            }
+        );
+    }
+}
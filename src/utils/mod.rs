@@ -1,6 +1,8 @@
+pub mod cidr;
 pub mod components;
 pub mod encoding;
 pub mod hashing;
+pub mod listener;
 pub mod logging;
 pub mod parsing;
 pub mod random_name;